@@ -0,0 +1,328 @@
+mod recurrence;
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use ical::{parser::ical::component::IcalEvent, property::Property, IcalParser};
+use log::debug;
+
+use self::recurrence::RecurrenceRule;
+
+/// Events of a day, as shown by the agenda panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agenda {
+    pub day: NaiveDate,
+    pub events: Vec<AgendaEvent>,
+    /// Set when the calendar could not be refreshed and events may be outdated.
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgendaEvent {
+    /// Start time, `None` for all-day events.
+    pub start: Option<NaiveTime>,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EventTime {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+struct Event {
+    uid: Option<String>,
+    title: String,
+    start: EventTime,
+    end: Option<EventTime>,
+    rule: Option<RecurrenceRule>,
+    exceptions: Vec<NaiveDate>,
+    /// Set on a modified occurrence of a recurring event, replacing the one of this day.
+    recurrence_id: Option<NaiveDate>,
+    cancelled: bool,
+}
+
+/// Events of the iCal calendar `ics` happening on `day`, sorted with all-day events first.
+///
+/// Times with a TZID are read as local times, which is right as long as the calendar and
+/// the frame share the same time zone. Events that cannot be understood are ignored.
+pub fn events_on(ics: &str, day: NaiveDate) -> Result<Vec<AgendaEvent>> {
+    let mut parsed = Vec::new();
+    for calendar in IcalParser::new(ics.as_bytes()) {
+        let calendar = calendar.context("Cannot parse iCal calendar")?;
+        for event in calendar.events {
+            match Event::parse(&event) {
+                Ok(event) => parsed.push(event),
+                Err(err) => debug!("Ignoring event: {:#}", err),
+            }
+        }
+    }
+
+    let mut overridden: HashMap<&str, Vec<NaiveDate>> = HashMap::new();
+    for event in &parsed {
+        if let (Some(uid), Some(recurrence_id)) = (&event.uid, event.recurrence_id) {
+            overridden.entry(uid).or_default().push(recurrence_id);
+        }
+    }
+
+    let mut events: Vec<_> = parsed
+        .iter()
+        .filter(|event| {
+            let is_overridden = event.rule.is_some()
+                && event.uid.as_deref().is_some_and(|uid| {
+                    overridden
+                        .get(uid)
+                        .is_some_and(|dates| dates.contains(&day))
+                });
+            !is_overridden && event.occurs_on(day)
+        })
+        .map(|event| AgendaEvent {
+            start: event.start_time_on(day),
+            title: event.title.clone(),
+        })
+        .collect();
+    events.sort_by(|a, b| (a.start, &a.title).cmp(&(b.start, &b.title)));
+    Ok(events)
+}
+
+impl EventTime {
+    fn date(&self) -> NaiveDate {
+        match self {
+            EventTime::Date(date) => *date,
+            EventTime::DateTime(date_time) => date_time.date(),
+        }
+    }
+
+    fn parse(property: &Property) -> Result<Self> {
+        let value = property
+            .value
+            .as_deref()
+            .with_context(|| format!("Missing {} value", property.name))?;
+        let is_date = property
+            .params
+            .iter()
+            .flatten()
+            .any(|(name, values)| name == "VALUE" && values.iter().any(|v| v == "DATE"));
+        if is_date || value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(EventTime::Date)
+                .with_context(|| format!("Invalid date {value:?}"));
+        }
+        let (value, utc) = match value.strip_suffix('Z') {
+            Some(value) => (value, true),
+            None => (value, false),
+        };
+        let date_time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .with_context(|| format!("Invalid date time {value:?}"))?;
+        Ok(EventTime::DateTime(if utc {
+            Local.from_utc_datetime(&date_time).naive_local()
+        } else {
+            date_time
+        }))
+    }
+}
+
+impl Event {
+    fn parse(event: &IcalEvent) -> Result<Self> {
+        let mut uid = None;
+        let mut title = None;
+        let mut start = None;
+        let mut end = None;
+        let mut rule = None;
+        let mut exceptions = Vec::new();
+        let mut recurrence_id = None;
+        let mut cancelled = false;
+        for property in &event.properties {
+            let value = property.value.as_deref().unwrap_or_default();
+            match property.name.as_str() {
+                "UID" => uid = Some(value.to_owned()),
+                "SUMMARY" => title = Some(unescape(value)),
+                "DTSTART" => start = Some(EventTime::parse(property)?),
+                "DTEND" => end = Some(EventTime::parse(property)?),
+                "RRULE" => rule = Some(value.parse::<RecurrenceRule>()?),
+                "EXDATE" => {
+                    for date in value.split(',') {
+                        let property = Property {
+                            value: Some(date.to_owned()),
+                            ..property.clone()
+                        };
+                        exceptions.push(EventTime::parse(&property)?.date());
+                    }
+                }
+                "RECURRENCE-ID" => recurrence_id = Some(EventTime::parse(property)?.date()),
+                "STATUS" => cancelled = value == "CANCELLED",
+                _ => {}
+            }
+        }
+        let Some(start) = start else {
+            bail!("Event {uid:?} has no start");
+        };
+        Ok(Event {
+            uid,
+            title: title.unwrap_or_default(),
+            start,
+            end,
+            rule,
+            exceptions,
+            recurrence_id,
+            cancelled,
+        })
+    }
+
+    fn occurs_on(&self, day: NaiveDate) -> bool {
+        if self.cancelled {
+            return false;
+        }
+        let start = self.start.date();
+        match &self.rule {
+            Some(rule) => !self.exceptions.contains(&day) && rule.occurs_on(start, day),
+            None => start <= day && day <= self.last_day(),
+        }
+    }
+
+    /// Last day covered by a single occurrence of the event, the iCal end being exclusive.
+    fn last_day(&self) -> NaiveDate {
+        let start = self.start.date();
+        let last = match self.end {
+            Some(EventTime::Date(end)) => end.pred_opt().unwrap_or(end),
+            Some(EventTime::DateTime(end)) if end.time() == NaiveTime::MIN => {
+                end.date().pred_opt().unwrap_or(end.date())
+            }
+            Some(EventTime::DateTime(end)) => end.date(),
+            None => start,
+        };
+        last.max(start)
+    }
+
+    /// Start time on `day`, days after the first one of a long event are shown as all-day.
+    fn start_time_on(&self, day: NaiveDate) -> Option<NaiveTime> {
+        match self.start {
+            EventTime::DateTime(start) if self.rule.is_some() || start.date() == day => {
+                Some(start.time())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => result.push(' '),
+                Some(escaped) => result.push(escaped),
+                None => {}
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, NaiveTime};
+    use googletest::{
+        expect_that, gtest,
+        matchers::matches_pattern,
+        prelude::{elements_are, eq, is_empty, none, some},
+    };
+
+    use super::{events_on, AgendaEvent};
+
+    const SIMPLE: &str = include_str!("fixtures/simple.ics");
+    const RECURRING: &str = include_str!("fixtures/recurring.ics");
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[gtest]
+    fn test_events_of_the_day() {
+        let events = events_on(SIMPLE, date(2025, 3, 14)).unwrap();
+        expect_that!(
+            events,
+            elements_are![
+                matches_pattern!(AgendaEvent {
+                    start: none(),
+                    title: eq("Holidays"),
+                }),
+                matches_pattern!(AgendaEvent {
+                    start: some(eq(time(9, 30))),
+                    title: eq("Dentist, Dr. Smith"),
+                }),
+                matches_pattern!(AgendaEvent {
+                    start: some(eq(time(18, 0))),
+                    title: eq("Dinner"),
+                }),
+            ]
+        );
+    }
+
+    #[gtest]
+    fn test_multi_day_event_end_is_exclusive() {
+        let events = events_on(SIMPLE, date(2025, 3, 16)).unwrap();
+        expect_that!(events, is_empty());
+    }
+
+    #[gtest]
+    fn test_cancelled_events_are_hidden() {
+        let events = events_on(SIMPLE, date(2025, 3, 20)).unwrap();
+        expect_that!(events, is_empty());
+    }
+
+    #[gtest]
+    fn test_recurring_events() {
+        // Weekly on Tuesdays and Thursdays
+        let events = events_on(RECURRING, date(2025, 3, 13)).unwrap();
+        expect_that!(
+            events,
+            elements_are![matches_pattern!(AgendaEvent {
+                start: some(eq(time(7, 45))),
+                title: eq("Swimming"),
+            })]
+        );
+        // Excluded occurrence
+        expect_that!(events_on(RECURRING, date(2025, 3, 18)).unwrap(), is_empty());
+        // Not a recurrence day
+        expect_that!(events_on(RECURRING, date(2025, 3, 12)).unwrap(), is_empty());
+    }
+
+    #[gtest]
+    fn test_moved_occurrence() {
+        let events = events_on(RECURRING, date(2025, 3, 20)).unwrap();
+        expect_that!(
+            events,
+            elements_are![matches_pattern!(AgendaEvent {
+                start: some(eq(time(19, 0))),
+                title: eq("Swimming (evening)"),
+            })]
+        );
+    }
+
+    #[gtest]
+    fn test_monthly_and_yearly_events() {
+        let events = events_on(RECURRING, date(2026, 4, 1)).unwrap();
+        expect_that!(
+            events,
+            elements_are![
+                matches_pattern!(AgendaEvent {
+                    start: none(),
+                    title: eq("Alice's birthday"),
+                }),
+                matches_pattern!(AgendaEvent {
+                    start: some(eq(time(20, 0))),
+                    title: eq("Book club"),
+                }),
+            ]
+        );
+    }
+}
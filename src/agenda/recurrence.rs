@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error, Result};
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// Subset of the iCal RRULE used by common calendars: FREQ, INTERVAL, COUNT, UNTIL,
+/// BYDAY and BYMONTHDAY. Rules using other parts are rejected rather than guessed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurrenceRule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDate>,
+    /// Week days, with an optional position in the month ("2MO", "-1FR").
+    by_day: Vec<(Option<i8>, Weekday)>,
+    by_month_day: Vec<i8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl RecurrenceRule {
+    /// Whether an event first happening on `start` happens again on `day`.
+    pub fn occurs_on(&self, start: NaiveDate, day: NaiveDate) -> bool {
+        if day < start || self.until.is_some_and(|until| day > until) {
+            return false;
+        }
+        if !self.matches(start, day) {
+            return false;
+        }
+        match self.count {
+            Some(count) => {
+                let occurrences = start
+                    .iter_days()
+                    .take_while(|d| *d <= day)
+                    .filter(|d| self.matches(start, *d))
+                    .count();
+                occurrences <= count as usize
+            }
+            None => true,
+        }
+    }
+
+    fn matches(&self, start: NaiveDate, day: NaiveDate) -> bool {
+        let interval = self.interval as i64;
+        match self.frequency {
+            Frequency::Daily => {
+                (day - start).num_days() % interval == 0
+                    && (self.by_day.is_empty()
+                        || self.by_day.iter().any(|(_, wd)| *wd == day.weekday()))
+            }
+            Frequency::Weekly => {
+                let weeks = (day.week(Weekday::Mon).first_day()
+                    - start.week(Weekday::Mon).first_day())
+                .num_weeks();
+                weeks % interval == 0
+                    && if self.by_day.is_empty() {
+                        day.weekday() == start.weekday()
+                    } else {
+                        self.by_day.iter().any(|(_, wd)| *wd == day.weekday())
+                    }
+            }
+            Frequency::Monthly => {
+                let months = (day.year() * 12 + day.month0() as i32)
+                    - (start.year() * 12 + start.month0() as i32);
+                if months as i64 % interval != 0 {
+                    return false;
+                }
+                if !self.by_day.is_empty() {
+                    self.by_day
+                        .iter()
+                        .any(|(nth, wd)| *wd == day.weekday() && nth_matches(*nth, day))
+                } else if !self.by_month_day.is_empty() {
+                    self.by_month_day
+                        .iter()
+                        .any(|month_day| month_day_matches(*month_day, day))
+                } else {
+                    day.day() == start.day()
+                }
+            }
+            Frequency::Yearly => {
+                (day.year() - start.year()) as i64 % interval == 0
+                    && day.month() == start.month()
+                    && day.day() == start.day()
+            }
+        }
+    }
+}
+
+/// Whether `day` is the `nth` occurrence of its week day in the month, negative values
+/// counting from the end of the month.
+fn nth_matches(nth: Option<i8>, day: NaiveDate) -> bool {
+    match nth {
+        None => true,
+        Some(nth) if nth > 0 => (day.day0() / 7 + 1) as i8 == nth,
+        Some(nth) => ((day.num_days_in_month() as u32 - day.day()) / 7 + 1) as i8 == -nth,
+    }
+}
+
+fn month_day_matches(month_day: i8, day: NaiveDate) -> bool {
+    if month_day > 0 {
+        day.day() == month_day as u32
+    } else {
+        day.num_days_in_month() as i32 + 1 + month_day as i32 == day.day() as i32
+    }
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday> {
+    Ok(match s {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => bail!("Invalid week day {s:?}"),
+    })
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut rule = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+        };
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .with_context(|| format!("Invalid rule part {part:?}"))?;
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => bail!("Unsupported frequency {value:?}"),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .with_context(|| format!("Invalid interval {value:?}"))?
+                }
+                "COUNT" => {
+                    rule.count = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid count {value:?}"))?,
+                    )
+                }
+                "UNTIL" => {
+                    let date = value.get(..8).unwrap_or(value);
+                    rule.until = Some(
+                        NaiveDate::parse_from_str(date, "%Y%m%d")
+                            .with_context(|| format!("Invalid end date {value:?}"))?,
+                    )
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let (nth, weekday) = day.split_at(day.len().saturating_sub(2));
+                        let nth = if nth.is_empty() {
+                            None
+                        } else {
+                            Some(
+                                nth.trim_start_matches('+')
+                                    .parse()
+                                    .with_context(|| format!("Invalid week day {day:?}"))?,
+                            )
+                        };
+                        rule.by_day.push((nth, parse_weekday(weekday)?));
+                    }
+                }
+                "BYMONTHDAY" => {
+                    for month_day in value.split(',') {
+                        rule.by_month_day.push(
+                            month_day
+                                .parse()
+                                .with_context(|| format!("Invalid month day {month_day:?}"))?,
+                        );
+                    }
+                }
+                // Only changes how weeks are counted with an interval, Monday is assumed.
+                "WKST" => {}
+                _ => bail!("Unsupported rule part {name}"),
+            }
+        }
+        rule.frequency = frequency.context("Missing rule frequency")?;
+        Ok(rule)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true},
+    };
+
+    use super::RecurrenceRule;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn occurrences(rule: &str, start: NaiveDate, days: u64) -> Vec<NaiveDate> {
+        let rule: RecurrenceRule = rule.parse().unwrap();
+        start
+            .iter_days()
+            .take(days as usize)
+            .filter(|day| rule.occurs_on(start, *day))
+            .collect()
+    }
+
+    #[gtest]
+    fn test_weekly_by_day() {
+        // 2025-01-06 is a Monday
+        let days = occurrences("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE", date(2025, 1, 6), 21);
+        expect_that!(
+            days,
+            eq(vec![
+                date(2025, 1, 6),
+                date(2025, 1, 8),
+                date(2025, 1, 20),
+                date(2025, 1, 22),
+            ])
+        );
+    }
+
+    #[gtest]
+    fn test_monthly_nth_weekday() {
+        let days = occurrences("FREQ=MONTHLY;BYDAY=-1FR;COUNT=2", date(2025, 1, 31), 120);
+        expect_that!(days, eq(vec![date(2025, 1, 31), date(2025, 2, 28)]));
+    }
+
+    #[gtest]
+    fn test_until_and_yearly() {
+        let rule: RecurrenceRule = "FREQ=YEARLY;UNTIL=20260101T000000Z".parse().unwrap();
+        expect_that!(
+            rule.occurs_on(date(2020, 3, 14), date(2025, 3, 14)),
+            is_true()
+        );
+        expect_that!(
+            rule.occurs_on(date(2020, 3, 14), date(2026, 3, 14)),
+            is_false()
+        );
+        expect_that!(
+            rule.occurs_on(date(2020, 3, 14), date(2019, 3, 14)),
+            is_false()
+        );
+    }
+
+    #[gtest]
+    fn test_unsupported_rule() {
+        expect_that!(
+            "FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU"
+                .parse::<RecurrenceRule>()
+                .is_err(),
+            is_true()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::configuration::QualityLevel;
+
+/// Frames slower than this on average over a window lower the quality, below 25 FPS.
+const SLOW_FRAME: Duration = Duration::from_millis(40);
+/// Frames faster than this on average over a window leave room to raise it again.
+const FAST_FRAME: Duration = Duration::from_millis(25);
+/// Frames averaged together.
+const WINDOW_FRAMES: u32 = 30;
+/// Consecutive fast windows before raising the quality, so that it doesn't flip back and
+/// forth when the lower quality is only just fast enough.
+const FAST_WINDOWS_TO_RAISE: u32 = 10;
+
+/// Lowers the quality of the blurred background when frames take too long, and raises it
+/// back once there is headroom again.
+#[derive(Default)]
+pub struct AdaptiveQuality {
+    level: QualityLevel,
+    last_frame: Option<Instant>,
+    window_time: Duration,
+    window_frames: u32,
+    fast_windows: u32,
+}
+
+impl AdaptiveQuality {
+    /// Records a frame presented at `now`, returns the new level when it changes.
+    pub fn frame_presented(&mut self, now: Instant) -> Option<QualityLevel> {
+        let last_frame = self.last_frame.replace(now)?;
+        self.window_time += now.saturating_duration_since(last_frame);
+        self.window_frames += 1;
+        if self.window_frames < WINDOW_FRAMES {
+            return None;
+        }
+        let average = self.window_time / self.window_frames;
+        self.window_time = Duration::ZERO;
+        self.window_frames = 0;
+
+        let level = if average > SLOW_FRAME {
+            self.fast_windows = 0;
+            let level = self.level.lower()?;
+            warn!("Frames take {average:?}, lowering quality to {level:?}");
+            level
+        } else if average < FAST_FRAME {
+            self.fast_windows += 1;
+            if self.fast_windows < FAST_WINDOWS_TO_RAISE {
+                return None;
+            }
+            self.fast_windows = 0;
+            let level = self.level.raise()?;
+            info!("Frames take {average:?}, raising quality to {level:?}");
+            level
+        } else {
+            self.fast_windows = 0;
+            return None;
+        };
+        self.level = level;
+        Some(level)
+    }
+
+    /// Nothing is drawn until the next animation, this time doesn't count as a frame.
+    pub fn idle(&mut self) {
+        self.last_frame = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, none, some},
+    };
+
+    use super::{AdaptiveQuality, FAST_WINDOWS_TO_RAISE, WINDOW_FRAMES};
+    use crate::configuration::QualityLevel;
+
+    /// Presents `count` frames `frame_time` apart, returns the last level change.
+    fn present(
+        quality: &mut AdaptiveQuality,
+        time: &mut Instant,
+        count: u32,
+        frame_time: Duration,
+    ) -> Option<QualityLevel> {
+        let mut change = None;
+        for _ in 0..count {
+            *time += frame_time;
+            change = quality.frame_presented(*time).or(change);
+        }
+        change
+    }
+
+    #[gtest]
+    fn test_slow_frames_lower_quality() {
+        let mut quality = AdaptiveQuality::default();
+        let mut time = Instant::now();
+        let slow = Duration::from_millis(60);
+
+        let change = present(&mut quality, &mut time, WINDOW_FRAMES + 1, slow);
+        expect_that!(change, some(eq(QualityLevel::FewerBlurPasses)));
+        let change = present(&mut quality, &mut time, WINDOW_FRAMES, slow);
+        expect_that!(change, some(eq(QualityLevel::NoBlur)));
+        let change = present(&mut quality, &mut time, WINDOW_FRAMES, slow);
+        expect_that!(change, none());
+    }
+
+    #[gtest]
+    fn test_fast_frames_raise_quality_back() {
+        let mut quality = AdaptiveQuality::default();
+        let mut time = Instant::now();
+        present(
+            &mut quality,
+            &mut time,
+            WINDOW_FRAMES + 1,
+            Duration::from_millis(60),
+        );
+
+        let fast = Duration::from_millis(16);
+        let change = present(&mut quality, &mut time, WINDOW_FRAMES, fast);
+        expect_that!(change, none());
+        let change = present(
+            &mut quality,
+            &mut time,
+            WINDOW_FRAMES * FAST_WINDOWS_TO_RAISE,
+            fast,
+        );
+        expect_that!(change, some(eq(QualityLevel::Full)));
+    }
+
+    #[gtest]
+    fn test_idle_time_is_not_a_frame() {
+        let mut quality = AdaptiveQuality::default();
+        let mut time = Instant::now();
+        for _ in 0..WINDOW_FRAMES * 2 {
+            time += Duration::from_secs(10);
+            quality.idle();
+            expect_that!(quality.frame_presented(time), none());
+        }
+    }
+}
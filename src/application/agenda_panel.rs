@@ -0,0 +1,148 @@
+use anyhow::{Context, Result};
+use chrono::NaiveTime;
+use epaint::{
+    text::{LayoutJob, TextFormat},
+    Color32, FontId, Pos2, RectShape,
+};
+use tokio::sync::watch;
+use vek::Vec2;
+
+use crate::{
+    agenda::Agenda,
+    configuration::{Settings, TimeRange},
+    graphics::{Drawable, Graphics, ShapeContainer, TextContainer},
+};
+
+/// Part of the screen width used by the panel.
+const PANEL_WIDTH_RATIO: f32 = 0.3;
+const PADDING: f32 = 20.0;
+const BACKGROUND_ALPHA: f32 = 0.7;
+const TITLE_FONT_SIZE: f32 = 32.0;
+const EVENT_FONT_SIZE: f32 = 24.0;
+
+/// Column on the right of the screen listing today's events. Slides are laid out next to
+/// it while it's shown.
+pub struct AgendaPanel {
+    agenda: watch::Receiver<Option<Agenda>>,
+    hours: Option<TimeRange>,
+    text: TextContainer,
+    background: Option<ShapeContainer>,
+    visible: bool,
+}
+
+impl AgendaPanel {
+    pub fn new(
+        graphics: &mut Graphics,
+        agenda: watch::Receiver<Option<Agenda>>,
+        hours: Option<TimeRange>,
+    ) -> Result<Self> {
+        let text = graphics
+            .create_text_container()
+            .context("Cannot create agenda text container")?;
+        Ok(Self {
+            agenda,
+            hours,
+            text,
+            background: None,
+            visible: false,
+        })
+    }
+
+    /// The same panel for new `graphics`, e.g. after the GL context was lost. It's laid out
+    /// again on its next update.
+    pub fn recreate(&self, graphics: &mut Graphics) -> Result<Self> {
+        Self::new(graphics, self.agenda.clone(), self.hours)
+    }
+
+    /// Follows agenda updates and display hours, returns whether a redraw is needed.
+    pub fn update(
+        &mut self,
+        graphics: &mut Graphics,
+        config: &Settings,
+        now: NaiveTime,
+    ) -> Result<bool> {
+        let changed = self.agenda.has_changed().unwrap_or(false);
+        let visible =
+            self.hours.is_none_or(|hours| hours.contains(now)) && self.agenda.borrow().is_some();
+        if !changed && visible == self.visible {
+            return Ok(false);
+        }
+        let was_visible = std::mem::replace(&mut self.visible, visible);
+        let agenda = self.agenda.borrow_and_update().clone();
+        let Some(agenda) = agenda.filter(|_| visible) else {
+            graphics.set_reserved_right(0);
+            return Ok(was_visible);
+        };
+
+        let screen = graphics.get_dimensions().as_::<f32>();
+        let width = (screen.w * PANEL_WIDTH_RATIO).round();
+        graphics.set_reserved_right(width as u32);
+        self.text.set_layout(Self::layout(&agenda, config, width));
+        self.text
+            .set_position(Vec2::new(screen.w - width + PADDING, PADDING));
+
+        let rect = RectShape::filled(
+            epaint::Rect::from_min_size(Pos2::ZERO, epaint::Vec2::new(width, screen.h)),
+            0.0,
+            Color32::BLACK.linear_multiply(BACKGROUND_ALPHA),
+        );
+        let mut background = graphics.create_shape(rect.into(), None)?;
+        background.set_position(Vec2::new(screen.w - width, 0.));
+        self.background = Some(background);
+        Ok(true)
+    }
+
+    fn layout(agenda: &Agenda, config: &Settings, width: f32) -> LayoutJob {
+        let title_format =
+            TextFormat::simple(FontId::proportional(TITLE_FONT_SIZE), Color32::WHITE);
+        let time_format =
+            TextFormat::simple(FontId::proportional(EVENT_FONT_SIZE), Color32::LIGHT_GRAY);
+        let event_format =
+            TextFormat::simple(FontId::proportional(EVENT_FONT_SIZE), Color32::WHITE);
+
+        let mut job = LayoutJob::default();
+        job.wrap.max_width = width - PADDING * 2.;
+        let day = agenda
+            .day
+            .format_localized(
+                "%A %e %B",
+                config.caption.date_format.locale.first().for_dates(),
+            )
+            .to_string();
+        job.append(&day, 0., title_format);
+        if agenda.stale {
+            // The calendar could not be refreshed, events may be outdated
+            job.append(
+                " (!)",
+                0.,
+                TextFormat::simple(FontId::proportional(TITLE_FONT_SIZE), Color32::ORANGE),
+            );
+        }
+        job.append("\n", 0., event_format.clone());
+
+        if agenda.events.is_empty() {
+            job.append("\n-", 0., time_format.clone());
+        }
+        for event in &agenda.events {
+            let time = match event.start {
+                Some(start) => start.format("%H:%M").to_string(),
+                None => "--:--".into(),
+            };
+            job.append(&format!("\n{time}  "), 0., time_format.clone());
+            job.append(&event.title, 0., event_format.clone());
+        }
+        job
+    }
+}
+
+impl Drawable for AgendaPanel {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        if !self.visible {
+            return Ok(());
+        }
+        if let Some(background) = &self.background {
+            background.draw(graphics)?;
+        }
+        self.text.draw(graphics)
+    }
+}
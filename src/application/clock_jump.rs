@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::TimeDelta;
+
+/// Smaller differences between the wall clock and the monotonic clock are drift or
+/// scheduling delays, not the clock being set.
+const MIN_CLOCK_JUMP: Duration = Duration::from_secs(60);
+
+/// Notices when the wall clock is set, e.g. by NTP once a device without real time clock
+/// is online, by comparing its progress with the monotonic clock.
+pub struct ClockJumpDetector {
+    last_wall: SystemTime,
+    last_monotonic: Instant,
+}
+
+impl ClockJumpDetector {
+    pub fn new(wall: SystemTime, now: Instant) -> Self {
+        Self {
+            last_wall: wall,
+            last_monotonic: now,
+        }
+    }
+
+    /// How far the wall clock jumped since the last check, negative when set backward.
+    pub fn check(&mut self, wall: SystemTime, now: Instant) -> Option<TimeDelta> {
+        let wall_elapsed = match wall.duration_since(self.last_wall) {
+            Ok(elapsed) => TimeDelta::from_std(elapsed).unwrap_or(TimeDelta::MAX),
+            Err(err) => -TimeDelta::from_std(err.duration()).unwrap_or(TimeDelta::MAX),
+        };
+        let monotonic_elapsed =
+            TimeDelta::from_std(now.saturating_duration_since(self.last_monotonic))
+                .unwrap_or(TimeDelta::MAX);
+        self.last_wall = wall;
+        self.last_monotonic = now;
+
+        let jump = wall_elapsed - monotonic_elapsed;
+        (jump.abs().to_std().unwrap_or(Duration::MAX) > MIN_CLOCK_JUMP).then_some(jump)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant, SystemTime};
+
+    use chrono::TimeDelta;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, none, some},
+    };
+
+    use super::ClockJumpDetector;
+
+    #[gtest]
+    fn test_clocks_progressing_together() {
+        let wall = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let now = Instant::now();
+        let mut detector = ClockJumpDetector::new(wall, now);
+
+        let elapsed = Duration::from_secs(10);
+        expect_that!(detector.check(wall + elapsed, now + elapsed), none());
+        // A late loop or a small NTP correction
+        expect_that!(
+            detector.check(
+                wall + elapsed * 2 + Duration::from_secs(30),
+                now + elapsed * 2
+            ),
+            none()
+        );
+    }
+
+    #[gtest]
+    fn test_clock_set_after_boot() {
+        let wall = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let now = Instant::now();
+        let mut detector = ClockJumpDetector::new(wall, now);
+
+        let elapsed = Duration::from_secs(1);
+        let set = wall + Duration::from_secs(3 * 3600);
+        expect_that!(
+            detector.check(set, now + elapsed),
+            some(eq(TimeDelta::seconds(3 * 3600 - 1)))
+        );
+        // Only reported once
+        expect_that!(detector.check(set + elapsed, now + elapsed * 2), none());
+    }
+
+    #[gtest]
+    fn test_clock_set_backward() {
+        let wall = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let now = Instant::now();
+        let mut detector = ClockJumpDetector::new(wall, now);
+
+        expect_that!(
+            detector.check(wall - Duration::from_secs(3600), now),
+            some(eq(TimeDelta::seconds(-3600)))
+        );
+    }
+}
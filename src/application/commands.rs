@@ -0,0 +1,159 @@
+use struct_patch::Merge;
+
+use super::ControlCommand;
+
+/// Reduces a burst of commands, e.g. from a misbehaving automation, to the ones changing
+/// something, in the same order:
+/// - consecutive [`ControlCommand::NextSlide`] are shown once, also across settings changes
+///   keeping the display duration,
+/// - consecutive [`ControlCommand::DisplayOn`] and [`ControlCommand::DisplayOff`] leave the
+///   last one,
+/// - consecutive [`ControlCommand::ConfigChanged`] are merged, later values first, to be
+///   applied and saved once.
+pub fn coalesce(commands: Vec<ControlCommand>) -> Vec<ControlCommand> {
+    let mut coalesced: Vec<ControlCommand> = Vec::with_capacity(commands.len());
+    for command in commands {
+        if matches!(command, ControlCommand::NextSlide) && pending_next_slide(&coalesced) {
+            continue;
+        }
+        let kept = match (coalesced.last_mut(), command) {
+            (
+                Some(last @ (ControlCommand::DisplayOn | ControlCommand::DisplayOff)),
+                command @ (ControlCommand::DisplayOn | ControlCommand::DisplayOff),
+            ) => {
+                *last = command;
+                None
+            }
+            (Some(ControlCommand::ConfigChanged(last)), ControlCommand::ConfigChanged(patch)) => {
+                *last = std::mem::take(last).merge(patch);
+                None
+            }
+            (_, command) => Some(command),
+        };
+        coalesced.extend(kept);
+    }
+    coalesced
+}
+
+/// Whether a next slide is already asked for once the settings changes since then are
+/// applied, a change of the display duration must be applied before.
+fn pending_next_slide(commands: &[ControlCommand]) -> bool {
+    for command in commands.iter().rev() {
+        match command {
+            ControlCommand::NextSlide => return true,
+            ControlCommand::ConfigChanged(patch) if patch.display_duration.is_none() => {}
+            _ => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use googletest::{expect_that, gtest, prelude::eq};
+
+    use super::coalesce;
+    use crate::{application::ControlCommand, configuration::SettingsPatch};
+
+    fn names(commands: &[ControlCommand]) -> Vec<&'static str> {
+        commands
+            .iter()
+            .map(|command| match command {
+                ControlCommand::NextSlide => "next",
+                ControlCommand::DisplayOn => "on",
+                ControlCommand::DisplayOff => "off",
+                ControlCommand::ConfigChanged(_) => "config",
+                ControlCommand::Pin => "pin",
+                ControlCommand::Unpin => "unpin",
+                ControlCommand::SelectSource(_) => "source",
+                ControlCommand::GuestMode(_) => "guest",
+                ControlCommand::ShowSynced(_) => "synced",
+            })
+            .collect()
+    }
+
+    fn config(patch: SettingsPatch) -> ControlCommand {
+        ControlCommand::ConfigChanged(patch)
+    }
+
+    #[gtest]
+    fn test_next_slide_burst_shown_once() {
+        let commands = (0..500).map(|_| ControlCommand::NextSlide).collect();
+        expect_that!(names(&coalesce(commands)), eq(&vec!["next"]));
+
+        // Kept in order around other commands
+        let commands = vec![
+            ControlCommand::NextSlide,
+            ControlCommand::Pin,
+            ControlCommand::NextSlide,
+        ];
+        expect_that!(names(&coalesce(commands)), eq(&vec!["next", "pin", "next"]));
+    }
+
+    #[gtest]
+    fn test_display_commands_keep_final_state() {
+        let commands = vec![
+            ControlCommand::DisplayOff,
+            ControlCommand::DisplayOn,
+            ControlCommand::DisplayOff,
+            ControlCommand::DisplayOn,
+        ];
+        expect_that!(names(&coalesce(commands)), eq(&vec!["on"]));
+
+        let commands = vec![ControlCommand::DisplayOn, ControlCommand::DisplayOff];
+        expect_that!(names(&coalesce(commands)), eq(&vec!["off"]));
+    }
+
+    #[gtest]
+    fn test_config_changes_merged() {
+        let commands = vec![
+            config(SettingsPatch {
+                display_duration: Some(Duration::from_secs(10)),
+                show_album_headers: Some(true),
+                ..Default::default()
+            }),
+            config(SettingsPatch {
+                display_duration: Some(Duration::from_secs(20)),
+                ..Default::default()
+            }),
+        ];
+        let coalesced = coalesce(commands);
+        expect_that!(names(&coalesced), eq(&vec!["config"]));
+        let ControlCommand::ConfigChanged(patch) = &coalesced[0] else {
+            panic!("Not a settings change");
+        };
+        expect_that!(patch.display_duration, eq(Some(Duration::from_secs(20))));
+        expect_that!(patch.show_album_headers, eq(Some(true)));
+    }
+
+    #[gtest]
+    fn test_next_slide_not_moved_across_display_duration_change() {
+        let duration = config(SettingsPatch {
+            display_duration: Some(Duration::from_secs(5)),
+            ..Default::default()
+        });
+        let commands = vec![
+            ControlCommand::NextSlide,
+            duration,
+            ControlCommand::NextSlide,
+        ];
+        expect_that!(
+            names(&coalesce(commands)),
+            eq(&vec!["next", "config", "next"])
+        );
+
+        // Other settings don't change when the next photo is due
+        let headers = config(SettingsPatch {
+            show_album_headers: Some(true),
+            ..Default::default()
+        });
+        let commands = vec![
+            ControlCommand::NextSlide,
+            headers,
+            ControlCommand::NextSlide,
+        ];
+        expect_that!(names(&coalesce(commands)), eq(&vec!["next", "config"]));
+    }
+}
@@ -1,16 +1,49 @@
 use std::path::PathBuf;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use config::Config;
 use directories::ProjectDirs;
 use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 use struct_patch::Merge;
 
-use crate::configuration::{AppConfig, Settings, SettingsPatch};
+use super::profiles::Profiles;
+use crate::configuration::{lenient, AppConfig, Settings, SettingsPatch};
+
+/// Stored next to the dynamic settings file.
+const PINNED_PHOTO_FILE: &str = "pinned-photo.json";
+/// Stored next to the dynamic settings file, see [`ConfigProvider::load_mqtt_id`].
+const MQTT_ID_FILE: &str = "mqtt-id";
+
+/// Photo kept on screen across restarts until unpinned.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PinnedPhoto {
+    pub asset_id: String,
+}
+
+/// What the frame runs with, to check a setup without guessing how the settings files were
+/// merged. Secrets of the configuration are redacted.
+#[derive(Serialize, Debug)]
+pub struct EffectiveConfig {
+    pub config: AppConfig,
+    /// Settings with their saved overrides and the active profile applied.
+    pub settings: Settings,
+}
+
+impl EffectiveConfig {
+    pub fn new(config: &AppConfig, settings: Settings) -> Self {
+        Self {
+            config: config.redacted(),
+            settings,
+        }
+    }
+}
 
 pub struct ConfigProvider {
     dynamic_settings_path: Option<PathBuf>,
     settings_path: String,
+    /// Fail on unknown fields instead of ignoring them, see [`lenient::deserialize`].
+    strict: bool,
 }
 
 impl ConfigProvider {
@@ -29,9 +62,16 @@ impl ConfigProvider {
         ConfigProvider {
             dynamic_settings_path,
             settings_path,
+            strict: false,
         }
     }
 
+    /// Rejects unknown fields, to validate the configuration files.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
     pub fn load_settings(&self) -> Result<Settings> {
         let mut builder =
             Config::builder().add_source(::config::File::with_name(&self.settings_path));
@@ -43,9 +83,8 @@ impl ConfigProvider {
         }
 
         let settings = builder.build().context("Cannot parse configuration")?;
-        let config: Settings = settings
-            .try_deserialize()
-            .context("Cannot deserialize settings")?;
+        let config: Settings =
+            lenient::deserialize(settings, self.strict).context("Cannot deserialize settings")?;
         Ok(config)
     }
 
@@ -55,12 +94,17 @@ impl ConfigProvider {
             .add_source(::config::File::with_name(&config_path))
             .build()
             .context("Cannot parse configuration")?;
-        let config: AppConfig = settings
-            .try_deserialize()
-            .context("Cannot deserialize sources")?;
+        let config: AppConfig =
+            lenient::deserialize(settings, self.strict).context("Cannot deserialize sources")?;
         Ok(config)
     }
 
+    /// Settings as loaded at startup, with the profiles of `config`.
+    pub fn load_effective_config(&self, config: &AppConfig) -> Result<EffectiveConfig> {
+        let profiles = Profiles::new(config.profiles.clone(), self.load_settings()?);
+        Ok(EffectiveConfig::new(config, profiles.settings()))
+    }
+
     pub fn save_settings_override(&self, settings: &SettingsPatch) -> Result<()> {
         if let Some(dynamic_settings_path) = &self.dynamic_settings_path {
             let existing_patch = if dynamic_settings_path.exists() {
@@ -88,6 +132,95 @@ impl ConfigProvider {
         }
         Ok(())
     }
+
+    /// Directory where the dynamic settings and pinned photo are saved.
+    pub fn storage_dir(&self) -> Option<PathBuf> {
+        self.dynamic_settings_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(PathBuf::from)
+    }
+
+    fn pinned_photo_path(&self) -> Option<PathBuf> {
+        self.dynamic_settings_path
+            .as_ref()
+            .map(|path| path.with_file_name(PINNED_PHOTO_FILE))
+    }
+
+    pub fn load_pinned_photo(&self) -> Result<Option<PinnedPhoto>> {
+        let Some(path) = self.pinned_photo_path().filter(|path| path.exists()) else {
+            return Ok(None);
+        };
+        let file = std::fs::File::open(path).context("Cannot open pinned photo file")?;
+        serde_json::from_reader(file)
+            .map(Some)
+            .context("Cannot parse pinned photo file")
+    }
+
+    /// Persist the pinned photo, or forget it when `pinned` is `None`.
+    pub fn save_pinned_photo(&self, pinned: Option<&PinnedPhoto>) -> Result<()> {
+        let Some(path) = self.pinned_photo_path() else {
+            warn!("Dynamic settings path is not set; cannot save pinned photo");
+            return Ok(());
+        };
+        match pinned {
+            Some(pinned) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)
+                        .context("Cannot create directories for pinned photo file")?;
+                }
+                let writer =
+                    std::fs::File::create(&path).context("Cannot create pinned photo file")?;
+                serde_json::to_writer(writer, pinned)
+                    .context("Cannot serialize pinned photo file")?;
+            }
+            None if path.exists() => {
+                std::fs::remove_file(&path).context("Cannot remove pinned photo file")?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn mqtt_id_path(&self) -> Option<PathBuf> {
+        self.dynamic_settings_path
+            .as_ref()
+            .map(|path| path.with_file_name(MQTT_ID_FILE))
+    }
+
+    /// Identity of the frame in Home Assistant generated on the first run, kept when the
+    /// machine id changes e.g. once the SD card is flashed again.
+    pub fn load_mqtt_id(&self) -> Result<Option<String>> {
+        let Some(path) = self.mqtt_id_path().filter(|path| path.exists()) else {
+            return Ok(None);
+        };
+        let id = std::fs::read_to_string(path).context("Cannot read MQTT id file")?;
+        let id = id.trim();
+        if id.is_empty() {
+            bail!("MQTT id file is empty");
+        }
+        Ok(Some(id.to_owned()))
+    }
+
+    pub fn save_mqtt_id(&self, id: &str) -> Result<()> {
+        let path = self
+            .mqtt_id_path()
+            .context("Dynamic settings path is not set; cannot save MQTT id")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("Cannot create directories for MQTT id file")?;
+        }
+        std::fs::write(&path, id).context("Cannot write MQTT id file")
+    }
+
+    /// Provider saving its files in `dir`, the static settings being the default ones.
+    #[cfg(test)]
+    pub fn in_dir(dir: &std::path::Path) -> Self {
+        ConfigProvider {
+            dynamic_settings_path: Some(dir.join("settings.json")),
+            settings_path: "settings".to_string(),
+            strict: false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -97,8 +230,10 @@ mod tests {
     use googletest::{expect_that, gtest, prelude::eq};
     use temp_dir::TempDir;
 
-    use super::ConfigProvider;
-    use crate::configuration::SettingsPatch;
+    use super::{ConfigProvider, PinnedPhoto};
+    use crate::configuration::{
+        AppConfig, ImmichInstance, ImmichSource, MqttConfig, MqttCredentials, SettingsPatch, Source,
+    };
 
     #[gtest]
     fn test_load_default_settings() {
@@ -113,6 +248,7 @@ mod tests {
                 .to_str()
                 .unwrap()
                 .to_string(),
+            strict: false,
         };
         let settings = provider.load_settings().unwrap();
         expect_that!(settings.debug.show_fps, eq(false));
@@ -132,6 +268,7 @@ mod tests {
                 .to_str()
                 .unwrap()
                 .to_string(),
+            strict: false,
         };
         let settings = provider.load_settings().unwrap();
         expect_that!(settings.transition_duration, eq(Duration::from_millis(500)));
@@ -153,6 +290,7 @@ debug:
                 .to_str()
                 .unwrap()
                 .to_string(),
+            strict: false,
         };
         let settings = provider.load_settings().unwrap();
         expect_that!(settings.debug.show_fps, eq(true));
@@ -179,6 +317,7 @@ debug:
                 .to_str()
                 .unwrap()
                 .to_string(),
+            strict: false,
         };
         let settings = provider.load_settings().unwrap();
         expect_that!(settings.debug.show_fps, eq(false));
@@ -202,6 +341,7 @@ debug:
                 .to_str()
                 .unwrap()
                 .to_string(),
+            strict: false,
         };
         let settings = provider.load_settings().unwrap();
         expect_that!(settings.debug.show_fps, eq(true));
@@ -209,6 +349,7 @@ debug:
             .save_settings_override(&SettingsPatch {
                 debug: Some(crate::configuration::DebugSettingsPatch {
                     show_fps: Some(false),
+                    ..Default::default()
                 }),
                 ..Default::default()
             })
@@ -222,6 +363,117 @@ debug:
         assert_eq!(settings.display_duration, Duration::from_secs(51));
     }
 
+    #[gtest]
+    fn test_pinned_photo_round_trip() {
+        let dir = empty_dir().unwrap();
+        let provider = ConfigProvider {
+            dynamic_settings_path: Some(dir.path().join("settings.json")),
+            settings_path: "settings".to_string(),
+            strict: false,
+        };
+        expect_that!(provider.load_pinned_photo().unwrap(), eq(None));
+
+        let pinned = PinnedPhoto {
+            asset_id: "asset-id".to_string(),
+        };
+        provider.save_pinned_photo(Some(&pinned)).unwrap();
+        expect_that!(provider.load_pinned_photo().unwrap(), eq(Some(pinned)));
+
+        provider.save_pinned_photo(None).unwrap();
+        expect_that!(provider.load_pinned_photo().unwrap(), eq(None));
+    }
+
+    #[gtest]
+    fn test_mqtt_id_round_trip() {
+        let dir = empty_dir().unwrap();
+        let provider = ConfigProvider::in_dir(dir.path());
+        expect_that!(provider.load_mqtt_id().unwrap(), eq(None));
+
+        provider.save_mqtt_id("0123abcd").unwrap();
+        expect_that!(
+            provider.load_mqtt_id().unwrap(),
+            eq(Some("0123abcd".to_string()))
+        );
+        // Written by hand
+        std::fs::write(dir.path().join("mqtt-id"), "kitchen\n").unwrap();
+        expect_that!(
+            provider.load_mqtt_id().unwrap(),
+            eq(Some("kitchen".to_string()))
+        );
+    }
+
+    #[gtest]
+    fn test_effective_config_includes_overrides() {
+        let settings = r#"---
+display_duration: "20s"
+debug:
+  show_fps: true
+"#;
+        let settings_dir = gen_settings_from_str(settings).unwrap();
+        let overload_dir = empty_dir().unwrap();
+        let provider = ConfigProvider {
+            dynamic_settings_path: Some(overload_dir.path().join("settings.yaml")),
+            settings_path: settings_dir
+                .path()
+                .join("settings.yaml")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            strict: false,
+        };
+        provider
+            .save_settings_override(&SettingsPatch {
+                display_duration: Some(Duration::from_secs(51)),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let effective = provider
+            .load_effective_config(&AppConfig::default())
+            .unwrap();
+        expect_that!(
+            serde_json::to_value(&effective.settings).unwrap(),
+            eq(&serde_json::to_value(provider.load_settings().unwrap()).unwrap())
+        );
+        expect_that!(
+            effective.settings.display_duration,
+            eq(Duration::from_secs(51))
+        );
+        expect_that!(effective.settings.debug.show_fps, eq(true));
+    }
+
+    #[gtest]
+    fn test_effective_config_redacts_secrets() {
+        let config = AppConfig {
+            sources: vec![Source::Immich(ImmichSource {
+                instance: Some(ImmichInstance {
+                    url: "http://immich:2283".to_string(),
+                    api_key: "0123456789abcdefWXYZ".to_string(),
+                    asset_quality: Default::default(),
+                    in_memory_download_mb: 8,
+                    max_download_mb: 200,
+                    background_thumbnail: true,
+                }),
+                ..Default::default()
+            })],
+            mqtt: Some(MqttConfig {
+                credentials: Some(MqttCredentials {
+                    username: "frame".to_string(),
+                    password: "short".to_string(),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let dump = serde_json::to_string(&config.redacted()).unwrap();
+        expect_that!(dump.contains("0123456789abcdef"), eq(false));
+        expect_that!(dump.contains(r#""api_key":"***WXYZ""#), eq(true));
+        expect_that!(dump.contains(r#""password":"***""#), eq(true));
+        expect_that!(dump.contains(r#""username":"frame""#), eq(true));
+        expect_that!(dump.contains("http://immich:2283"), eq(true));
+    }
+
     fn gen_settings_from_str(s: &str) -> Result<TempDir, anyhow::Error> {
         let temp_dir = TempDir::new().unwrap();
         let settings_path = temp_dir.path().join("settings.yaml");
@@ -0,0 +1,176 @@
+use std::{
+    ffi::CString,
+    mem::MaybeUninit,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use crate::configuration::StorageConfig;
+
+/// Checks the free space where files are written, disabling writes when it runs low so
+/// that a full SD card doesn't prevent the system from working.
+pub struct DiskSpaceMonitor {
+    path: Option<PathBuf>,
+    min_free_space: u64,
+    interval: Duration,
+    last_check: Option<Instant>,
+    writable: bool,
+}
+
+impl DiskSpaceMonitor {
+    /// Monitors the file system of `path`, nothing is checked without one.
+    pub fn new(path: Option<PathBuf>, config: &StorageConfig) -> Self {
+        Self {
+            path,
+            min_free_space: config.min_free_space_mb.saturating_mul(1024 * 1024),
+            interval: config.check_interval,
+            last_check: None,
+            writable: true,
+        }
+    }
+
+    /// Checks the free space again when the check interval elapsed.
+    pub fn check_if_due(&mut self, now: Instant) {
+        if self
+            .last_check
+            .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            return;
+        }
+        self.last_check = Some(now);
+        if let Some(path) = &self.path {
+            let available = available_space(path);
+            self.update(available);
+        }
+    }
+
+    /// Whether there is enough free space to write files.
+    pub fn writable(&mut self) -> bool {
+        self.check_if_due(Instant::now());
+        self.writable
+    }
+
+    fn update(&mut self, available: Result<u64>) {
+        let available = match available {
+            Ok(available) => available,
+            Err(err) => {
+                // Unknown free space must not disable writes
+                warn!("Cannot check free disk space: {:?}", err);
+                return;
+            }
+        };
+        let writable = available >= self.min_free_space;
+        if writable != self.writable {
+            if writable {
+                info!(
+                    "{} MB of free disk space, saving files again",
+                    available / 1024 / 1024
+                );
+            } else {
+                warn!(
+                    "Only {} MB of free disk space left, below the {} MB minimum: settings and \
+                     pinned photo are not saved anymore",
+                    available / 1024 / 1024,
+                    self.min_free_space / 1024 / 1024
+                );
+            }
+        }
+        self.writable = writable;
+    }
+}
+
+/// Free space available to unprivileged users on the file system of `path`. Missing
+/// directories are resolved to their closest existing parent.
+fn available_space(path: &Path) -> Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|dir| dir.exists())
+        .context("No existing parent directory")?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).context("Invalid path")?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Cannot get file system stats of {}", existing.display()));
+    }
+    let stats = unsafe { stats.assume_init() };
+    // Field types depend on the platform
+    #[allow(clippy::unnecessary_cast)]
+    let available = stats.f_bavail as u64 * stats.f_frsize as u64;
+    Ok(available)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use anyhow::anyhow;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{gt, is_false, is_true},
+    };
+    use temp_dir::TempDir;
+
+    use super::{available_space, DiskSpaceMonitor};
+    use crate::configuration::StorageConfig;
+
+    fn monitor() -> DiskSpaceMonitor {
+        DiskSpaceMonitor::new(
+            None,
+            &StorageConfig {
+                min_free_space_mb: 20,
+                check_interval: Duration::from_secs(60),
+            },
+        )
+    }
+
+    #[gtest]
+    fn test_low_space_disables_writes() {
+        let mut monitor = monitor();
+        monitor.update(Ok(10 * 1024 * 1024));
+        expect_that!(monitor.writable, is_false());
+        monitor.update(Ok(30 * 1024 * 1024));
+        expect_that!(monitor.writable, is_true());
+    }
+
+    #[gtest]
+    fn test_unknown_space_keeps_state() {
+        let mut monitor = monitor();
+        monitor.update(Err(anyhow!("unsupported")));
+        expect_that!(monitor.writable, is_true());
+        monitor.update(Ok(0));
+        monitor.update(Err(anyhow!("unsupported")));
+        expect_that!(monitor.writable, is_false());
+    }
+
+    #[gtest]
+    fn test_checks_are_spaced() {
+        let dir = TempDir::new().unwrap();
+        let mut monitor = DiskSpaceMonitor::new(
+            Some(dir.path().to_owned()),
+            &StorageConfig {
+                min_free_space_mb: u64::MAX,
+                check_interval: Duration::from_secs(60),
+            },
+        );
+        let start = Instant::now();
+        monitor.check_if_due(start);
+        expect_that!(monitor.writable, is_false());
+
+        monitor.writable = true;
+        monitor.check_if_due(start + Duration::from_secs(30));
+        expect_that!(monitor.writable, is_true());
+        monitor.check_if_due(start + Duration::from_secs(61));
+        expect_that!(monitor.writable, is_false());
+    }
+
+    #[gtest]
+    fn test_available_space_of_missing_directory() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("not/created/yet");
+        expect_that!(available_space(&missing).unwrap(), gt(0));
+    }
+}
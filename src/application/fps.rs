@@ -7,7 +7,7 @@ use epaint::{
 };
 use log::debug;
 
-use crate::graphics::{Drawable, Graphics, TextContainer};
+use crate::graphics::{Drawable, Graphics, TessellationStats, TextContainer};
 
 pub struct FPSCounter {
     last_fps: u32,
@@ -17,7 +17,14 @@ pub struct FPSCounter {
 }
 
 impl FPSCounter {
-    pub fn count_frame(&mut self, now: Instant) {
+    /// Counts a frame, with the text tessellation work of the previous one and the opacity
+    /// of the photo, as animated and as drawn.
+    pub fn count_frame(
+        &mut self,
+        now: Instant,
+        text: TessellationStats,
+        opacity: Option<(f32, f32)>,
+    ) {
         let elapsed = now - self.last_instant;
         if elapsed > Duration::from_secs(1) {
             self.last_fps = self.frames;
@@ -27,8 +34,15 @@ impl FPSCounter {
         }
         self.frames += 1;
 
+        let mut hud = format!(
+            "FPS: {} ({} frames)\nText: {:?} ({} tessellated, {} cached)",
+            self.last_fps, self.frames, text.time, text.tessellated, text.cached
+        );
+        if let Some((raw, drawn)) = opacity {
+            hud.push_str(&format!("\nOpacity: {raw:.2} ({drawn:.2} drawn)"));
+        }
         self.fps_text.set_layout(LayoutJob::single_section(
-            format!("FPS: {} ({} frames)", self.last_fps, self.frames),
+            hud,
             TextFormat {
                 background: Color32::RED,
                 ..TextFormat::simple(FontId::proportional(28.), Color32::DEBUG_COLOR)
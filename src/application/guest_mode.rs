@@ -0,0 +1,130 @@
+use std::{ops::Range, time::Instant};
+
+use log::{info, warn};
+use struct_patch::Patch;
+
+use crate::configuration::{CaptionOptionsPatch, GuestModeConfig, Settings, SettingsPatch};
+
+/// Shows a single source with settings hiding private details, until it's switched off or
+/// times out. See [`GuestModeConfig`].
+pub struct GuestMode {
+    /// Galleries of the source, see [`crate::gallery::source_galleries`].
+    galleries: Range<usize>,
+    settings: SettingsPatch,
+    config: GuestModeConfig,
+    /// End of the guest mode, set while it's on.
+    until: Option<Instant>,
+}
+
+impl GuestMode {
+    /// Shows the photos of `galleries`, those built from the configured source.
+    pub fn new(config: GuestModeConfig, galleries: Range<usize>) -> Self {
+        let mut settings = config.settings.clone();
+        if settings.active_profile.take().is_some() {
+            warn!("Guest mode cannot select a profile, ignoring it");
+        }
+        // Dates and places are not for guests to see
+        settings.caption = Some(CaptionOptionsPatch {
+            enabled: Some(false),
+            ..settings.caption.unwrap_or_default()
+        });
+        Self {
+            galleries,
+            settings,
+            config,
+            until: None,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.until.is_some()
+    }
+
+    /// Galleries shown to guests.
+    pub fn galleries(&self) -> Range<usize> {
+        self.galleries.clone()
+    }
+
+    /// Switches the guest mode on until the configured duration elapsed, or off.
+    pub fn set(&mut self, on: bool, now: Instant) {
+        self.until = on.then(|| now + self.config.duration);
+        if on {
+            info!("Guest mode on for {:?}", self.config.duration);
+        } else {
+            info!("Guest mode off");
+        }
+    }
+
+    /// Whether the guest mode is on but timed out at `now`.
+    pub fn has_expired(&self, now: Instant) -> bool {
+        self.until.is_some_and(|until| now >= until)
+    }
+
+    /// Applies the guest settings over `settings` while the guest mode is on.
+    pub fn apply(&self, settings: &mut Settings) {
+        if self.is_on() {
+            settings.apply(self.settings.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true},
+    };
+
+    use super::GuestMode;
+    use crate::configuration::{GuestModeConfig, Settings, SettingsPatch};
+
+    fn guest_mode() -> GuestMode {
+        GuestMode::new(
+            GuestModeConfig {
+                source: 1,
+                settings: SettingsPatch {
+                    display_duration: Some(Duration::from_secs(10)),
+                    active_profile: Some("evening".into()),
+                    ..Default::default()
+                },
+                duration: Duration::from_secs(60),
+            },
+            1..3,
+        )
+    }
+
+    #[gtest]
+    fn test_guest_settings_hide_captions() {
+        let mut guest_mode = guest_mode();
+        let mut settings = Settings::default();
+        guest_mode.apply(&mut settings);
+        expect_that!(settings.caption.enabled, is_true());
+
+        guest_mode.set(true, Instant::now());
+        guest_mode.apply(&mut settings);
+        expect_that!(settings.caption.enabled, is_false());
+        expect_that!(settings.display_duration, eq(Duration::from_secs(10)));
+        expect_that!(settings.active_profile, eq(""));
+    }
+
+    #[gtest]
+    fn test_guest_mode_expires() {
+        let mut guest_mode = guest_mode();
+        let now = Instant::now();
+        guest_mode.set(true, now);
+        expect_that!(guest_mode.has_expired(now), is_false());
+        expect_that!(
+            guest_mode.has_expired(now + Duration::from_secs(60)),
+            is_true()
+        );
+
+        guest_mode.set(false, now);
+        expect_that!(guest_mode.is_on(), is_false());
+        expect_that!(
+            guest_mode.has_expired(now + Duration::from_secs(60)),
+            is_false()
+        );
+    }
+}
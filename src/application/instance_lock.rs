@@ -0,0 +1,341 @@
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions, Permissions},
+    io::{ErrorKind, Read, Seek, Write},
+    os::{fd::AsRawFd, unix::fs::PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::{debug, error, warn};
+
+use super::config_provider::ConfigProvider;
+
+/// Exit code of the process when another frame runs, systemd doesn't restart it.
+const ALREADY_RUNNING_EXIT_CODE: i32 = 3;
+/// Directory of the lock file, set by the session of the user or by the service.
+const RUNTIME_DIR_VAR: &str = "XDG_RUNTIME_DIR";
+/// Created in the runtime directory, see [`lock_path`].
+const LOCK_FILE: &str = "memocadre.lock";
+
+/// Another frame holds the lock.
+#[derive(Debug)]
+struct AlreadyRunning {
+    /// Process of the other frame, unknown when it didn't write it yet.
+    pid: Option<u32>,
+    path: PathBuf,
+}
+
+impl Display for AlreadyRunning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pid {
+            Some(pid) => write!(
+                f,
+                "Another frame runs with PID {pid}, holding {}",
+                self.path.display()
+            ),
+            None => write!(f, "Another frame runs, holding {}", self.path.display()),
+        }
+    }
+}
+
+impl std::error::Error for AlreadyRunning {}
+
+/// Tells whether processes still run, see [`SystemProcesses`].
+trait Processes {
+    fn is_running(&self, pid: u32) -> bool;
+}
+
+/// Processes of the system, checked with a null signal.
+struct SystemProcesses;
+
+impl Processes for SystemProcesses {
+    fn is_running(&self, pid: u32) -> bool {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        // No signal is sent, only the permission to send one is checked
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            return true;
+        }
+        // Running under another user
+        std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+}
+
+/// Advisory lock held while the frame runs, so that a second one started by mistake doesn't
+/// fight for the screen nor for the MQTT client id. The kernel releases it when the process
+/// exits, even on a crash.
+pub struct InstanceLock {
+    /// Locked as long as it's open.
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock at `path` and writes the PID of the frame in it. With `force`, the
+    /// lock is taken over when the process written in it is gone, e.g. when a stray child
+    /// still holds it.
+    fn acquire(path: &Path, force: bool, processes: &impl Processes) -> Result<Self> {
+        let mut file = open(path)?;
+        if !try_lock(&file)? {
+            match read_pid(&mut file) {
+                Some(pid) if force && !processes.is_running(pid) => {
+                    warn!("Taking the lock over, the frame with PID {pid} is gone");
+                }
+                pid => {
+                    return Err(AlreadyRunning {
+                        pid,
+                        path: path.to_owned(),
+                    }
+                    .into())
+                }
+            }
+            // The stray holder keeps its lock on the removed file
+            fs::remove_file(path).context("Cannot remove stale lock file")?;
+            file = open(path)?;
+            if !try_lock(&file)? {
+                return Err(AlreadyRunning {
+                    pid: read_pid(&mut file),
+                    path: path.to_owned(),
+                }
+                .into());
+            }
+        }
+        file.set_len(0).context("Cannot truncate lock file")?;
+        file.rewind().context("Cannot rewind lock file")?;
+        write!(file, "{}", std::process::id()).context("Cannot write PID to lock file")?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Opened without truncating, the PID of the frame holding it is read first. An existing
+/// file is opened without creating it, which the kernel may refuse for files of other users.
+fn open(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true);
+    let file = match options.open(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            match options.clone().create_new(true).open(path) {
+                Ok(file) => {
+                    // Frames started by other users lock it too, only its owner can change that
+                    let _ = file.set_permissions(Permissions::from_mode(0o666));
+                    Ok(file)
+                }
+                // Created by another frame meanwhile
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => options.open(path),
+                Err(err) => Err(err),
+            }
+        }
+        result => result,
+    };
+    file.with_context(|| format!("Cannot open lock file {}", path.display()))
+}
+
+/// Whether `file` was locked, `false` when another open file holds it.
+fn try_lock(file: &File) -> Result<bool> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        return Ok(false);
+    }
+    Err(err).context("Cannot lock file")
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+/// Lock file in the runtime directory, or next to the dynamic settings without one.
+fn lock_path(runtime_dir: Option<PathBuf>, storage_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let dir = runtime_dir.filter(|dir| !dir.as_os_str().is_empty());
+    Some(dir.or(storage_dir)?.join(LOCK_FILE))
+}
+
+/// Takes the lock at `path`, `None` when its directory doesn't exist: there is nowhere to
+/// look for another frame.
+fn lock_at(
+    path: Option<PathBuf>,
+    force: bool,
+    processes: &impl Processes,
+) -> Result<Option<InstanceLock>> {
+    let Some(path) = path.filter(|path| path.parent().is_some_and(Path::is_dir)) else {
+        warn!("No directory for the lock file, not checking for another frame");
+        return Ok(None);
+    };
+    let lock = InstanceLock::acquire(&path, force, processes)?;
+    debug!("Locked {}", path.display());
+    Ok(Some(lock))
+}
+
+/// Takes the lock before the screen is opened, exiting with [`ALREADY_RUNNING_EXIT_CODE`]
+/// when another frame holds it. Fails when the lock cannot be taken, as another frame
+/// may run.
+pub fn lock_instance(force: bool) -> Result<Option<InstanceLock>> {
+    let runtime_dir = std::env::var_os(RUNTIME_DIR_VAR).map(PathBuf::from);
+    let path = lock_path(runtime_dir, ConfigProvider::new().storage_dir());
+    lock_at(path, force, &SystemProcesses).map_err(|err| {
+        if let Some(running) = err.downcast_ref::<AlreadyRunning>() {
+            let hint = if force {
+                "stop it before starting another frame"
+            } else {
+                "--force takes the lock over once that process is gone"
+            };
+            error!("{running}, {hint}");
+            std::process::exit(ALREADY_RUNNING_EXIT_CODE);
+        }
+        err.context("Cannot check for another frame")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf, process::Command};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true, none, some},
+    };
+    use temp_dir::TempDir;
+
+    use super::{lock_at, lock_path, AlreadyRunning, InstanceLock, Processes, SystemProcesses};
+
+    struct FakeProcesses {
+        running: bool,
+    }
+
+    impl Processes for FakeProcesses {
+        fn is_running(&self, _pid: u32) -> bool {
+            self.running
+        }
+    }
+
+    fn holder(err: anyhow::Error) -> Option<u32> {
+        err.downcast_ref::<AlreadyRunning>()
+            .expect("Not refused for another frame")
+            .pid
+    }
+
+    #[gtest]
+    fn test_lock_path() {
+        let runtime = Some(PathBuf::from("/run/memocadre"));
+        let storage = Some(PathBuf::from("/var/lib/memocadre"));
+        expect_that!(
+            lock_path(runtime, storage.clone()),
+            some(eq(&PathBuf::from("/run/memocadre/memocadre.lock")))
+        );
+        expect_that!(
+            lock_path(Some(PathBuf::new()), storage.clone()),
+            some(eq(&PathBuf::from("/var/lib/memocadre/memocadre.lock")))
+        );
+        expect_that!(
+            lock_path(None, storage),
+            some(eq(&PathBuf::from("/var/lib/memocadre/memocadre.lock")))
+        );
+        expect_that!(lock_path(None, None), none());
+    }
+
+    #[gtest]
+    fn test_missing_dir_not_checked() {
+        let dir = TempDir::new().unwrap();
+        let running = FakeProcesses { running: true };
+        let path = dir.path().join("missing/memocadre.lock");
+        expect_that!(
+            lock_at(Some(path), false, &running).unwrap().is_none(),
+            is_true()
+        );
+        expect_that!(lock_at(None, false, &running).unwrap().is_none(), is_true());
+    }
+
+    #[gtest]
+    fn test_lock_file_that_cannot_be_opened_fails() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("memocadre.lock");
+        fs::create_dir(&path).unwrap();
+        let err = lock_at(Some(path), false, &FakeProcesses { running: true })
+            .err()
+            .unwrap();
+        expect_that!(err.downcast_ref::<AlreadyRunning>().is_none(), is_true());
+    }
+
+    #[gtest]
+    fn test_existing_lock_file_opened() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("memocadre.lock");
+        fs::write(&path, "").unwrap();
+        let lock = lock_at(Some(path.clone()), false, &FakeProcesses { running: true });
+        expect_that!(lock.unwrap().is_some(), is_true());
+        expect_that!(
+            fs::read_to_string(&path).unwrap(),
+            eq(&std::process::id().to_string())
+        );
+    }
+
+    #[gtest]
+    fn test_lock_file_shared_with_other_users() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("memocadre.lock");
+        let _lock = InstanceLock::acquire(&path, false, &FakeProcesses { running: true }).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        expect_that!(mode & 0o777, eq(0o666));
+    }
+
+    #[gtest]
+    fn test_second_frame_refused() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("memocadre.lock");
+        let running = FakeProcesses { running: true };
+        let _lock = InstanceLock::acquire(&path, false, &running).unwrap();
+        expect_that!(
+            fs::read_to_string(&path).unwrap(),
+            eq(&std::process::id().to_string())
+        );
+
+        let err = InstanceLock::acquire(&path, false, &running).err().unwrap();
+        expect_that!(holder(err), some(eq(std::process::id())));
+        // Not taken over while its process runs
+        let err = InstanceLock::acquire(&path, true, &running).err().unwrap();
+        expect_that!(holder(err), some(eq(std::process::id())));
+    }
+
+    #[gtest]
+    fn test_lock_of_gone_process_taken_over_with_force() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("memocadre.lock");
+        let gone = FakeProcesses { running: false };
+        // Still open, like in a stray child of the previous frame
+        let _stray = InstanceLock::acquire(&path, false, &gone).unwrap();
+
+        expect_that!(
+            InstanceLock::acquire(&path, false, &gone).is_err(),
+            is_true()
+        );
+        expect_that!(InstanceLock::acquire(&path, true, &gone).is_ok(), is_true());
+    }
+
+    #[gtest]
+    fn test_released_when_dropped() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("memocadre.lock");
+        let running = FakeProcesses { running: true };
+        drop(InstanceLock::acquire(&path, false, &running).unwrap());
+        expect_that!(
+            InstanceLock::acquire(&path, false, &running).is_ok(),
+            is_true()
+        );
+    }
+
+    #[gtest]
+    fn test_system_processes_liveness() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        expect_that!(SystemProcesses.is_running(child.id()), is_true());
+        child.kill().unwrap();
+        child.wait().unwrap();
+        expect_that!(SystemProcesses.is_running(child.id()), is_false());
+        expect_that!(SystemProcesses.is_running(u32::MAX), is_false());
+    }
+}
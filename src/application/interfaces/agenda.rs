@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use log::{info, warn};
+use tokio::sync::watch;
+
+use super::Interface;
+use crate::{
+    agenda::{self, Agenda},
+    configuration::AgendaConfig,
+    network,
+};
+
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+
+/// Downloads the calendar periodically and publishes today's events.
+pub struct AgendaFetcher {
+    config: AgendaConfig,
+    agenda: watch::Sender<Option<Agenda>>,
+    /// Changes when the wall clock was set, today may not be the same day anymore.
+    clock_jumps: watch::Receiver<()>,
+}
+
+impl AgendaFetcher {
+    pub fn new(
+        config: AgendaConfig,
+        agenda: watch::Sender<Option<Agenda>>,
+        clock_jumps: watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            config,
+            agenda,
+            clock_jumps,
+        }
+    }
+
+    fn download(url: &str) -> Result<String> {
+        let response = network::get(url)
+            .with_timeout(DOWNLOAD_TIMEOUT_SECS)
+            .send()
+            .context("Cannot download calendar")?;
+        if response.status_code >= 400 {
+            bail!(
+                "Cannot download calendar: {} {}",
+                response.status_code,
+                response.reason_phrase
+            );
+        }
+        Ok(response
+            .as_str()
+            .context("Calendar is not valid UTF-8")?
+            .to_owned())
+    }
+}
+
+impl Interface for AgendaFetcher {
+    async fn start(&self) -> Result<()> {
+        info!("Starting agenda fetcher");
+        let mut interval =
+            tokio::time::interval(self.config.refresh_interval.max(MIN_REFRESH_INTERVAL));
+        // Last calendar downloaded, kept to show something when the server is unreachable
+        let mut last_ics: Option<String> = None;
+        let mut clock_jumps = self.clock_jumps.clone();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                Ok(()) = clock_jumps.changed() => info!("Clock was set, refreshing agenda"),
+            }
+            let url = self.config.url.clone();
+            let downloaded = tokio::task::spawn_blocking(move || Self::download(&url))
+                .await
+                .context("Calendar download panicked")?;
+            let stale = match downloaded {
+                Ok(ics) => {
+                    last_ics = Some(ics);
+                    false
+                }
+                Err(err) => {
+                    warn!("Cannot refresh agenda, keeping the last one: {:?}", err);
+                    true
+                }
+            };
+            let Some(ics) = &last_ics else {
+                continue;
+            };
+            let day = Local::now().date_naive();
+            match agenda::events_on(ics, day) {
+                Ok(events) => {
+                    self.agenda
+                        .send_replace(Some(Agenda { day, events, stale }));
+                }
+                Err(err) => warn!("Cannot read agenda: {:?}", err),
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use log::debug;
+use serde::Serialize;
+use tokio::sync::{broadcast, watch};
+
+use crate::{application::ApplicationState, configuration::Settings};
+
+/// Events kept for clients reading slower than they happen, beyond they are told to
+/// refetch everything.
+const CLIENT_BACKLOG: usize = 16;
+/// Sent to idle clients, so that disconnected ones are noticed and forgotten.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Changes of the application streamed by GET /events, as Server-Sent Events.
+#[derive(Clone)]
+pub struct EventHub {
+    events: broadcast::Sender<Bytes>,
+    /// Id of the last event sent, they are numbered from 1.
+    last_id: Arc<AtomicU64>,
+    clients: Arc<AtomicUsize>,
+    max_clients: usize,
+}
+
+/// Payload of the "state" events.
+#[derive(Serialize)]
+struct StateEvent<'a> {
+    display: bool,
+    pinned: bool,
+    guest_mode: bool,
+    error: &'a Option<String>,
+}
+
+impl EventHub {
+    pub fn new(max_clients: usize) -> Self {
+        let (events, _) = broadcast::channel(CLIENT_BACKLOG);
+        Self {
+            events,
+            last_id: Arc::default(),
+            clients: Arc::default(),
+            max_clients,
+        }
+    }
+
+    /// Sends an event for each change of the state or settings, until the application
+    /// stops.
+    pub async fn publish(
+        &self,
+        mut state: watch::Receiver<ApplicationState>,
+        mut settings: watch::Receiver<Settings>,
+    ) -> Result<()> {
+        // Only changes are sent, clients fetch the current values first
+        state.borrow_and_update();
+        settings.borrow_and_update();
+        loop {
+            tokio::select! {
+                changed = state.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let state = state.borrow_and_update().clone();
+                    self.send("state", &StateEvent {
+                        display: state.display,
+                        pinned: state.pinned,
+                        guest_mode: state.guest_mode,
+                        error: &state.error,
+                    })?;
+                }
+                changed = settings.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let settings = settings.borrow_and_update().clone();
+                    self.send("settings", &settings)?;
+                }
+            }
+        }
+    }
+
+    fn send(&self, kind: &str, data: &impl Serialize) -> Result<()> {
+        let data = serde_json::to_string(data).context("Cannot serialize event")?;
+        let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+        // Fails without clients, the event is lost for nobody
+        let _ = self.events.send(sse_event(id, kind, &data));
+        Ok(())
+    }
+
+    /// Adds a client, `None` when there are too many of them already. A client
+    /// reconnecting with the id of the last event it received is told to refetch
+    /// everything when it missed some.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Option<EventClient> {
+        let slot = ClientSlot::take(&self.clients, self.max_clients)?;
+        let events = self.events.subscribe();
+        let last_id = self.last_id.load(Ordering::Relaxed);
+        Some(EventClient {
+            events,
+            pending: last_event_id
+                .filter(|&id| id != last_id)
+                .map(|_| resync_event(last_id)),
+            last_id: Arc::clone(&self.last_id),
+            _slot: slot,
+        })
+    }
+}
+
+/// Connection to GET /events, gone when its response is dropped on disconnection.
+pub struct EventClient {
+    events: broadcast::Receiver<Bytes>,
+    /// Sent before the next event.
+    pending: Option<Bytes>,
+    last_id: Arc<AtomicU64>,
+    _slot: ClientSlot,
+}
+
+impl EventClient {
+    /// Waits for the next event, a keep-alive comment after a while without any. Returns
+    /// `None` once the application stopped.
+    pub async fn next_chunk(&mut self) -> Option<Bytes> {
+        if let Some(chunk) = self.pending.take() {
+            return Some(chunk);
+        }
+        match tokio::time::timeout(KEEP_ALIVE_INTERVAL, self.events.recv()).await {
+            Ok(Ok(chunk)) => Some(chunk),
+            Ok(Err(broadcast::error::RecvError::Lagged(missed))) => {
+                debug!("Event client missed {missed} events");
+                Some(resync_event(self.last_id.load(Ordering::Relaxed)))
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) => None,
+            Err(_) => Some(Bytes::from_static(b": keep-alive\n\n")),
+        }
+    }
+}
+
+/// Counts a client until dropped.
+struct ClientSlot(Arc<AtomicUsize>);
+
+impl ClientSlot {
+    fn take(clients: &Arc<AtomicUsize>, max_clients: usize) -> Option<Self> {
+        clients
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count < max_clients).then_some(count + 1)
+            })
+            .ok()?;
+        Some(Self(Arc::clone(clients)))
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Event telling a client that it missed events, and should fetch /status and /settings
+/// again.
+fn resync_event(last_id: u64) -> Bytes {
+    sse_event(last_id, "resync", "{}")
+}
+
+/// Frames an event, `data` must fit on one line, as serialized JSON does.
+fn sse_event(id: u64, kind: &str, data: &str) -> Bytes {
+    Bytes::from(format!("id: {id}\nevent: {kind}\ndata: {data}\n\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use axum::body::Bytes;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_true, none, some},
+    };
+
+    use super::{sse_event, EventHub};
+    use crate::configuration::Settings;
+
+    #[gtest]
+    fn test_event_framing() {
+        expect_that!(
+            sse_event(3, "state", r#"{"display":true}"#),
+            eq(Bytes::from_static(
+                b"id: 3\nevent: state\ndata: {\"display\":true}\n\n"
+            ))
+        );
+    }
+
+    #[gtest]
+    fn test_events_numbered_in_order() {
+        let hub = EventHub::new(4);
+        let mut client = hub.subscribe(None).expect("Client should be accepted");
+        hub.send("settings", &Settings::default()).unwrap();
+        hub.send("state", &"on").unwrap();
+        let first = client.events.try_recv().unwrap();
+        expect_that!(first.starts_with(b"id: 1\nevent: settings\n"), is_true());
+        expect_that!(
+            client.events.try_recv().ok(),
+            some(eq(&Bytes::from_static(
+                b"id: 2\nevent: state\ndata: \"on\"\n\n"
+            )))
+        );
+    }
+
+    #[gtest]
+    fn test_reconnect_after_missed_events() {
+        let hub = EventHub::new(4);
+        hub.send("state", &"on").unwrap();
+        hub.send("state", &"off").unwrap();
+
+        // Up to date, or connecting for the first time
+        expect_that!(hub.subscribe(Some(2)).unwrap().pending, none());
+        expect_that!(hub.subscribe(None).unwrap().pending, none());
+        // Missed the last event
+        expect_that!(
+            hub.subscribe(Some(1)).unwrap().pending,
+            some(eq(&Bytes::from_static(
+                b"id: 2\nevent: resync\ndata: {}\n\n"
+            )))
+        );
+        // Ids from before a restart
+        expect_that!(
+            hub.subscribe(Some(40)).unwrap().pending.is_some(),
+            is_true()
+        );
+    }
+
+    #[gtest]
+    fn test_clients_capped() {
+        let hub = EventHub::new(2);
+        let first = hub.subscribe(None);
+        let _second = hub.subscribe(None);
+        expect_that!(hub.subscribe(None).is_none(), is_true());
+
+        // Disconnected clients free their slot
+        drop(first);
+        expect_that!(hub.subscribe(None).is_some(), is_true());
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use tokio::{process::Command, sync::watch};
+
+use crate::{application::ApplicationState, configuration::HooksConfig};
+
+/// Photo put on screen, described to the slide change hook.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShownPhoto {
+    pub asset_id: Option<String>,
+    pub city: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Event a hook may be configured for.
+#[derive(Debug, Clone, PartialEq)]
+enum HookEvent {
+    SlideChange(ShownPhoto),
+    DisplayOn,
+    DisplayOff,
+}
+
+/// Starts the hook commands, see [`ProcessSpawner`].
+pub trait Spawner {
+    /// Starts `command` with `env`, killing it after `timeout`. `slot` is held until it
+    /// exits.
+    fn spawn(
+        &self,
+        command: &str,
+        env: Vec<(&'static str, String)>,
+        timeout: Duration,
+        slot: HookSlot,
+    ) -> Result<()>;
+}
+
+/// Runs the commands with `sh -c`, on the tokio runtime.
+pub struct ProcessSpawner;
+
+/// Runs the configured commands on events of the application, one at a time for each kind
+/// of event.
+pub struct Hooks<S> {
+    config: HooksConfig,
+    spawner: S,
+    /// Set while the hook of each kind of event runs, see [`HookEvent::kind`].
+    busy: [Arc<AtomicBool>; 3],
+}
+
+/// Held while a hook runs, so that no other of the same event starts meanwhile.
+pub struct HookSlot(Arc<AtomicBool>);
+
+impl HookSlot {
+    fn take(busy: &Arc<AtomicBool>) -> Option<Self> {
+        busy.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()?;
+        Some(Self(Arc::clone(busy)))
+    }
+}
+
+impl Drop for HookSlot {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl HookEvent {
+    /// Index of the kind of event in [`Hooks::busy`].
+    fn kind(&self) -> usize {
+        match self {
+            HookEvent::SlideChange(_) => 0,
+            HookEvent::DisplayOn => 1,
+            HookEvent::DisplayOff => 2,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            HookEvent::SlideChange(_) => "slide change",
+            HookEvent::DisplayOn => "display on",
+            HookEvent::DisplayOff => "display off",
+        }
+    }
+
+    /// Environment of the hook, the details of the photo missing are left empty.
+    fn env(&self) -> Vec<(&'static str, String)> {
+        match self {
+            HookEvent::SlideChange(photo) => vec![
+                (
+                    "MEMOCADRE_ASSET_ID",
+                    photo.asset_id.clone().unwrap_or_default(),
+                ),
+                ("MEMOCADRE_CITY", photo.city.clone().unwrap_or_default()),
+                (
+                    "MEMOCADRE_DATE",
+                    photo.date.map(|date| date.to_rfc3339()).unwrap_or_default(),
+                ),
+            ],
+            HookEvent::DisplayOn | HookEvent::DisplayOff => Vec::new(),
+        }
+    }
+}
+
+impl<S: Spawner> Hooks<S> {
+    pub fn new(config: HooksConfig, spawner: S) -> Self {
+        Self {
+            config,
+            spawner,
+            busy: Default::default(),
+        }
+    }
+
+    /// Runs the hooks of the photos shown and of the display being turned on and off,
+    /// until the application stops.
+    pub async fn run(
+        &self,
+        mut shown: watch::Receiver<Option<ShownPhoto>>,
+        mut state: watch::Receiver<ApplicationState>,
+    ) -> Result<()> {
+        shown.borrow_and_update();
+        let mut display = state.borrow_and_update().display;
+        loop {
+            tokio::select! {
+                changed = shown.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let photo = shown.borrow_and_update().clone();
+                    if let Some(photo) = photo {
+                        self.trigger(HookEvent::SlideChange(photo));
+                    }
+                }
+                changed = state.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let on = state.borrow_and_update().display;
+                    if on != display {
+                        display = on;
+                        self.trigger(if on { HookEvent::DisplayOn } else { HookEvent::DisplayOff });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts the hook of `event` if any, unless the previous one of the same event still
+    /// runs. Failures are only logged.
+    fn trigger(&self, event: HookEvent) {
+        let command = match event {
+            HookEvent::SlideChange(_) => &self.config.on_slide_change,
+            HookEvent::DisplayOn => &self.config.on_display_on,
+            HookEvent::DisplayOff => &self.config.on_display_off,
+        };
+        let Some(command) = command else {
+            return;
+        };
+        let Some(slot) = HookSlot::take(&self.busy[event.kind()]) else {
+            debug!(
+                "Skipping {} hook, the previous one still runs",
+                event.name()
+            );
+            return;
+        };
+        if let Err(err) = self
+            .spawner
+            .spawn(command, event.env(), self.config.timeout, slot)
+        {
+            warn!("Cannot run {} hook: {:?}", event.name(), err);
+        }
+    }
+}
+
+impl Spawner for ProcessSpawner {
+    fn spawn(
+        &self,
+        command: &str,
+        env: Vec<(&'static str, String)>,
+        timeout: Duration,
+        slot: HookSlot,
+    ) -> Result<()> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .envs(env)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Cannot start {command:?}"))?;
+        let command = command.to_owned();
+        tokio::spawn(async move {
+            let _slot = slot;
+            match tokio::time::timeout(timeout, child.wait()).await {
+                Ok(Ok(status)) if status.success() => debug!("Hook {command:?} done"),
+                Ok(Ok(status)) => warn!("Hook {command:?} failed: {status}"),
+                Ok(Err(err)) => warn!("Cannot wait for hook {command:?}: {:?}", err),
+                Err(_) => {
+                    warn!("Killing hook {command:?}, still running after {timeout:?}");
+                    if let Err(err) = child.kill().await {
+                        warn!("Cannot kill hook {command:?}: {:?}", err);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, time::Duration};
+
+    use anyhow::Result;
+    use chrono::{TimeZone, Utc};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{empty, eq, len},
+    };
+
+    use super::{HookEvent, HookSlot, Hooks, ShownPhoto, Spawner};
+    use crate::configuration::HooksConfig;
+
+    /// Keeps the hooks running until their slots are dropped.
+    #[derive(Default)]
+    struct FakeSpawner {
+        started: RefCell<Vec<(String, Vec<(&'static str, String)>)>>,
+        running: RefCell<Vec<HookSlot>>,
+    }
+
+    impl Spawner for &FakeSpawner {
+        fn spawn(
+            &self,
+            command: &str,
+            env: Vec<(&'static str, String)>,
+            _timeout: Duration,
+            slot: HookSlot,
+        ) -> Result<()> {
+            self.started.borrow_mut().push((command.to_owned(), env));
+            self.running.borrow_mut().push(slot);
+            Ok(())
+        }
+    }
+
+    fn config() -> HooksConfig {
+        HooksConfig {
+            on_slide_change: Some("notify-slide".into()),
+            on_display_off: Some("notify-off".into()),
+            ..Default::default()
+        }
+    }
+
+    #[gtest]
+    fn test_slide_change_env() {
+        let photo = ShownPhoto {
+            asset_id: Some("asset-1".into()),
+            city: Some("Bordeaux".into()),
+            date: Utc.with_ymd_and_hms(2023, 7, 14, 12, 30, 0).single(),
+        };
+        expect_that!(
+            HookEvent::SlideChange(photo).env(),
+            eq(&vec![
+                ("MEMOCADRE_ASSET_ID", "asset-1".to_string()),
+                ("MEMOCADRE_CITY", "Bordeaux".to_string()),
+                ("MEMOCADRE_DATE", "2023-07-14T12:30:00+00:00".to_string()),
+            ])
+        );
+        // Missing details are set empty, not inherited from the frame
+        expect_that!(
+            HookEvent::SlideChange(ShownPhoto::default()).env(),
+            eq(&vec![
+                ("MEMOCADRE_ASSET_ID", String::new()),
+                ("MEMOCADRE_CITY", String::new()),
+                ("MEMOCADRE_DATE", String::new()),
+            ])
+        );
+        expect_that!(HookEvent::DisplayOn.env(), empty());
+    }
+
+    fn started(spawner: &FakeSpawner) -> Vec<String> {
+        spawner
+            .started
+            .borrow()
+            .iter()
+            .map(|(command, _)| command.clone())
+            .collect()
+    }
+
+    #[gtest]
+    fn test_hook_skipped_while_previous_runs() {
+        let spawner = FakeSpawner::default();
+        let hooks = Hooks::new(config(), &spawner);
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        expect_that!(started(&spawner), eq(&vec!["notify-slide".to_string()]));
+
+        // Done
+        spawner.running.borrow_mut().clear();
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        expect_that!(*spawner.started.borrow(), len(eq(2)));
+
+        // Not configured, nothing runs nor blocks the next ones
+        spawner.running.borrow_mut().clear();
+        hooks.trigger(HookEvent::DisplayOn);
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        expect_that!(*spawner.started.borrow(), len(eq(3)));
+    }
+
+    #[gtest]
+    fn test_hooks_of_other_events_run_meanwhile() {
+        let spawner = FakeSpawner::default();
+        let hooks = Hooks::new(config(), &spawner);
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        // The lamp is still turned off while the slide change hook runs
+        hooks.trigger(HookEvent::DisplayOff);
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        hooks.trigger(HookEvent::DisplayOff);
+        expect_that!(
+            started(&spawner),
+            eq(&vec!["notify-slide".to_string(), "notify-off".to_string()])
+        );
+
+        // The display off hook is done, the slide change one still runs
+        spawner.running.borrow_mut().remove(1);
+        hooks.trigger(HookEvent::SlideChange(ShownPhoto::default()));
+        hooks.trigger(HookEvent::DisplayOff);
+        expect_that!(
+            started(&spawner),
+            eq(&vec![
+                "notify-slide".to_string(),
+                "notify-off".to_string(),
+                "notify-off".to_string(),
+            ])
+        );
+    }
+}
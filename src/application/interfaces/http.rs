@@ -1,44 +1,157 @@
-use std::sync::mpsc;
+use std::{convert::Infallible, sync::mpsc, time::Instant};
 
 use anyhow::{Context, Result};
 use axum::{
-    http::StatusCode,
-    routing::{get, patch},
+    body::Body,
+    extract::Path,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post},
     Json, Router,
 };
 use log::info;
-use tokio::sync::watch;
+use serde::Serialize;
+use tokio::{sync::watch, try_join};
 
-use super::Interface;
+use super::{
+    events::EventHub,
+    stream::{StreamHub, BOUNDARY},
+    Interface,
+};
 use crate::{
-    application::ControlCommand,
-    configuration::{HttpConfig, Settings, SettingsPatch},
+    application::{
+        config_provider::EffectiveConfig, ApplicationState, ControlCommand, ALL_SOURCES,
+    },
+    configuration::{AppConfig, HttpConfig, Settings, SettingsPatch},
+    gallery, logger,
 };
 
 pub struct HttpInterface {
     config: HttpConfig,
     control: mpsc::Sender<ControlCommand>,
     settings: watch::Receiver<Settings>,
+    state: watch::Receiver<ApplicationState>,
+    empty_sources: watch::Receiver<Vec<String>>,
+    retry_at: watch::Receiver<Option<Instant>>,
+    /// Names of the profiles of the static configuration.
+    profiles: Vec<String>,
+    /// Static configuration, secrets redacted.
+    app_config: AppConfig,
+    /// Clients of GET /stream.
+    stream: StreamHub,
+}
+
+#[derive(Serialize)]
+struct ProfilesStatus {
+    profiles: Vec<String>,
+    /// Empty when no profile is active.
+    active: String,
+}
+
+#[derive(Serialize)]
+struct Status {
+    display: bool,
+    pinned: bool,
+    guest_mode: bool,
+    /// Sources that currently match no photo.
+    empty_sources: Vec<String>,
+    /// Seconds before the sources are tried again, when they all failed.
+    retry_in: Option<u64>,
+    /// Why photos cannot be shown anymore.
+    error: Option<String>,
 }
 
 impl HttpInterface {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: HttpConfig,
         settings: watch::Receiver<Settings>,
         control: mpsc::Sender<ControlCommand>,
+        state: watch::Receiver<ApplicationState>,
+        empty_sources: watch::Receiver<Vec<String>>,
+        retry_at: watch::Receiver<Option<Instant>>,
+        profiles: Vec<String>,
+        app_config: AppConfig,
+        stream: StreamHub,
     ) -> Self {
         Self {
             config,
             settings,
             control,
+            state,
+            empty_sources,
+            retry_at,
+            profiles,
+            app_config,
+            stream,
+        }
+    }
+}
+
+impl HttpInterface {
+    fn send_command(
+        control: &mpsc::Sender<ControlCommand>,
+        command: ControlCommand,
+    ) -> Result<(), StatusCode> {
+        control.send(command).map_err(|err| {
+            log::error!("Failed to send control command: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+
+    /// Response of GET /events, telling a client reconnecting with the `Last-Event-ID`
+    /// header to refetch everything when it missed events.
+    fn events(events: &EventHub, headers: &HeaderMap) -> Result<Response, StatusCode> {
+        let last_event_id = headers
+            .get("last-event-id")
+            .and_then(|id| id.to_str().ok())
+            .and_then(|id| id.parse().ok());
+        // Dropped with the response when the client disconnects
+        let client = events
+            .subscribe(last_event_id)
+            .ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+        let chunks = futures_util::stream::unfold(client, |mut client| async move {
+            let chunk = client.next_chunk().await?;
+            Some((Ok::<_, Infallible>(chunk), client))
+        });
+        let headers = [
+            (header::CONTENT_TYPE, "text/event-stream"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ];
+        Ok((headers, Body::from_stream(chunks)).into_response())
+    }
+
+    /// Shows only the photos of the gallery at `index`, unless there are only `sources`.
+    fn select_source(
+        control: &mpsc::Sender<ControlCommand>,
+        index: usize,
+        sources: usize,
+    ) -> Result<(), StatusCode> {
+        if index >= sources {
+            return Err(StatusCode::NOT_FOUND);
         }
+        Self::send_command(control, ControlCommand::SelectSource(index))
+    }
+
+    fn select_profile(
+        control: &mpsc::Sender<ControlCommand>,
+        name: String,
+    ) -> Result<(), StatusCode> {
+        Self::send_command(
+            control,
+            ControlCommand::ConfigChanged(SettingsPatch {
+                active_profile: Some(name),
+                ..Default::default()
+            }),
+        )
     }
 }
 
 impl Interface for HttpInterface {
     async fn start(&self) -> Result<()> {
         info!("Starting HTTP interface");
-        let app = Router::new()
+        let events = EventHub::new(self.config.max_event_clients);
+        let mut app = Router::new()
             .route(
                 "/settings",
                 get({
@@ -63,14 +176,224 @@ impl Interface for HttpInterface {
                     }
                 }),
             )
-            .fallback(|| async { StatusCode::NOT_FOUND });
+            .route(
+                "/effective-settings",
+                get({
+                    let app_config = self.app_config.clone();
+                    let settings = self.settings.clone();
+                    || async move {
+                        Json::from(EffectiveConfig {
+                            config: app_config,
+                            settings: settings.borrow().clone(),
+                        })
+                    }
+                }),
+            )
+            .route(
+                "/status",
+                get({
+                    let state = self.state.clone();
+                    let empty_sources = self.empty_sources.clone();
+                    let retry_at = self.retry_at.clone();
+                    || async move {
+                        Json::from(Status {
+                            display: state.borrow().display,
+                            pinned: state.borrow().pinned,
+                            guest_mode: state.borrow().guest_mode,
+                            error: state.borrow().error.clone(),
+                            empty_sources: empty_sources.borrow().clone(),
+                            retry_in: retry_at.borrow().map(|retry_at| {
+                                retry_at.saturating_duration_since(Instant::now()).as_secs()
+                            }),
+                        })
+                    }
+                }),
+            )
+            .route(
+                "/pin",
+                post({
+                    let control = self.control.clone();
+                    || async move { Self::send_command(&control, ControlCommand::Pin) }
+                })
+                .delete({
+                    let control = self.control.clone();
+                    || async move { Self::send_command(&control, ControlCommand::Unpin) }
+                }),
+            )
+            .route(
+                "/guest",
+                post({
+                    let control = self.control.clone();
+                    || async move { Self::send_command(&control, ControlCommand::GuestMode(true)) }
+                })
+                .delete({
+                    let control = self.control.clone();
+                    || async move { Self::send_command(&control, ControlCommand::GuestMode(false)) }
+                }),
+            )
+            .route(
+                "/source/{index}",
+                post({
+                    let control = self.control.clone();
+                    let sources = gallery::gallery_total(&self.app_config.sources);
+                    move |Path(index): Path<usize>| async move {
+                        Self::select_source(&control, index, sources)
+                    }
+                }),
+            )
+            .route(
+                "/source",
+                delete({
+                    let control = self.control.clone();
+                    || async move {
+                        Self::send_command(&control, ControlCommand::SelectSource(ALL_SOURCES))
+                    }
+                }),
+            )
+            .route(
+                "/profiles",
+                get({
+                    let profiles = self.profiles.clone();
+                    let settings = self.settings.clone();
+                    || async move {
+                        Json::from(ProfilesStatus {
+                            profiles,
+                            active: settings.borrow().active_profile.clone(),
+                        })
+                    }
+                }),
+            )
+            .route(
+                "/profile/{name}",
+                post({
+                    let control = self.control.clone();
+                    let profiles = self.profiles.clone();
+                    move |Path(name): Path<String>| async move {
+                        if !profiles.contains(&name) {
+                            return Err(StatusCode::NOT_FOUND);
+                        }
+                        Self::select_profile(&control, name)
+                    }
+                }),
+            )
+            .route(
+                "/profile",
+                delete({
+                    let control = self.control.clone();
+                    || async move { Self::select_profile(&control, String::new()) }
+                }),
+            )
+            .route("/logs", get(|| async { logger::recent_lines().join("\n") }))
+            .route(
+                "/events",
+                get({
+                    let events = events.clone();
+                    move |headers: HeaderMap| async move { Self::events(&events, &headers) }
+                }),
+            );
+        if self.config.stream_fps > 0. {
+            app = app.route(
+                "/stream",
+                get({
+                    let stream = self.stream.clone();
+                    || async move {
+                        // Dropped with the response when the client disconnects
+                        let client = stream.subscribe();
+                        let chunks = futures_util::stream::unfold(client, |client| async move {
+                            let chunk = client.next_chunk().await;
+                            Some((Ok::<_, Infallible>(chunk), client))
+                        });
+                        (
+                            [
+                                (
+                                    header::CONTENT_TYPE,
+                                    format!("multipart/x-mixed-replace; boundary={BOUNDARY}"),
+                                ),
+                                (header::CACHE_CONTROL, "no-cache".to_string()),
+                            ],
+                            Body::from_stream(chunks),
+                        )
+                    }
+                }),
+            );
+        }
+        let app = app.fallback(|| async { StatusCode::NOT_FOUND });
 
         let listener = tokio::net::TcpListener::bind(&self.config.bind_address)
             .await
             .context("Failed to bind to address")?;
-        axum::serve(listener, app)
-            .await
-            .context("Failed to start HTTP server")?;
+        try_join!(
+            async {
+                axum::serve(listener, app)
+                    .await
+                    .context("Failed to start HTTP server")
+            },
+            events.publish(self.state.clone(), self.settings.clone()),
+        )?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use axum::{
+        body::Bytes,
+        http::{header, HeaderMap, HeaderValue, StatusCode},
+    };
+    use futures_util::StreamExt;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, err, ok, some},
+    };
+
+    use super::HttpInterface;
+    use crate::application::{interfaces::events::EventHub, ControlCommand};
+
+    #[gtest]
+    fn test_select_unknown_source() {
+        let (control, commands) = mpsc::channel();
+        expect_that!(
+            HttpInterface::select_source(&control, 2, 2),
+            err(eq(StatusCode::NOT_FOUND))
+        );
+        expect_that!(commands.try_recv().is_err(), eq(true));
+
+        expect_that!(HttpInterface::select_source(&control, 1, 2), ok(eq(&())));
+        expect_that!(
+            matches!(commands.try_recv(), Ok(ControlCommand::SelectSource(1))),
+            eq(true)
+        );
+    }
+
+    #[gtest]
+    fn test_events_resync_after_last_event_id() {
+        let hub = EventHub::new(1);
+        let mut headers = HeaderMap::new();
+        headers.insert("last-event-id", HeaderValue::from_static("5"));
+        let response = HttpInterface::events(&hub, &headers).unwrap();
+        expect_that!(
+            response.headers().get(header::CONTENT_TYPE),
+            some(eq(&HeaderValue::from_static("text/event-stream")))
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut body = response.into_body().into_data_stream();
+        let chunk = runtime.block_on(body.next()).unwrap().unwrap();
+        // No event was sent since the restart of the frame
+        expect_that!(
+            chunk,
+            eq(&Bytes::from_static(b"id: 0\nevent: resync\ndata: {}\n\n"))
+        );
+
+        // The client is still connected
+        expect_that!(
+            HttpInterface::events(&hub, &HeaderMap::new()).map(|_| ()),
+            err(eq(StatusCode::SERVICE_UNAVAILABLE))
+        );
+    }
+}
@@ -1,14 +1,35 @@
+mod agenda;
+mod events;
+mod hooks;
 mod http;
 mod mqtt;
+mod stream;
 
-use std::{sync::mpsc, thread};
+use std::{sync::mpsc, thread, time::Instant};
 
 use anyhow::{Context, Result};
-use tokio::{sync::watch, try_join};
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, watch},
+    try_join,
+};
 
-use self::{http::HttpInterface, mqtt::MqttInterface};
+use self::{
+    agenda::AgendaFetcher,
+    hooks::{Hooks, ProcessSpawner},
+    http::HttpInterface,
+    mqtt::{MqttChannels, MqttInterface},
+};
+pub use self::{
+    hooks::ShownPhoto,
+    stream::{frame_stream, FrameCapture, FrameStream},
+};
 use super::{ApplicationState, ControlCommand};
-use crate::configuration::{AppConfig, HttpConfig, MqttConfig, Settings};
+use crate::{
+    agenda::Agenda,
+    configuration::{AgendaConfig, AppConfig, HttpConfig, MqttConfig, Settings},
+    gallery,
+    worker::SyncedPhoto,
+};
 
 pub struct InterfaceManager {}
 
@@ -21,14 +42,24 @@ impl InterfaceManager {
         Self {}
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         &self,
         config: &AppConfig,
         control: mpsc::Sender<ControlCommand>,
         state: watch::Sender<ApplicationState>,
         settings: watch::Receiver<Settings>,
+        empty_sources: watch::Receiver<Vec<String>>,
+        retry_at: watch::Receiver<Option<Instant>>,
+        agenda: watch::Sender<Option<Agenda>>,
+        clock_jumps: watch::Receiver<()>,
+        shown: watch::Receiver<Option<ShownPhoto>>,
+        synced: UnboundedReceiver<SyncedPhoto>,
+        frames: FrameStream,
     ) -> Result<thread::JoinHandle<Result<()>>> {
         let config = config.clone();
+        let profiles: Vec<String> = config.profiles.keys().cloned().collect();
+        let redacted_config = config.redacted();
         let bg_thread = std::thread::Builder::new()
             .name("interfaces".to_string())
             .spawn(move || -> Result<()> {
@@ -40,25 +71,60 @@ impl InterfaceManager {
                 runtime.block_on(async move {
                     let http = async {
                         if let Some(http_config @ HttpConfig { enabled: true, .. }) = config.http {
-                            let interface =
-                                HttpInterface::new(http_config, settings.clone(), control.clone());
-                            interface.start().await?;
+                            let max_width = http_config.stream_max_width;
+                            let interface = HttpInterface::new(
+                                http_config,
+                                settings.clone(),
+                                control.clone(),
+                                state.subscribe(),
+                                empty_sources,
+                                retry_at,
+                                profiles.clone(),
+                                redacted_config,
+                                frames.hub(),
+                            );
+                            try_join!(interface.start(), frames.encode(max_width))?;
                         }
                         Ok::<(), anyhow::Error>(())
                     };
                     let mqtt = async {
                         if let Some(mqtt_config @ MqttConfig { enabled: true, .. }) = config.mqtt {
+                            let channels = MqttChannels {
+                                control: control.clone(),
+                                state: state.clone(),
+                                settings: settings.clone(),
+                                synced,
+                            };
                             let mqtt = MqttInterface::new(
                                 mqtt_config,
-                                control.clone(),
-                                state.clone(),
-                                settings.clone(),
+                                channels,
+                                profiles.clone(),
+                                config.guest_mode.is_some(),
+                                gallery::gallery_total(&config.sources),
                             );
                             mqtt.start().await?
                         }
                         Ok::<(), anyhow::Error>(())
                     };
-                    try_join!(http, mqtt)
+                    let agenda = async {
+                        if let Some(agenda_config @ AgendaConfig { enabled: true, .. }) =
+                            config.agenda
+                        {
+                            AgendaFetcher::new(agenda_config, agenda, clock_jumps)
+                                .start()
+                                .await?
+                        }
+                        Ok::<(), anyhow::Error>(())
+                    };
+                    let hooks = async {
+                        if let Some(hooks_config) = config.hooks {
+                            Hooks::new(hooks_config, ProcessSpawner)
+                                .run(shown, state.subscribe())
+                                .await?
+                        }
+                        Ok::<(), anyhow::Error>(())
+                    };
+                    try_join!(http, mqtt, agenda, hooks)
                 })?;
                 Ok(())
             })?;
@@ -12,14 +12,70 @@ use rumqttc::v5::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tokio::{sync::watch, try_join};
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, watch},
+    try_join,
+};
 
 use super::Interface;
 use crate::{
-    application::{ApplicationState, ControlCommand},
-    configuration::{MqttConfig, Settings, SettingsPatch},
+    application::{config_provider::ConfigProvider, ApplicationState, ControlCommand, ALL_SOURCES},
+    configuration::{
+        ColorAdjustmentSettingsPatch, DebugSettingsPatch, MqttConfig, Settings, SettingsPatch,
+        SyncRole,
+    },
+    worker::SyncedPhoto,
 };
 
+/// Home Assistant publishes "online" on this topic when it (re)starts.
+const HOMEASSISTANT_STATUS_TOPIC: &str = "homeassistant/status";
+/// Option of the profile select going back to the base settings.
+const NO_PROFILE: &str = "none";
+
+/// Topic of the photos prepared by the leader of the synchronized frames of `group`.
+fn sync_topic(group: &str) -> String {
+    format!("memocadre/sync/{group}")
+}
+
+/// Identity of the device in Home Assistant: `env`, from the MQTT_ID environment variable,
+/// the `id` of the configuration, or the one saved by `provider`, generated on the first
+/// run. The machine id is only used when none can be saved, so that it stays the same.
+fn device_id(env: Option<String>, config: &MqttConfig, provider: &ConfigProvider) -> String {
+    if let Some(id) = env.or_else(|| config.id.clone()) {
+        return id;
+    }
+    match provider.load_mqtt_id() {
+        Ok(Some(id)) => return id,
+        Ok(None) => {}
+        Err(err) => {
+            // Not generated again, the entities of the saved one would be left behind
+            warn!("Cannot load MQTT id, using the machine id: {:?}", err);
+            return machine_id();
+        }
+    }
+    // Not from the shared generator: frames with the same random_seed would get the same id
+    let id = format!("{:016x}", rand::random::<u64>());
+    if let Err(err) = provider.save_mqtt_id(&id) {
+        warn!("Cannot save MQTT id, using the machine id: {:?}", err);
+        return machine_id();
+    }
+    let previous = machine_id();
+    info!(
+        "Using the new MQTT id {id}. Entities of a previous install are left unavailable in \
+         Home Assistant: delete the \"MemoCadre {previous}\" device there, or clear the \
+         retained homeassistant/device/memocadre_{previous}/config topic"
+    );
+    id
+}
+
+fn machine_id() -> String {
+    machine_uid::get().unwrap_or_else(|err| {
+        let def = "memocadre".to_string();
+        warn!("Failed to get machine id: {}, defaulting to {}", err, def);
+        def
+    })
+}
+
 pub struct MqttInterface {
     id: String,
     config: MqttConfig,
@@ -27,32 +83,67 @@ pub struct MqttInterface {
     control: mpsc::Sender<ControlCommand>,
     state: watch::Sender<ApplicationState>,
     settings: watch::Receiver<Settings>,
+    /// Names of the profiles of the static configuration.
+    profiles: Vec<String>,
+    /// Whether a guest mode is configured, to switch on and off.
+    guest_mode: bool,
+    /// Number of galleries of the static configuration, to select one of them, see
+    /// [`crate::gallery::gallery_total`].
+    sources: usize,
+    /// Photos prepared by the leader frame, published to the followers.
+    synced: RefCell<UnboundedReceiver<SyncedPhoto>>,
+}
+
+/// Channels between the MQTT interface and the display thread.
+pub struct MqttChannels {
+    pub control: mpsc::Sender<ControlCommand>,
+    pub state: watch::Sender<ApplicationState>,
+    pub settings: watch::Receiver<Settings>,
+    /// Photos prepared by this frame when it leads the synchronized ones, in order.
+    pub synced: UnboundedReceiver<SyncedPhoto>,
 }
 
 impl MqttInterface {
     pub fn new(
         config: MqttConfig,
-        control: mpsc::Sender<ControlCommand>,
-        state: watch::Sender<ApplicationState>,
-        settings: watch::Receiver<Settings>,
+        channels: MqttChannels,
+        profiles: Vec<String>,
+        guest_mode: bool,
+        sources: usize,
     ) -> Self {
-        let id = std::env::var("MQTT_ID").unwrap_or_else(|_| match machine_uid::get() {
-            Ok(id) => id,
-            Err(err) => {
-                let def = "memocadre".to_string();
-                warn!("Failed to get machine id: {}, defaulting to {}", err, def);
-                def
-            }
-        });
+        let MqttChannels {
+            control,
+            state,
+            settings,
+            synced,
+        } = channels;
+        let id = device_id(
+            std::env::var("MQTT_ID").ok(),
+            &config,
+            &ConfigProvider::new(),
+        );
         Self {
             id,
             config,
             control,
             state,
             settings,
+            profiles,
+            guest_mode,
+            sources,
+            synced: RefCell::new(synced),
         }
     }
 
+    /// Topic of the photos shown in the group of this frame, when it follows a leader.
+    fn followed_topic(&self) -> Option<String> {
+        self.config
+            .sync
+            .as_ref()
+            .filter(|sync| sync.role == SyncRole::Follower)
+            .map(|sync| sync_topic(&sync.group))
+    }
+
     fn topic(&self, kind: &str) -> String {
         format!("homeassistant/device/memocadre_{}/{}", self.id, kind)
     }
@@ -75,9 +166,13 @@ impl MqttInterface {
 
     fn config_payload(&self) -> serde_json::Value {
         let c = |c| self.component_id(c);
-        json!({
+        let mut payload = json!({
             "device": {
-                "name": format!("MemoCadre {}", self.id),
+                "name": self
+                    .config
+                    .device_name
+                    .clone()
+                    .unwrap_or_else(|| format!("MemoCadre {}", self.id)),
                 "identifiers": [self.id],
             },
             "origin": {
@@ -103,6 +198,13 @@ impl MqttInterface {
                     "command_template": r#"{ "type": "display_enabled", "value": {{ "true" if value == "ON" else "false" }} }"#,
                     "unique_id": c("display_enabled"),
                 },
+                c("pinned"): {
+                    "p": "switch",
+                    "name": "Pin current photo",
+                    "value_template": r#"{{ "ON" if value_json.pinned else "OFF" }}"#,
+                    "command_template": r#"{ "type": "pinned", "value": {{ "true" if value == "ON" else "false" }} }"#,
+                    "unique_id": c("pinned"),
+                },
                 c("next"): {
                     "p": "button",
                     "name": "Next photo",
@@ -112,7 +214,37 @@ impl MqttInterface {
             },
             "command_topic": self.command_topic(),
             "state_topic": self.state_topic(),
-        })
+        });
+        for (kind, name) in [
+            ("brightness", "Brightness"),
+            ("contrast", "Contrast"),
+            ("saturation", "Saturation"),
+        ] {
+            payload["components"][c(kind)] = color_adjustment_component(kind, name, c(kind));
+        }
+        if !self.profiles.is_empty() {
+            let options: Vec<&str> = std::iter::once(NO_PROFILE)
+                .chain(self.profiles.iter().map(String::as_str))
+                .collect();
+            payload["components"][c("profile")] = json!({
+                "p": "select",
+                "name": "Profile",
+                "options": options,
+                "value_template": "{{ value_json.profile }}",
+                "command_template": r#"{ "type": "profile", "value": "{{ value }}" }"#,
+                "unique_id": c("profile"),
+            });
+        }
+        if self.guest_mode {
+            payload["components"][c("guest_mode")] = json!({
+                "p": "switch",
+                "name": "Guest mode",
+                "value_template": r#"{{ "ON" if value_json.guest_mode else "OFF" }}"#,
+                "command_template": r#"{ "type": "guest_mode", "value": {{ "true" if value == "ON" else "false" }} }"#,
+                "unique_id": c("guest_mode"),
+            });
+        }
+        payload
     }
 
     fn try_send_config_and_subscribe(&self, client: &AsyncClient) -> Result<()> {
@@ -129,9 +261,36 @@ impl MqttInterface {
         client
             .try_subscribe(self.command_topic(), QoS::AtLeastOnce)
             .context("Failed to subscribe to command topic")?;
+        client
+            .try_subscribe(HOMEASSISTANT_STATUS_TOPIC, QoS::AtLeastOnce)
+            .context("Failed to subscribe to Home Assistant status topic")?;
+        if let Some(topic) = self.followed_topic() {
+            client
+                .try_subscribe(topic, QoS::AtLeastOnce)
+                .context("Failed to subscribe to sync topic")?;
+        }
         Ok(())
     }
 
+    fn republish_config(&self, client: &AsyncClient, reason: &str) {
+        debug!("Re-publishing discovery config: {reason}");
+        if let Err(err) = self.try_send_config_and_subscribe(client) {
+            warn!("Cannot re-publish discovery config ({reason}): {err:?}");
+        }
+    }
+
+    async fn discovery_refresh(&self, client: &AsyncClient) -> Result<()> {
+        let period = self.config.discovery_interval;
+        if period.is_zero() {
+            return Ok(());
+        }
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            self.republish_config(client, "periodic refresh");
+        }
+    }
+
     async fn state_send(&self, client: &AsyncClient) -> Result<()> {
         let mut state = self.state.subscribe();
         let mut settings = self.settings.clone();
@@ -159,8 +318,41 @@ impl MqttInterface {
         }
     }
 
+    /// Publishes the photos prepared when leading the synchronized frames, with when they
+    /// are shown, each of them in order. The latest one is retained, so that followers
+    /// starting later show it.
+    async fn sync_send(&self, client: &AsyncClient) -> Result<()> {
+        let Some(sync) = self
+            .config
+            .sync
+            .as_ref()
+            .filter(|sync| sync.role == SyncRole::Leader)
+        else {
+            return Ok(());
+        };
+        let topic = sync_topic(&sync.group);
+        // Only borrowed here, the interface is started once
+        #[allow(clippy::await_holding_refcell_ref)]
+        let mut synced = self.synced.borrow_mut();
+        while let Some(message) = synced.recv().await {
+            // Replaced by the next one already prepared
+            let retain = synced.is_empty();
+            client
+                .publish(
+                    &topic,
+                    QoS::AtLeastOnce,
+                    retain,
+                    serde_json::to_string(&message).context("Failed to serialize sync payload")?,
+                )
+                .await
+                .context("Failed to publish prepared photo")?;
+        }
+        Ok(())
+    }
+
     async fn command_receive(&self, client: &AsyncClient, connection: EventLoop) -> Result<()> {
         let command_topic = self.command_topic();
+        let followed_topic = self.followed_topic();
         let poller = RetryPoller::new(connection);
         loop {
             let n = poller.poll().await.context("Failed to poll mqtt")?;
@@ -173,10 +365,21 @@ impl MqttInterface {
                         .context("Initializing MQTT resources")?;
                 }
                 Event::Incoming(Incoming::Publish(publish)) => {
+                    if is_homeassistant_online(&publish.topic, &publish.payload) {
+                        self.republish_config(client, "Home Assistant is online");
+                        continue;
+                    }
+                    if followed_topic
+                        .as_ref()
+                        .is_some_and(|topic| publish.topic == *topic)
+                    {
+                        self.handle_sync_message(&publish.payload)?;
+                        continue;
+                    }
                     if publish.topic != command_topic {
                         continue;
                     }
-                    self.handle_mqtt_message(publish)
+                    self.handle_mqtt_message(client, publish)
                         .await
                         .context("Error when processing MQTT message")?
                 }
@@ -185,7 +388,21 @@ impl MqttInterface {
         }
     }
 
-    async fn handle_mqtt_message(&self, publish: Publish) -> Result<()> {
+    fn handle_sync_message(&self, payload: &[u8]) -> Result<()> {
+        let message: SyncedPhoto = match serde_json::from_slice(payload) {
+            Ok(m) => m,
+            Err(err) => {
+                error!("Failed to parse photo of the leader frame: {}", err);
+                return Ok(());
+            }
+        };
+        debug!("Sync message: {:?}", message);
+        self.control
+            .send(ControlCommand::ShowSynced(message))
+            .context("Failed to send control command")
+    }
+
+    async fn handle_mqtt_message(&self, client: &AsyncClient, publish: Publish) -> Result<()> {
         let message: MqttMessage = match serde_json::from_slice(&publish.payload) {
             Ok(m) => m,
             Err(err) => {
@@ -219,11 +436,121 @@ impl MqttInterface {
                     .send(ControlCommand::NextSlide)
                     .context("Failed to send control command")?;
             }
+            MqttMessage::Pinned(pinned) => {
+                self.control
+                    .send(if pinned {
+                        ControlCommand::Pin
+                    } else {
+                        ControlCommand::Unpin
+                    })
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::GuestMode(on) => {
+                self.control
+                    .send(ControlCommand::GuestMode(on))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::SelectSource(index) => {
+                let Some(index) = source_to_select(index, self.sources) else {
+                    error!("No source {index}, there are {} of them", self.sources);
+                    return Ok(());
+                };
+                self.control
+                    .send(ControlCommand::SelectSource(index))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::Profile(name) => {
+                let name = if name == NO_PROFILE {
+                    String::new()
+                } else {
+                    name
+                };
+                self.control
+                    .send(ControlCommand::ConfigChanged(SettingsPatch {
+                        active_profile: Some(name),
+                        ..Default::default()
+                    }))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::LogFilter(log_filter) => {
+                self.control
+                    .send(ControlCommand::ConfigChanged(SettingsPatch {
+                        debug: Some(DebugSettingsPatch {
+                            log_filter: Some(log_filter),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::Brightness(brightness) => {
+                self.control
+                    .send(color_adjustment(ColorAdjustmentSettingsPatch {
+                        brightness: Some(brightness),
+                        ..Default::default()
+                    }))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::Contrast(contrast) => {
+                self.control
+                    .send(color_adjustment(ColorAdjustmentSettingsPatch {
+                        contrast: Some(contrast),
+                        ..Default::default()
+                    }))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::Saturation(saturation) => {
+                self.control
+                    .send(color_adjustment(ColorAdjustmentSettingsPatch {
+                        saturation: Some(saturation),
+                        ..Default::default()
+                    }))
+                    .context("Failed to send control command")?;
+            }
+            MqttMessage::ForceDiscovery => {
+                self.republish_config(client, "forced by command");
+            }
         }
         Ok(())
     }
 }
 
+/// Number component of a factor of [`ColorAdjustmentSettingsPatch`], 1 leaves the colors as
+/// is.
+fn color_adjustment_component(kind: &str, name: &str, unique_id: String) -> serde_json::Value {
+    json!({
+        "p": "number",
+        "min": 0,
+        "max": 2,
+        "step": 0.05,
+        "mode": "slider",
+        "name": name,
+        "value_template": format!("{{{{ value_json.{kind} }}}}"),
+        "command_template": format!(r#"{{ "type": "{kind}", "value": {{{{ value }}}} }}"#),
+        "unique_id": unique_id,
+    })
+}
+
+fn color_adjustment(patch: ColorAdjustmentSettingsPatch) -> ControlCommand {
+    ControlCommand::ConfigChanged(SettingsPatch {
+        color_adjustment: Some(patch),
+        ..Default::default()
+    })
+}
+
+/// Source selected by the `index` of a message among `sources`, -1 going back to all of
+/// them. `None` when there is no such source.
+fn source_to_select(index: i64, sources: usize) -> Option<usize> {
+    match usize::try_from(index) {
+        Ok(index) => (index < sources).then_some(index),
+        Err(_) => (index == -1).then_some(ALL_SOURCES),
+    }
+}
+
+fn is_homeassistant_online(topic: &[u8], payload: &[u8]) -> bool {
+    topic == HOMEASSISTANT_STATUS_TOPIC.as_bytes() && payload.trim_ascii() == b"online"
+}
+
 struct RetryPoller {
     connection: RefCell<EventLoop>,
 }
@@ -275,7 +602,10 @@ impl RetryPoller {
 
 #[cfg(test)]
 mod test {
+    use chrono::{TimeZone, Utc};
+
     use super::*;
+    use crate::configuration::{SyncConfig, TransitionKind};
 
     #[test]
     fn test_is_recoverable() {
@@ -288,12 +618,172 @@ mod test {
         let err = ConnectionError::Io(std::io::ErrorKind::HostUnreachable.into());
         assert_eq!(true, RetryPoller::is_recoverable(&err));
     }
+
+    #[test]
+    fn test_homeassistant_online_triggers_discovery() {
+        assert_eq!(
+            true,
+            is_homeassistant_online(b"homeassistant/status", b"online")
+        );
+        assert_eq!(
+            true,
+            is_homeassistant_online(b"homeassistant/status", b"online\n")
+        );
+    }
+
+    #[test]
+    fn test_homeassistant_offline_does_not_trigger_discovery() {
+        assert_eq!(
+            false,
+            is_homeassistant_online(b"homeassistant/status", b"offline")
+        );
+    }
+
+    #[test]
+    fn test_other_topic_does_not_trigger_discovery() {
+        assert_eq!(
+            false,
+            is_homeassistant_online(b"homeassistant/device/memocadre_id/set", b"online")
+        );
+    }
+
+    #[test]
+    fn test_parse_force_discovery() {
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "force_discovery" }"#).unwrap();
+        assert!(matches!(message, MqttMessage::ForceDiscovery));
+    }
+
+    #[test]
+    fn test_parse_pinned() {
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "pinned", "value": true }"#).unwrap();
+        assert!(matches!(message, MqttMessage::Pinned(true)));
+    }
+
+    #[test]
+    fn test_parse_select_source() {
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "select_source", "value": -1 }"#).unwrap();
+        assert!(matches!(message, MqttMessage::SelectSource(-1)));
+    }
+
+    #[test]
+    fn test_source_to_select() {
+        assert_eq!(source_to_select(-1, 2), Some(ALL_SOURCES));
+        assert_eq!(source_to_select(1, 2), Some(1));
+        assert_eq!(source_to_select(2, 2), None);
+        assert_eq!(source_to_select(-2, 2), None);
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "profile", "value": "evening" }"#).unwrap();
+        assert!(matches!(message, MqttMessage::Profile(name) if name == "evening"));
+    }
+
+    #[test]
+    fn test_parse_guest_mode() {
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "guest_mode", "value": true }"#).unwrap();
+        assert!(matches!(message, MqttMessage::GuestMode(true)));
+    }
+
+    #[test]
+    fn test_parse_color_adjustment() {
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "brightness", "value": 0.8 }"#).unwrap();
+        assert!(matches!(message, MqttMessage::Brightness(value) if value == 0.8));
+        // Home Assistant renders whole numbers without decimals
+        let message: MqttMessage =
+            serde_json::from_str(r#"{ "type": "saturation", "value": 0 }"#).unwrap();
+        assert!(matches!(message, MqttMessage::Saturation(value) if value == 0.));
+    }
+
+    #[test]
+    fn test_device_id_precedence() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let provider = ConfigProvider::in_dir(dir.path());
+        provider.save_mqtt_id("saved").unwrap();
+        let config = MqttConfig {
+            id: Some("configured".into()),
+            ..Default::default()
+        };
+        assert_eq!("env", device_id(Some("env".into()), &config, &provider));
+        assert_eq!("configured", device_id(None, &config, &provider));
+        assert_eq!("saved", device_id(None, &MqttConfig::default(), &provider));
+    }
+
+    #[test]
+    fn test_device_id_generated_once() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let provider = ConfigProvider::in_dir(dir.path());
+        let id = device_id(None, &MqttConfig::default(), &provider);
+        assert_eq!(16, id.len());
+        assert_eq!(Some(id.clone()), provider.load_mqtt_id().unwrap());
+        assert_eq!(id, device_id(None, &MqttConfig::default(), &provider));
+    }
+
+    #[test]
+    fn test_parse_sync_message() {
+        let message: SyncedPhoto = serde_json::from_str(
+            r#"{ "asset_id": "abc", "transition": "ease-in-out", "show_at": "2024-07-02T09:00:05Z" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            SyncedPhoto {
+                asset_id: "abc".into(),
+                transition: Some(TransitionKind::EaseInOut),
+                show_at: Utc.with_ymd_and_hms(2024, 7, 2, 9, 0, 5).unwrap(),
+            },
+            message
+        );
+        // Leaders without transition leave it to the followers
+        let message: SyncedPhoto =
+            serde_json::from_str(r#"{ "asset_id": "abc", "show_at": "2024-07-02T09:00:05Z" }"#)
+                .unwrap();
+        assert_eq!(None, message.transition);
+        assert_eq!(
+            r#"{"asset_id":"abc","transition":null,"show_at":"2024-07-02T09:00:05Z"}"#,
+            serde_json::to_string(&message).unwrap()
+        );
+        assert_eq!("memocadre/sync/living-room", sync_topic("living-room"));
+    }
+
+    #[test]
+    fn test_sync_group_fits_in_topic() {
+        let group = |group: &str| {
+            serde_json::from_value::<SyncConfig>(json!({ "role": "leader", "group": group }))
+                .map(|sync| sync.group)
+        };
+        assert_eq!("living-room", group("living-room").unwrap());
+        for invalid in ["", "a/b", "a+", "#"] {
+            assert!(group(invalid).is_err(), "{invalid:?} accepted");
+        }
+    }
+
+    #[test]
+    fn test_color_adjustment_command_template() {
+        let component = color_adjustment_component("contrast", "Contrast", "id_contrast".into());
+        assert_eq!(
+            component["command_template"],
+            r#"{ "type": "contrast", "value": {{ value }} }"#
+        );
+        assert_eq!(component["value_template"], "{{ value_json.contrast }}");
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct MqttState {
     display_duration: u64,
     display_enabled: bool,
+    pinned: bool,
+    profile: String,
+    guest_mode: bool,
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -302,6 +792,20 @@ enum MqttMessage {
     DisplayDuration(u64),
     DisplayEnabled(bool),
     NextSlide,
+    Pinned(bool),
+    GuestMode(bool),
+    /// Index of the only gallery to show, see [`ControlCommand::SelectSource`], or -1 for all
+    /// of them.
+    SelectSource(i64),
+    /// Name of the profile to apply, or "none" for the base settings.
+    Profile(String),
+    /// Log levels, e.g. `{ "type": "log_filter", "value": "memocadre::gallery=trace" }`.
+    LogFilter(String),
+    /// Factors of [`Settings::color_adjustment`].
+    Brightness(f32),
+    Contrast(f32),
+    Saturation(f32),
+    ForceDiscovery,
 }
 
 impl From<(&Settings, &ApplicationState)> for MqttState {
@@ -309,6 +813,15 @@ impl From<(&Settings, &ApplicationState)> for MqttState {
         MqttState {
             display_duration: state.0.display_duration.as_secs(),
             display_enabled: state.1.display,
+            pinned: state.1.pinned,
+            profile: match state.0.active_profile.as_str() {
+                "" => NO_PROFILE.to_string(),
+                name => name.to_string(),
+            },
+            guest_mode: state.1.guest_mode,
+            brightness: state.0.color_adjustment.brightness,
+            contrast: state.0.color_adjustment.contrast,
+            saturation: state.0.color_adjustment.saturation,
         }
     }
 }
@@ -330,6 +843,8 @@ impl Interface for MqttInterface {
         try_join!(
             self.state_send(&client),
             self.command_receive(&client, connection),
+            self.discovery_refresh(&client),
+            self.sync_send(&client),
         )
         .context("in MQTT interface")?;
         Ok(())
@@ -0,0 +1,313 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use image::{codecs::jpeg::JpegEncoder, imageops, DynamicImage, RgbaImage};
+use log::{debug, error};
+use tokio::sync::{mpsc, Notify};
+
+use crate::{configuration::HttpConfig, gl::GlContext};
+
+/// Separates the frames of the multipart response of GET /stream.
+pub const BOUNDARY: &str = "memocadre-frame";
+/// Frames waiting for a client reading slower than they are captured, the oldest ones are
+/// dropped beyond.
+const CLIENT_QUEUE_SIZE: usize = 2;
+const JPEG_QUALITY: u8 = 80;
+
+/// Creates both ends of the screen mirroring, the capture is `None` when it is disabled.
+pub fn frame_stream(config: Option<&HttpConfig>) -> (Option<FrameCapture>, FrameStream) {
+    let hub = StreamHub::default();
+    // A frame captured while the previous one is still being encoded is dropped
+    let (sender, frames) = mpsc::channel(1);
+    let capture = config
+        .filter(|config| config.enabled && config.stream_fps > 0.)
+        .map(|config| FrameCapture {
+            clients: Arc::clone(&hub.count),
+            frames: sender,
+            interval: Duration::from_secs_f32(1. / config.stream_fps),
+            last: None,
+        });
+    (capture, FrameStream { hub, frames })
+}
+
+/// Render loop end of the screen mirroring.
+pub struct FrameCapture {
+    /// Clients connected to the stream.
+    clients: Arc<AtomicUsize>,
+    frames: mpsc::Sender<RgbaImage>,
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl FrameCapture {
+    /// Whether the screen should be captured at `time`, never while nobody watches it.
+    pub fn is_due(&self, time: Instant) -> bool {
+        self.clients.load(Ordering::Relaxed) > 0
+            && self.last.is_none_or(|last| time >= last + self.interval)
+    }
+
+    /// Reads the screen being drawn back for the clients, it is scaled down and encoded on
+    /// the interfaces thread.
+    pub fn capture(&mut self, gl: &GlContext, time: Instant) -> Result<()> {
+        self.last = Some(time);
+        let frame = gl.read_screen().context("Cannot read the screen")?;
+        // Full while the previous frame is being encoded, the render loop never waits
+        let _ = self.frames.try_send(frame);
+        Ok(())
+    }
+}
+
+/// Interfaces end of the screen mirroring, encodes the captured frames for the clients.
+pub struct FrameStream {
+    hub: StreamHub,
+    frames: mpsc::Receiver<RgbaImage>,
+}
+
+impl FrameStream {
+    pub fn hub(&self) -> StreamHub {
+        self.hub.clone()
+    }
+
+    /// Encodes the frames until the render loop stops. The encoding runs on the blocking
+    /// threads, not to hold the HTTP and MQTT interfaces sharing this thread.
+    pub async fn encode(mut self, max_width: u32) -> Result<()> {
+        while let Some(frame) = self.frames.recv().await {
+            let encoded = tokio::task::spawn_blocking(move || encode_frame(frame, max_width))
+                .await
+                .context("Stream frame encoding panicked")?;
+            match encoded {
+                Ok(jpeg) => {
+                    let lagging = self.hub.broadcast(multipart_chunk(&jpeg));
+                    if lagging > 0 {
+                        debug!("Dropped a stream frame for {lagging} slow clients");
+                    }
+                }
+                Err(err) => error!("Cannot encode stream frame: {:?}", err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Clients of the stream, shared with the HTTP handlers.
+#[derive(Clone, Default)]
+pub struct StreamHub {
+    clients: Arc<Mutex<Vec<Weak<ClientQueue>>>>,
+    /// Read by the render loop, so that nothing is captured without client.
+    count: Arc<AtomicUsize>,
+}
+
+impl StreamHub {
+    pub fn subscribe(&self) -> StreamClient {
+        let queue = Arc::new(ClientQueue::default());
+        self.clients.lock().unwrap().push(Arc::downgrade(&queue));
+        self.count.fetch_add(1, Ordering::Relaxed);
+        StreamClient {
+            queue,
+            count: Arc::clone(&self.count),
+        }
+    }
+
+    /// Queues a part for each client, returns how many of them dropped a frame for it.
+    fn broadcast(&self, chunk: Bytes) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| client.strong_count() > 0);
+        clients
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|client| client.push(chunk.clone()))
+            .count()
+    }
+}
+
+/// Viewer of the stream, gone when its response is dropped on disconnection.
+pub struct StreamClient {
+    queue: Arc<ClientQueue>,
+    count: Arc<AtomicUsize>,
+}
+
+impl StreamClient {
+    /// Waits for the next frame, as a part of the multipart response.
+    pub async fn next_chunk(&self) -> Bytes {
+        self.queue.pop().await
+    }
+}
+
+impl Drop for StreamClient {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct ClientQueue {
+    chunks: Mutex<VecDeque<Bytes>>,
+    ready: Notify,
+}
+
+impl ClientQueue {
+    /// Returns whether the oldest frame was dropped to make room.
+    fn push(&self, chunk: Bytes) -> bool {
+        let mut chunks = self.chunks.lock().unwrap();
+        let dropped = chunks.len() >= CLIENT_QUEUE_SIZE && chunks.pop_front().is_some();
+        chunks.push_back(chunk);
+        self.ready.notify_one();
+        dropped
+    }
+
+    async fn pop(&self) -> Bytes {
+        loop {
+            let chunk = self.chunks.lock().unwrap().pop_front();
+            if let Some(chunk) = chunk {
+                return chunk;
+            }
+            self.ready.notified().await;
+        }
+    }
+}
+
+/// Scales a frame read back from the screen down to `max_width`, puts it upright and
+/// compresses it.
+fn encode_frame(frame: RgbaImage, max_width: u32) -> Result<Vec<u8>> {
+    let mut frame = if max_width > 0 && frame.width() > max_width {
+        let height = (frame.height() as u64 * max_width as u64 / frame.width() as u64).max(1);
+        imageops::resize(
+            &frame,
+            max_width,
+            height as u32,
+            imageops::FilterType::Triangle,
+        )
+    } else {
+        frame
+    };
+    // Rows start at the bottom in GL
+    imageops::flip_vertical_in_place(&mut frame);
+    let frame = DynamicImage::ImageRgba8(frame).into_rgb8();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode_image(&frame)
+        .context("Cannot encode frame")?;
+    Ok(jpeg)
+}
+
+/// Part of the multipart/x-mixed-replace response holding one frame.
+fn multipart_chunk(jpeg: &[u8]) -> Bytes {
+    let mut chunk = format!(
+        "--{BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+        jpeg.len()
+    )
+    .into_bytes();
+    chunk.extend_from_slice(jpeg);
+    chunk.extend_from_slice(b"\r\n");
+    Bytes::from(chunk)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use axum::body::Bytes;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, gt, is_false, is_true},
+    };
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::{encode_frame, frame_stream, multipart_chunk};
+    use crate::configuration::HttpConfig;
+
+    fn config() -> HttpConfig {
+        HttpConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[gtest]
+    fn test_multipart_chunk_framing() {
+        expect_that!(
+            multipart_chunk(b"jpeg"),
+            eq(Bytes::from_static(
+                b"--memocadre-frame\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\n\r\njpeg\r\n"
+            ))
+        );
+    }
+
+    #[gtest]
+    fn test_slow_client_keeps_latest_frames() {
+        let (_, stream) = frame_stream(Some(&config()));
+        let hub = stream.hub();
+        let client = hub.subscribe();
+        expect_that!(hub.broadcast(Bytes::from_static(b"1")), eq(0));
+        expect_that!(hub.broadcast(Bytes::from_static(b"2")), eq(0));
+        // The client didn't read anything, the oldest frames make room
+        expect_that!(hub.broadcast(Bytes::from_static(b"3")), eq(1));
+        expect_that!(hub.broadcast(Bytes::from_static(b"4")), eq(1));
+        let queued: Vec<Bytes> = client
+            .queue
+            .chunks
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        expect_that!(
+            queued,
+            eq(&vec![Bytes::from_static(b"3"), Bytes::from_static(b"4")])
+        );
+
+        // Disconnected clients are forgotten
+        drop(client);
+        expect_that!(hub.broadcast(Bytes::from_static(b"5")), eq(0));
+        expect_that!(hub.clients.lock().unwrap().len(), eq(0));
+    }
+
+    #[gtest]
+    fn test_captured_only_while_watched() {
+        let (capture, stream) = frame_stream(Some(&config()));
+        let mut capture = capture.expect("Stream should be enabled");
+        let now = Instant::now();
+        expect_that!(capture.is_due(now), is_false());
+        let client = stream.hub().subscribe();
+        expect_that!(capture.is_due(now), is_true());
+        // Two frames per second by default
+        capture.last = Some(now);
+        expect_that!(capture.is_due(now + Duration::from_millis(100)), is_false());
+        expect_that!(capture.is_due(now + Duration::from_millis(500)), is_true());
+        drop(client);
+        expect_that!(capture.is_due(now + Duration::from_millis(500)), is_false());
+
+        let disabled = HttpConfig {
+            stream_fps: 0.,
+            ..config()
+        };
+        expect_that!(frame_stream(Some(&disabled)).0.is_none(), is_true());
+        expect_that!(frame_stream(None).0.is_none(), is_true());
+    }
+
+    #[gtest]
+    fn test_frame_scaled_down_upright() {
+        // Red bottom row, as read back from GL
+        let mut frame = RgbaImage::from_pixel(1280, 720, Rgba([0, 0, 255, 255]));
+        for x in 0..1280 {
+            for y in 0..32 {
+                frame.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+        }
+        let jpeg = encode_frame(frame, 640).expect("Frame should be encoded");
+        let decoded = image::load_from_memory(&jpeg).expect("Frame should be a JPEG");
+        expect_that!(decoded.dimensions(), eq((640, 360)));
+        let top = decoded.get_pixel(320, 4);
+        let bottom = decoded.get_pixel(320, 355);
+        expect_that!(top.0[2], gt(200));
+        expect_that!(bottom.0[0], gt(200));
+    }
+}
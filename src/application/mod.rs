@@ -1,29 +1,66 @@
+mod adaptive_quality;
+mod agenda_panel;
+mod clock_jump;
+mod commands;
 mod config_provider;
+mod disk_space;
 mod fps;
+mod guest_mode;
+mod instance_lock;
 mod interfaces;
+mod profiles;
+mod progress_indicator;
+mod recap;
+mod retry_countdown;
+mod simulation;
 mod slideshow;
+mod watchdog;
+mod watermark;
 
 use std::{
+    collections::VecDeque,
+    ops::Range,
     rc::Rc,
     sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
     thread,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{Context, Result};
-use config_provider::ConfigProvider;
-use log::debug;
+use chrono::{DateTime, Local, Utc};
+use config_provider::{ConfigProvider, PinnedPhoto};
+use log::{debug, error, info, warn};
 use struct_patch::Patch;
 use tokio::sync::watch;
-use vek::Extent2;
+use vek::{Extent2, Vec2};
 
-use self::{fps::FPSCounter, slideshow::Slideshow};
+use self::{
+    adaptive_quality::AdaptiveQuality,
+    agenda_panel::AgendaPanel,
+    clock_jump::ClockJumpDetector,
+    disk_space::DiskSpaceMonitor,
+    fps::FPSCounter,
+    guest_mode::GuestMode,
+    interfaces::{FrameCapture, ShownPhoto},
+    profiles::Profiles,
+    progress_indicator::ProgressIndicator,
+    recap::{RecapSlide, RecapThumbnails},
+    retry_countdown::RetryCountdown,
+    slideshow::Slideshow,
+    watchdog::Watchdog,
+    watermark::Watermark,
+};
+pub use self::{simulation::SimulationOptions, slideshow::pick_transition};
 use crate::{
-    configuration::{Settings, SettingsPatch},
-    gl::{FutureGlThreadContext, GlContext},
+    configuration::{
+        AgendaConfig, AppConfig, QualityLevel, Settings, SettingsPatch, Source, SyncRole,
+    },
+    gallery,
+    gl::{ContextLost, FutureGlThreadContext, GlContext},
     graphics::{Drawable, Graphics},
-    support::{ApplicationContext, DrawResult},
-    worker::Worker,
+    logger, network, random,
+    support::{self, ApplicationContext, Backend, DrawResult, PresentationTiming},
+    worker::{Message, PreloadedSlide, SyncPart, SyncedOrder, SyncedPhoto, Worker},
 };
 
 pub enum ControlCommand {
@@ -31,13 +68,33 @@ pub enum ControlCommand {
     DisplayOn,
     DisplayOff,
     ConfigChanged(SettingsPatch),
+    /// Keep the current photo on screen, even after a restart.
+    Pin,
+    Unpin,
+    /// Only show photos of the gallery at this index, or of all of them in turn with
+    /// [`ALL_SOURCES`]. Galleries are numbered in configuration order, each spec of each
+    /// Immich instance counting as one, see [`gallery::source_galleries`].
+    SelectSource(usize),
+    /// Only show the guest source, without captions, see
+    /// [`crate::configuration::GuestModeConfig`].
+    GuestMode(bool),
+    /// Show this photo at the same time as the leader frame, see
+    /// [`crate::configuration::SyncConfig`].
+    ShowSynced(SyncedPhoto),
     // PreviousSlide,
 }
 
+/// Value of [`ControlCommand::SelectSource`] going back to all sources in turn.
+pub const ALL_SOURCES: usize = usize::MAX;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ApplicationState {
     pub display: bool,
     pub force_load_next: bool,
+    pub pinned: bool,
+    pub guest_mode: bool,
+    /// Why photos cannot be shown, also displayed on screen until photos come again.
+    pub error: Option<String>,
 }
 
 impl Default for ApplicationState {
@@ -45,57 +102,333 @@ impl Default for ApplicationState {
         Self {
             display: true,
             force_load_next: false,
+            pinned: false,
+            guest_mode: false,
+            error: None,
         }
     }
 }
 
+/// Turns per second of the spinner shown when the next photo is late.
+const OVERDUE_INDICATOR_VELOCITY: f32 = 1.;
+
+/// Number of assets listed by [`test_spec`].
+const TESTED_ASSETS_SHOWN: usize = 10;
+
+/// Size of the window opened by [`preview`].
+const PREVIEW_WINDOW_SIZE: Extent2<u32> = Extent2::new(960, 540);
+
+/// How the application starts, see [`preview`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StartOptions {
+    /// Shows the test pattern, starting with the default configuration and settings when
+    /// they cannot be loaded, so that no network nor configuration is needed.
+    pub test_pattern: bool,
+    /// Shows the built-in demo photos instead of the configured sources, loading the
+    /// configuration like [`StartOptions::test_pattern`], see [`Source::Demo`].
+    pub demo: bool,
+    /// Takes the lock of another frame over when its process is gone, see
+    /// [`instance_lock::lock_instance`].
+    pub force: bool,
+}
+
+/// Starts the frame on its screen, or in a window when a window system runs.
+pub fn run(options: StartOptions) -> Result<()> {
+    let _lock = instance_lock::lock_instance(options.force)?;
+    support::start::<Application>(Backend::Auto, options)
+}
+
+/// Opens the slideshow in a small window, even on a frame able to drive its screen, to try
+/// a configuration out quickly.
+pub fn preview(options: StartOptions) -> Result<()> {
+    let _lock = instance_lock::lock_instance(options.force)?;
+    support::start::<Application>(Backend::Window(PREVIEW_WINDOW_SIZE), options)
+}
+
+/// Prints what the search of the Immich spec at `index` matches, numbered like
+/// [`ControlCommand::SelectSource`], without starting the slideshow.
+pub fn test_spec(index: usize) -> Result<()> {
+    let app_config = ConfigProvider::new().load_config()?;
+    network::configure(&app_config.network);
+    let check = gallery::check_immich_spec(&app_config.sources, index)
+        .with_context(|| format!("Cannot test spec {index}"))?;
+    println!("Spec: {}", check.name);
+    if let Some(person_sets) = &check.person_sets {
+        let sets: Vec<_> = person_sets.iter().map(|ids| ids.join(", ")).collect();
+        let sets = sets.join(" or ");
+        println!("Person ids: {sets}");
+    }
+    println!("Assets in the first batch: {}", check.assets.len());
+    for (id, city) in check.assets.iter().take(TESTED_ASSETS_SHOWN) {
+        println!("  {id} {}", city.as_deref().unwrap_or("-"));
+    }
+    Ok(())
+}
+
+/// Runs the slideshow against a virtual clock with a fake worker, and prints how long the
+/// photos stayed on screen and how often they were late.
+pub fn simulate(options: SimulationOptions) -> Result<()> {
+    let settings = ConfigProvider::new().load_settings().unwrap_or_else(|err| {
+        warn!(
+            "Cannot load the settings, simulating the default ones: {:?}",
+            err
+        );
+        Settings::default()
+    });
+    println!("Worker latency: {}", options.worker_latency);
+    let report = simulation::simulate_offscreen(&settings, &options)?;
+    println!("{report}");
+    Ok(())
+}
+
+/// Loads the configuration and the settings, failing on unknown fields that are otherwise
+/// only warned about.
+pub fn check_config() -> Result<()> {
+    let provider = ConfigProvider::new().strict();
+    provider.load_config()?;
+    provider.load_settings()?;
+    println!("Configuration and settings are valid");
+    Ok(())
+}
+
+/// Prints the configuration and settings the frame would start with, secrets redacted.
+/// The output is JSON, which is also valid YAML.
+pub fn dump_effective_settings() -> Result<()> {
+    let provider = ConfigProvider::new();
+    let app_config = provider.load_config()?;
+    let effective = provider.load_effective_config(&app_config)?;
+    let dump = serde_json::to_string_pretty(&effective).context("Cannot serialize the settings")?;
+    println!("{dump}");
+    Ok(())
+}
+
 pub struct Application {
     slides: Slideshow,
     worker: Worker,
     gl: Rc<GlContext>,
     graphics: Graphics,
     config_sender: watch::Sender<Settings>,
+    /// Settings without the guest mode, published to the remote interfaces: what they read
+    /// is what they change.
+    base_settings: watch::Sender<Settings>,
+    /// Settings in use, with the active profile applied.
     settings: Settings,
+    profiles: Profiles,
+    /// Set when a guest mode is configured.
+    guest_mode: Option<GuestMode>,
+    /// Galleries selected with [`ControlCommand::SelectSource`], restored after the guest
+    /// mode.
+    selected_source: Option<Range<usize>>,
+    /// Asset id of the photo on screen, if any.
+    current_asset: Option<String>,
+    /// Asset id of the pinned photo, kept on screen once shown.
+    pinned_asset: Option<String>,
     fps: Option<FPSCounter>,
+    /// Set when the quality adapts to the frame time, see
+    /// [`crate::configuration::DebugSettings::adaptive_quality`].
+    adaptive_quality: Option<AdaptiveQuality>,
+    agenda_panel: Option<AgendaPanel>,
+    /// Spinner in the corner while the next photo is late, see
+    /// [`Settings::overdue_indicator_delay`].
+    overdue_indicator: ProgressIndicator,
+    overdue_shown: bool,
+    /// Shown while the worker waits before trying the sources again.
+    retry_countdown: RetryCountdown,
+    /// Settings and the pinned photo are not saved when disk space runs low.
+    disk_space: DiskSpaceMonitor,
+    /// Set when the slides were laid out again, the next frame must be drawn.
+    needs_redraw: bool,
     state: ApplicationState,
     state_notifier: watch::Sender<ApplicationState>,
     control: Receiver<ControlCommand>,
+    /// Commands received but not handled yet, see [`commands::coalesce`].
+    pending_commands: VecDeque<ControlCommand>,
     bg_interfaces_thread: Option<thread::JoinHandle<Result<()>>>,
+    /// When the application was created, until the first frame is presented.
+    started: Option<Instant>,
+    /// Transitions start when their first frame is expected on screen.
+    presentation: PresentationTiming,
+    clock: ClockJumpDetector,
+    /// Notified when the wall clock was set, for the agenda to show the right day.
+    clock_jumps: watch::Sender<()>,
+    watchdog: Option<Watchdog>,
+    recap_thumbnails: RecapThumbnails,
+    /// Mosaic shown before turning the display off, see [`Settings::display_off_recap`].
+    recap: Option<RecapSlide>,
+    /// Logo over the photos, see [`Settings::watermark`].
+    watermark: Option<Watermark>,
+    /// Path of the logo loaded, `None` until loaded with the current context.
+    watermark_path: Option<String>,
+    /// Set when the screen can be mirrored over HTTP.
+    frame_capture: Option<FrameCapture>,
+    /// Photos put on screen, for the hooks.
+    shown_photos: watch::Sender<Option<ShownPhoto>>,
+    /// Set when the photos are picked by a leader frame, see
+    /// [`ControlCommand::ShowSynced`].
+    follower: bool,
+    /// Set when showing the same photos as other frames, at the time they are due.
+    synchronized: bool,
+    /// Message of the worker received before it's due, a synchronized photo or one
+    /// received to check for it, see [`Self::next_message`].
+    held_message: Option<Message>,
+}
+
+/// What the application draws with its GL context, created again when it's lost, see
+/// [`Application::context_restored`].
+struct Screen {
+    graphics: Graphics,
+    fps: Option<FPSCounter>,
+    agenda_panel: Option<AgendaPanel>,
+    overdue_indicator: ProgressIndicator,
+    retry_countdown: RetryCountdown,
+    slides: Slideshow,
+}
+
+impl Screen {
+    /// Creates everything drawn again with `gl`, the objects of the `lost` context are
+    /// dropped without being deleted. The slideshow starts over from its first slide.
+    fn recreate(
+        lost: &GlContext,
+        gl: &Rc<GlContext>,
+        settings: &Settings,
+        fps: bool,
+        agenda_panel: Option<&AgendaPanel>,
+        retry_at: watch::Receiver<Option<Instant>>,
+    ) -> Result<Self> {
+        lost.mark_lost();
+        let mut graphics = Application::create_graphics(gl, settings)?;
+        Ok(Self {
+            fps: fps.then(|| FPSCounter::new(&mut graphics)).transpose()?,
+            agenda_panel: agenda_panel
+                .map(|panel| panel.recreate(&mut graphics))
+                .transpose()?,
+            overdue_indicator: Application::create_overdue_indicator(&mut graphics)?,
+            retry_countdown: RetryCountdown::new(&mut graphics, retry_at)?,
+            slides: Slideshow::create(&mut graphics, settings)?,
+            graphics,
+        })
+    }
 }
 
 impl ApplicationContext for Application {
+    type Options = StartOptions;
+
     const WINDOW_TITLE: &'static str = "test";
 
-    fn new(gl: Rc<GlContext>, bg_gl: FutureGlThreadContext) -> Result<Self> {
+    fn new(gl: Rc<GlContext>, bg_gl: FutureGlThreadContext, options: StartOptions) -> Result<Self> {
+        let started = Instant::now();
         let provider = ConfigProvider::new();
-        let app_config = provider.load_config()?;
-        let settings = provider.load_settings()?;
+        let (mut app_config, mut base_settings) = if options.test_pattern || options.demo {
+            let app_config = provider.load_config().unwrap_or_else(|err| {
+                warn!("Using the default configuration: {:#}", err);
+                AppConfig::default()
+            });
+            let settings = provider.load_settings().unwrap_or_else(|err| {
+                warn!("Using the default settings: {:#}", err);
+                Settings::default()
+            });
+            (app_config, settings)
+        } else {
+            (provider.load_config()?, provider.load_settings()?)
+        };
+        base_settings.debug.test_pattern |= options.test_pattern;
+        if options.demo {
+            app_config.sources = vec![Source::Demo];
+        }
+        random::seed(app_config.random_seed);
+        network::configure(&app_config.network);
+        gallery::remove_stale_downloads();
+        let profiles = Profiles::new(app_config.profiles.clone(), base_settings);
+        let settings = profiles.settings();
+        if let Err(err) = logger::set_filter(&settings.debug.log_filter) {
+            log::error!("Cannot set log filter: {:?}", err);
+        }
+        let mut disk_space = DiskSpaceMonitor::new(provider.storage_dir(), &app_config.storage);
+        disk_space.check_if_due(Instant::now());
+        let pinned = provider.load_pinned_photo().unwrap_or_else(|err| {
+            warn!("Cannot load pinned photo: {:?}", err);
+            None
+        });
         let config_sender = watch::Sender::new(settings.clone());
+        let base_settings = watch::Sender::new(settings.clone());
         let (control_sender, control) = mpsc::channel();
-        let state_notifier = watch::Sender::new(ApplicationState::default());
+        let state_notifier = watch::Sender::new(ApplicationState {
+            pinned: pinned.is_some(),
+            ..Default::default()
+        });
+        let empty_sources = watch::Sender::new(Vec::new());
+        let retry_at = watch::Sender::new(None);
+        let agenda = watch::Sender::new(None);
+        let clock_jumps = watch::Sender::new(());
+        let shown_photos = watch::Sender::new(None);
+        let (frame_capture, frames) = interfaces::frame_stream(app_config.http.as_ref());
+        let (synced_photos, synced) = tokio::sync::mpsc::unbounded_channel();
+        let sync = app_config.sync().map(|sync| match sync.role {
+            SyncRole::Leader => SyncPart::Leader(synced_photos),
+            SyncRole::Follower => SyncPart::Follower,
+        });
+        let follower = matches!(sync, Some(SyncPart::Follower));
+        let synchronized = sync.is_some();
 
         let bg_interfaces_thread = interfaces::InterfaceManager::new()
             .start(
                 &app_config,
                 control_sender,
                 state_notifier.clone(),
-                config_sender.subscribe(),
+                base_settings.subscribe(),
+                empty_sources.subscribe(),
+                retry_at.subscribe(),
+                agenda.clone(),
+                clock_jumps.subscribe(),
+                shown_photos.subscribe(),
+                synced,
+                frames,
             )
             .context("Cannot start interface")?;
 
-        let mut graphics =
-            Graphics::new(Rc::clone(&gl), settings.rotation).context("Cannot create Graphics")?;
+        let guest_mode = app_config.guest_mode.and_then(|config| {
+            match gallery::source_galleries(&app_config.sources, config.source) {
+                Some(galleries) => Some(GuestMode::new(config, galleries)),
+                None => {
+                    error!("No source {} to show guests, no guest mode", config.source);
+                    None
+                }
+            }
+        });
+        let mut graphics = Self::create_graphics(&gl, &settings)?;
+        let countdown_retry_at = retry_at.subscribe();
         let worker = Worker::new(
             config_sender.subscribe(),
             Self::get_ideal_image_size(&gl, &graphics),
             bg_gl,
             app_config.sources,
+            app_config.randomize_start,
+            empty_sources,
+            pinned.as_ref().map(|pinned| pinned.asset_id.clone()),
+            app_config.reverse_geocoding.clone(),
+            retry_at,
+            sync,
         );
+        let watchdog = app_config
+            .watchdog
+            .as_ref()
+            .map(|watchdog| Watchdog::start(watchdog.timeout))
+            .transpose()?;
         let fps = if settings.debug.show_fps {
             Some(FPSCounter::new(&mut graphics)?)
         } else {
             None
         };
+        let agenda_panel = match &app_config.agenda {
+            Some(AgendaConfig {
+                enabled: true,
+                hours,
+                ..
+            }) => Some(AgendaPanel::new(&mut graphics, agenda.subscribe(), *hours)?),
+            _ => None,
+        };
+        let overdue_indicator = Self::create_overdue_indicator(&mut graphics)?;
+        let retry_countdown = RetryCountdown::new(&mut graphics, countdown_retry_at)?;
         let slides = Slideshow::create(&mut graphics, &settings)?;
         Ok(Self {
             graphics,
@@ -103,18 +436,126 @@ impl ApplicationContext for Application {
             slides,
             worker,
             config_sender,
+            base_settings,
             settings,
+            profiles,
+            guest_mode,
+            selected_source: None,
+            current_asset: None,
+            pinned_asset: pinned.map(|pinned| pinned.asset_id),
             fps,
+            adaptive_quality: settings
+                .debug
+                .adaptive_quality
+                .then(AdaptiveQuality::default),
+            agenda_panel,
+            overdue_indicator,
+            overdue_shown: false,
+            retry_countdown,
+            disk_space,
+            needs_redraw: false,
             control,
+            pending_commands: VecDeque::new(),
             state: state_notifier.clone().borrow().clone(),
             state_notifier,
             bg_interfaces_thread: Some(bg_interfaces_thread),
+            started: Some(started),
+            presentation: PresentationTiming::default(),
+            clock: ClockJumpDetector::new(SystemTime::now(), Instant::now()),
+            clock_jumps,
+            watchdog,
+            recap_thumbnails: RecapThumbnails::default(),
+            recap: None,
+            watermark: None,
+            watermark_path: None,
+            frame_capture,
+            shown_photos,
+            follower,
+            synchronized,
+            held_message: None,
         })
     }
 
+    fn context_restored(&mut self, gl: Rc<GlContext>, bg_gl: FutureGlThreadContext) -> Result<()> {
+        let Screen {
+            graphics,
+            fps,
+            agenda_panel,
+            overdue_indicator,
+            retry_countdown,
+            slides,
+        } = Screen::recreate(
+            &self.gl,
+            &gl,
+            &self.settings,
+            self.fps.is_some(),
+            self.agenda_panel.as_ref(),
+            self.retry_countdown.retry_at(),
+        )?;
+        self.graphics = graphics;
+        self.fps = fps;
+        self.agenda_panel = agenda_panel;
+        self.overdue_indicator = overdue_indicator;
+        self.retry_countdown = retry_countdown;
+        self.slides = slides;
+        // Loaded again with the new context
+        self.watermark = None;
+        self.watermark_path = None;
+        self.gl = gl;
+        let recap = self.recap.take().is_some();
+        self.worker
+            .restore_context(bg_gl, self.current_asset.take());
+        // Its textures are gone with the lost context
+        self.held_message = None;
+        self.state.force_load_next = true;
+        self.needs_redraw = true;
+        if recap {
+            self.start_recap();
+        }
+        if let Some(error) = self.state.error.clone() {
+            self.show_error(error)?;
+        }
+        info!("GL context restored");
+        Ok(())
+    }
+
+    fn frame_presented(&mut self, at: Instant) {
+        self.presentation.presented(at);
+    }
+
     fn draw_frame(&mut self) -> Result<DrawResult> {
-        self.check_bg_thread()?;
-        while let Ok(command) = self.control.try_recv() {
+        if let Some(watchdog) = &self.watchdog {
+            // Nothing is drawn while the display is off, turning it on may take a while
+            if self.state.display {
+                watchdog.beat();
+            } else {
+                watchdog.pause();
+            }
+        }
+        self.check_bg_thread();
+        if self
+            .guest_mode
+            .as_ref()
+            .is_some_and(|guest_mode| guest_mode.has_expired(Instant::now()))
+        {
+            info!("Guest mode timed out");
+            self.set_guest_mode(false);
+        }
+        // Left for the next frame when one of them turns the display off or on
+        let received: Vec<_> = self
+            .pending_commands
+            .drain(..)
+            .chain(self.control.try_iter())
+            .collect();
+        let count = received.len();
+        self.pending_commands = commands::coalesce(received).into();
+        if self.pending_commands.len() < count {
+            debug!(
+                "Coalesced {count} commands into {}",
+                self.pending_commands.len()
+            );
+        }
+        while let Some(command) = self.pending_commands.pop_front() {
             if let Some(res) = self.handle_command(command) {
                 return Ok(res);
             }
@@ -136,6 +577,18 @@ impl ApplicationContext for Application {
         }
         self.draw()
     }
+
+    #[cfg(feature = "winit")]
+    fn resized(&mut self, _width: u32, _height: u32) {
+        // The viewport is already set, the screen size is read back from it
+        if !self.graphics.update_vp() {
+            return;
+        }
+        if let Err(err) = self.slides.relayout(&mut self.graphics, &self.settings) {
+            log::error!("Cannot lay slides out after resize: {:?}", err);
+        }
+        self.needs_redraw = true;
+    }
 }
 
 impl Application {
@@ -148,39 +601,313 @@ impl Application {
         Extent2::min(fb_dims, hw_max)
     }
 
+    fn overdue_indicator_radius(graphics: &Graphics) -> f32 {
+        graphics.get_dimensions().reduce_min() as f32 / 40.
+    }
+
+    fn create_graphics(gl: &Rc<GlContext>, settings: &Settings) -> Result<Graphics> {
+        let mut graphics =
+            Graphics::new(Rc::clone(gl), settings.rotation).context("Cannot create Graphics")?;
+        graphics.set_rotation_degrees(settings.rotation_degrees);
+        Self::warm_up_fonts(&mut graphics, settings);
+        Ok(graphics)
+    }
+
+    /// Adds the common glyphs of the captions to the font atlas before the first one is
+    /// shown, see [`Graphics::warm_up_fonts`].
+    fn warm_up_fonts(graphics: &mut Graphics, settings: &Settings) {
+        if settings.caption.enabled {
+            graphics.warm_up_fonts(&[settings.caption.font_size]);
+        }
+    }
+
+    fn create_overdue_indicator(graphics: &mut Graphics) -> Result<ProgressIndicator> {
+        let radius = Self::overdue_indicator_radius(graphics);
+        ProgressIndicator::create(graphics, radius, OVERDUE_INDICATOR_VELOCITY)
+    }
+
+    /// Shows the spinner when the next photo is late, returns whether it appeared or
+    /// disappeared.
+    fn update_overdue_indicator(&mut self, frozen: bool, time: Instant) -> bool {
+        let delay = self.settings.overdue_indicator_delay;
+        let overdue = !frozen
+            && !delay.is_zero()
+            && self
+                .slides
+                .next_slide_due()
+                .is_some_and(|due| time >= due + delay);
+        if overdue {
+            // In the bottom right corner, one diameter away from the edges
+            let margin = Self::overdue_indicator_radius(&self.graphics) * 2.;
+            let corner: Vec2<f32> = self.graphics.get_dimensions().as_::<f32>().into();
+            self.overdue_indicator.set_center(corner - margin);
+            self.overdue_indicator.update(time);
+        }
+        std::mem::replace(&mut self.overdue_shown, overdue) != overdue
+    }
+
+    /// Loads the logo when its path changed and places it, whatever is shown, returns
+    /// whether it changed on screen. A logo that cannot be loaded is skipped, the photos
+    /// are still shown.
+    fn update_watermark(&mut self) -> bool {
+        let path = &self.settings.watermark.path;
+        let changed = self.watermark_path.as_ref() != Some(path);
+        if changed {
+            self.watermark = match path.as_str() {
+                "" => None,
+                path => match Watermark::load(&self.gl, path) {
+                    Ok(watermark) => Some(watermark),
+                    Err(err) => {
+                        error!("Cannot load watermark {path}: {:?}", err);
+                        None
+                    }
+                },
+            };
+            self.watermark_path = Some(path.clone());
+        }
+        match &mut self.watermark {
+            Some(watermark) => {
+                watermark.update(&self.graphics, &self.settings.watermark) || changed
+            }
+            None => changed,
+        }
+    }
+
     fn handle_command(&mut self, command: ControlCommand) -> Option<DrawResult> {
         match command {
             ControlCommand::NextSlide => {
+                self.worker.retry_now();
                 self.state.force_load_next = true;
                 self.state_notifier.send_replace(self.state.clone());
             }
             ControlCommand::DisplayOn => {
+                if self.recap.take().is_some() {
+                    self.needs_redraw = true;
+                }
                 if !self.state.display {
+                    // Nothing is taken from the worker while the display is off, the photo
+                    // it prepared meanwhile is shown right away without a thumbnail first
                     self.state.display = true;
                     self.state_notifier.send_replace(self.state.clone());
                     return Some(DrawResult::TurnDisplayOn);
                 }
             }
             ControlCommand::DisplayOff => {
-                if self.state.display {
-                    self.state.display = false;
-                    self.state_notifier.send_replace(self.state.clone());
-                    return Some(DrawResult::TurnDisplayOff);
+                // Turned off right away when asked again during the mosaic
+                if self.state.display && (self.recap.take().is_some() || !self.start_recap()) {
+                    return Some(self.turn_display_off());
                 }
             }
             ControlCommand::ConfigChanged(patch) => {
-                let provider = ConfigProvider::new();
-                if let Err(err) = provider.save_settings_override(&patch) {
+                let patch = self.profiles.check(patch);
+                if !self.disk_space.writable() {
+                    warn!("Low disk space, settings are applied but not saved");
+                } else if let Err(err) = ConfigProvider::new().save_settings_override(&patch) {
                     log::error!("Cannot save settings: {}", err);
                 }
-                self.settings.apply(patch);
+                let test_pattern = self.settings.debug.test_pattern;
+                let daily_photo = self.settings.daily_photo;
+                let caption = (
+                    self.settings.caption.enabled,
+                    self.settings.caption.font_size,
+                );
+                let log_filter = self.settings.debug.log_filter.clone();
+                let color_adjustment = self.settings.color_adjustment;
+                self.settings = self.profiles.apply(patch);
+                self.base_settings.send_replace(self.settings.clone());
+                if let Some(guest_mode) = &self.guest_mode {
+                    guest_mode.apply(&mut self.settings);
+                }
+                if self.settings.debug.log_filter != log_filter {
+                    if let Err(err) = logger::set_filter(&self.settings.debug.log_filter) {
+                        log::error!("Cannot set log filter: {:?}", err);
+                    }
+                }
+                if self.settings.debug.adaptive_quality != self.adaptive_quality.is_some() {
+                    self.adaptive_quality = self
+                        .settings
+                        .debug
+                        .adaptive_quality
+                        .then(AdaptiveQuality::default);
+                    self.worker.set_quality(QualityLevel::Full);
+                }
+                if self.settings.daily_photo != daily_photo {
+                    self.slides.reschedule(&self.settings, Instant::now());
+                }
+                if (
+                    self.settings.caption.enabled,
+                    self.settings.caption.font_size,
+                ) != caption
+                {
+                    Self::warm_up_fonts(&mut self.graphics, &self.settings);
+                }
+                // Applied to the photo on screen
+                if self.settings.color_adjustment != color_adjustment {
+                    self.needs_redraw = true;
+                }
+                if self.settings.debug.test_pattern != test_pattern {
+                    match Slideshow::create(&mut self.graphics, &self.settings) {
+                        Ok(slides) => self.slides = slides,
+                        Err(err) => log::error!("Cannot recreate slideshow: {:?}", err),
+                    }
+                }
                 self.config_sender.send_replace(self.settings.clone());
             }
+            ControlCommand::Pin => {
+                if let Some(asset_id) = self.current_asset.clone() {
+                    let pinned = PinnedPhoto {
+                        asset_id: asset_id.clone(),
+                    };
+                    if !self.disk_space.writable() {
+                        warn!("Low disk space, the photo is pinned until the next restart");
+                    } else if let Err(err) = ConfigProvider::new().save_pinned_photo(Some(&pinned))
+                    {
+                        log::error!("Cannot save pinned photo: {:?}", err);
+                    }
+                    self.pinned_asset = Some(asset_id);
+                    self.state.pinned = true;
+                    self.state_notifier.send_replace(self.state.clone());
+                } else {
+                    warn!("No photo displayed, nothing to pin");
+                }
+            }
+            ControlCommand::SelectSource(index) => {
+                self.selected_source = (index != ALL_SOURCES).then_some(index..index + 1);
+                if self.state.guest_mode {
+                    info!("Source selected after the guest mode");
+                } else {
+                    self.worker.set_active_source(self.selected_source.clone());
+                }
+            }
+            ControlCommand::GuestMode(on) => self.set_guest_mode(on),
+            ControlCommand::ShowSynced(photo) => {
+                if !self.follower {
+                    warn!(
+                        "Not following a leader frame, ignoring photo {}",
+                        photo.asset_id
+                    );
+                    return None;
+                }
+                if self.worker.show_synced(photo) == SyncedOrder::NewSchedule {
+                    self.discard_held_message();
+                }
+            }
+            ControlCommand::Unpin => {
+                if let Err(err) = ConfigProvider::new().save_pinned_photo(None) {
+                    log::error!("Cannot forget pinned photo: {:?}", err);
+                }
+                self.pinned_asset = None;
+                self.state.pinned = false;
+                self.state_notifier.send_replace(self.state.clone());
+            }
         }
         None
     }
 
-    fn check_bg_thread(&mut self) -> Result<()> {
+    /// Next message of the worker, once it's `due`. Synchronized photos are held until
+    /// their time instead, as well as the other messages received while waiting for them.
+    /// Next on the leader frame brings the photo held forward for all frames, see
+    /// [`Worker::restart_schedule`], followers keep waiting for the leader.
+    fn next_message(&mut self, due: bool) -> Result<Message, TryRecvError> {
+        let mut message = match self.held_message.take() {
+            Some(message) => message,
+            None => self.worker.try_recv(&self.gl)?,
+        };
+        let show_at = match &mut message {
+            Message::Slide(slide) => {
+                if self.state.force_load_next {
+                    self.worker.restart_schedule(slide);
+                }
+                slide.show_at
+            }
+            _ => None,
+        };
+        if is_ready(show_at, due, Utc::now()) {
+            return Ok(message);
+        }
+        self.held_message = Some(message);
+        Err(TryRecvError::Empty)
+    }
+
+    /// Deletes the photo held, if any, it won't be shown.
+    fn discard_held_message(&mut self) {
+        if let Some(message) = self.held_message.take() {
+            message.delete(&self.gl);
+        }
+    }
+
+    /// Time until the synchronized photo held is shown, if any.
+    fn held_slide_wait(&self) -> Option<Duration> {
+        match &self.held_message {
+            Some(Message::Slide(PreloadedSlide {
+                show_at: Some(show_at),
+                ..
+            })) => Some((*show_at - Utc::now()).to_std().unwrap_or_default()),
+            _ => None,
+        }
+    }
+
+    fn turn_display_off(&mut self) -> DrawResult {
+        self.state.display = false;
+        self.state_notifier.send_replace(self.state.clone());
+        DrawResult::TurnDisplayOff
+    }
+
+    /// Shows the mosaic of the photos of the day, returns whether there is one.
+    fn start_recap(&mut self) -> bool {
+        if !self.settings.display_off_recap {
+            return false;
+        }
+        let thumbnails = self.recap_thumbnails.of_day(Local::now().date_naive());
+        if thumbnails.is_empty() {
+            return false;
+        }
+        let start = self.presentation.next_present(Instant::now());
+        match RecapSlide::create(
+            &self.graphics,
+            thumbnails,
+            start,
+            self.settings.display_off_recap_duration,
+        ) {
+            Ok(recap) => {
+                self.recap = Some(recap);
+                true
+            }
+            Err(err) => {
+                error!("Cannot show the photos of the day: {:?}", err);
+                false
+            }
+        }
+    }
+
+    fn set_guest_mode(&mut self, on: bool) {
+        let Some(guest_mode) = &mut self.guest_mode else {
+            warn!("No guest mode configured");
+            return;
+        };
+        if on == guest_mode.is_on() {
+            return;
+        }
+        guest_mode.set(on, Instant::now());
+        self.settings = self.profiles.settings();
+        guest_mode.apply(&mut self.settings);
+        self.config_sender.send_replace(self.settings.clone());
+        if on {
+            // The photo already prepared may not be fit for guests
+            self.worker.switch_source(Some(guest_mode.galleries()));
+            self.discard_held_message();
+            self.state.force_load_next = true;
+        } else {
+            // The guest photo already prepared is shown before going back to the others
+            self.worker.set_active_source(self.selected_source.clone());
+        }
+        self.state.guest_mode = on;
+        self.state_notifier.send_replace(self.state.clone());
+    }
+
+    /// The slideshow goes on without the remote interfaces, a frame without network is
+    /// better than a black screen.
+    fn check_bg_thread(&mut self) {
         if let Some(bg) = &self.bg_interfaces_thread {
             if bg.is_finished() {
                 let bg = self
@@ -188,53 +915,321 @@ impl Application {
                     .take()
                     .expect("bg thread is finished");
                 match bg.join() {
-                    Err(err) => anyhow::bail!("Panic in bg thread: {:?}", err),
-                    Ok(Err(err)) => Err(err).context("Error in bg thread: {}")?,
+                    Err(err) => error!("Panic in bg thread, remote control stopped: {:?}", err),
+                    Ok(Err(err)) => error!("Error in bg thread, remote control stopped: {:?}", err),
                     Ok(Ok(())) => {
                         debug!("bg interfaces thread finished");
                     }
                 };
             }
         }
+    }
+
+    /// Shows why photos cannot be shown anymore instead of exiting, the frame can still be
+    /// controlled remotely.
+    fn show_error(&mut self, error: String) -> Result<()> {
+        self.slides
+            .show_error(&mut self.graphics, &error)
+            .context("Cannot show error slide")?;
+        self.state.force_load_next = false;
+        if self.state.error.as_ref() != Some(&error) {
+            self.state.error = Some(error);
+            self.state_notifier.send_replace(self.state.clone());
+        }
         Ok(())
     }
 
+    /// Photos come again, e.g. from the worker started again after an error.
+    fn clear_error(&mut self) {
+        if self.state.error.take().is_some() {
+            self.state_notifier.send_replace(self.state.clone());
+        }
+    }
+
     fn draw(&mut self) -> Result<DrawResult, anyhow::Error> {
+        if self.gl.is_lost() {
+            return Ok(DrawResult::ContextLost);
+        }
         self.gl.clear();
         let time = Instant::now();
+        if let Some(jump) = self.clock.check(SystemTime::now(), time) {
+            // Schedules read the wall clock, drawing again applies them
+            info!("Wall clock jumped by {jump}, updating schedules");
+            self.needs_redraw = true;
+            self.clock_jumps.send_replace(());
+            if self.settings.daily_photo {
+                self.slides.reschedule(&self.settings, time);
+            }
+        }
+        self.disk_space.check_if_due(time);
         self.worker
             .set_ideal_max_size(Self::get_ideal_image_size(&self.gl, &self.graphics));
-        if self.slides.should_load_next(time) || self.state.force_load_next {
-            match self.worker.recv().try_recv() {
+        // Before loading the next slide, which is laid out next to the panel
+        let agenda_changed = match &mut self.agenda_panel {
+            Some(panel) => panel
+                .update(&mut self.graphics, &self.settings, Local::now().time())
+                .context("Cannot update agenda panel")?,
+            None => false,
+        };
+        // A pinned photo stays on screen, but the pinned photo itself must be loaded at startup,
+        // the photos shown when it fails to load go on
+        // Unless guests are watching, the pinned photo may be private
+        let frozen = !self.state.guest_mode
+            && self.pinned_asset.is_some()
+            && self.current_asset == self.pinned_asset;
+        if let Some(recap) = &self.recap {
+            if recap.is_finished(time) {
+                self.recap = None;
+                self.needs_redraw = true;
+                return Ok(self.turn_display_off());
+            }
+        }
+        let due = self.slides.should_load_next(time) || self.state.force_load_next;
+        // Synchronized photos are received early, and shown at their time
+        if self.slides.accepts_slides()
+            && self.recap.is_none()
+            && !frozen
+            && (due || self.synchronized)
+        {
+            match self.next_message(due) {
                 Err(TryRecvError::Empty) => {}
-                Err(error) => Err(error).context("Cannot get next image")?,
-                Ok(preloaded_slide) => {
+                Err(TryRecvError::Disconnected) => {
+                    self.show_error("The photo preparation stopped unexpectedly".into())?
+                }
+                Ok(Message::Slide(mut preloaded_slide)) => {
+                    self.clear_error();
+                    if let Some(thumbnail) = preloaded_slide.recap_thumbnail.take() {
+                        self.recap_thumbnails
+                            .record(thumbnail, Local::now().date_naive());
+                    }
+                    debug!(
+                        "Showing {:?} ({:?})",
+                        preloaded_slide.details.asset_id, preloaded_slide.preparation
+                    );
+                    self.current_asset = preloaded_slide.details.asset_id.clone();
+                    self.shown_photos.send_replace(Some(ShownPhoto {
+                        asset_id: preloaded_slide.details.asset_id.clone(),
+                        city: preloaded_slide.details.city.clone(),
+                        date: preloaded_slide.details.date,
+                    }));
+                    // Animations start with the first frame reaching the screen, not with
+                    // this one being drawn
+                    let start = self.presentation.next_present(Instant::now());
                     self.slides
-                        .load_next(&mut self.graphics, preloaded_slide, &self.settings, time)
+                        .load_next(&mut self.graphics, preloaded_slide, &self.settings, start)
                         .context("Cannot load next frame")?;
                     self.state.force_load_next = false;
                 }
+                Ok(Message::NoPhotos) => {
+                    self.clear_error();
+                    self.slides
+                        .show_no_photos(&mut self.graphics, &self.settings)
+                        .context("Cannot show no photos slide")?;
+                    self.state.force_load_next = false;
+                }
+                Ok(Message::Failed(error)) => self.show_error(error)?,
             }
         }
-        let sleep = self
-            .slides
-            .update_get_sleep(&self.graphics, &self.settings, time);
-        if let Some(sleep) = sleep {
-            thread::sleep(sleep.min(Duration::from_millis(250)));
+        // After loading the next slide, which may be the thumbnail being replaced
+        let upgraded = match self.worker.upgrades().try_recv() {
+            Ok(slide) => self
+                .slides
+                .upgrade(&mut self.graphics, slide, &self.settings, time)
+                .context("Cannot replace thumbnail")?,
+            Err(_) => false,
+        };
+        let watermark_changed = self.update_watermark();
+        let sleep = match &mut self.recap {
+            Some(recap) => recap.update_get_sleep(time),
+            None => self
+                .slides
+                .update_get_sleep(&mut self.graphics, &self.settings, time),
+        };
+        let needs_redraw = std::mem::take(&mut self.needs_redraw);
+        // Drawn once more when it disappears, and on each frame while it turns. Followers
+        // wait for the leader frame, their photos are never late.
+        let overdue_changed =
+            self.update_overdue_indicator(frozen || self.recap.is_some() || self.follower, time);
+        let animated = self.overdue_shown || overdue_changed;
+        let countdown_changed = self.retry_countdown.update(&mut self.graphics, time);
+        // Drawn again for the stream clients, the screen can only be read before the swap
+        let capture = self
+            .frame_capture
+            .as_ref()
+            .is_some_and(|capture| capture.is_due(time));
+        if let Some(sleep) = sleep.filter(|_| {
+            !agenda_changed
+                && !upgraded
+                && !watermark_changed
+                && !countdown_changed
+                && !needs_redraw
+                && !animated
+                && !capture
+        }) {
+            if let Some(adaptive_quality) = &mut self.adaptive_quality {
+                adaptive_quality.idle();
+            }
+            // Woken up in time for the synchronized photo
+            let held = self.held_slide_wait().unwrap_or(Duration::MAX);
+            thread::sleep(sleep.min(Duration::from_millis(250)).min(held));
             return Ok(DrawResult::Noop);
         }
 
         if let Some(fps) = &mut self.fps {
-            fps.count_frame(time);
+            fps.count_frame(time, self.graphics.text_stats(), self.slides.opacity());
         }
 
         self.graphics.begin_frame();
         self.graphics.update();
-        self.slides.draw(&self.graphics)?;
-        if let Some(fps) = &self.fps {
-            fps.draw(&self.graphics)?;
+        self.graphics
+            .set_rotated_rendering(self.settings.rotated_rendering);
+        self.graphics
+            .set_color_adjustment(self.settings.color_adjustment);
+        self.graphics.render(|graphics| {
+            match &self.recap {
+                Some(recap) => recap.draw(graphics)?,
+                None => self.slides.draw(graphics)?,
+            }
+            if let Some(watermark) = &self.watermark {
+                watermark.draw(graphics)?;
+            }
+            if self.overdue_shown {
+                self.overdue_indicator.draw(graphics)?;
+            }
+            self.retry_countdown.draw(graphics)?;
+            if let Some(agenda_panel) = &self.agenda_panel {
+                agenda_panel.draw(graphics)?;
+            }
+            if let Some(fps) = &self.fps {
+                fps.draw(graphics)?;
+            }
+            Ok(())
+        })?;
+        if let Some(frame_capture) = self.frame_capture.as_mut().filter(|_| capture) {
+            if let Err(err) = frame_capture.capture(&self.gl, time) {
+                warn!("Cannot capture the screen for the stream: {:?}", err);
+            }
+        }
+        if let Err(err) = self.gl.swap_buffers() {
+            if err.is::<ContextLost>() {
+                return Ok(DrawResult::ContextLost);
+            }
+            return Err(err);
+        }
+        if let Some(adaptive_quality) = &mut self.adaptive_quality {
+            if let Some(level) = adaptive_quality.frame_presented(Instant::now()) {
+                self.worker.set_quality(level);
+            }
+        }
+        if let Some(started) = self.started.take() {
+            info!(
+                "First frame presented {:?} after startup",
+                started.elapsed()
+            );
         }
-        self.gl.swap_buffers()?;
         Ok(DrawResult::FrameDrawn)
     }
 }
+
+/// Whether a message of the worker is shown now: a synchronized photo at its time `show_at`,
+/// the others once `due`.
+fn is_ready(show_at: Option<DateTime<Utc>>, due: bool, now: DateTime<Utc>) -> bool {
+    show_at.map_or(due, |show_at| show_at <= now)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        rc::Rc,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Instant,
+    };
+
+    use chrono::{TimeDelta, Utc};
+    use faux::when;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, gt, is_true},
+    };
+    use tokio::sync::watch;
+    use vek::Extent2;
+
+    use super::{is_ready, Screen, Slideshow};
+    use crate::{
+        configuration::{Background, OrientationName, Settings},
+        gallery::ImageDetails,
+        gl::{texture::DetachedTexture, wrapper::mocked_gl, GlContext},
+        graphics::Graphics,
+        worker::{LiveSlide, PreloadedSlide},
+    };
+
+    #[gtest]
+    fn test_synchronized_photo_held_until_its_time() {
+        let now = Utc::now();
+        let show_at = now + TimeDelta::seconds(3);
+        // Even when the previous photo was shown long enough
+        expect_that!(is_ready(Some(show_at), true, now), eq(false));
+        expect_that!(is_ready(Some(show_at), true, show_at), eq(true));
+        // Not waiting for the previous photo
+        expect_that!(is_ready(Some(show_at), false, show_at), eq(true));
+
+        expect_that!(is_ready(None, false, now), eq(false));
+        expect_that!(is_ready(None, true, now), eq(true));
+    }
+
+    #[gtest]
+    fn test_screen_rebuilt_on_restored_context() {
+        let deleted = Arc::new(AtomicUsize::new(0));
+        let mut lost_gl = mocked_gl();
+        let counter = Arc::clone(&deleted);
+        when!(lost_gl.delete_texture).then(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let lost_gl = Rc::new(GlContext::mocked(lost_gl));
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let mut graphics = Graphics::new(lost_gl.clone(), OrientationName::Angle0).unwrap();
+        let mut slides = Slideshow::create(&mut graphics, &config).unwrap();
+        let slide = PreloadedSlide {
+            live: LiveSlide::counted(),
+            details: ImageDetails {
+                asset_id: None,
+                album: None,
+                starts_album: false,
+                city: None,
+                country: None,
+                gps: None,
+                date: None,
+                people: Default::default(),
+            },
+            texture: DetachedTexture::mock(Extent2::new(100, 100)),
+            blurred_texture: None,
+            preparation: Default::default(),
+            recap_thumbnail: None,
+            transition: None,
+            show_at: None,
+        };
+        slides
+            .load_next(&mut graphics, slide, &config, Instant::now())
+            .unwrap();
+
+        let created = Arc::new(AtomicUsize::new(0));
+        let mut new_gl = mocked_gl();
+        let counter = Arc::clone(&created);
+        when!(new_gl.create_program).then(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(glow::NativeProgram(std::num::NonZeroU32::new(1).unwrap()))
+        });
+        let new_gl = Rc::new(GlContext::mocked(new_gl));
+        let (_retry_at, retry_at) = watch::channel(None);
+        let screen = Screen::recreate(&lost_gl, &new_gl, &config, true, None, retry_at).unwrap();
+        expect_that!(created.load(Ordering::SeqCst), gt(0));
+        expect_that!(screen.fps.is_some(), is_true());
+        drop(slides);
+        drop(graphics);
+        expect_that!(deleted.load(Ordering::SeqCst), eq(0));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use log::{info, warn};
+use struct_patch::Patch;
+
+use crate::configuration::{Settings, SettingsPatch};
+
+/// Named sets of settings applied over the base settings. Switching profiles starts again
+/// from the base settings, so a profile never leaves its values behind.
+pub struct Profiles {
+    profiles: BTreeMap<String, SettingsPatch>,
+    /// Settings without any profile, including the changes made at runtime.
+    base: Settings,
+}
+
+impl Profiles {
+    pub fn new(mut profiles: BTreeMap<String, SettingsPatch>, base: Settings) -> Self {
+        for (name, profile) in profiles.iter_mut() {
+            if profile.active_profile.take().is_some() {
+                warn!("Profile {name} cannot select another profile, ignoring it");
+            }
+        }
+        let mut profiles = Self { profiles, base };
+        let name = &profiles.base.active_profile;
+        if !profiles.is_known(name) {
+            warn!("Unknown profile {name}, using the base settings");
+            profiles.base.active_profile.clear();
+        }
+        profiles
+    }
+
+    /// Drops the selection of an unknown profile from `patch`.
+    pub fn check(&self, mut patch: SettingsPatch) -> SettingsPatch {
+        if let Some(name) = &patch.active_profile {
+            if !self.is_known(name) {
+                warn!("Unknown profile {name}, keeping the current one");
+                patch.active_profile = None;
+            }
+        }
+        patch
+    }
+
+    /// Whether `name` is a profile, or empty for none.
+    fn is_known(&self, name: &str) -> bool {
+        name.is_empty() || self.profiles.contains_key(name)
+    }
+
+    /// Applies a change to the base settings, returns the settings to use. Values set by
+    /// the active profile still take precedence.
+    pub fn apply(&mut self, patch: SettingsPatch) -> Settings {
+        let previous = self.base.active_profile.clone();
+        self.base.apply(patch);
+        if self.base.active_profile != previous {
+            match self.base.active_profile.as_str() {
+                "" => info!("No active profile"),
+                name => info!("Active profile: {name}"),
+            }
+        }
+        self.settings()
+    }
+
+    /// Base settings with the active profile applied.
+    pub fn settings(&self) -> Settings {
+        let mut settings = self.base.clone();
+        if let Some(profile) = self.profiles.get(&settings.active_profile) {
+            settings.apply(profile.clone());
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true, none},
+    };
+
+    use super::Profiles;
+    use crate::configuration::{CaptionOptionsPatch, Settings, SettingsPatch};
+
+    fn select(name: &str) -> SettingsPatch {
+        SettingsPatch {
+            active_profile: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    fn profiles() -> Profiles {
+        let day = SettingsPatch {
+            display_duration: Some(Duration::from_secs(20)),
+            ..Default::default()
+        };
+        let evening = SettingsPatch {
+            display_duration: Some(Duration::from_secs(60)),
+            caption: Some(CaptionOptionsPatch {
+                enabled: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut base = Settings::default();
+        base.display_duration = Duration::from_secs(30);
+        Profiles::new(
+            BTreeMap::from([("day".into(), day), ("evening".into(), evening)]),
+            base,
+        )
+    }
+
+    #[gtest]
+    fn test_switching_profiles_restores_base() {
+        let mut profiles = profiles();
+
+        let settings = profiles.apply(select("evening"));
+        expect_that!(settings.display_duration, eq(Duration::from_secs(60)));
+        expect_that!(settings.caption.enabled, is_false());
+
+        let settings = profiles.apply(select("day"));
+        expect_that!(settings.display_duration, eq(Duration::from_secs(20)));
+        expect_that!(settings.caption.enabled, is_true());
+
+        let settings = profiles.apply(select(""));
+        expect_that!(settings.display_duration, eq(Duration::from_secs(30)));
+        expect_that!(settings.caption.enabled, is_true());
+        expect_that!(settings.active_profile, eq(""));
+    }
+
+    #[gtest]
+    fn test_changes_apply_to_base() {
+        let mut profiles = profiles();
+        profiles.apply(select("evening"));
+
+        let settings = profiles.apply(SettingsPatch {
+            display_duration: Some(Duration::from_secs(10)),
+            show_album_headers: Some(true),
+            ..Default::default()
+        });
+        // Overridden by the profile
+        expect_that!(settings.display_duration, eq(Duration::from_secs(60)));
+        expect_that!(settings.show_album_headers, is_true());
+
+        let settings = profiles.apply(select(""));
+        expect_that!(settings.display_duration, eq(Duration::from_secs(10)));
+        expect_that!(settings.show_album_headers, is_true());
+    }
+
+    #[gtest]
+    fn test_unknown_profile_is_ignored() {
+        let mut profiles = profiles();
+        profiles.apply(select("day"));
+
+        let patch = profiles.check(select("night"));
+        expect_that!(patch.active_profile, none());
+        let settings = profiles.apply(patch);
+        expect_that!(settings.active_profile, eq("day"));
+    }
+}
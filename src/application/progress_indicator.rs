@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use epaint::{CircleShape, Color32};
+use vek::Vec2;
+
+use crate::graphics::{Drawable, Graphics, ShapeContainer};
+
+const CIRCLE_ELEMENTS: u8 = 12;
+
+/// Dots turning around a circle, fading towards the tail, to show that something is in
+/// progress.
+pub struct ProgressIndicator {
+    circles: [ShapeContainer; CIRCLE_ELEMENTS as usize - 1],
+    /// Positions of the dots around the center.
+    positions: [Vec2<f32>; CIRCLE_ELEMENTS as usize],
+    center: Vec2<f32>,
+    last_time: Instant,
+    /// Time each dot is shown at a position, in milliseconds.
+    step: u16,
+}
+
+impl ProgressIndicator {
+    /// Indicator turning around a circle of `radius`, `velocity` times per second.
+    pub fn create(graphics: &mut Graphics, radius: f32, velocity: f32) -> Result<Self> {
+        let circle_size = radius * 0.2;
+
+        // TODO: use std::array::try_from_fn when stabilized
+        let circles = array_init::try_array_init(|i| {
+            let gray = 255.0 / CIRCLE_ELEMENTS as f32 * (i + 1) as f32;
+            let shape =
+                CircleShape::filled((0., 0.).into(), circle_size, Color32::from_gray(gray as u8));
+            graphics.create_shape(epaint::Shape::Circle(shape), None)
+        })?;
+
+        let positions = std::array::from_fn(|i| {
+            let angle = 2.0 * std::f32::consts::PI / CIRCLE_ELEMENTS as f32 * i as f32;
+            let x = angle.cos() * radius;
+            let y = angle.sin() * radius;
+            Vec2::new(x, y)
+        });
+
+        Ok(Self {
+            circles,
+            positions,
+            center: Vec2::zero(),
+            last_time: Instant::now(),
+            step: step_duration(velocity),
+        })
+    }
+
+    pub fn set_center(&mut self, center: Vec2<f32>) {
+        self.center = center;
+    }
+
+    /// Moves the dots to their position at `time`.
+    pub fn update(&mut self, time: Instant) {
+        let p = time.duration_since(self.last_time).as_millis() / self.step as u128;
+        let p = (p % CIRCLE_ELEMENTS as u128) as u8;
+
+        for (i, circle) in self.circles.iter_mut().enumerate() {
+            let position = self.positions[((i as u8 + p) % CIRCLE_ELEMENTS) as usize];
+            circle.set_position(position + self.center);
+        }
+    }
+}
+
+/// Milliseconds between two moves of the dots for `velocity` turns per second.
+fn step_duration(velocity: f32) -> u16 {
+    ((1000. / velocity) as u16 / CIRCLE_ELEMENTS as u16).max(1)
+}
+
+impl Drawable for ProgressIndicator {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        for circle in self.circles.iter() {
+            circle.draw(graphics)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use googletest::{expect_that, gtest, prelude::eq};
+
+    use super::step_duration;
+
+    #[gtest]
+    fn test_step_duration() {
+        expect_that!(step_duration(1.5), eq(55));
+        expect_that!(step_duration(1.), eq(83));
+        // Never zero, even when turning too fast to see
+        expect_that!(step_duration(1000.), eq(1));
+    }
+}
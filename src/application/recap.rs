@@ -0,0 +1,294 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use image::{DynamicImage, GenericImageView};
+use vek::{Extent2, Vec2};
+
+use crate::graphics::{Drawable, Graphics, SharedTexture2d, Sprite};
+
+/// Photos of the day kept for the mosaic, the oldest ones are dropped.
+const MAX_THUMBNAILS: usize = 24;
+/// Pixels between two photos of the mosaic.
+const TILE_GAP: f32 = 4.;
+/// Length of the fade in and of the fade out, at most a third of the mosaic duration each.
+const FADE_DURATION: Duration = Duration::from_secs(1);
+
+/// Thumbnails of the photos shown during the day, for the mosaic shown when the display is
+/// turned off, see [`crate::configuration::Settings::display_off_recap`].
+#[derive(Default)]
+pub struct RecapThumbnails {
+    day: Option<NaiveDate>,
+    thumbnails: VecDeque<DynamicImage>,
+}
+
+impl RecapThumbnails {
+    pub fn record(&mut self, thumbnail: DynamicImage, today: NaiveDate) {
+        self.roll_over(today);
+        if self.thumbnails.len() == MAX_THUMBNAILS {
+            self.thumbnails.pop_front();
+        }
+        self.thumbnails.push_back(thumbnail);
+    }
+
+    /// Photos shown today, oldest first.
+    pub fn of_day(&mut self, today: NaiveDate) -> &VecDeque<DynamicImage> {
+        self.roll_over(today);
+        &self.thumbnails
+    }
+
+    fn roll_over(&mut self, today: NaiveDate) {
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.thumbnails.clear();
+        }
+    }
+}
+
+/// Mosaic of the photos of the day, faded in and out before the display turns off.
+pub struct RecapSlide {
+    sprites: Vec<Sprite>,
+    /// Opacity of the last frame, the mosaic is only drawn again when it changes.
+    opacity: Option<f32>,
+    start: Instant,
+    duration: Duration,
+}
+
+impl RecapSlide {
+    pub fn create(
+        graphics: &Graphics,
+        thumbnails: &VecDeque<DynamicImage>,
+        start: Instant,
+        duration: Duration,
+    ) -> Result<Self> {
+        let sizes: Vec<Extent2<u32>> = thumbnails
+            .iter()
+            .map(|thumbnail| thumbnail.dimensions().into())
+            .collect();
+        let tiles = mosaic_layout(graphics.get_dimensions().as_(), &sizes);
+        let sprites = thumbnails
+            .iter()
+            .zip(tiles)
+            .map(|(thumbnail, tile)| {
+                let texture = graphics
+                    .texture_from_image(thumbnail)
+                    .context("Cannot create mosaic texture")?;
+                let mut sprite = Sprite::new(SharedTexture2d::new(texture));
+                sprite.position = tile.position;
+                sprite.size = tile.size;
+                sprite.opacity = 0.;
+                sprite.set_sub_center_size(Vec2::broadcast(0.5), tile.uv_half_size);
+                Ok(sprite)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            sprites,
+            opacity: None,
+            start,
+            duration,
+        })
+    }
+
+    pub fn is_finished(&self, time: Instant) -> bool {
+        time >= self.start + self.duration
+    }
+
+    /// Fades the mosaic for `time`, returns how long it can sleep when it stays still.
+    pub fn update_get_sleep(&mut self, time: Instant) -> Option<Duration> {
+        let elapsed = time.saturating_duration_since(self.start);
+        let opacity = opacity(elapsed, self.duration);
+        if self.opacity.replace(opacity) != Some(opacity) {
+            for sprite in &mut self.sprites {
+                sprite.opacity = opacity;
+            }
+            return None;
+        }
+        let fade_out_at = self.duration.saturating_sub(fade_duration(self.duration));
+        Some(fade_out_at.saturating_sub(elapsed))
+    }
+}
+
+impl Drawable for RecapSlide {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        for sprite in &self.sprites {
+            sprite.draw(graphics)?;
+        }
+        Ok(())
+    }
+}
+
+/// Place of a photo in the mosaic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Tile {
+    position: Vec2<f32>,
+    size: Extent2<f32>,
+    /// Half size of the centered part of the photo filling the tile, in texture coordinates.
+    uv_half_size: Vec2<f32>,
+}
+
+/// Lays the photos out in a grid covering the screen, the incomplete last row is centered.
+fn mosaic_layout(screen: Extent2<f32>, photos: &[Extent2<u32>]) -> Vec<Tile> {
+    let grid = grid_size(photos.len(), screen);
+    let cell = screen / grid.as_::<f32>();
+    let size = cell.map(|side| (side - TILE_GAP).max(0.));
+    photos
+        .iter()
+        .enumerate()
+        .map(|(index, photo)| {
+            let (row, column) = (index / grid.w, index % grid.w);
+            let in_row = (photos.len() - row * grid.w).min(grid.w);
+            let offset = (grid.w - in_row) as f32 * cell.w * 0.5;
+            let position = Vec2::new(offset + column as f32 * cell.w, row as f32 * cell.h)
+                + Vec2::broadcast(TILE_GAP * 0.5);
+            Tile {
+                position,
+                size,
+                uv_half_size: aspect_fill(photo.as_(), size),
+            }
+        })
+        .collect()
+}
+
+/// Columns and rows giving the largest square that fits in a tile, the fewest columns win
+/// ties to leave fewer empty cells.
+fn grid_size(count: usize, screen: Extent2<f32>) -> Extent2<usize> {
+    let mut best = Extent2::new(1, 1);
+    let mut best_side = 0.;
+    for columns in 1..=count.max(1) {
+        let rows = count.div_ceil(columns).max(1);
+        let side = (screen.w / columns as f32).min(screen.h / rows as f32);
+        if side > best_side {
+            best = Extent2::new(columns, rows);
+            best_side = side;
+        }
+    }
+    best
+}
+
+/// Half size of the centered part of a `photo` filling a `tile` without being stretched.
+fn aspect_fill(photo: Extent2<f32>, tile: Extent2<f32>) -> Vec2<f32> {
+    if photo.product() == 0. || tile.product() == 0. {
+        return Vec2::broadcast(0.5);
+    }
+    let photo_ratio = photo.w / photo.h;
+    let tile_ratio = tile.w / tile.h;
+    if photo_ratio > tile_ratio {
+        Vec2::new(0.5 * tile_ratio / photo_ratio, 0.5)
+    } else {
+        Vec2::new(0.5, 0.5 * photo_ratio / tile_ratio)
+    }
+}
+
+fn fade_duration(duration: Duration) -> Duration {
+    FADE_DURATION.min(duration / 3)
+}
+
+fn opacity(elapsed: Duration, duration: Duration) -> f32 {
+    let fade = fade_duration(duration).as_secs_f32();
+    if fade == 0. {
+        return 1.;
+    }
+    let fade_in = elapsed.as_secs_f32() / fade;
+    let fade_out = duration.saturating_sub(elapsed).as_secs_f32() / fade;
+    fade_in.min(fade_out).clamp(0., 1.)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{approx_eq, eq},
+    };
+    use image::DynamicImage;
+    use vek::{Extent2, Vec2};
+
+    use super::{aspect_fill, grid_size, mosaic_layout, opacity, RecapThumbnails, TILE_GAP};
+
+    const SCREEN: Extent2<f32> = Extent2::new(1920., 1080.);
+
+    #[gtest]
+    fn test_grid_fits_any_count() {
+        expect_that!(grid_size(0, SCREEN), eq(Extent2::new(1, 1)));
+        expect_that!(grid_size(1, SCREEN), eq(Extent2::new(1, 1)));
+        expect_that!(grid_size(2, SCREEN), eq(Extent2::new(2, 1)));
+        expect_that!(grid_size(4, SCREEN), eq(Extent2::new(2, 2)));
+        expect_that!(grid_size(5, SCREEN), eq(Extent2::new(3, 2)));
+        expect_that!(grid_size(24, SCREEN), eq(Extent2::new(6, 4)));
+        expect_that!(
+            grid_size(3, Extent2::new(1080., 1920.)),
+            eq(Extent2::new(1, 3))
+        );
+    }
+
+    #[gtest]
+    fn test_aspect_fill_crops_the_longest_side() {
+        let square = Extent2::new(100., 100.);
+        expect_that!(
+            aspect_fill(Extent2::new(200., 100.), square),
+            eq(Vec2::new(0.25, 0.5))
+        );
+        expect_that!(
+            aspect_fill(Extent2::new(100., 400.), square),
+            eq(Vec2::new(0.5, 0.125))
+        );
+        expect_that!(aspect_fill(square, square), eq(Vec2::new(0.5, 0.5)));
+        expect_that!(
+            aspect_fill(Extent2::zero(), square),
+            eq(Vec2::new(0.5, 0.5))
+        );
+    }
+
+    #[gtest]
+    fn test_incomplete_last_row_centered() {
+        let tiles = mosaic_layout(SCREEN, &[Extent2::new(160, 120); 5]);
+        expect_that!(tiles.len(), eq(5));
+        let half_gap = TILE_GAP * 0.5;
+        expect_that!(tiles[0].position, eq(Vec2::new(half_gap, half_gap)));
+        expect_that!(
+            tiles[0].size,
+            eq(Extent2::new(640. - TILE_GAP, 540. - TILE_GAP))
+        );
+        // Two photos in the last row of three columns, shifted by half a column
+        expect_that!(
+            tiles[3].position,
+            eq(Vec2::new(320. + half_gap, 540. + half_gap))
+        );
+        expect_that!(
+            tiles[4].position,
+            eq(Vec2::new(960. + half_gap, 540. + half_gap))
+        );
+    }
+
+    #[gtest]
+    fn test_mosaic_fades_in_and_out() {
+        let duration = Duration::from_secs(10);
+        expect_that!(opacity(Duration::ZERO, duration), eq(0.));
+        expect_that!(
+            opacity(Duration::from_millis(500), duration),
+            approx_eq(0.5)
+        );
+        expect_that!(opacity(Duration::from_secs(5), duration), eq(1.));
+        expect_that!(
+            opacity(Duration::from_millis(9750), duration),
+            approx_eq(0.25)
+        );
+        expect_that!(opacity(duration, duration), eq(0.));
+    }
+
+    #[gtest]
+    fn test_thumbnails_cleared_daily() {
+        let mut thumbnails = RecapThumbnails::default();
+        let day = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        for _ in 0..30 {
+            thumbnails.record(DynamicImage::new_rgb8(16, 16), day);
+        }
+        expect_that!(thumbnails.of_day(day).len(), eq(24));
+        expect_that!(thumbnails.of_day(day.succ_opt().unwrap()).len(), eq(0));
+    }
+}
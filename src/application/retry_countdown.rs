@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use epaint::{
+    text::{LayoutJob, TextFormat},
+    Color32, FontId,
+};
+use tokio::sync::watch;
+use vek::Vec2;
+
+use crate::graphics::{Drawable, Graphics, TextContainer};
+
+/// Distance of the text from the edges of the screen, in pixels.
+const MARGIN: f32 = 20.;
+
+/// "Retrying in 43s" in the bottom left corner while the worker waits before trying the
+/// sources again, after they all failed.
+pub struct RetryCountdown {
+    /// Published by the worker while it waits.
+    retry_at: watch::Receiver<Option<Instant>>,
+    text: TextContainer,
+    /// Seconds shown, `None` when hidden.
+    shown: Option<u64>,
+}
+
+impl RetryCountdown {
+    pub fn new(
+        graphics: &mut Graphics,
+        retry_at: watch::Receiver<Option<Instant>>,
+    ) -> Result<Self> {
+        let text = graphics
+            .create_text_container()
+            .context("Cannot create retry countdown text container")?;
+        Ok(Self {
+            retry_at,
+            text,
+            shown: None,
+        })
+    }
+
+    /// Lays the countdown out for `now`, returns whether it changed on screen.
+    pub fn update(&mut self, graphics: &mut Graphics, now: Instant) -> bool {
+        let seconds = *self.retry_at.borrow();
+        let seconds = seconds.map(|retry_at| seconds_left(retry_at, now));
+        if seconds == self.shown {
+            return false;
+        }
+        self.shown = seconds;
+        if let Some(seconds) = seconds {
+            self.text.set_layout(LayoutJob::single_section(
+                format!("Retrying in {seconds}s"),
+                TextFormat {
+                    background: Color32::from_black_alpha(128),
+                    ..TextFormat::simple(FontId::proportional(20.), Color32::LIGHT_GRAY)
+                },
+            ));
+            graphics.force_text_container_update(&self.text);
+            let screen = graphics.get_dimensions().as_::<f32>();
+            let height = self.text.get_dimensions().h;
+            self.text
+                .set_position(Vec2::new(MARGIN, screen.h - height - MARGIN));
+        }
+        true
+    }
+
+    /// Receiver of the retry time, to create the countdown again.
+    pub fn retry_at(&self) -> watch::Receiver<Option<Instant>> {
+        self.retry_at.clone()
+    }
+}
+
+/// Whole seconds until `retry_at`, rounded up: "0s" is never shown, even when late.
+fn seconds_left(retry_at: Instant, now: Instant) -> u64 {
+    let left = retry_at.saturating_duration_since(now);
+    (left + Duration::from_nanos(999_999_999)).as_secs().max(1)
+}
+
+impl Drawable for RetryCountdown {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        if self.shown.is_some() {
+            self.text.draw(graphics)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{expect_that, gtest, prelude::eq};
+
+    use super::seconds_left;
+
+    #[gtest]
+    fn test_seconds_left_rounded_up() {
+        let now = Instant::now();
+        expect_that!(seconds_left(now + Duration::from_secs(43), now), eq(43));
+        expect_that!(
+            seconds_left(now + Duration::from_millis(42_100), now),
+            eq(43)
+        );
+        expect_that!(seconds_left(now + Duration::from_millis(100), now), eq(1));
+        // Late, about to retry
+        expect_that!(seconds_left(now, now + Duration::from_secs(1)), eq(1));
+    }
+}
@@ -0,0 +1,474 @@
+//! Runs the slideshow against a virtual clock, with a fake worker instead of the photo
+//! sources, to see how it copes with slow photo preparation without waiting for hours.
+//! Started with `--simulate`.
+
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Error, Result};
+use humantime_serde::re::humantime;
+use image::{DynamicImage, Rgb, RgbImage};
+use rand::Rng;
+use vek::{Extent2, Rect};
+
+use super::slideshow::Slideshow;
+use crate::{
+    configuration::{OrientationName, Settings},
+    gallery::{ImageDetails, ImageWithDetails},
+    gl::FutureGlThreadContext,
+    graphics::Graphics,
+    random,
+    worker::{prepare_slide, PreloadedSlide},
+};
+
+/// Time between two frames while something moves on screen.
+const FRAME: Duration = Duration::from_micros(16_667);
+/// Size of the offscreen screen, which only matters for the layout of the captions.
+const SCREEN: Extent2<i32> = Extent2::new(1920, 1080);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationOptions {
+    /// Simulated time, set with `--hours`. Defaults to 24 hours.
+    pub duration: Duration,
+    /// Time the fake worker takes to prepare each photo, set with `--worker-latency`.
+    /// Defaults to 3 seconds.
+    pub worker_latency: Latency,
+    /// Sends a next slide command this often, set with `--next-every`.
+    pub next_every: Option<Duration>,
+}
+
+/// Time taken to prepare a photo, either always the same ("3s") or drawn uniformly from a
+/// range ("1s..5s").
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    Uniform(Duration, Duration),
+}
+
+/// What happened during a simulation.
+#[derive(Debug, Default)]
+pub struct SimulationReport {
+    pub simulated: Duration,
+    /// Time each photo stayed on screen, until the next one was loaded.
+    pub display_durations: Vec<Duration>,
+    /// How late each photo loaded after its due time was, past that time.
+    pub lateness: Vec<Duration>,
+    /// Photos late enough for the overdue indicator to show, see
+    /// [`Settings::overdue_indicator_delay`].
+    pub overdue_indicators: usize,
+    /// Photos loaded while the previous transition was still running.
+    pub interrupted_transitions: usize,
+    /// Time spent before the first photo, with the initial slide on screen.
+    pub loading: Duration,
+}
+
+/// Prepares the photos one after the other, each being taken when the slideshow loads it.
+struct FakeWorker {
+    latency: Latency,
+    /// When the photo being prepared is ready.
+    ready_at: Instant,
+    prepared: usize,
+}
+
+impl SimulationOptions {
+    /// Reads the options following `--simulate`.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Self {
+            duration: Duration::from_secs(24 * 60 * 60),
+            worker_latency: Latency::Fixed(Duration::from_secs(3)),
+            next_every: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Missing the value of {arg}"))
+            };
+            match arg.as_str() {
+                "--hours" => {
+                    let hours: f64 = value()?.parse().context("Invalid number of hours")?;
+                    options.duration = Duration::try_from_secs_f64(hours * 3600.)
+                        .context("Invalid number of hours")?;
+                }
+                "--worker-latency" => options.worker_latency = value()?.parse()?,
+                "--next-every" => {
+                    options.next_every = Some(
+                        humantime::parse_duration(&value()?)
+                            .context("Invalid next slide interval")?,
+                    )
+                }
+                _ => bail!("Unknown simulation option {arg}"),
+            }
+        }
+        if options.next_every.is_some_and(|every| every.is_zero()) {
+            bail!("The next slide interval cannot be zero");
+        }
+        Ok(options)
+    }
+}
+
+impl Latency {
+    fn sample(&self) -> Duration {
+        match *self {
+            Latency::Fixed(latency) => latency,
+            Latency::Uniform(min, max) => random::with_rng(|rng| rng.random_range(min..=max)),
+        }
+    }
+}
+
+impl FromStr for Latency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |s: &str| {
+            humantime::parse_duration(s.trim())
+                .with_context(|| format!("Invalid worker latency {s:?}"))
+        };
+        match s.split_once("..") {
+            None => Ok(Latency::Fixed(parse(s)?)),
+            Some((min, max)) => {
+                let (min, max) = (parse(min)?, parse(max)?);
+                if min > max {
+                    bail!("The worker latency range {s:?} is reversed");
+                }
+                Ok(Latency::Uniform(min, max))
+            }
+        }
+    }
+}
+
+impl Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Latency::Fixed(latency) => write!(f, "{latency:?}"),
+            Latency::Uniform(min, max) => write!(f, "{min:?} to {max:?}"),
+        }
+    }
+}
+
+impl FakeWorker {
+    fn new(latency: Latency, start: Instant) -> Self {
+        Self {
+            latency,
+            ready_at: start + latency.sample(),
+            prepared: 0,
+        }
+    }
+
+    /// Number of the photo ready at `time`, the next one is prepared from then on.
+    fn try_recv(&mut self, time: Instant) -> Option<usize> {
+        if time < self.ready_at {
+            return None;
+        }
+        let number = self.prepared;
+        self.prepared += 1;
+        self.ready_at = time + self.latency.sample();
+        Some(number)
+    }
+}
+
+/// Simulates the slideshow with small photos prepared like real ones, on an offscreen GL
+/// context of the first EGL device. The slides are real textures, so this fails without
+/// an EGL device: a GPU, or Mesa's software one (llvmpipe) on machines without any. Only
+/// [`simulate`] runs without GL, on the mocked context of the tests.
+pub fn simulate_offscreen(
+    config: &Settings,
+    options: &SimulationOptions,
+) -> Result<SimulationReport> {
+    let gl = FutureGlThreadContext::surfaceless(None)
+        .and_then(FutureGlThreadContext::activate)
+        .context("Cannot create an offscreen GL context, the simulation needs an EGL device")?;
+    gl.set_viewport(Rect::new(0, 0, SCREEN.w, SCREEN.h));
+    let mut graphics = Graphics::new(Rc::clone(&gl), OrientationName::Angle0)?;
+    simulate(&mut graphics, config, options, |graphics, number| {
+        prepare_slide(photo(number), &gl, graphics.blurr(), config)
+    })
+}
+
+/// Plain photo, with a caption changing with its number.
+fn photo(number: usize) -> ImageWithDetails {
+    let shade = (number % 4 * 60) as u8;
+    ImageWithDetails {
+        image: DynamicImage::ImageRgb8(RgbImage::from_pixel(60, 40, Rgb([shade, 120, 180]))),
+        details: ImageDetails {
+            asset_id: Some(format!("simulation:{number}")),
+            album: None,
+            starts_album: false,
+            city: Some(format!("Photo {number}")),
+            country: None,
+            gps: None,
+            date: None,
+            people: Vec::new(),
+        },
+        low_res: false,
+        background_image: None,
+        preparation: Default::default(),
+    }
+}
+
+/// Drives the slideshow like the application does, jumping from one frame or wake-up to
+/// the next instead of waiting for them. `prepare` creates the photo numbered as asked.
+pub fn simulate(
+    graphics: &mut Graphics,
+    config: &Settings,
+    options: &SimulationOptions,
+    mut prepare: impl FnMut(&Graphics, usize) -> Result<PreloadedSlide>,
+) -> Result<SimulationReport> {
+    let start = Instant::now();
+    let end = start + options.duration;
+    let mut slides = Slideshow::create(graphics, config)?;
+    let mut worker = FakeWorker::new(options.worker_latency, start);
+    let mut report = SimulationReport {
+        simulated: options.duration,
+        ..Default::default()
+    };
+    let mut next_command = options.next_every.map(|every| start + every);
+    let mut force_load_next = false;
+    let mut shown_at = None;
+    let mut time = start;
+    while time < end {
+        if let Some(every) = options.next_every {
+            if next_command.is_some_and(|at| at <= time) {
+                force_load_next = true;
+                next_command = Some(time + every);
+            }
+        }
+        if slides.should_load_next(time) || force_load_next {
+            if let Some(number) = worker.try_recv(time) {
+                report.record_load(&slides, config, time);
+                if let Some(shown_at) = shown_at.replace(time) {
+                    report.display_durations.push(time - shown_at);
+                }
+                let preloaded = prepare(graphics, number)
+                    .with_context(|| format!("Cannot prepare simulated photo {number}"))?;
+                slides
+                    .load_next(graphics, preloaded, config, time)
+                    .context("Cannot load next frame")?;
+                force_load_next = false;
+            }
+        }
+        let loading = shown_at.is_none();
+        let sleep = slides.update_get_sleep(graphics, config, time);
+        let mut next = match sleep {
+            Some(sleep) => time.checked_add(sleep).unwrap_or(end),
+            None => {
+                // Drawn, which uploads the glyphs of the captions
+                graphics.begin_frame();
+                graphics.update();
+                time + FRAME
+            }
+        };
+        if slides.should_load_next(time) || force_load_next {
+            next = next.min(worker.ready_at);
+        }
+        if let Some(at) = next_command {
+            next = next.min(at);
+        }
+        let next = next.max(time + Duration::from_millis(1)).min(end);
+        if loading {
+            report.loading += next - time;
+        }
+        time = next;
+    }
+    Ok(report)
+}
+
+impl SimulationReport {
+    fn record_load(&mut self, slides: &Slideshow, config: &Settings, time: Instant) {
+        if matches!(
+            slides,
+            Slideshow::Transitioning(_) | Slideshow::PendingTransition(_)
+        ) {
+            self.interrupted_transitions += 1;
+        }
+        let Some(due) = slides.next_slide_due().filter(|due| *due < time) else {
+            return;
+        };
+        let lateness = time - due;
+        self.lateness.push(lateness);
+        let indicator_delay = config.overdue_indicator_delay;
+        if !indicator_delay.is_zero() && lateness >= indicator_delay {
+            self.overdue_indicators += 1;
+        }
+    }
+}
+
+/// Value below which `share` of the sorted `durations` are.
+fn percentile(durations: &[Duration], share: f64) -> Duration {
+    let index = ((durations.len() - 1) as f64 * share).round() as usize;
+    durations[index]
+}
+
+impl Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Simulated: {}",
+            humantime::format_duration(self.simulated)
+        )?;
+        writeln!(f, "Photos shown: {}", self.display_durations.len() + 1)?;
+        let mut durations = self.display_durations.clone();
+        durations.sort();
+        if !durations.is_empty() {
+            let mean = durations.iter().sum::<Duration>() / durations.len() as u32;
+            writeln!(
+                f,
+                "Display duration: min {:.1?}, median {:.1?}, p90 {:.1?}, p99 {:.1?}, max {:.1?}, mean {:.1?}",
+                durations[0],
+                percentile(&durations, 0.5),
+                percentile(&durations, 0.9),
+                percentile(&durations, 0.99),
+                durations[durations.len() - 1],
+                mean,
+            )?;
+        }
+        let max_lateness = self.lateness.iter().max().copied().unwrap_or_default();
+        writeln!(
+            f,
+            "Overdue photos: {} (max {:.1?} late), {} with the overdue indicator",
+            self.lateness.len(),
+            max_lateness,
+            self.overdue_indicators
+        )?;
+        writeln!(
+            f,
+            "Interrupted transitions: {}",
+            self.interrupted_transitions
+        )?;
+        write!(f, "Time loading: {:.1?}", self.loading)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{rc::Rc, time::Duration};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{empty, eq, ge, gt, is_true, le, len},
+    };
+    use vek::Extent2;
+
+    use super::{simulate, Latency, SimulationOptions, SimulationReport};
+    use crate::{
+        configuration::{Background, OrientationName, Settings},
+        gallery::ImageDetails,
+        gl::{texture::DetachedTexture, wrapper::mocked_gl, GlContext},
+        graphics::Graphics,
+        worker::{LiveSlide, PreloadedSlide},
+    };
+
+    fn preloaded_slide() -> PreloadedSlide {
+        PreloadedSlide {
+            live: LiveSlide::counted(),
+            details: ImageDetails {
+                asset_id: None,
+                album: None,
+                starts_album: false,
+                city: None,
+                country: None,
+                gps: None,
+                date: None,
+                people: Default::default(),
+            },
+            texture: DetachedTexture::mock(Extent2::new(100, 100)),
+            blurred_texture: None,
+            preparation: Default::default(),
+            recap_thumbnail: None,
+            transition: None,
+            show_at: None,
+        }
+    }
+
+    fn config() -> Settings {
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        config.display_duration = Duration::from_secs(30);
+        config.max_display_animation_duration = Some(Duration::from_secs(1));
+        config
+    }
+
+    fn run(config: &Settings, latency: &str, next_every: Option<Duration>) -> SimulationReport {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+        let options = SimulationOptions {
+            duration: Duration::from_secs(3600),
+            worker_latency: latency.parse().unwrap(),
+            next_every,
+        };
+        simulate(
+            &mut graphics,
+            config,
+            &options,
+            |_, _| Ok(preloaded_slide()),
+        )
+        .unwrap()
+    }
+
+    #[gtest]
+    fn test_parse_options() {
+        let args = ["--hours", "2", "--worker-latency", "1s..5s"].map(String::from);
+        let options = SimulationOptions::parse(args.into_iter()).unwrap();
+        expect_that!(options.duration, eq(Duration::from_secs(7200)));
+        expect_that!(
+            options.worker_latency,
+            eq(Latency::Uniform(
+                Duration::from_secs(1),
+                Duration::from_secs(5)
+            ))
+        );
+        expect_that!(
+            "3s".parse::<Latency>().unwrap(),
+            eq(Latency::Fixed(Duration::from_secs(3)))
+        );
+        expect_that!("5s..1s".parse::<Latency>().is_err(), is_true());
+        let unknown = ["--days", "2"].map(String::from);
+        expect_that!(
+            SimulationOptions::parse(unknown.into_iter()).is_err(),
+            is_true()
+        );
+    }
+
+    #[gtest]
+    fn test_quick_worker_keeps_up() {
+        let config = config();
+        let report = run(&config, "1s", None);
+        expect_that!(report.lateness, empty());
+        expect_that!(report.interrupted_transitions, eq(0));
+        expect_that!(report.loading, eq(Duration::from_secs(1)));
+        // Shown for the display duration, after the transition
+        let longest = config.display_duration + config.transition_duration * 2;
+        expect_that!(report.display_durations.len(), ge(100));
+        for duration in &report.display_durations {
+            expect_that!(*duration, ge(config.display_duration));
+            expect_that!(*duration, le(longest));
+        }
+    }
+
+    #[gtest]
+    fn test_slow_worker_overdue() {
+        let config = config();
+        let report = run(&config, "38s", None);
+        expect_that!(report.display_durations, len(eq(93)));
+        // Each photo is shown until the next one is ready
+        for duration in &report.display_durations {
+            expect_that!(*duration, eq(Duration::from_secs(38)));
+        }
+        expect_that!(report.lateness, len(eq(93)));
+        expect_that!(report.overdue_indicators, eq(0));
+
+        let report = run(&config, "45s", None);
+        expect_that!(report.overdue_indicators, eq(report.lateness.len()));
+        expect_that!(report.overdue_indicators, gt(0));
+    }
+
+    #[gtest]
+    fn test_next_slide_interrupts_transitions() {
+        let report = run(&config(), "0s", Some(Duration::from_millis(200)));
+        expect_that!(report.interrupted_transitions, gt(0));
+        expect_that!(report.lateness, empty());
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use epaint::{
+    text::{LayoutJob, TextFormat},
+    Color32, FontId,
+};
+use vek::Vec2;
+
+use super::{slide::Slide, StaticSlide};
+use crate::graphics::{Drawable, Graphics, TextContainer};
+
+/// How long the title stays on screen, at most the display duration.
+pub const HEADER_DURATION: Duration = Duration::from_secs(4);
+
+/// Title card naming the album or the day of the next photo, shown before it.
+pub struct AlbumHeaderSlide {
+    text: TextContainer,
+    displayed: bool,
+    /// Photo shown once the header is finished.
+    pub next: Slide,
+    pub finish_at: Instant,
+}
+
+impl AlbumHeaderSlide {
+    pub fn create(
+        graphics: &mut Graphics,
+        title: &str,
+        next: Slide,
+        finish_at: Instant,
+    ) -> Result<Self> {
+        let screen = graphics.get_dimensions().as_::<f32>();
+        let text = graphics
+            .create_text_container()
+            .context("Cannot create album header text container")?;
+        let mut job = LayoutJob::single_section(
+            title.to_owned(),
+            TextFormat::simple(FontId::proportional(56.), Color32::WHITE),
+        );
+        job.halign = epaint::emath::Align::Center;
+        job.wrap.max_width = screen.w * 0.8;
+        text.set_layout(job);
+        graphics.force_text_container_update(&text);
+
+        let bounds = text.get_bounding_rect();
+        let screen_center = Vec2::new(screen.w, screen.h) * 0.5;
+        text.set_position(screen_center - bounds.center());
+        Ok(Self {
+            text,
+            displayed: false,
+            next,
+            finish_at,
+        })
+    }
+
+    /// Centers the title again, e.g. after the window was resized.
+    pub fn relayout(&mut self, graphics: &Graphics) {
+        let screen = graphics.get_dimensions().as_::<f32>();
+        let screen_center = Vec2::new(screen.w, screen.h) * 0.5;
+        let bounds = self.text.get_bounding_rect();
+        self.text
+            .set_position(self.text.get_position() + screen_center - bounds.center());
+        self.displayed = false;
+    }
+
+    pub fn is_finished(&self, time: Instant) -> bool {
+        time >= self.finish_at
+    }
+}
+
+impl StaticSlide for AlbumHeaderSlide {
+    fn displayed(&mut self) -> &mut bool {
+        &mut self.displayed
+    }
+}
+
+impl Drawable for AlbumHeaderSlide {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        self.text.draw(graphics)
+    }
+}
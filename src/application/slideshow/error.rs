@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use epaint::{
+    text::{LayoutJob, TextFormat},
+    Color32, FontId,
+};
+use vek::Vec2;
+
+use super::StaticSlide;
+use crate::graphics::{Drawable, Graphics, TextContainer};
+
+const TITLE: &str = "Photos cannot be shown";
+
+/// Shown when photos cannot be prepared anymore, e.g. a source cannot be built. The frame
+/// keeps running, so that it can still be controlled remotely.
+pub struct ErrorSlide {
+    text: TextContainer,
+    message: String,
+    displayed: bool,
+}
+
+impl ErrorSlide {
+    pub fn create(graphics: &mut Graphics, message: &str) -> Result<Self> {
+        let screen = graphics.get_dimensions().as_::<f32>();
+        let text = graphics
+            .create_text_container()
+            .context("Cannot create error text container")?;
+        let mut job = LayoutJob::default();
+        job.append(
+            TITLE,
+            0.,
+            TextFormat::simple(FontId::proportional(32.), Color32::WHITE),
+        );
+        job.append(
+            &format!("\n{message}"),
+            0.,
+            TextFormat::simple(FontId::proportional(20.), Color32::LIGHT_GRAY),
+        );
+        job.halign = epaint::emath::Align::Center;
+        job.wrap.max_width = screen.w * 0.8;
+        text.set_layout(job);
+        graphics.force_text_container_update(&text);
+
+        let bounds = text.get_bounding_rect();
+        let screen_center = Vec2::new(screen.w, screen.h) * 0.5;
+        text.set_position(screen_center - bounds.center());
+        Ok(Self {
+            text,
+            message: message.to_owned(),
+            displayed: false,
+        })
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl StaticSlide for ErrorSlide {
+    fn displayed(&mut self) -> &mut bool {
+        &mut self.displayed
+    }
+}
+
+impl Drawable for ErrorSlide {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        self.text.draw(graphics)
+    }
+}
@@ -1,32 +1,56 @@
+mod album_header;
 mod animated_properties;
+mod error;
 mod loading;
+mod no_photos;
 mod slide;
+#[cfg(test)]
+mod snapshot_test;
+mod test_pattern;
 mod transition;
 
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
 use glissade::Easing;
-use transition::EaseInOutTransition;
-use vek::Vec2;
+use log::warn;
+use rand::Rng;
+use vek::{Extent2, Rect, Vec2};
 
 use self::{
+    album_header::{AlbumHeaderSlide, HEADER_DURATION},
+    error::ErrorSlide,
     loading::LoadingSlide,
-    slide::{AnimatedSlide, AnimatedSlideProperties, Slide, SlideProperties},
-    transition::{DissolveTransition, Transition},
+    no_photos::NoPhotosSlide,
+    slide::{format_date, AnimatedSlide, AnimatedSlideProperties, Slide, SlideProperties},
+    test_pattern::TestPatternSlide,
+    transition::{DissolveTransition, EaseInOutTransition, PushTransition, Transition},
 };
 use crate::{
-    configuration::{InitSlideOptions, Settings},
+    configuration::{InitSlideOptions, Settings, TransitionKind},
     graphics::{Drawable, Graphics},
+    random,
     worker::PreloadedSlide,
 };
 
+/// Zoom of a photo when it appears, before it slowly zooms in to fill its area.
+const START_ZOOM: f32 = 0.9;
+
 #[allow(clippy::large_enum_variant)]
 pub enum Slideshow {
     None,
     Loading(LoadingSlide),
     Single(AnimatedSlide),
     Transitioning(TransitioningSlide),
+    /// Next photo warmed up, or its caption waiting for new glyphs of the font atlas, its
+    /// transition starts on the next update.
+    PendingTransition(PendingTransition),
+    TestPattern(TestPatternSlide),
+    NoPhotos(NoPhotosSlide),
+    /// Photos cannot be prepared anymore.
+    Failed(ErrorSlide),
+    AlbumHeader(AlbumHeaderSlide),
 }
 
 pub struct TransitioningSlide {
@@ -34,8 +58,28 @@ pub struct TransitioningSlide {
     next: AnimatedSlide,
 }
 
+pub struct PendingTransition {
+    prev: AnimatedSlide,
+    next: Slide,
+    transition: TransitionKind,
+}
+
+/// Slide that doesn't move: only its first frame needs to be drawn, until it's laid out
+/// again.
+trait StaticSlide {
+    /// Set once the slide was drawn, cleared to draw it again.
+    fn displayed(&mut self) -> &mut bool;
+
+    fn needs_redraw(&mut self) -> bool {
+        !std::mem::replace(self.displayed(), true)
+    }
+}
+
 impl Slideshow {
     pub fn create(graphics: &mut Graphics, config: &Settings) -> Result<Self> {
+        if config.debug.test_pattern {
+            return Ok(Slideshow::TestPattern(TestPatternSlide::create(graphics)?));
+        }
         match &config.init_slide {
             InitSlideOptions::Empty => Ok(Slideshow::None),
             InitSlideOptions::LoadingCircle(loading_circle_options) => {
@@ -45,15 +89,61 @@ impl Slideshow {
         }
     }
 
+    /// Schedules the next photo again from `time`, e.g. once the wall clock was set, since
+    /// the photo of the day is replaced at midnight.
+    pub fn reschedule(&mut self, config: &Settings, time: Instant) {
+        if let Slideshow::Single(slide) = self {
+            slide.finish_at = time + display_time(config, Local::now());
+        }
+    }
+
     pub fn should_load_next(&self, time: Instant) -> bool {
         match self {
             Slideshow::None => true,
             Slideshow::Loading(_) => true,
             Slideshow::Single(slide) => slide.is_finished(time),
             Slideshow::Transitioning(_) => false,
+            Slideshow::PendingTransition(_) => false,
+            Slideshow::TestPattern(_) => false,
+            Slideshow::NoPhotos(_) => true,
+            // Photos come again once the worker started again
+            Slideshow::Failed(_) => true,
+            Slideshow::AlbumHeader(_) => false,
         }
     }
 
+    /// When the photo on screen is due to be replaced, if a next photo is expected.
+    pub fn next_slide_due(&self) -> Option<Instant> {
+        match self {
+            Slideshow::Single(slide) => Some(slide.finish_at),
+            _ => None,
+        }
+    }
+
+    /// Replace the current slide with a notice that no photo matches the sources.
+    pub fn show_no_photos(&mut self, graphics: &mut Graphics, config: &Settings) -> Result<()> {
+        if !matches!(self, Slideshow::NoPhotos(_)) {
+            *self =
+                Slideshow::NoPhotos(NoPhotosSlide::create(graphics, &config.no_photos_message)?);
+        }
+        Ok(())
+    }
+
+    /// Replace the current slide with an error preventing photos to be shown.
+    pub fn show_error(&mut self, graphics: &mut Graphics, message: &str) -> Result<()> {
+        if !matches!(self, Slideshow::Failed(error) if error.message() == message) {
+            *self = Slideshow::Failed(ErrorSlide::create(graphics, message)?);
+        }
+        Ok(())
+    }
+
+    /// Whether preloaded slides can be displayed, the test pattern stays until disabled.
+    pub fn accepts_slides(&self) -> bool {
+        !matches!(self, Slideshow::TestPattern(_))
+    }
+
+    /// Shows `slide` next, with its own transition when it has one, e.g. the one of the
+    /// leader of synchronized frames.
     pub fn load_next(
         &mut self,
         graphics: &mut Graphics,
@@ -61,55 +151,231 @@ impl Slideshow {
         config: &Settings,
         time: Instant,
     ) -> Result<()> {
-        let slide = Slide::create(slide, graphics, config)?;
+        let transition = slide
+            .transition
+            .unwrap_or_else(|| random::with_rng(|rng| pick_transition(&config.transitions, rng)));
+        // Numbered after the photo on screen, so that captions alternate their locale
+        let number = self
+            .current_slide()
+            .map_or(0, |current| current.number() + 1);
+        let mut slide = Slide::create(slide, graphics, config, number)?;
+        slide.set_caption_visible(is_caption_shown(config));
+        Self::warm_up(graphics, &slide, config);
+        if let Some(title) = self.header_title(&slide, config) {
+            let finish_at = time + HEADER_DURATION.min(config.display_duration);
+            let header = AlbumHeaderSlide::create(graphics, &title, slide, finish_at)?;
+            *self = Slideshow::AlbumHeader(header);
+            return Ok(());
+        }
         let mut old_self = Self::None;
         std::mem::swap(self, &mut old_self);
         match old_self {
-            Slideshow::None | Slideshow::Loading(_) => {
-                *self = Self::to_single(
-                    graphics,
-                    slide,
-                    SlideProperties {
-                        zoom: 0.9,
-                        ..SlideProperties::default()
-                    },
-                    config,
-                    time,
-                )
-            }
-            Slideshow::Single(mut old)
-            | Slideshow::Transitioning(TransitioningSlide {
-                prev: _,
-                next: mut old,
-            }) => {
-                let transition = get_random_transition();
-                let transition_duration = config.transition_duration;
-                transition.ease_out(time, transition_duration, &mut old.animation);
-                let mut animation = transition.ease_in(time, transition_duration);
-                animation.set_zoom_no_ease(0.9);
-                animation.set_text_position_no_ease([0., graphics.get_dimensions().h as f32]);
-                let new = AnimatedSlide {
-                    slide,
-                    animation,
-                    finish_at: time,
+            // First photo, after the initial slide
+            Slideshow::None | Slideshow::Loading(_) if config.intro.skip_first => {
+                *self = Self::appear(graphics, slide, 1.0, config, time);
+            }
+            Slideshow::None
+            | Slideshow::Loading(_)
+            | Slideshow::TestPattern(_)
+            | Slideshow::NoPhotos(_)
+            | Slideshow::Failed(_)
+            | Slideshow::AlbumHeader(_) => {
+                *self = Self::appear(graphics, slide, START_ZOOM, config, time);
+            }
+            Slideshow::Single(old)
+            | Slideshow::Transitioning(TransitioningSlide { prev: _, next: old })
+            | Slideshow::PendingTransition(PendingTransition { prev: old, .. }) => {
+                // Started once the warmed up photo can be drawn without flashing, and the
+                // caption without rebuilding the font atlas during the first frames
+                *self = if config.warm_up_textures || graphics.font_atlas_pending() {
+                    Slideshow::PendingTransition(PendingTransition {
+                        prev: old,
+                        next: slide,
+                        transition,
+                    })
+                } else {
+                    Self::start_transition(graphics, old, slide, transition, config, time)
                 };
+            }
+        }
+        Ok(())
+    }
 
-                *self = Slideshow::Transitioning(TransitioningSlide {
-                    prev: old,
-                    next: new,
-                })
+    fn start_transition(
+        graphics: &Graphics,
+        mut old: AnimatedSlide,
+        slide: Slide,
+        kind: TransitionKind,
+        config: &Settings,
+        time: Instant,
+    ) -> Self {
+        let transition = get_transition(kind);
+        let transition_duration = config.transition_duration;
+        transition.ease_out(time, transition_duration, &mut old.animation);
+        let mut animation = AnimatedSlideProperties::default();
+        animation.set_zoom_no_ease(START_ZOOM);
+        animation.set_text_position_no_ease([0., graphics.get_dimensions().h as f32]);
+        transition.ease_in(time, transition_duration, &mut animation);
+        let new = AnimatedSlide {
+            slide,
+            animation,
+            finish_at: time,
+        };
+
+        Slideshow::Transitioning(TransitioningSlide {
+            prev: old,
+            next: new,
+        })
+    }
+
+    /// Draws `slide` offscreen when enabled, see [`Graphics::warm_up`]. It's only a
+    /// workaround, the slide is shown even when it fails.
+    fn warm_up(graphics: &mut Graphics, slide: &Slide, config: &Settings) {
+        if config.warm_up_textures {
+            if let Err(err) = slide.warm_up(graphics) {
+                warn!("Cannot warm up the next slide: {:?}", err);
+            }
+        }
+    }
+
+    /// Replaces the photo on screen with a better version of it, without transition.
+    /// Returns whether it was shown, a photo already replaced by the next one is deleted.
+    pub fn upgrade(
+        &mut self,
+        graphics: &mut Graphics,
+        slide: PreloadedSlide,
+        config: &Settings,
+        time: Instant,
+    ) -> Result<bool> {
+        let current = match self {
+            Slideshow::Single(current)
+            | Slideshow::Transitioning(TransitioningSlide { next: current, .. }) => {
+                &mut current.slide
+            }
+            Slideshow::PendingTransition(PendingTransition { next, .. }) => next,
+            Slideshow::AlbumHeader(header) => &mut header.next,
+            _ => {
+                slide.delete(graphics.gl());
+                return Ok(false);
+            }
+        };
+        let asset_id = slide.details.asset_id.as_deref();
+        if asset_id.is_none() || current.asset_id() != asset_id {
+            slide.delete(graphics.gl());
+            return Ok(false);
+        }
+        current.upgrade(slide, graphics, config)?;
+        Self::warm_up(graphics, current, config);
+        if let Slideshow::Single(current)
+        | Slideshow::Transitioning(TransitioningSlide { next: current, .. }) = self
+        {
+            current.update(time);
+        }
+        Ok(true)
+    }
+
+    /// Lays the slides out again for the current screen size, e.g. after the window was
+    /// resized, instead of waiting for the next photo.
+    pub fn relayout(&mut self, graphics: &mut Graphics, config: &Settings) -> Result<()> {
+        match self {
+            // Centered on each update
+            Slideshow::None | Slideshow::Loading(_) => {}
+            Slideshow::Single(slide) => Self::relayout_slide(graphics, slide, config)?,
+            Slideshow::Transitioning(TransitioningSlide { prev, next }) => {
+                Self::relayout_slide(graphics, prev, config)?;
+                // Its caption is placed once the transition is finished
+                next.slide.relayout(graphics, config)?;
+            }
+            Slideshow::PendingTransition(PendingTransition { prev, next, .. }) => {
+                Self::relayout_slide(graphics, prev, config)?;
+                next.relayout(graphics, config)?;
+            }
+            Slideshow::TestPattern(_) => {
+                *self = Slideshow::TestPattern(TestPatternSlide::create(graphics)?);
+            }
+            Slideshow::NoPhotos(_) => {
+                *self = Slideshow::NoPhotos(NoPhotosSlide::create(
+                    graphics,
+                    &config.no_photos_message,
+                )?);
+            }
+            Slideshow::Failed(error) => {
+                *self = Slideshow::Failed(ErrorSlide::create(graphics, error.message())?);
+            }
+            Slideshow::AlbumHeader(header) => {
+                header.relayout(graphics);
+                header.next.relayout(graphics, config)?;
             }
         }
         Ok(())
     }
 
+    fn relayout_slide(
+        graphics: &mut Graphics,
+        slide: &mut AnimatedSlide,
+        config: &Settings,
+    ) -> Result<()> {
+        slide.slide.relayout(graphics, config)?;
+        if let Some((target_pos, _)) = caption_placement(graphics, &slide.slide) {
+            slide
+                .animation
+                .set_text_position_no_ease(target_pos.into_array());
+        }
+        Ok(())
+    }
+
+    /// Title of the card shown before `next`: its album when it starts it within its source,
+    /// and its day when it differs from the one of the photo on screen.
+    fn header_title(&self, next: &Slide, config: &Settings) -> Option<String> {
+        let current = self.current_slide();
+        let album = next
+            .album()
+            .filter(|_| config.show_album_headers && next.starts_album())
+            .map(str::to_owned);
+        let day = next
+            .day()
+            .filter(|day| config.group_by_day && Some(*day) != current.and_then(Slide::day))
+            .map(|day| format_date(day, &config.caption.date_format, next.number()));
+        let title = [album, day].into_iter().flatten().collect::<Vec<_>>();
+        (!title.is_empty()).then(|| title.join("\n"))
+    }
+
+    /// Album of the photo on screen, or about to be.
+    fn current_slide(&self) -> Option<&Slide> {
+        match self {
+            Slideshow::Single(current)
+            | Slideshow::Transitioning(TransitioningSlide { next: current, .. }) => {
+                Some(&current.slide)
+            }
+            Slideshow::PendingTransition(PendingTransition { next, .. }) => Some(next),
+            Slideshow::AlbumHeader(header) => Some(&header.next),
+            _ => None,
+        }
+    }
+
+    /// Opacity of the photo on screen as animated and as drawn, shown by the debug overlay.
+    pub fn opacity(&self) -> Option<(f32, f32)> {
+        self.current_slide().map(Slide::opacity)
+    }
+
     // TODO: Test me !
     // Returns the time during wich the application can safely sleep if there is no need to redraw
     pub fn update_get_sleep(
         &mut self,
-        graphics: &Graphics,
+        graphics: &mut Graphics,
+        config: &Settings,
+        time: Instant,
+    ) -> Option<Duration> {
+        self.update_get_sleep_at(graphics, config, time, Utc::now())
+    }
+
+    /// Like [`Self::update_get_sleep`], with relative dates in captions as of `now`.
+    fn update_get_sleep_at(
+        &mut self,
+        graphics: &mut Graphics,
         config: &Settings,
         time: Instant,
+        now: DateTime<Utc>,
     ) -> Option<Duration> {
         let mut old_self = Self::None;
         let mut max_sleep = None;
@@ -119,17 +385,55 @@ impl Slideshow {
                 max_sleep = Some(Duration::MAX);
                 old_self
             }
+            Slideshow::TestPattern(ref mut pattern) => {
+                if !pattern.needs_redraw() {
+                    max_sleep = Some(Duration::MAX);
+                }
+                old_self
+            }
+            Slideshow::NoPhotos(ref mut no_photos) => {
+                if !no_photos.needs_redraw() {
+                    max_sleep = Some(Duration::MAX);
+                }
+                old_self
+            }
+            Slideshow::Failed(ref mut error) => {
+                if !error.needs_redraw() {
+                    max_sleep = Some(Duration::MAX);
+                }
+                old_self
+            }
             Slideshow::Loading(ref mut loading) => {
                 loading.update(graphics, time);
                 old_self
             }
+            Slideshow::AlbumHeader(mut header) => {
+                if header.is_finished(time) {
+                    Self::appear(graphics, header.next, START_ZOOM, config, time)
+                } else {
+                    if !header.needs_redraw() {
+                        max_sleep = Some(header.finish_at - time);
+                    }
+                    Slideshow::AlbumHeader(header)
+                }
+            }
             Slideshow::Single(ref mut slide) => {
+                // Checked each minute, even while the photo is animated
+                let time_sensitive = config.caption.is_time_sensitive();
+                let caption_refreshed =
+                    time_sensitive && Self::refresh_caption(graphics, slide, now);
                 slide.update(time);
-                if slide.animation.is_finished(time) {
-                    max_sleep = Some(if slide.finish_at >= time {
+                let caption_changed = slide.slide.set_caption_visible(is_caption_shown(config));
+                if !caption_changed && !caption_refreshed && slide.animation.is_finished(time) {
+                    let sleep = if slide.finish_at >= time {
                         slide.finish_at - time
                     } else {
                         Duration::MAX
+                    };
+                    max_sleep = Some(if time_sensitive {
+                        sleep.min(until_next_minute(Local::now()))
+                    } else {
+                        sleep
                     });
                 }
                 old_self
@@ -148,10 +452,59 @@ impl Slideshow {
                     Slideshow::Transitioning(t)
                 }
             }
+            // Drawn once more, which uploads the glyphs of the next caption
+            Slideshow::PendingTransition(pending) if graphics.font_atlas_pending() => {
+                Slideshow::PendingTransition(pending)
+            }
+            Slideshow::PendingTransition(PendingTransition {
+                prev,
+                next,
+                transition,
+            }) => Self::start_transition(graphics, prev, next, transition, config, time),
         };
         max_sleep
     }
 
+    /// Renders the caption of `slide` again, placed anew since its size may have changed.
+    /// Returns whether it changed.
+    fn refresh_caption(
+        graphics: &mut Graphics,
+        slide: &mut AnimatedSlide,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match slide.slide.refresh_caption(graphics, now) {
+            Ok(refreshed) => {
+                if let Some((target_pos, _)) =
+                    caption_placement(graphics, &slide.slide).filter(|_| refreshed)
+                {
+                    slide
+                        .animation
+                        .set_text_position_no_ease(target_pos.into_array());
+                }
+                refreshed
+            }
+            Err(err) => {
+                warn!("Cannot refresh caption: {:?}", err);
+                false
+            }
+        }
+    }
+
+    /// Shows `slide` without transition, zooming in from `zoom`.
+    fn appear(
+        graphics: &Graphics,
+        slide: Slide,
+        zoom: f32,
+        config: &Settings,
+        start: Instant,
+    ) -> Self {
+        let properties = SlideProperties {
+            zoom,
+            ..SlideProperties::default()
+        };
+        Self::to_single(graphics, slide, properties, config, start)
+    }
+
     fn to_single(
         graphics: &Graphics,
         slide: Slide,
@@ -164,13 +517,19 @@ impl Slideshow {
             .max_display_animation_duration
             .unwrap_or(config.display_duration)
             .min(config.display_duration);
-        animation.ease_zoom(1.0, start, display_animation_duration, Easing::CubicInOut);
-        if let Some(text) = slide.get_text() {
-            let size = text.size().as_::<f32>();
-            let screen = graphics.get_dimensions().as_::<f32>();
-
-            let target_pos = Vec2::new(screen.w * 0.5 - size.w * 0.5, screen.h - size.h);
-            let from_pos = target_pos + Vec2::new(0., size.h);
+        let framing = &config.portrait_framing;
+        match framing
+            .enabled
+            .then(|| slide.portrait_framing(framing.face_size))
+            .flatten()
+        {
+            Some((pan, zoom)) => {
+                animation.ease_zoom(zoom, start, display_animation_duration, Easing::CubicInOut);
+                animation.ease_pan(pan, start, display_animation_duration, Easing::CubicInOut);
+            }
+            None => animation.ease_zoom(1.0, start, display_animation_duration, Easing::CubicInOut),
+        }
+        if let Some((target_pos, from_pos)) = caption_placement(graphics, &slide) {
             animation.set_text_position_no_ease(from_pos.into_array());
             animation.ease_text_position(
                 target_pos.into_array(),
@@ -183,7 +542,7 @@ impl Slideshow {
         Self::Single(AnimatedSlide {
             slide,
             animation,
-            finish_at: start + config.display_duration,
+            finish_at: start + display_time(config, Local::now()),
         })
     }
 }
@@ -214,14 +573,573 @@ impl Drawable for Slideshow {
             Slideshow::Loading(slide) => slide.draw(graphics),
             Slideshow::Single(slide) => slide.draw(graphics),
             Slideshow::Transitioning(transitioning_slide) => transitioning_slide.draw(graphics),
+            // The next slide isn't visible yet
+            Slideshow::PendingTransition(pending) => pending.prev.draw(graphics),
+            Slideshow::TestPattern(pattern) => pattern.draw(graphics),
+            Slideshow::NoPhotos(no_photos) => no_photos.draw(graphics),
+            Slideshow::Failed(error) => error.draw(graphics),
+            Slideshow::AlbumHeader(header) => header.draw(graphics),
         }
     }
 }
 
-fn get_random_transition() -> Box<dyn Transition> {
-    match rand::random::<u8>() % 2 {
-        0 => Box::new(DissolveTransition),
-        1 => Box::new(EaseInOutTransition),
-        _ => unreachable!(),
+/// Where the caption of `slide` is shown, and where it slides in from.
+fn caption_placement(graphics: &Graphics, slide: &Slide) -> Option<(Vec2<f32>, Vec2<f32>)> {
+    let size = slide.get_text()?.size().as_::<f32>();
+    let screen = graphics.get_slide_dimensions().as_::<f32>();
+    let target_pos = slide.caption_position(screen)?;
+    // Slides in from the closest screen edge
+    let from_pos = if target_pos.y < screen.h * 0.5 {
+        target_pos - Vec2::new(0., size.h)
+    } else {
+        target_pos + Vec2::new(0., size.h)
+    };
+    Some((target_pos, from_pos))
+}
+
+/// Position of a caption of `size`, at the bottom center of the screen unless it covers one
+/// of `faces`. The top center, then the corners are tried before accepting the overlap.
+fn caption_position(
+    size: Extent2<f32>,
+    screen: Extent2<f32>,
+    faces: &[Rect<f32, f32>],
+) -> Vec2<f32> {
+    let center = screen.w * 0.5 - size.w * 0.5;
+    let right = screen.w - size.w;
+    let bottom = screen.h - size.h;
+    let candidates = [
+        Vec2::new(center, bottom),
+        Vec2::new(center, 0.),
+        Vec2::new(0., bottom),
+        Vec2::new(right, bottom),
+        Vec2::new(0., 0.),
+        Vec2::new(right, 0.),
+    ];
+    candidates
+        .into_iter()
+        .find(|position| {
+            let caption = Rect::new(position.x, position.y, size.w, size.h);
+            !faces.iter().any(|face| caption.collides_with_rect(*face))
+        })
+        .unwrap_or(candidates[0])
+}
+
+fn is_caption_shown(config: &Settings) -> bool {
+    config.caption.is_shown_at(Local::now().time())
+}
+
+/// Time until the next minute starts, when relative dates in captions are refreshed.
+fn until_next_minute<Tz: TimeZone>(now: DateTime<Tz>) -> Duration {
+    let elapsed = Duration::new(now.second().into(), now.nanosecond().min(999_999_999));
+    Duration::from_secs(60).saturating_sub(elapsed)
+}
+
+/// How long a photo shown at `now` stays on screen, see [`Settings::daily_photo`].
+fn display_time(config: &Settings, now: DateTime<Local>) -> Duration {
+    if config.daily_photo {
+        until_next_day(now)
+    } else {
+        config.display_duration
+    }
+}
+
+/// Time left before midnight, a day when it cannot be known.
+fn until_next_day<Tz: TimeZone>(now: DateTime<Tz>) -> Duration {
+    now.date_naive()
+        .succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(now.timezone()).earliest())
+        .and_then(|midnight| (midnight - now).to_std().ok())
+        .unwrap_or(Duration::from_secs(24 * 60 * 60))
+}
+
+/// One of the enabled `transitions`, a dissolve when none is.
+pub fn pick_transition(transitions: &[TransitionKind], rng: &mut impl Rng) -> TransitionKind {
+    if transitions.is_empty() {
+        return TransitionKind::Dissolve;
+    }
+    transitions[rng.random_range(0..transitions.len())]
+}
+
+fn get_transition(kind: TransitionKind) -> Box<dyn Transition> {
+    match kind {
+        TransitionKind::Dissolve => Box::new(DissolveTransition),
+        TransitionKind::EaseInOut => Box::new(EaseInOutTransition),
+        TransitionKind::Push => Box::new(PushTransition),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        rc::Rc,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use chrono::{DateTime, FixedOffset, TimeDelta, TimeZone, Utc};
+    use faux::when;
+    use googletest::{
+        expect_that, gtest,
+        matchers::matches_pattern,
+        prelude::{approx_eq, eq, is_false, is_true, none, some},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use vek::{Extent2, Rect, Vec2};
+
+    use super::{caption_position, pick_transition, until_next_day, Slide, Slideshow, START_ZOOM};
+    use crate::{
+        configuration::{Background, OrientationName, Settings, TransitionKind},
+        gallery::ImageDetails,
+        gl::{texture::DetachedTexture, wrapper::mocked_gl, GlContext},
+        graphics::Graphics,
+        worker::{LiveSlide, PreloadedSlide},
+    };
+
+    fn preloaded_slide(album: Option<&str>) -> PreloadedSlide {
+        PreloadedSlide {
+            live: LiveSlide::counted(),
+            details: ImageDetails {
+                asset_id: None,
+                album: album.map(str::to_owned),
+                starts_album: album.is_some(),
+                city: None,
+                country: None,
+                gps: None,
+                date: None,
+                people: Default::default(),
+            },
+            texture: DetachedTexture::mock(Extent2::new(100, 100)),
+            blurred_texture: None,
+            preparation: Default::default(),
+            recap_thumbnail: None,
+            transition: None,
+            show_at: None,
+        }
+    }
+
+    #[gtest]
+    fn test_single_slide_loads_next_after_display_duration() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        config.display_duration = Duration::from_secs(30);
+        config.max_display_animation_duration = Some(Duration::from_secs(10));
+        let start = Instant::now();
+        let mut slides = Slideshow::None;
+        expect_that!(slides.should_load_next(start), is_true());
+
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, start)
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::Single(_)), is_true());
+        // Zooming in
+        expect_that!(
+            slides.should_load_next(start + Duration::from_secs(5)),
+            is_false()
+        );
+        // Zoom done, still displayed
+        expect_that!(
+            slides.should_load_next(start + Duration::from_secs(20)),
+            is_false()
+        );
+        expect_that!(
+            slides.should_load_next(start + Duration::from_secs(30)),
+            is_true()
+        );
+        expect_that!(
+            slides.next_slide_due(),
+            eq(Some(start + Duration::from_secs(30)))
+        );
+    }
+
+    #[gtest]
+    fn test_first_slide_skips_intro_zoom() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let start = Instant::now();
+        let zoom = |slides: &Slideshow| match slides {
+            Slideshow::Single(slide) => slide.animation.get_zoom(start),
+            _ => panic!("Not showing a single slide"),
+        };
+
+        let mut slides = Slideshow::None;
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, start)
+            .unwrap();
+        expect_that!(zoom(&slides), approx_eq(START_ZOOM));
+
+        config.intro.skip_first = true;
+        let mut slides = Slideshow::None;
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, start)
+            .unwrap();
+        expect_that!(zoom(&slides), approx_eq(1.0));
+
+        // Not the first photo anymore
+        slides.show_no_photos(&mut graphics, &config).unwrap();
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, start)
+            .unwrap();
+        expect_that!(zoom(&slides), approx_eq(START_ZOOM));
+    }
+
+    #[gtest]
+    fn test_daily_photo_held_until_midnight() {
+        let evening = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 21, 30, 0)
+            .unwrap();
+        expect_that!(
+            until_next_day(evening),
+            eq(Duration::from_secs(2 * 60 * 60 + 30 * 60))
+        );
+        let midnight = Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap();
+        expect_that!(
+            until_next_day(midnight),
+            eq(Duration::from_secs(24 * 60 * 60))
+        );
+    }
+
+    #[gtest]
+    fn test_slide_replaces_error() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let config = Settings::default();
+        let now = Instant::now();
+        let mut slides = Slideshow::None;
+
+        slides
+            .show_error(&mut graphics, "Cannot build source 0")
+            .unwrap();
+        expect_that!(
+            matches!(&slides, Slideshow::Failed(error) if error.message() == "Cannot build source 0"),
+            is_true()
+        );
+        expect_that!(slides.update_get_sleep(&mut graphics, &config, now), none());
+        // Static afterwards
+        expect_that!(
+            slides.update_get_sleep(&mut graphics, &config, now),
+            some(eq(Duration::MAX))
+        );
+
+        // Sent by the worker started again
+        expect_that!(slides.should_load_next(now), is_true());
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, now)
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::Single(_)), is_true());
+    }
+
+    #[gtest]
+    fn test_album_header_on_album_change() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        config.show_album_headers = true;
+        let start = Instant::now();
+        let mut slides = Slideshow::None;
+
+        slides
+            .load_next(
+                &mut graphics,
+                preloaded_slide(Some("Holidays")),
+                &config,
+                start,
+            )
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::AlbumHeader(_)), is_true());
+        expect_that!(slides.should_load_next(start), is_false());
+
+        let after_header = start + Duration::from_secs(5);
+        slides.update_get_sleep(&mut graphics, &config, after_header);
+        expect_that!(matches!(slides, Slideshow::Single(_)), is_true());
+
+        // Same album in its source: straight to the photo
+        let mut same_album = preloaded_slide(Some("Holidays"));
+        same_album.details.starts_album = false;
+        slides
+            .load_next(&mut graphics, same_album, &config, after_header)
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::Transitioning(_)), is_true());
+
+        slides
+            .load_next(
+                &mut graphics,
+                preloaded_slide(Some("Birthday")),
+                &config,
+                after_header,
+            )
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::AlbumHeader(_)), is_true());
+    }
+
+    #[gtest]
+    fn test_day_header_between_days() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        config.group_by_day = true;
+        let taken_at = |date: &str| {
+            let mut slide = preloaded_slide(None);
+            slide.details.date = Some(date.parse().unwrap());
+            slide
+        };
+        let start = Instant::now();
+        let mut slides = Slideshow::None;
+
+        slides
+            .load_next(
+                &mut graphics,
+                taken_at("2024-07-01T09:00:00Z"),
+                &config,
+                start,
+            )
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::AlbumHeader(_)), is_true());
+        let after_header = start + Duration::from_secs(5);
+        slides.update_get_sleep(&mut graphics, &config, after_header);
+
+        // Same day: straight to the photo
+        slides
+            .load_next(
+                &mut graphics,
+                taken_at("2024-07-01T18:00:00Z"),
+                &config,
+                after_header,
+            )
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::Transitioning(_)), is_true());
+
+        slides
+            .load_next(
+                &mut graphics,
+                taken_at("2024-07-02T08:00:00Z"),
+                &config,
+                after_header,
+            )
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::AlbumHeader(_)), is_true());
+    }
+
+    #[gtest]
+    fn test_no_album_header_when_disabled() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let mut slides = Slideshow::None;
+
+        slides
+            .load_next(
+                &mut graphics,
+                preloaded_slide(Some("Holidays")),
+                &config,
+                Instant::now(),
+            )
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::Single(_)), is_true());
+    }
+
+    #[gtest]
+    fn test_warm_up_once_per_slide() {
+        // Counts the switches to the warm up picture
+        let warm_ups = Arc::new(AtomicUsize::new(0));
+        let mut gl = mocked_gl();
+        let counter = Arc::clone(&warm_ups);
+        when!(gl.viewport).then(move |viewport| {
+            if viewport == (0, 0, 4, 4) {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        config.warm_up_textures = true;
+        let start = Instant::now();
+        let mut slides = Slideshow::None;
+
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, start)
+            .unwrap();
+        expect_that!(warm_ups.load(Ordering::SeqCst), eq(1));
+
+        let next_at = start + config.display_duration;
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, next_at)
+            .unwrap();
+        expect_that!(warm_ups.load(Ordering::SeqCst), eq(2));
+        expect_that!(matches!(slides, Slideshow::PendingTransition(_)), is_true());
+
+        // The transition starts on the next update, without warming up again
+        let sleep = slides.update_get_sleep(&mut graphics, &config, next_at);
+        expect_that!(sleep.is_none(), is_true());
+        expect_that!(matches!(slides, Slideshow::Transitioning(_)), is_true());
+        slides.update_get_sleep(&mut graphics, &config, next_at + Duration::from_secs(1));
+        expect_that!(warm_ups.load(Ordering::SeqCst), eq(2));
+    }
+
+    #[gtest]
+    fn test_transition_waits_for_caption_glyphs() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let in_city = |city: &str| {
+            let mut slide = preloaded_slide(None);
+            slide.details.city = Some(city.into());
+            slide
+        };
+        let start = Instant::now();
+        let mut slides = Slideshow::None;
+        slides
+            .load_next(&mut graphics, in_city("Bordeaux"), &config, start)
+            .unwrap();
+        graphics.begin_frame();
+        graphics.update();
+
+        // New glyphs for the caption
+        let next_at = start + config.display_duration;
+        slides
+            .load_next(&mut graphics, in_city("Zürich"), &config, next_at)
+            .unwrap();
+        expect_that!(matches!(slides, Slideshow::PendingTransition(_)), is_true());
+        expect_that!(
+            slides.update_get_sleep(&mut graphics, &config, next_at),
+            none()
+        );
+        expect_that!(matches!(slides, Slideshow::PendingTransition(_)), is_true());
+
+        graphics.begin_frame();
+        graphics.update();
+        slides.update_get_sleep(&mut graphics, &config, next_at);
+        expect_that!(matches!(slides, Slideshow::Transitioning(_)), is_true());
+    }
+
+    #[gtest]
+    fn test_caption_avoids_faces() {
+        let size = Extent2::new(200., 50.);
+        let screen = Extent2::new(800., 600.);
+        let expect_position = |faces: &[Rect<f32, f32>], x: f32, y: f32| {
+            expect_that!(
+                caption_position(size, screen, faces),
+                matches_pattern!(Vec2 {
+                    x: approx_eq(x),
+                    y: approx_eq(y),
+                })
+            );
+        };
+
+        expect_position(&[], 300., 550.);
+        // Face at the bottom center: moved to the top
+        expect_position(&[Rect::new(350., 500., 100., 100.)], 300., 0.);
+        // Faces at the bottom and top centers: bottom left corner
+        expect_position(
+            &[
+                Rect::new(350., 500., 100., 100.),
+                Rect::new(350., 0., 100., 100.),
+            ],
+            0.,
+            550.,
+        );
+        // Faces everywhere: overlap accepted at the bottom center
+        expect_position(&[Rect::new(0., 0., 800., 600.)], 300., 550.);
+    }
+
+    #[gtest]
+    fn test_seeded_transitions_deterministic() {
+        let transitions = [
+            TransitionKind::Dissolve,
+            TransitionKind::EaseInOut,
+            TransitionKind::Push,
+        ];
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..12)
+                .map(|_| pick_transition(&transitions, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        expect_that!(picks(7), eq(&picks(7)));
+        expect_that!(
+            pick_transition(&[], &mut StdRng::seed_from_u64(7)),
+            eq(TransitionKind::Dissolve)
+        );
+    }
+
+    #[gtest]
+    fn test_transition_of_synced_slide() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        config.transitions = vec![TransitionKind::Push];
+        // Kept pending, with the transition it will start
+        config.warm_up_textures = true;
+        let start = Instant::now();
+        let mut slides = Slideshow::None;
+        slides
+            .load_next(&mut graphics, preloaded_slide(None), &config, start)
+            .unwrap();
+
+        // The one of the leader frame, even when not enabled here
+        let synced = PreloadedSlide {
+            transition: Some(TransitionKind::EaseInOut),
+            ..preloaded_slide(None)
+        };
+        let next_at = start + config.display_duration;
+        slides
+            .load_next(&mut graphics, synced, &config, next_at)
+            .unwrap();
+        let pending = match &slides {
+            Slideshow::PendingTransition(pending) => Some(pending.transition),
+            _ => None,
+        };
+        expect_that!(pending, some(eq(TransitionKind::EaseInOut)));
+    }
+
+    #[gtest]
+    fn test_relative_caption_refreshed_while_animated() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.caption.date_format.relative = true;
+        let minute = DateTime::from_timestamp(Utc::now().timestamp() / 60 * 60, 0).unwrap();
+        let mut preloaded = preloaded_slide(None);
+        preloaded.details.date = Some(minute - TimeDelta::seconds(5 * 60 - 30));
+        let slide = Slide::create(preloaded, &mut graphics, &config, 0).unwrap();
+        let start = Instant::now();
+        let mut slides = Slideshow::appear(&graphics, slide, START_ZOOM, &config, start);
+        let caption = |slides: &Slideshow| {
+            slides
+                .current_slide()
+                .and_then(Slide::caption_text)
+                .map(str::to_owned)
+        };
+
+        // The photo zooms in during the whole display duration
+        let time = start + Duration::from_secs(1);
+        let now = minute + TimeDelta::minutes(20);
+        slides.update_get_sleep_at(&mut graphics, &config, time, now);
+        expect_that!(
+            matches!(&slides, Slideshow::Single(slide) if !slide.animation.is_finished(time)),
+            is_true()
+        );
+        expect_that!(caption(&slides), some(eq("24 minutes ago")));
+        // Once a minute
+        let now = now + TimeDelta::seconds(45);
+        slides.update_get_sleep_at(&mut graphics, &config, time, now);
+        expect_that!(caption(&slides), some(eq("24 minutes ago")));
+        let now = minute + TimeDelta::minutes(21);
+        slides.update_get_sleep_at(&mut graphics, &config, time, now);
+        expect_that!(caption(&slides), some(eq("25 minutes ago")));
     }
 }
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use epaint::{
+    text::{LayoutJob, TextFormat},
+    Color32, FontId,
+};
+use vek::Vec2;
+
+use super::StaticSlide;
+use crate::graphics::{Drawable, Graphics, TextContainer};
+
+/// Informational slide displayed when sources work but none of them match any photo.
+pub struct NoPhotosSlide {
+    text: TextContainer,
+    displayed: bool,
+}
+
+impl NoPhotosSlide {
+    pub fn create(graphics: &mut Graphics, message: &str) -> Result<Self> {
+        let screen = graphics.get_dimensions().as_::<f32>();
+        let text = graphics
+            .create_text_container()
+            .context("Cannot create no photos text container")?;
+        let mut job = LayoutJob::single_section(
+            message.to_owned(),
+            TextFormat::simple(FontId::proportional(32.), Color32::WHITE),
+        );
+        job.halign = epaint::emath::Align::Center;
+        job.wrap.max_width = screen.w * 0.8;
+        text.set_layout(job);
+        graphics.force_text_container_update(&text);
+
+        let bounds = text.get_bounding_rect();
+        let screen_center = Vec2::new(screen.w, screen.h) * 0.5;
+        text.set_position(screen_center - bounds.center());
+        Ok(Self {
+            text,
+            displayed: false,
+        })
+    }
+}
+
+impl StaticSlide for NoPhotosSlide {
+    fn displayed(&mut self) -> &mut bool {
+        &mut self.displayed
+    }
+}
+
+impl Drawable for NoPhotosSlide {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        self.text.draw(graphics)
+    }
+}
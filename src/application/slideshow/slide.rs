@@ -1,25 +1,48 @@
 use std::time::Instant;
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
 use epaint::{
-    text::{LayoutJob, TextFormat},
-    Color32, FontId, Pos2, RectShape,
+    text::{LayoutJob, TextFormat, TextWrapping},
+    Color32, FontId, Mesh, Pos2, RectShape, Shape,
 };
+use itertools::Itertools;
 use vek::{Extent2, Rect, Vec2};
 
 use crate::{
     application::slideshow::animated_properties::animated_properties,
-    configuration::{Background, BlurBackground, Settings},
-    gallery::ImageDetails,
-    gl::texture::DetachedTexture,
+    configuration::{
+        Background, BlurBackground, CaptionBackground, CaptionOptions, ConfigLocale, DateFormat,
+        LocaleMode, Settings,
+    },
+    gallery::{BoxInImage, ImageDetails},
+    gl::texture::{DetachedTexture, TextureWrapMode},
     graphics::{Drawable, Graphics, ShapeContainer, SharedTexture2d, Sprite, TextContainer},
-    worker::PreloadedSlide,
+    worker::{LiveSlide, PreloadedSlide},
 };
 
 pub struct Slide {
+    /// What the photo shows, its caption is made of.
+    details: ImageDetails,
+    /// Options the caption is rendered with, see [`Slide::refresh_caption`].
+    caption: CaptionOptions,
+    /// Text of the caption on screen, if any.
+    caption_text: Option<String>,
+    /// Minute since the epoch the caption was last rendered for, it's rendered again at
+    /// most once a minute.
+    caption_minute: i64,
     main_sprite: Sprite,
     background: Option<[Sprite; 2]>,
+    shadow: Option<ShapeContainer>,
     text: Option<TextWithBackground>,
+    caption_visible: bool,
+    /// Position in the slideshow, picks the locale of the date when they alternate.
+    number: usize,
+    /// Opacity as animated, before [`perceptual_opacity`].
+    raw_opacity: f32,
+    perceptual_fades: bool,
+    /// Counts the photo among the ones alive until the slide is dropped.
+    _live: LiveSlide,
 }
 
 pub struct AnimatedSlide {
@@ -30,19 +53,40 @@ pub struct AnimatedSlide {
 
 pub struct TextWithBackground {
     container: TextContainer,
-    background: ShapeContainer,
+    background: Backdrop,
     bg_padding: f32,
 }
 
+/// Drawn behind the caption, see [`CaptionBackground`].
+enum Backdrop {
+    Box(ShapeContainer),
+    /// Gradient darkening the edge of the screen the caption is shown on, built once the
+    /// edge is known, see [`TextWithBackground::anchor_scrim`].
+    Scrim(Option<ShapeContainer>),
+    None,
+}
+
 animated_properties!(SlideProperties {
     global_opacity: f32 = 1.0,
     zoom: f32 = 1.0,
+    /// Center of the part of the photo shown when zoomed in, from 0 to 1.
+    pan: [f32; 2] = [0.5, 0.5],
     text_position: [f32; 2] = [0.0, 0.0],
 });
 
 const BG_PADDING: f32 = 5.0;
 const TEXT_CORNER_RADIUS: f32 = 10.0;
 const BACKGROUND_BLUR_ALPHA: f32 = 0.5;
+/// Height of the scrim relative to the caption, which covers its darkest part.
+const SCRIM_HEIGHT_FACTOR: f32 = 2.;
+/// Opacity of the scrim on the screen edge, fading out towards the middle of the screen.
+const SCRIM_ALPHA: f32 = 0.7;
+/// Horizontal bands of the scrim, smoothing its gradient.
+const SCRIM_BANDS: u32 = 8;
+/// Smallest share of the photo shown by the portrait framing, lower zooms look blurry.
+const MIN_PORTRAIT_ZOOM: f32 = 0.4;
+/// Gamma of the sRGB screen, which blending in sRGB space doesn't account for.
+const FADE_GAMMA: f32 = 2.2;
 
 impl AnimatedSlide {
     pub fn update(&mut self, instant: Instant) {
@@ -50,6 +94,8 @@ impl AnimatedSlide {
         self.slide.apply(properties);
     }
 
+    /// Whether the next photo can be shown: the display time is over (`finish_at` included)
+    /// and no animation is running anymore, even when it lasts longer than the display time.
     pub fn is_finished(&self, instant: Instant) -> bool {
         instant >= self.finish_at && self.animation.is_finished(instant)
     }
@@ -60,9 +106,10 @@ impl Slide {
         preloaded_slide: PreloadedSlide,
         graphics: &mut Graphics,
         config: &Settings,
+        number: usize,
     ) -> Result<Self> {
-        let texture = SharedTexture2d::new(graphics.texture_from_detached(preloaded_slide.texture));
-        let main_sprite = Self::create_main_sprite(graphics, &texture)?;
+        let texture = Self::sprite_texture(graphics, preloaded_slide.texture);
+        let main_sprite = Self::create_main_sprite(graphics, &texture, config)?;
 
         let background = Self::create_blurred_background(
             graphics,
@@ -70,38 +117,230 @@ impl Slide {
             config,
             &main_sprite,
         )?;
+        let shadow = Self::create_shadow(graphics, &main_sprite, config)?;
 
-        let text = Self::create_text(graphics, &preloaded_slide.details, config)?;
+        let details = preloaded_slide.details;
+        let now = Utc::now();
+        let caption_text = caption_text(&details, &config.caption, number, now);
+        let text = Self::create_text(graphics, caption_text.clone(), &config.caption)?;
 
-        Ok(Slide {
+        let mut slide = Slide {
+            details,
+            caption: config.caption.clone(),
+            caption_text,
+            caption_minute: minute_of(now),
             main_sprite,
             background,
+            shadow,
             text,
-        })
+            caption_visible: true,
+            number,
+            raw_opacity: 1.,
+            perceptual_fades: config.perceptual_fades,
+            _live: preloaded_slide.live,
+        };
+        slide.anchor_scrim(graphics)?;
+        slide.clip_to_area(graphics);
+        Ok(slide)
+    }
+
+    /// Replaces the photo with a better version of it, like the full image of a thumbnail,
+    /// keeping the caption. Animated properties are applied again on the next update.
+    pub fn upgrade(
+        &mut self,
+        preloaded_slide: PreloadedSlide,
+        graphics: &mut Graphics,
+        config: &Settings,
+    ) -> Result<()> {
+        let texture = Self::sprite_texture(graphics, preloaded_slide.texture);
+        let main_sprite = Self::create_main_sprite(graphics, &texture, config)?;
+        let background = Self::create_blurred_background(
+            graphics,
+            preloaded_slide.blurred_texture,
+            config,
+            &main_sprite,
+        )?;
+        let opacity = self.main_sprite.opacity;
+        self.shadow = Self::create_shadow(graphics, &main_sprite, config)?;
+        self.main_sprite = main_sprite;
+        self.background = background;
+        self.set_opacity(opacity);
+        self.clip_to_area(graphics);
+        Ok(())
+    }
+
+    pub fn asset_id(&self) -> Option<&str> {
+        self.details.asset_id.as_deref()
+    }
+
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Album the photo was listed from, if known.
+    pub fn album(&self) -> Option<&str> {
+        self.details.album.as_deref()
+    }
+
+    /// Whether the photo is the first of its album in its source, see
+    /// [`ImageDetails::starts_album`].
+    pub fn starts_album(&self) -> bool {
+        self.details.starts_album
+    }
+
+    /// Day the photo was taken, as shown in its caption.
+    pub fn day(&self) -> Option<NaiveDate> {
+        self.details.date.map(|date| date.date_naive())
+    }
+
+    fn faces(&self) -> impl Iterator<Item = &BoxInImage> {
+        self.details
+            .people
+            .iter()
+            .filter_map(|person| person.face.as_ref())
+    }
+
+    /// Faces of the photo, in screen coordinates.
+    pub fn faces_on_screen(&self) -> Vec<Rect<f32, f32>> {
+        self.faces()
+            .map(|face| box_to_screen(face, self.main_sprite.position, self.main_sprite.size))
+            .collect()
+    }
+
+    /// Where the caption is shown on a screen of `screen` size, moved away from the faces
+    /// when [`CaptionOptions::avoid_faces`] is set. `None` without a caption.
+    pub fn caption_position(&self, screen: Extent2<f32>) -> Option<Vec2<f32>> {
+        let size = self.text.as_ref()?.size();
+        let faces = if self.caption.avoid_faces {
+            self.faces_on_screen()
+        } else {
+            Vec::new()
+        };
+        Some(super::caption_position(size, screen, &faces))
+    }
+
+    /// Builds the scrim of the caption, if any, on the screen edge the caption is shown on.
+    fn anchor_scrim(&mut self, graphics: &mut Graphics) -> Result<()> {
+        let screen = graphics.get_slide_dimensions().as_::<f32>();
+        let Some(position) = self.caption_position(screen) else {
+            return Ok(());
+        };
+        if let Some(text) = &mut self.text {
+            text.anchor_scrim(graphics, position.y < screen.h * 0.5)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps what spills out of the area of the slide, like the shadow of a photo filling it,
+    /// from being drawn under the reserved column, see [`Graphics::slide_clip`].
+    fn clip_to_area(&mut self, graphics: &Graphics) {
+        let clip = graphics.slide_clip();
+        self.main_sprite.clip = clip;
+        for sprite in self.background.iter_mut().flatten() {
+            sprite.clip = clip;
+        }
+        if let Some(shadow) = &mut self.shadow {
+            shadow.clip = clip;
+        }
+        if let Some(text) = &mut self.text {
+            text.set_clip(clip);
+        }
+    }
+
+    /// Pan and zoom showing the largest, most central face, filling `face_size` of the
+    /// height of the photo on screen, if any face is known.
+    pub fn portrait_framing(&self, face_size: f32) -> Option<([f32; 2], f32)> {
+        let face = self.faces().max_by(|a, b| {
+            face_score(a)
+                .partial_cmp(&face_score(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        let (center, size) = box_in_texture(face);
+        let zoom = (size.h / face_size.max(f32::EPSILON)).clamp(MIN_PORTRAIT_ZOOM, 1.);
+        Some((center.into_array(), zoom))
+    }
+
+    /// Lays the photo out again for the current screen size, e.g. after the window was
+    /// resized. The caption position is animated, it's left to the caller.
+    pub fn relayout(&mut self, graphics: &mut Graphics, config: &Settings) -> Result<()> {
+        let display_size = graphics.get_slide_dimensions();
+        Self::place_main_sprite(&mut self.main_sprite, display_size, config);
+        if let Some(background) = &self.background {
+            let texture_blur = SharedTexture2d::clone(&background[0].texture);
+            let opacity = background[0].opacity;
+            let mut background =
+                Self::calculate_background_sprites(&self.main_sprite, &texture_blur, display_size);
+            for sprite in background.iter_mut() {
+                sprite.opacity = opacity;
+            }
+            self.background = Some(background);
+        }
+        self.shadow = Self::create_shadow(graphics, &self.main_sprite, config)?;
+        self.anchor_scrim(graphics)?;
+        self.set_opacity(self.main_sprite.opacity);
+        self.clip_to_area(graphics);
+        Ok(())
+    }
+
+    /// Texture drawn by the sprites of the slide, clamped to its edges. The photo and the
+    /// strips of its blurred background are drawn right up to the edges of their regions,
+    /// where linear filtering samples beyond them: repeating would blend the opposite edge
+    /// in, as a seam along the photo. The epaint atlas rather keeps the wrap mode epaint
+    /// asks for, its glyphs are padded and never sampled up to the edges of the atlas.
+    fn sprite_texture(graphics: &Graphics, detached: DetachedTexture) -> SharedTexture2d {
+        let mut texture = graphics.texture_from_detached(detached);
+        texture.set_wrap(TextureWrapMode::ClampToEdge);
+        SharedTexture2d::new(texture)
     }
 
-    fn create_main_sprite(graphics: &mut Graphics, texture: &SharedTexture2d) -> Result<Sprite> {
+    fn create_main_sprite(
+        graphics: &mut Graphics,
+        texture: &SharedTexture2d,
+        config: &Settings,
+    ) -> Result<Sprite> {
         let mut main_sprite = Sprite::new(SharedTexture2d::clone(texture));
-        let display_size = graphics.get_dimensions();
-        main_sprite.resize_respecting_ratio(display_size);
+        main_sprite.corner_radius = config.corner_radius;
+        Self::place_main_sprite(&mut main_sprite, graphics.get_slide_dimensions(), config);
+        Ok(main_sprite)
+    }
 
-        let free_space = display_size.as_() - main_sprite.size;
+    /// Fits the photo in `display_size`, centered. A photo with nearly the ratio of the
+    /// screen is stretched to fill it, the strips left around it would look like a bug.
+    /// Only its size changes, the texture region drawn stays the same.
+    fn place_main_sprite(main_sprite: &mut Sprite, display_size: Extent2<u32>, config: &Settings) {
+        main_sprite.resize_respecting_ratio(display_size);
+        let display_size = display_size.as_::<f32>();
+        if let Background::Blur(BlurBackground { snap_tolerance, .. }) = config.background {
+            let free_space = (display_size - main_sprite.size) / display_size;
+            if free_space.reduce_partial_max() * 100. < snap_tolerance {
+                main_sprite.size = display_size;
+            }
+        }
+        let free_space = display_size - main_sprite.size;
         main_sprite.position = Vec2::from(free_space * 0.5).round();
-        Ok(main_sprite)
     }
 
     fn create_blurred_background(
         graphics: &mut Graphics,
-        blurred_texture: DetachedTexture,
+        blurred_texture: Option<DetachedTexture>,
         config: &Settings,
         main_sprite: &Sprite,
     ) -> Result<Option<[Sprite; 2]>> {
-        if let Background::Blur(BlurBackground { min_free_space }) = config.background {
-            let display_size = graphics.get_dimensions();
-            let free_space = display_size.as_::<f32>() - main_sprite.size;
-            if free_space.reduce_partial_max() > min_free_space as f32 {
-                let texture_blur = graphics.texture_from_detached(blurred_texture);
-                let texture_blur = SharedTexture2d::new(texture_blur);
+        // Thumbnails shown first are not blurred, the background stays plain until replaced
+        let Some(blurred_texture) = blurred_texture else {
+            return Ok(None);
+        };
+        if let Background::Blur(BlurBackground { min_free_space, .. }) = config.background {
+            let display_size = graphics.get_slide_dimensions().as_::<f32>();
+            let free_space = display_size - main_sprite.size;
+            // Strips are on the sides with the most free space
+            let (free, side) = if free_space.w > free_space.h {
+                (free_space.w, display_size.w)
+            } else {
+                (free_space.h, display_size.h)
+            };
+            if free > min_free_space.to_pixels(side) {
+                let texture_blur = Self::sprite_texture(graphics, blurred_texture);
 
                 let background_sprites =
                     Self::calculate_background_sprites(main_sprite, &texture_blur, display_size);
@@ -111,6 +350,36 @@ impl Slide {
         Ok(None)
     }
 
+    /// Shadow of the photo, skipped over a black background where it wouldn't show.
+    fn create_shadow(
+        graphics: &mut Graphics,
+        main_sprite: &Sprite,
+        config: &Settings,
+    ) -> Result<Option<ShapeContainer>> {
+        let shadow = &config.shadow;
+        if !shadow.enabled || config.background == Background::Black {
+            return Ok(None);
+        }
+        let rect = RectShape {
+            blur_width: shadow.blur,
+            ..RectShape::filled(
+                epaint::Rect::from_min_size(
+                    Pos2::ZERO,
+                    epaint::Vec2::new(main_sprite.size.w, main_sprite.size.h),
+                ),
+                config.corner_radius,
+                Color32::BLACK.linear_multiply(shadow.opacity.clamp(0., 1.)),
+            )
+        };
+        let mut container = graphics
+            .create_shape(rect.into(), None)
+            .context("Cannot create photo shadow")?;
+        container.set_position(main_sprite.position + Vec2::from(shadow.offset));
+        Ok(Some(container))
+    }
+
+    /// Regions of the blurred texture are relative to its size, so that it can be smaller
+    /// than the photo.
     fn calculate_background_sprites(
         main_sprite: &Sprite,
         texture_blur: &SharedTexture2d,
@@ -125,131 +394,318 @@ impl Slide {
             blur_sprite.size = main_sprite.size;
         }
 
-        let (width, height) = display_size.as_::<i32>().into_tuple();
         let free_space = display_size.as_::<f32>() - main_sprite.size;
         if free_space.w > free_space.h {
+            // Share of the photo width covered by each strip
+            let w = (main_sprite.position.x / main_sprite.size.w).min(1.);
+            let half_size = Vec2::new(w * 0.5, 0.5);
             blur_sprites[0].size.w = main_sprite.position.x;
-            blur_sprites[0].set_sub_rect(Rect::new(0, 0, main_sprite.position.x as _, height));
+            blur_sprites[0].set_sub_center_size(Vec2::new(w * 0.5, 0.5), half_size);
 
             blur_sprites[1].position.x = main_sprite.position.x + main_sprite.size.w;
             blur_sprites[1].size.w = display_size.w as f32 - blur_sprites[1].position.x;
-            blur_sprites[1].set_sub_rect(Rect::new(
-                texture_blur.size().w as i32 - main_sprite.position.x as i32,
-                0,
-                main_sprite.position.x as _,
-                height,
-            ));
+            blur_sprites[1].set_sub_center_size(Vec2::new(1. - w * 0.5, 0.5), half_size);
         } else {
+            let h = (main_sprite.position.y / main_sprite.size.h).min(1.);
+            let half_size = Vec2::new(0.5, h * 0.5);
             blur_sprites[0].size.h = main_sprite.position.y;
-            blur_sprites[0].set_sub_rect(Rect::new(0, 0, width, main_sprite.position.y as i32));
+            blur_sprites[0].set_sub_center_size(Vec2::new(0.5, h * 0.5), half_size);
 
             blur_sprites[1].position.y = main_sprite.position.y + main_sprite.size.h;
             blur_sprites[1].size.h = main_sprite.position.y;
-            blur_sprites[1].set_sub_rect(Rect::new(
-                0,
-                texture_blur.size().h as i32 - main_sprite.position.y as i32,
-                width,
-                main_sprite.position.y as i32,
-            ));
+            blur_sprites[1].set_sub_center_size(Vec2::new(0.5, 1. - h * 0.5), half_size);
         }
         blur_sprites
     }
 
     fn create_text(
         graphics: &mut Graphics,
-        details: &ImageDetails,
-        config: &Settings,
+        text: Option<String>,
+        caption: &CaptionOptions,
     ) -> Result<Option<TextWithBackground>> {
-        if !config.caption.enabled {
-            return Ok(None);
-        }
-
-        let date = details.date.map(|date| {
-            date.date_naive()
-                .format_localized(
-                    &config.caption.date_format.format,
-                    config.caption.date_format.locale.0,
-                )
-                .to_string()
-        });
-        let text = [details.city.clone(), date]
-            .into_iter()
-            .flatten()
-            .collect::<Vec<_>>();
-
-        if text.is_empty() {
+        let Some(text) = text else {
             return Ok(None);
-        }
-
-        let text = text.join("\n");
-        TextWithBackground::create(graphics, text, config.caption.font_size)
+        };
+        TextWithBackground::create(graphics, text, caption)
             .map(Some)
             .context("Failed to create text for slide")
     }
 
+    /// Renders the caption again for `now`, e.g. its relative date, unless it already was
+    /// in the same minute. It's only laid out again when its text changed, returns whether
+    /// it did. The caption is to be placed again by the caller, its size may have changed.
+    pub fn refresh_caption(&mut self, graphics: &mut Graphics, now: DateTime<Utc>) -> Result<bool> {
+        let minute = minute_of(now);
+        if minute == self.caption_minute {
+            return Ok(false);
+        }
+        self.caption_minute = minute;
+        let text = caption_text(&self.details, &self.caption, self.number, now);
+        if text == self.caption_text {
+            return Ok(false);
+        }
+        self.text = Self::create_text(graphics, text.clone(), &self.caption)?;
+        self.caption_text = text;
+        self.anchor_scrim(graphics)?;
+        self.set_opacity(self.main_sprite.opacity);
+        self.clip_to_area(graphics);
+        Ok(true)
+    }
+
     fn set_opacity(&mut self, alpha: f32) {
         for sprite in self.background.iter_mut().flatten() {
             sprite.opacity = alpha;
         }
+        if let Some(shadow) = &mut self.shadow {
+            shadow.set_opacity(alpha);
+        }
         self.main_sprite.opacity = alpha;
         if let Some(text) = &mut self.text {
             text.set_opacity(alpha);
         };
     }
 
+    /// Draws the slide once offscreen, see [`Graphics::warm_up`].
+    pub fn warm_up(&self, graphics: &mut Graphics) -> Result<()> {
+        graphics.warm_up(|graphics| self.draw(graphics))
+    }
+
+    /// Text of the caption, as rendered last.
+    #[cfg(test)]
+    pub fn caption_text(&self) -> Option<&str> {
+        self.caption_text.as_deref()
+    }
+
     pub fn get_text(&self) -> Option<&TextWithBackground> {
         self.text.as_ref()
     }
 
+    /// Shows or hides the caption, returns whether a redraw is needed.
+    pub fn set_caption_visible(&mut self, visible: bool) -> bool {
+        let changed = self.caption_visible != visible;
+        self.caption_visible = visible;
+        changed && self.text.is_some()
+    }
+
+    /// Opacity as animated and as drawn, see [`Settings::perceptual_fades`].
+    pub fn opacity(&self) -> (f32, f32) {
+        (self.raw_opacity, self.main_sprite.opacity)
+    }
+
     pub fn apply(&mut self, properties: SlideProperties) {
-        self.set_opacity(properties.global_opacity);
+        // Only drawn through the curve, animations still reach their exact targets
+        self.raw_opacity = properties.global_opacity;
+        self.set_opacity(if self.perceptual_fades {
+            perceptual_opacity(properties.global_opacity)
+        } else {
+            properties.global_opacity
+        });
+        let half_size = properties.zoom * 0.5;
+        // The pan stops where the zoomed part would leave the photo
+        let center = Vec2::from(properties.pan).map(|c| {
+            if half_size >= 0.5 {
+                0.5
+            } else {
+                c.clamp(half_size, 1. - half_size)
+            }
+        });
         self.main_sprite
-            .set_sub_center_size(0.5.into(), (properties.zoom * 0.5).into());
+            .set_sub_center_size(center, half_size.into());
         if let Some(text) = self.text.as_mut() {
             text.set_position(properties.text_position.into());
         }
     }
 }
 
+/// Opacity drawn for the animated `alpha`, so that a linear fade looks linear on screen:
+/// exact at both ends, higher in between.
+pub fn perceptual_opacity(alpha: f32) -> f32 {
+    alpha.clamp(0., 1.).powf(1. / FADE_GAMMA)
+}
+
+/// Maps an area of an image to the screen, the image being drawn at `image_position` with
+/// `image_size`, after [`Sprite::resize_respecting_ratio`] and centering.
+fn box_to_screen(
+    area: &BoxInImage,
+    image_position: Vec2<f32>,
+    image_size: Extent2<f32>,
+) -> Rect<f32, f32> {
+    let scale = image_size
+        / Extent2::new(area.width, area.height)
+            .as_::<f32>()
+            .map(|v| v.max(1.));
+    let start = Vec2::new(area.box_x_start, area.box_y_start).as_::<f32>();
+    let end = Vec2::new(area.box_x_end, area.box_y_end).as_::<f32>();
+    Rect::new(
+        image_position.x + start.x * scale.w,
+        image_position.y + start.y * scale.h,
+        (end.x - start.x).max(0.) * scale.w,
+        (end.y - start.y).max(0.) * scale.h,
+    )
+}
+
+/// Center and size of an area of an image, in texture coordinates from 0 to 1.
+fn box_in_texture(area: &BoxInImage) -> (Vec2<f32>, Extent2<f32>) {
+    let image = Extent2::new(area.width, area.height)
+        .as_::<f32>()
+        .map(|v| v.max(1.));
+    let start = Vec2::new(area.box_x_start, area.box_y_start).as_::<f32>() / Vec2::from(image);
+    let end = Vec2::new(area.box_x_end, area.box_y_end).as_::<f32>() / Vec2::from(image);
+    let size = Extent2::from(end - start).map(|v| v.max(0.));
+    (start + Vec2::from(size) * 0.5, size)
+}
+
+/// Larger faces closer to the center of the photo are framed first.
+fn face_score(face: &BoxInImage) -> f32 {
+    let (center, size) = box_in_texture(face);
+    let distance = center.distance(Vec2::broadcast(0.5));
+    size.product() * (1. - distance)
+}
+
+/// Minutes since the epoch at `now`.
+fn minute_of(now: DateTime<Utc>) -> i64 {
+    now.timestamp().div_euclid(60)
+}
+
+/// Lines of the caption of a photo shown at `now`, `None` when it has none.
+fn caption_text(
+    details: &ImageDetails,
+    caption: &CaptionOptions,
+    number: usize,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    if !caption.enabled {
+        return None;
+    }
+    let date = details.date.map(|date| {
+        if caption.date_format.relative {
+            format_relative_date(date, now)
+        } else {
+            format_date(date.date_naive(), &caption.date_format, number)
+        }
+    });
+    let label = caption
+        .label
+        .clone()
+        .filter(|label| !label.trim().is_empty());
+    let text = [details.city.clone(), date, label]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    (!text.is_empty()).then(|| text.join("\n"))
+}
+
+/// How long before `now` the photo was taken, like "3 years ago". Dates in the future, from
+/// a camera with a wrong clock, are "just now".
+fn format_relative_date(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now - date;
+    let days = elapsed.num_days();
+    if elapsed.num_minutes() < 1 {
+        return "just now".into();
+    }
+    let (count, unit) = if elapsed.num_hours() < 1 {
+        (elapsed.num_minutes(), "minute")
+    } else if days < 1 {
+        (elapsed.num_hours(), "hour")
+    } else if days < 30 {
+        (days, "day")
+    } else if days < 365 {
+        (days / 30, "month")
+    } else {
+        (days / 365, "year")
+    };
+    let plural = if count > 1 { "s" } else { "" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// Date in the locale of the `number`th slide, or in all locales, one per line.
+pub fn format_date(date: NaiveDate, date_format: &DateFormat, number: usize) -> String {
+    let locales = &date_format.locale.0;
+    let format =
+        |locale: &ConfigLocale| date.format_localized(&date_format.format, locale.for_dates());
+    match date_format.mode {
+        LocaleMode::Alternate => format(&locales[number % locales.len()]).to_string(),
+        LocaleMode::All => locales.iter().map(format).join("\n"),
+    }
+}
+
 impl TextWithBackground {
-    // TODO Test me !
-    fn create(graphics: &mut Graphics, text: String, font_size: f32) -> Result<Self> {
+    /// Lays out the caption, wrapped to at most [`CaptionOptions::max_width`] of the screen.
+    /// The background hugs the wrapped text, as wide as its longest line.
+    fn create(graphics: &mut Graphics, text: String, caption: &CaptionOptions) -> Result<Self> {
+        let screen_width = graphics.get_slide_dimensions().w as f32;
+        let max_width = screen_width * caption.max_width / 100. - BG_PADDING * 2.;
         let container = {
             let container = graphics
                 .create_text_container()
                 .context("Cannot create text container")?;
             container.set_layout(LayoutJob {
                 halign: epaint::emath::Align::Center,
+                // At least a glyph per line on tiny screens
+                wrap: TextWrapping {
+                    max_width: max_width.max(caption.font_size),
+                    ..Default::default()
+                },
                 ..LayoutJob::single_section(
                     text,
-                    TextFormat::simple(FontId::proportional(font_size), Color32::WHITE),
+                    TextFormat::simple(FontId::proportional(caption.font_size), Color32::WHITE),
                 )
             });
             graphics.force_text_container_update(&container);
             container
         };
-        let shape = {
-            let dims = container.get_dimensions() + BG_PADDING * 2.;
-            let rect = RectShape {
-                blur_width: BG_PADDING,
-                ..RectShape::filled(
-                    epaint::Rect::from_min_size(Pos2::ZERO, epaint::Vec2::new(dims.w, dims.h)),
-                    TEXT_CORNER_RADIUS,
-                    Color32::BLACK.linear_multiply(BACKGROUND_BLUR_ALPHA),
-                )
-            };
-            graphics.create_shape(rect.into(), None)?
+        let dims = container.get_dimensions() + BG_PADDING * 2.;
+        let background = match caption.background_style {
+            CaptionBackground::Box => {
+                let rect = RectShape {
+                    blur_width: BG_PADDING,
+                    ..RectShape::filled(
+                        epaint::Rect::from_min_size(Pos2::ZERO, epaint::Vec2::new(dims.w, dims.h)),
+                        TEXT_CORNER_RADIUS,
+                        Color32::BLACK.linear_multiply(BACKGROUND_BLUR_ALPHA),
+                    )
+                };
+                Backdrop::Box(graphics.create_shape(rect.into(), None)?)
+            }
+            CaptionBackground::Scrim => Backdrop::Scrim(None),
+            CaptionBackground::None => Backdrop::None,
         };
         Ok(Self {
             container,
-            background: shape,
+            background,
             bg_padding: BG_PADDING,
         })
     }
 
     fn set_opacity(&mut self, alpha: f32) {
         self.container.set_opacity(alpha);
-        self.background.set_opacity(alpha);
+        match &mut self.background {
+            Backdrop::Box(shape) | Backdrop::Scrim(Some(shape)) => shape.set_opacity(alpha),
+            Backdrop::Scrim(None) | Backdrop::None => {}
+        }
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect<i32, i32>>) {
+        self.container.set_clip(clip);
+        match &mut self.background {
+            Backdrop::Box(shape) | Backdrop::Scrim(Some(shape)) => shape.clip = clip,
+            Backdrop::Scrim(None) | Backdrop::None => {}
+        }
+    }
+
+    /// Builds the scrim across the top or the bottom edge of the screen, high enough to
+    /// cover the caption there. The scrim stays on that edge while the caption moves.
+    fn anchor_scrim(&mut self, graphics: &mut Graphics, at_top: bool) -> Result<()> {
+        let caption_height = self.size().h;
+        let Backdrop::Scrim(scrim) = &mut self.background else {
+            return Ok(());
+        };
+        let screen = graphics.get_slide_dimensions().as_::<f32>();
+        let size = Extent2::new(screen.w, caption_height * SCRIM_HEIGHT_FACTOR);
+        let mut shape = graphics.create_shape(Shape::mesh(scrim_mesh(size, at_top)), None)?;
+        shape.set_position(Vec2::new(0., if at_top { 0. } else { screen.h - size.h }));
+        *scrim = Some(shape);
+        Ok(())
     }
 
     fn set_position(&mut self, position: Vec2<f32>) {
@@ -258,7 +714,9 @@ impl TextWithBackground {
         let offset = c_pos - self.container.get_bounding_rect().position();
         self.container
             .set_position(position + offset + self.bg_padding);
-        self.background.set_position(position);
+        if let Backdrop::Box(shape) = &mut self.background {
+            shape.set_position(position);
+        }
     }
 
     pub fn size(&self) -> Extent2<f32> {
@@ -271,17 +729,43 @@ impl Drawable for Slide {
         for sprite in self.background.iter().flatten() {
             sprite.draw(graphics)?;
         }
+        if let Some(shadow) = &self.shadow {
+            shadow.draw(graphics)?;
+        }
         self.main_sprite.draw(graphics)?;
-        if let Some(text) = &self.text {
+        if let Some(text) = self.text.as_ref().filter(|_| self.caption_visible) {
             text.draw(graphics)?;
         }
         Ok(())
     }
 }
 
+/// Gradient of `size`, darkest on its top or bottom side, see [`SCRIM_ALPHA`].
+fn scrim_mesh(size: Extent2<f32>, dark_top: bool) -> Mesh {
+    let mut mesh = Mesh::default();
+    for band in 0..=SCRIM_BANDS {
+        let t = band as f32 / SCRIM_BANDS as f32;
+        let darkness = if dark_top { 1. - t } else { t };
+        // Smoothstep, without a visible line where the gradient starts
+        let alpha = SCRIM_ALPHA * darkness * darkness * (3. - 2. * darkness);
+        let color = Color32::from_black_alpha((alpha * 255.).round() as u8);
+        mesh.colored_vertex(Pos2::new(0., t * size.h), color);
+        mesh.colored_vertex(Pos2::new(size.w, t * size.h), color);
+        if band > 0 {
+            let i = band * 2;
+            mesh.add_triangle(i - 2, i - 1, i);
+            mesh.add_triangle(i - 1, i + 1, i);
+        }
+    }
+    mesh
+}
+
 impl Drawable for TextWithBackground {
     fn draw(&self, graphics: &Graphics) -> Result<()> {
-        self.background.draw(graphics)?;
+        match &self.background {
+            Backdrop::Box(shape) | Backdrop::Scrim(Some(shape)) => shape.draw(graphics)?,
+            Backdrop::Scrim(None) | Backdrop::None => {}
+        }
         self.container.draw(graphics)?;
         Ok(())
     }
@@ -296,33 +780,58 @@ impl Drawable for AnimatedSlide {
 // Test module
 #[cfg(test)]
 mod test {
-    use std::rc::Rc;
+    use std::{
+        rc::Rc,
+        time::{Duration, Instant},
+    };
 
-    use chrono::{Locale, NaiveDate, Utc};
+    use chrono::{Locale, NaiveDate, TimeDelta, TimeZone, Utc};
+    use glissade::Easing;
     use googletest::{
         assert_pred, expect_pred, expect_that, gtest,
         matchers::matches_pattern,
-        prelude::{approx_eq, eq},
+        prelude::{approx_eq, eq, gt, is_false, is_true, le, lt, near, some},
     };
-    use vek::{Extent2, Vec2};
+    use vek::{Extent2, Rect, Vec2};
 
-    use super::{Background, PreloadedSlide, Settings, Slide};
+    use super::{
+        box_to_screen, format_relative_date, perceptual_opacity, scrim_mesh, AnimatedSlide,
+        AnimatedSlideProperties, Backdrop, Background, LiveSlide, PreloadedSlide, Settings, Slide,
+        SlideProperties, TextWithBackground, SCRIM_BANDS, SCRIM_HEIGHT_FACTOR,
+    };
     use crate::{
-        configuration::{BlurBackground, ConfigLocale, OrientationName},
-        gallery::ImageDetails,
-        gl::{texture::DetachedTexture, wrapper::mocked_gl, GlContext},
+        configuration::{
+            BlurBackground, CaptionBackground, ConfigLocale, ConfigLocales, FreeSpace, LocaleMode,
+            OrientationName,
+        },
+        gallery::{BoxInImage, DemoGalleryProvider, Gallery, ImageDetails, Person},
+        gl::{
+            texture::{DetachedTexture, TextureWrapMode},
+            wrapper::mocked_gl,
+            GlContext,
+        },
         graphics::{Graphics, TextureRegion},
     };
 
     fn preloaded_slide(size: Extent2<u32>) -> PreloadedSlide {
         PreloadedSlide {
+            live: LiveSlide::counted(),
             details: ImageDetails {
+                asset_id: None,
+                album: None,
+                starts_album: false,
                 city: None,
+                country: None,
+                gps: None,
                 date: None,
                 people: Default::default(),
             },
             texture: DetachedTexture::mock(size),
-            blurred_texture: DetachedTexture::mock(size),
+            blurred_texture: Some(DetachedTexture::mock(size)),
+            preparation: Default::default(),
+            recap_thumbnail: None,
+            transition: None,
+            show_at: None,
         }
     }
 
@@ -336,7 +845,7 @@ mod test {
         config.background = Background::Black;
         let preloaded_slide = preloaded_slide((100, 100).into());
 
-        let slide = Slide::create(preloaded_slide, &mut graphics, &config).unwrap();
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
         expect_pred!(slide.background.is_none());
         expect_that!(
             slide.main_sprite.size,
@@ -355,6 +864,257 @@ mod test {
         expect_pred!(slide.text.is_none());
     }
 
+    fn animated_slide(start: Instant, display: Duration, animation: Duration) -> AnimatedSlide {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+        let slide = Slide::create(
+            preloaded_slide((100, 100).into()),
+            &mut graphics,
+            &Settings::default(),
+            0,
+        )
+        .unwrap();
+        let mut properties = AnimatedSlideProperties::default();
+        properties.ease_zoom(0.9, start, animation, Easing::Linear);
+        AnimatedSlide {
+            slide,
+            animation: properties,
+            finish_at: start + display,
+        }
+    }
+
+    #[gtest]
+    fn test_slide_finishes_at_finish_at() {
+        let start = Instant::now();
+        let slide = animated_slide(start, Duration::from_secs(10), Duration::from_secs(2));
+
+        expect_that!(slide.is_finished(start), is_false());
+        // Animation done, still displayed
+        expect_that!(
+            slide.is_finished(start + Duration::from_secs(5)),
+            is_false()
+        );
+        expect_that!(
+            slide.is_finished(start + Duration::from_millis(9999)),
+            is_false()
+        );
+        expect_that!(
+            slide.is_finished(start + Duration::from_secs(10)),
+            is_true()
+        );
+        expect_that!(
+            slide.is_finished(start + Duration::from_secs(11)),
+            is_true()
+        );
+    }
+
+    #[gtest]
+    fn test_slide_waits_for_animation() {
+        let start = Instant::now();
+        let slide = animated_slide(start, Duration::from_secs(10), Duration::from_secs(20));
+
+        // Past finish_at, mid-animation
+        expect_that!(
+            slide.is_finished(start + Duration::from_secs(10)),
+            is_false()
+        );
+        expect_that!(
+            slide.is_finished(start + Duration::from_secs(15)),
+            is_false()
+        );
+        expect_that!(
+            slide.is_finished(start + Duration::from_secs(21)),
+            is_true()
+        );
+    }
+
+    #[gtest]
+    fn test_nearly_matching_ratio_fills_screen() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(0),
+            snap_tolerance: 2.,
+            ..Default::default()
+        });
+
+        // 10 pixels of free space, 1.25% of the width
+        let mut slide = Slide::create(
+            preloaded_slide((790, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_that!(
+            slide.main_sprite.size,
+            matches_pattern!(Extent2 {
+                w: approx_eq(800.),
+                h: approx_eq(600.),
+            })
+        );
+        expect_that!(
+            slide.main_sprite.position,
+            matches_pattern!(Vec2 {
+                x: approx_eq(0.),
+                y: approx_eq(0.),
+            })
+        );
+        expect_pred!(slide.background.is_none());
+
+        // The zoom still samples inside the texture
+        slide.apply(SlideProperties {
+            global_opacity: 1.,
+            zoom: 0.9,
+            pan: [0.5, 0.5],
+            text_position: [0., 0.],
+        });
+        let region = slide.main_sprite.get_sub_center_size();
+        let min = region.uv_center - Vec2::from(region.uv_size);
+        let max = region.uv_center + Vec2::from(region.uv_size);
+        expect_that!(min.reduce_partial_min() >= 0., is_true());
+        expect_that!(max.reduce_partial_max() <= 1., is_true());
+    }
+
+    #[gtest]
+    fn test_ratio_outside_snap_tolerance_keeps_strips() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(0),
+            snap_tolerance: 2.,
+            ..Default::default()
+        });
+
+        // 20 pixels of free space, 2.5% of the width
+        let slide = Slide::create(
+            preloaded_slide((780, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_that!(
+            slide.main_sprite.size,
+            matches_pattern!(Extent2 {
+                w: approx_eq(780.),
+                h: approx_eq(600.),
+            })
+        );
+        expect_that!(slide.main_sprite.position.x, approx_eq(10.));
+        expect_pred!(slide.background.is_some());
+    }
+
+    #[gtest]
+    fn test_shadow_behind_photo() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.shadow.enabled = true;
+        config.shadow.offset = [4., 8.];
+
+        let slide = Slide::create(
+            preloaded_slide((400, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        assert_pred!(slide.shadow.is_some());
+        expect_that!(
+            slide.shadow.as_ref().unwrap().position,
+            matches_pattern!(Vec2 {
+                x: approx_eq(204.),
+                y: approx_eq(8.),
+            })
+        );
+
+        config.background = Background::Black;
+        let slide = Slide::create(
+            preloaded_slide((400, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_pred!(slide.shadow.is_none());
+    }
+
+    #[gtest]
+    fn test_rounded_corners_only_on_photo() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+
+        let mut config = Settings::default();
+        config.corner_radius = 16.;
+        let preloaded_slide = preloaded_slide((400, 600).into());
+
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        expect_that!(slide.main_sprite.corner_radius, approx_eq(16.));
+        assert_pred!(slide.background.is_some());
+        for sprite in slide.background.as_ref().unwrap() {
+            expect_that!(sprite.corner_radius, approx_eq(0.));
+        }
+    }
+
+    #[gtest]
+    fn test_slide_next_to_reserved_column() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        graphics.set_reserved_right(300);
+
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let preloaded_slide = preloaded_slide((100, 100).into());
+
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        expect_that!(
+            slide.main_sprite.size,
+            matches_pattern!(Extent2 {
+                w: approx_eq(500.),
+                h: approx_eq(500.),
+            })
+        );
+        expect_that!(
+            slide.main_sprite.position,
+            matches_pattern!(Vec2 {
+                x: approx_eq(0.),
+                y: approx_eq(50.),
+            })
+        );
+        let area = Some(Rect::new(0, 0, 500, 600));
+        expect_that!(slide.main_sprite.clip, eq(area));
+
+        // The shadow of the photo filling the area is not drawn under the column
+        config.background = Background::default();
+        config.shadow.enabled = true;
+        let slide = Slide::create(
+            preloaded_slide((100, 100).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_that!(slide.shadow.map(|shadow| shadow.clip), some(eq(area)));
+
+        graphics.set_reserved_right(0);
+        let slide = Slide::create(
+            preloaded_slide((100, 100).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_that!(slide.main_sprite.clip, eq(None));
+    }
+
     #[gtest]
     fn test_slide_with_background_sides() {
         let gl = mocked_gl();
@@ -362,10 +1122,13 @@ mod test {
         let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
 
         let mut config = Settings::default();
-        config.background = Background::Blur(BlurBackground { min_free_space: 50 });
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(50),
+            ..Default::default()
+        });
         let preloaded_slide = preloaded_slide((400, 600).into());
 
-        let slide = Slide::create(preloaded_slide, &mut graphics, &config).unwrap();
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
         expect_that!(
             slide.main_sprite.size,
             matches_pattern!(Extent2 {
@@ -441,10 +1204,13 @@ mod test {
         let gl = Rc::new(GlContext::mocked(gl));
         let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
         let mut config = Settings::default();
-        config.background = Background::Blur(BlurBackground { min_free_space: 50 });
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(50),
+            ..Default::default()
+        });
         let preloaded_slide = preloaded_slide((800, 400).into());
 
-        let slide = Slide::create(preloaded_slide, &mut graphics, &config).unwrap();
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
         expect_that!(
             slide.main_sprite.size,
             matches_pattern!(Extent2 {
@@ -514,6 +1280,78 @@ mod test {
         );
     }
 
+    #[gtest]
+    fn test_slide_textures_clamped_to_edges() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(50),
+            ..Default::default()
+        });
+        // Prepared with the default wrap mode
+        let slide = Slide::create(
+            preloaded_slide((400, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_that!(
+            slide.main_sprite.texture.wrap(),
+            eq(TextureWrapMode::ClampToEdge)
+        );
+        let background = slide.background.as_ref().expect("Strips should be shown");
+        for strip in background {
+            expect_that!(strip.texture.wrap(), eq(TextureWrapMode::ClampToEdge));
+        }
+    }
+
+    #[gtest]
+    fn test_slide_with_smaller_blurred_texture() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+
+        let mut config = Settings::default();
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(50),
+            ..Default::default()
+        });
+        let mut preloaded_slide = preloaded_slide((400, 600).into());
+        // Blurred from a thumbnail of the photo
+        preloaded_slide.blurred_texture = Some(DetachedTexture::mock((40, 60).into()));
+
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        let background = slide.background.as_ref().unwrap();
+        expect_that!(
+            background[0].get_sub_center_size(),
+            matches_pattern!(TextureRegion {
+                uv_center: matches_pattern!(Vec2 {
+                    x: approx_eq(0.25),
+                    y: approx_eq(0.5)
+                }),
+                uv_size: matches_pattern!(Extent2 {
+                    w: approx_eq(0.25),
+                    h: approx_eq(0.5)
+                }),
+            })
+        );
+        expect_that!(
+            background[1].get_sub_center_size(),
+            matches_pattern!(TextureRegion {
+                uv_center: matches_pattern!(Vec2 {
+                    x: approx_eq(0.75),
+                    y: approx_eq(0.5)
+                }),
+                uv_size: matches_pattern!(Extent2 {
+                    w: approx_eq(0.25),
+                    h: approx_eq(0.5)
+                }),
+            })
+        );
+    }
+
     #[gtest]
     fn test_slide_text() {
         let gl = mocked_gl();
@@ -524,7 +1362,7 @@ mod test {
         let mut preloaded_slide = preloaded_slide((800, 600).into());
         preloaded_slide.details.city = Some("A wonderfull city".into());
 
-        let slide = Slide::create(preloaded_slide, &mut graphics, &config).unwrap();
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
         assert_pred!(slide.text.is_some());
         let text = slide.text.as_ref().unwrap();
         let galley = text.container.galley().unwrap();
@@ -538,7 +1376,7 @@ mod test {
         let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
 
         let mut config = Settings::default();
-        config.caption.date_format.locale = ConfigLocale(Locale::fr_FR);
+        config.caption.date_format.locale = ConfigLocales(vec![ConfigLocale(Locale::fr_FR)]);
         config.caption.date_format.format = "%A %e %B %Y".into();
         let mut preloaded_slide = preloaded_slide((800, 600).into());
         let date = NaiveDate::from_ymd_opt(2025, 01, 25)
@@ -549,10 +1387,485 @@ mod test {
             .unwrap();
         preloaded_slide.details.date = Some(date);
 
-        let slide = Slide::create(preloaded_slide, &mut graphics, &config).unwrap();
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
         assert_pred!(slide.text.is_some());
         let text = slide.text.as_ref().unwrap();
         let galley = text.container.galley().unwrap();
         expect_that!(galley.text(), eq("samedi 25 janvier 2025"));
     }
+
+    #[gtest]
+    fn test_relative_caption_refreshed_each_minute() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+
+        let mut config = Settings::default();
+        config.caption.date_format.relative = true;
+        let taken = Utc.with_ymd_and_hms(2025, 1, 25, 12, 0, 0).unwrap();
+        let mut preloaded_slide = preloaded_slide((800, 600).into());
+        preloaded_slide.details.date = Some(taken);
+        let mut slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+
+        let now = taken + TimeDelta::seconds(3 * 60 + 30);
+        slide.refresh_caption(&mut graphics, now).unwrap();
+        let text = |slide: &Slide| slide.text.as_ref().unwrap().container.galley().unwrap();
+        expect_that!(text(&slide).text(), eq("3 minutes ago"));
+        expect_that!(
+            slide
+                .refresh_caption(&mut graphics, now + TimeDelta::seconds(29))
+                .unwrap(),
+            is_false()
+        );
+        // Next minute
+        expect_that!(
+            slide
+                .refresh_caption(&mut graphics, now + TimeDelta::seconds(30))
+                .unwrap(),
+            is_true()
+        );
+        expect_that!(text(&slide).text(), eq("4 minutes ago"));
+    }
+
+    #[gtest]
+    fn test_absolute_caption_never_rebuilt() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+
+        let taken = Utc.with_ymd_and_hms(2025, 1, 25, 12, 0, 0).unwrap();
+        let mut preloaded_slide = preloaded_slide((800, 600).into());
+        preloaded_slide.details.date = Some(taken);
+        let mut slide =
+            Slide::create(preloaded_slide, &mut graphics, &Settings::default(), 0).unwrap();
+
+        for later in [
+            TimeDelta::minutes(1),
+            TimeDelta::days(1),
+            TimeDelta::days(400),
+        ] {
+            expect_that!(
+                slide
+                    .refresh_caption(&mut graphics, Utc::now() + later)
+                    .unwrap(),
+                is_false()
+            );
+        }
+    }
+
+    #[gtest]
+    fn test_format_relative_date() {
+        let taken = Utc.with_ymd_and_hms(2025, 1, 25, 12, 0, 0).unwrap();
+        let after = |delta| format_relative_date(taken, taken + delta);
+        expect_that!(after(TimeDelta::seconds(59)), eq("just now"));
+        expect_that!(after(TimeDelta::seconds(-3600)), eq("just now"));
+        expect_that!(after(TimeDelta::minutes(1)), eq("1 minute ago"));
+        expect_that!(after(TimeDelta::hours(5)), eq("5 hours ago"));
+        expect_that!(after(TimeDelta::days(1)), eq("1 day ago"));
+        expect_that!(after(TimeDelta::days(75)), eq("2 months ago"));
+        expect_that!(after(TimeDelta::days(3 * 365 + 1)), eq("3 years ago"));
+    }
+
+    #[gtest]
+    fn test_slide_text_date_locales() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+
+        let mut config = Settings::default();
+        config.caption.date_format.locale = ConfigLocales(vec![
+            ConfigLocale(Locale::fr_FR),
+            ConfigLocale(Locale::de_DE),
+        ]);
+        config.caption.date_format.format = "%e %B".into();
+        let date = NaiveDate::from_ymd_opt(2025, 01, 25)
+            .unwrap()
+            .and_hms_opt(12, 30, 50)
+            .unwrap()
+            .and_local_timezone(Utc)
+            .unwrap();
+        let caption = |graphics: &mut Graphics, config: &Settings, number| {
+            let mut preloaded_slide = preloaded_slide((800, 600).into());
+            preloaded_slide.details.date = Some(date);
+            let slide = Slide::create(preloaded_slide, graphics, config, number).unwrap();
+            let galley = slide.text.as_ref().unwrap().container.galley().unwrap();
+            galley.text().to_string()
+        };
+
+        expect_that!(caption(&mut graphics, &config, 0), eq("25 janvier"));
+        expect_that!(caption(&mut graphics, &config, 1), eq("25 Januar"));
+        expect_that!(caption(&mut graphics, &config, 2), eq("25 janvier"));
+
+        config.caption.date_format.mode = LocaleMode::All;
+        expect_that!(
+            caption(&mut graphics, &config, 1),
+            eq("25 janvier\n25 Januar")
+        );
+    }
+
+    #[gtest]
+    fn test_slide_text_label() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+
+        let mut config = Settings::default();
+        config.caption.label = Some("The Smith Family".into());
+        let slide = Slide::create(
+            preloaded_slide((800, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        assert_pred!(slide.text.is_some());
+        let galley = slide.text.as_ref().unwrap().container.galley().unwrap();
+        expect_that!(galley.text(), eq("The Smith Family"));
+
+        let mut preloaded_slide = preloaded_slide((800, 600).into());
+        preloaded_slide.details.city = Some("Bordeaux".into());
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        let text = slide.text.as_ref().unwrap();
+        expect_that!(
+            text.container.galley().unwrap().text(),
+            eq("Bordeaux\nThe Smith Family")
+        );
+        // The background covers both lines
+        expect_that!(text.size().h, gt(2. * config.caption.font_size));
+    }
+
+    #[gtest]
+    fn test_demo_photo_caption() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let config = Settings::default();
+        let demo_photo = DemoGalleryProvider::new()
+            .get_next_image(Duration::ZERO)
+            .unwrap();
+        let mut preloaded_slide = preloaded_slide((1500, 1000).into());
+        preloaded_slide.details = demo_photo.details;
+
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        let galley = slide.text.as_ref().unwrap().container.galley().unwrap();
+        expect_that!(galley.text(), eq("Étretat\nFriday, 14. July 2023"));
+    }
+
+    #[gtest]
+    fn test_scrim_anchored_to_screen_edge() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.caption.background_style = CaptionBackground::Scrim;
+        let mut preloaded_slide = preloaded_slide((800, 600).into());
+        preloaded_slide.details.city = Some("Bordeaux".into());
+        let mut slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        let screen_height = graphics.get_slide_dimensions().h as f32;
+        let text = slide.text.as_mut().unwrap();
+        let caption_height = text.size().h;
+
+        // Across the screen, up from the bottom edge where the caption is shown
+        let bottom = Vec2::new(0., screen_height - caption_height * SCRIM_HEIGHT_FACTOR);
+        let Backdrop::Scrim(Some(scrim)) = &text.background else {
+            panic!("The caption has no scrim");
+        };
+        expect_that!(scrim.position, eq(bottom));
+
+        // Not moved with the caption sliding in
+        text.set_position(Vec2::new(10., screen_height));
+        let Backdrop::Scrim(Some(scrim)) = &text.background else {
+            panic!("The caption has no scrim");
+        };
+        expect_that!(scrim.position, eq(bottom));
+
+        text.anchor_scrim(&mut graphics, true).unwrap();
+        let Backdrop::Scrim(Some(scrim)) = &text.background else {
+            panic!("The caption has no scrim");
+        };
+        expect_that!(scrim.position, eq(Vec2::new(0., 0.)));
+    }
+
+    #[gtest]
+    fn test_long_caption_wrapped() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+        let mut caption = Settings::default().caption;
+        caption.max_width = 25.;
+        let max_width = graphics.get_slide_dimensions().w as f32 * 0.25;
+
+        let text = TextWithBackground::create(
+            &mut graphics,
+            "Saint-Jean-Pied-de-Port, au pied des Pyrénées, en Pays basque".into(),
+            &caption,
+        )
+        .unwrap();
+        let galley = text.container.galley().unwrap();
+        expect_that!(galley.rows.len(), gt(1));
+        expect_that!(text.size().w, le(max_width));
+
+        // The box hugs short captions rather than taking the whole width
+        let text = TextWithBackground::create(&mut graphics, "Pau".into(), &caption).unwrap();
+        expect_that!(text.container.galley().unwrap().rows.len(), eq(1));
+        expect_that!(text.size().w, lt(max_width / 2.));
+    }
+
+    #[gtest]
+    fn test_scrim_darkest_on_its_edge() {
+        let mesh = scrim_mesh(Extent2::new(100., 50.), false);
+        expect_that!(mesh.vertices.len(), eq(2 * (SCRIM_BANDS as usize + 1)));
+        expect_that!(mesh.indices.len(), eq(6 * SCRIM_BANDS as usize));
+        let alphas = mesh
+            .vertices
+            .iter()
+            .map(|vertex| vertex.color.a())
+            .collect::<Vec<_>>();
+        expect_that!(alphas[0], eq(0));
+        expect_that!(alphas[alphas.len() - 1], eq(179));
+        expect_pred!(alphas.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let flipped = scrim_mesh(Extent2::new(100., 50.), true);
+        expect_that!(flipped.vertices[0].color.a(), eq(179));
+    }
+
+    #[gtest]
+    fn test_upgrade_thumbnail() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(50),
+            ..Default::default()
+        });
+        let mut thumbnail = preloaded_slide((40, 60).into());
+        thumbnail.blurred_texture = None;
+        thumbnail.details.city = Some("Bordeaux".into());
+
+        let mut slide = Slide::create(thumbnail, &mut graphics, &config, 0).unwrap();
+        slide.set_opacity(0.5);
+        expect_pred!(slide.background.is_none());
+
+        slide
+            .upgrade(preloaded_slide((400, 600).into()), &mut graphics, &config)
+            .unwrap();
+        expect_that!(
+            slide.main_sprite.get_texture_size(),
+            eq(Extent2::new(400, 600))
+        );
+        expect_that!(
+            slide.main_sprite.position,
+            matches_pattern!(Vec2 {
+                x: approx_eq(200.),
+                y: approx_eq(0.),
+            })
+        );
+        expect_that!(slide.main_sprite.opacity, approx_eq(0.5));
+        assert_pred!(slide.background.is_some());
+        expect_that!(
+            slide.background.as_ref().unwrap()[0].opacity,
+            approx_eq(0.5)
+        );
+        expect_pred!(slide.text.is_some());
+    }
+
+    fn face(x: u32, y: u32, size: u32) -> BoxInImage {
+        BoxInImage {
+            width: 2000,
+            height: 3000,
+            box_x_start: x,
+            box_y_start: y,
+            box_x_end: x + size,
+            box_y_end: y + size,
+        }
+    }
+
+    #[gtest]
+    fn test_box_to_screen() {
+        // Detected on a larger version, shown at 400x600 in the middle of the screen
+        let rect = box_to_screen(
+            &face(500, 1500, 500),
+            Vec2::new(200., 0.),
+            Extent2::new(400., 600.),
+        );
+        expect_that!(
+            rect,
+            matches_pattern!(Rect {
+                x: approx_eq(300.),
+                y: approx_eq(300.),
+                w: approx_eq(100.),
+                h: approx_eq(100.),
+            })
+        );
+    }
+
+    #[gtest]
+    fn test_faces_on_screen() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let mut preloaded_slide = preloaded_slide((200, 300).into());
+        preloaded_slide.details.people = vec![
+            Person {
+                name: Some("Alice".into()),
+                face: Some(face(0, 2500, 500)),
+            },
+            Person {
+                name: None,
+                face: None,
+            },
+        ];
+
+        let slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+        let faces = slide.faces_on_screen();
+        expect_that!(faces.len(), eq(1));
+        expect_that!(
+            faces[0],
+            matches_pattern!(Rect {
+                x: approx_eq(200.),
+                y: approx_eq(500.),
+                w: approx_eq(100.),
+                h: approx_eq(100.),
+            })
+        );
+    }
+
+    #[gtest]
+    fn test_portrait_framing() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let mut preloaded_slide = preloaded_slide((200, 300).into());
+        preloaded_slide.details.people = [face(0, 0, 400), face(800, 1200, 300)]
+            .into_iter()
+            .map(|face| Person {
+                name: None,
+                face: Some(face),
+            })
+            .collect();
+        let mut slide = Slide::create(preloaded_slide, &mut graphics, &config, 0).unwrap();
+
+        // The central face, even though it's smaller than the one in the corner
+        let (pan, zoom) = slide.portrait_framing(0.25).unwrap();
+        expect_that!(pan[0], approx_eq(0.475));
+        expect_that!(pan[1], approx_eq(0.45));
+        expect_that!(zoom, approx_eq(0.4));
+
+        // Panned to the top left corner, without leaving the photo
+        slide.apply(SlideProperties {
+            global_opacity: 1.,
+            zoom: 0.5,
+            pan: [0., 0.],
+            text_position: [0., 0.],
+        });
+        let region = slide.main_sprite.get_sub_center_size();
+        expect_that!(region.uv_center.x, approx_eq(0.25));
+        expect_that!(region.uv_center.y, approx_eq(0.25));
+    }
+
+    #[gtest]
+    fn test_no_portrait_framing_without_faces() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Black;
+        let slide = Slide::create(
+            preloaded_slide((200, 300).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        expect_that!(slide.portrait_framing(0.25).is_none(), is_true());
+    }
+
+    #[gtest]
+    fn test_relayout_after_resize() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl.clone(), OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        config.background = Background::Blur(BlurBackground {
+            min_free_space: FreeSpace::Pixels(50),
+            ..Default::default()
+        });
+        let mut slide = Slide::create(
+            preloaded_slide((400, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+
+        gl.set_viewport(Rect::new(0, 0, 600, 300));
+        expect_that!(graphics.update_vp(), is_true());
+        expect_that!(graphics.get_dimensions(), eq(Extent2::new(600, 300)));
+        expect_that!(graphics.update_vp(), is_false());
+
+        slide.relayout(&mut graphics, &config).unwrap();
+        expect_that!(
+            slide.main_sprite.size,
+            matches_pattern!(Extent2 {
+                w: approx_eq(200.),
+                h: approx_eq(300.),
+            })
+        );
+        expect_that!(
+            slide.main_sprite.position,
+            matches_pattern!(Vec2 {
+                x: approx_eq(200.),
+                y: approx_eq(0.),
+            })
+        );
+        let background = slide.background.as_ref().unwrap();
+        expect_that!(background[1].position.x, approx_eq(400.));
+        expect_that!(background[1].size.w, approx_eq(200.));
+    }
+
+    #[gtest]
+    fn test_perceptual_opacity() {
+        expect_that!(perceptual_opacity(0.), eq(0.));
+        expect_that!(perceptual_opacity(1.), eq(1.));
+        expect_that!(perceptual_opacity(0.5), near(0.7297, 1e-4));
+        expect_that!(perceptual_opacity(0.1), gt(0.3));
+        // Overshooting easings stay in range
+        expect_that!(perceptual_opacity(1.2), eq(1.));
+        expect_that!(perceptual_opacity(-0.1), eq(0.));
+    }
+
+    #[gtest]
+    fn test_fade_drawn_through_curve() {
+        let gl = mocked_gl();
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+        let mut config = Settings::default();
+        let properties = SlideProperties {
+            global_opacity: 0.5,
+            ..SlideProperties::default()
+        };
+
+        let mut slide = Slide::create(
+            preloaded_slide((400, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        slide.apply(properties.clone());
+        let (raw, drawn) = slide.opacity();
+        expect_that!(raw, eq(0.5));
+        expect_that!(drawn, near(0.7297, 1e-4));
+
+        config.perceptual_fades = false;
+        let mut slide = Slide::create(
+            preloaded_slide((400, 600).into()),
+            &mut graphics,
+            &config,
+            0,
+        )
+        .unwrap();
+        slide.apply(properties);
+        expect_that!(slide.opacity(), eq((0.5, 0.5)));
+    }
 }
@@ -0,0 +1,211 @@
+//! Renders slides with a real GPU and compares them with the reference pictures in
+//! tests/snapshots. Ignored by default, run them with `cargo test -- --ignored`; they are
+//! skipped when no EGL device is available, and fail when the reference of a scene is
+//! missing. Set `UPDATE_SNAPSHOTS=1` to write the references from the current rendering.
+
+use std::{env, path::PathBuf, rc::Rc};
+
+use anyhow::{Context, Result};
+use googletest::{assert_that, gtest, prelude::le};
+use image::{DynamicImage, Rgba, RgbaImage};
+use vek::{Extent2, Rect};
+
+use super::{
+    caption_placement,
+    slide::{Slide, SlideProperties},
+};
+use crate::{
+    configuration::{Background, OrientationName, Settings},
+    gallery::{ImageDetails, ImageWithDetails},
+    gl::{
+        framebuffer::FramebufferObject,
+        texture::{Texture, TextureFormat},
+        FutureGlThreadContext, GlContext,
+    },
+    graphics::{Drawable, Graphics},
+    worker::prepare_slide,
+};
+
+const SCREEN: Extent2<u32> = Extent2::new(160, 120);
+/// Largest difference of a color channel for pixels to be considered the same.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// Share of the pixels allowed to differ, as antialiasing varies between drivers.
+const MAX_DIFFERENT_PIXELS: f32 = 0.01;
+
+/// Real GL context without any surface, on the first EGL device.
+fn surfaceless_gl() -> Result<Rc<GlContext>> {
+    FutureGlThreadContext::surfaceless(None)?.activate()
+}
+
+/// Portrait photo, red on the left and blue on the right, so that flips and wrong texture
+/// coordinates show.
+fn two_color_photo() -> ImageWithDetails {
+    let image = RgbaImage::from_fn(60, 90, |x, _| {
+        if x < 30 {
+            Rgba([220, 40, 40, 255])
+        } else {
+            Rgba([40, 40, 220, 255])
+        }
+    });
+    ImageWithDetails {
+        image: DynamicImage::ImageRgba8(image),
+        details: ImageDetails {
+            asset_id: None,
+            album: None,
+            starts_album: false,
+            city: None,
+            country: None,
+            gps: None,
+            date: None,
+            people: Vec::new(),
+        },
+        low_res: false,
+        background_image: None,
+        preparation: Default::default(),
+    }
+}
+
+/// Draws the photo as a slide, once its transition is over, and reads the screen back.
+fn render(
+    gl: &Rc<GlContext>,
+    photo: ImageWithDetails,
+    orientation: OrientationName,
+    config: &Settings,
+) -> Result<RgbaImage> {
+    gl.set_viewport(Rect::new(0, 0, SCREEN.w as i32, SCREEN.h as i32));
+    let mut graphics = Graphics::new(Rc::clone(gl), orientation)?;
+    let preloaded = prepare_slide(photo, gl, graphics.blurr(), config)?;
+    let mut slide = Slide::create(preloaded, &mut graphics, config, 0)?;
+    let text_position = caption_placement(&graphics, &slide)
+        .map_or([0., 0.], |(position, _)| position.into_array());
+    slide.apply(SlideProperties {
+        global_opacity: 1.,
+        zoom: 1.,
+        pan: [0.5, 0.5],
+        text_position,
+    });
+
+    let texture = Texture::empty(Rc::clone(gl), TextureFormat::Rgba, SCREEN)?;
+    let target = FramebufferObject::with_texture(Rc::clone(gl), texture)?;
+    graphics.begin_frame();
+    graphics.update();
+    {
+        let _guard = target.bind_guard();
+        gl.clear();
+        graphics.render(|graphics| slide.draw(graphics))?;
+    }
+    target.read_pixels()
+}
+
+/// Share of the pixels of `actual` differing from `expected`.
+fn difference(actual: &RgbaImage, expected: &RgbaImage) -> f32 {
+    if actual.dimensions() != expected.dimensions() {
+        return 1.;
+    }
+    let different = actual
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, e)| {
+            a.0.iter()
+                .zip(e.0)
+                .any(|(a, e)| a.abs_diff(e) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    different as f32 / (actual.width() * actual.height()) as f32
+}
+
+fn snapshot_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}{suffix}.png"))
+}
+
+/// Compares `actual` with the reference `name`, or replaces it when updating. On mismatch,
+/// the rendering is saved next to the reference for inspection.
+fn check_snapshot(name: &str, actual: &RgbaImage) {
+    let reference = snapshot_path(name, "");
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        actual.save(&reference).unwrap();
+        return;
+    }
+    assert!(
+        reference.exists(),
+        "No snapshot {name} in {}, run with UPDATE_SNAPSHOTS=1 to create it",
+        reference.display()
+    );
+    let expected = image::open(&reference)
+        .with_context(|| format!("Cannot open {}", reference.display()))
+        .unwrap()
+        .into_rgba8();
+    let difference = difference(actual, &expected);
+    if difference > MAX_DIFFERENT_PIXELS {
+        actual.save(snapshot_path(name, ".actual")).unwrap();
+    }
+    assert_that!(difference, le(MAX_DIFFERENT_PIXELS));
+}
+
+/// Renders the photo and checks the result, unless there is no GPU to draw with.
+fn check_scene(
+    name: &str,
+    photo: ImageWithDetails,
+    orientation: OrientationName,
+    config: Settings,
+) {
+    let gl = match surfaceless_gl() {
+        Ok(gl) => gl,
+        Err(err) => {
+            eprintln!("Skipping snapshot {name}: {err:#}");
+            return;
+        }
+    };
+    let actual = render(&gl, photo, orientation, &config).unwrap();
+    check_snapshot(name, &actual);
+}
+
+#[gtest]
+#[ignore = "needs a GPU"]
+fn test_snapshot_black_background() {
+    let mut config = Settings::default();
+    config.background = Background::Black;
+    check_scene(
+        "black_background",
+        two_color_photo(),
+        OrientationName::Angle0,
+        config,
+    );
+}
+
+#[gtest]
+#[ignore = "needs a GPU"]
+fn test_snapshot_blur_background() {
+    check_scene(
+        "blur_background",
+        two_color_photo(),
+        OrientationName::Angle0,
+        Settings::default(),
+    );
+}
+
+#[gtest]
+#[ignore = "needs a GPU"]
+fn test_snapshot_rotations() {
+    for (name, orientation) in [
+        ("rotation_90", OrientationName::Angle90),
+        ("rotation_180", OrientationName::Angle180),
+        ("rotation_270", OrientationName::Angle270),
+    ] {
+        let mut photo = two_color_photo();
+        photo.details.city = Some("Bordeaux".into());
+        check_scene(name, photo, orientation, Settings::default());
+    }
+}
+
+#[gtest]
+#[ignore = "needs a GPU"]
+fn test_snapshot_caption() {
+    let mut photo = two_color_photo();
+    photo.details.city = Some("Bordeaux".into());
+    let mut config = Settings::default();
+    config.caption.font_size = 16.;
+    check_scene("caption", photo, OrientationName::Angle0, config);
+}
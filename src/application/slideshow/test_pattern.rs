@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use epaint::{pos2, vec2, Color32, Mesh, Rect, Shape};
+use vek::Extent2;
+
+use super::StaticSlide;
+use crate::graphics::{Drawable, Graphics, ShapeContainer};
+
+/// SMPTE-like 75% color bars, from left to right.
+const BARS: [Color32; 7] = [
+    Color32::from_rgb(191, 191, 191),
+    Color32::from_rgb(191, 191, 0),
+    Color32::from_rgb(0, 191, 191),
+    Color32::from_rgb(0, 191, 0),
+    Color32::from_rgb(191, 0, 191),
+    Color32::from_rgb(191, 0, 0),
+    Color32::from_rgb(0, 0, 191),
+];
+
+/// Pure primaries and white, displayed under the bars to check for dead pixels.
+const PRIMARIES: [Color32; 4] = [
+    Color32::WHITE,
+    Color32::from_rgb(255, 0, 0),
+    Color32::from_rgb(0, 255, 0),
+    Color32::from_rgb(0, 0, 255),
+];
+
+/// Fraction of the screen height used by the color bars.
+const BARS_HEIGHT: f32 = 2. / 3.;
+/// Fraction of the screen height used by the primaries strip.
+const PRIMARIES_HEIGHT: f32 = 1. / 12.;
+
+/// Static calibration screen made of color bars, a strip of primaries and a black to white
+/// gradient. It doesn't need any gallery and is only shown when `debug.test_pattern` is set.
+pub struct TestPatternSlide {
+    pattern: ShapeContainer,
+    displayed: bool,
+}
+
+impl TestPatternSlide {
+    pub fn create(graphics: &mut Graphics) -> Result<Self> {
+        let mesh = Self::build_mesh(graphics.get_dimensions().as_());
+        let pattern = graphics
+            .create_shape(Shape::mesh(mesh), None)
+            .context("Cannot create test pattern shape")?;
+        Ok(Self {
+            pattern,
+            displayed: false,
+        })
+    }
+
+    fn build_mesh(size: Extent2<f32>) -> Mesh {
+        let mut mesh = Mesh::default();
+
+        let bars_height = (size.h * BARS_HEIGHT).round();
+        let bar_width = size.w / BARS.len() as f32;
+        for (i, color) in BARS.iter().enumerate() {
+            let rect =
+                Rect::from_min_size(pos2(i as f32 * bar_width, 0.), vec2(bar_width, bars_height));
+            mesh.add_colored_rect(rect, *color);
+        }
+
+        let primaries_height = (size.h * PRIMARIES_HEIGHT).round();
+        let primary_width = size.w / PRIMARIES.len() as f32;
+        for (i, color) in PRIMARIES.iter().enumerate() {
+            let rect = Rect::from_min_size(
+                pos2(i as f32 * primary_width, bars_height),
+                vec2(primary_width, primaries_height),
+            );
+            mesh.add_colored_rect(rect, *color);
+        }
+
+        let gradient_top = bars_height + primaries_height;
+        let idx = mesh.vertices.len() as u32;
+        mesh.colored_vertex(pos2(0., gradient_top), Color32::BLACK);
+        mesh.colored_vertex(pos2(size.w, gradient_top), Color32::WHITE);
+        mesh.colored_vertex(pos2(size.w, size.h), Color32::WHITE);
+        mesh.colored_vertex(pos2(0., size.h), Color32::BLACK);
+        mesh.add_triangle(idx, idx + 1, idx + 2);
+        mesh.add_triangle(idx, idx + 2, idx + 3);
+
+        mesh
+    }
+}
+
+impl StaticSlide for TestPatternSlide {
+    fn displayed(&mut self) -> &mut bool {
+        &mut self.displayed
+    }
+}
+
+impl Drawable for TestPatternSlide {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        self.pattern.draw(graphics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use googletest::{expect_that, gtest, prelude::eq};
+    use vek::Extent2;
+
+    use super::{TestPatternSlide, BARS, PRIMARIES};
+
+    #[gtest]
+    fn test_pattern_covers_screen() {
+        let mesh = TestPatternSlide::build_mesh(Extent2::new(800., 600.));
+        let bounds = mesh.calc_bounds();
+        expect_that!(bounds.min.x, eq(0.));
+        expect_that!(bounds.min.y, eq(0.));
+        expect_that!(bounds.max.x, eq(800.));
+        expect_that!(bounds.max.y, eq(600.));
+        expect_that!(
+            mesh.vertices.len(),
+            eq((BARS.len() + PRIMARIES.len() + 1) * 4)
+        );
+    }
+}
@@ -5,7 +5,8 @@ use glissade::Easing;
 use super::slide::AnimatedSlideProperties;
 
 pub trait Transition {
-    fn ease_in(&self, time: Instant, duration: Duration) -> AnimatedSlideProperties;
+    /// Animates the incoming slide from `properties`, its state once shown.
+    fn ease_in(&self, time: Instant, duration: Duration, properties: &mut AnimatedSlideProperties);
     fn ease_out(&self, time: Instant, duration: Duration, properties: &mut AnimatedSlideProperties);
 }
 
@@ -13,12 +14,15 @@ pub struct DissolveTransition;
 
 pub struct EaseInOutTransition;
 
+pub struct PushTransition;
+
+/// Share of the photo shown at the oversized end of the push, the smaller the deeper.
+const PUSH_ZOOM: f32 = 0.8;
+
 impl Transition for DissolveTransition {
-    fn ease_in(&self, time: Instant, duration: Duration) -> AnimatedSlideProperties {
-        let mut properties = AnimatedSlideProperties::default();
+    fn ease_in(&self, time: Instant, duration: Duration, properties: &mut AnimatedSlideProperties) {
         properties.set_global_opacity_no_ease(0.0);
         properties.ease_global_opacity(1.0, time, duration, Easing::QuadraticInOut);
-        properties
     }
 
     fn ease_out(
@@ -32,8 +36,7 @@ impl Transition for DissolveTransition {
 }
 
 impl Transition for EaseInOutTransition {
-    fn ease_in(&self, time: Instant, duration: Duration) -> AnimatedSlideProperties {
-        let mut properties = AnimatedSlideProperties::default();
+    fn ease_in(&self, time: Instant, duration: Duration, properties: &mut AnimatedSlideProperties) {
         properties.set_global_opacity_no_ease(0.0);
         properties.ease_global_opacity(
             1.0,
@@ -41,7 +44,6 @@ impl Transition for EaseInOutTransition {
             duration / 2,
             Easing::QuadraticInOut,
         );
-        properties
     }
 
     fn ease_out(
@@ -53,3 +55,77 @@ impl Transition for EaseInOutTransition {
         properties.ease_global_opacity(0.0, time, duration / 2, Easing::QuadraticInOut);
     }
 }
+
+impl Transition for PushTransition {
+    fn ease_in(&self, time: Instant, duration: Duration, properties: &mut AnimatedSlideProperties) {
+        let zoom = properties.get_target_zoom();
+        // Never zoomed out past the photo, its edges would show
+        properties.set_zoom_no_ease((zoom * PUSH_ZOOM).min(1.));
+        properties.ease_zoom(zoom.min(1.), time, duration, Easing::QuadraticOut);
+        properties.set_global_opacity_no_ease(0.0);
+        properties.ease_global_opacity(1.0, time, duration, Easing::QuadraticInOut);
+    }
+
+    fn ease_out(
+        &self,
+        time: Instant,
+        duration: Duration,
+        properties: &mut AnimatedSlideProperties,
+    ) {
+        let zoom = properties.get_zoom(time);
+        properties.ease_zoom(
+            (zoom * PUSH_ZOOM).min(1.),
+            time,
+            duration,
+            Easing::QuadraticIn,
+        );
+        properties.ease_global_opacity(0.0, time, duration, Easing::QuadraticInOut);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{approx_eq, le},
+    };
+
+    use super::{PushTransition, Transition};
+    use crate::application::slideshow::slide::AnimatedSlideProperties;
+
+    #[gtest]
+    fn test_push_zooms_through_photos() {
+        let now = Instant::now();
+        let duration = Duration::from_secs(1);
+        let end = now + duration;
+
+        let mut incoming = AnimatedSlideProperties::default();
+        incoming.set_zoom_no_ease(0.9);
+        PushTransition.ease_in(now, duration, &mut incoming);
+        expect_that!(incoming.get_zoom(now), approx_eq(0.72));
+        expect_that!(incoming.get_global_opacity(now), approx_eq(0.));
+        expect_that!(incoming.get_zoom(end), approx_eq(0.9));
+        expect_that!(incoming.get_global_opacity(end), approx_eq(1.));
+
+        let mut outgoing = AnimatedSlideProperties::default();
+        PushTransition.ease_out(now, duration, &mut outgoing);
+        expect_that!(outgoing.get_zoom(end), approx_eq(0.8));
+        expect_that!(outgoing.get_global_opacity(end), approx_eq(0.));
+    }
+
+    #[gtest]
+    fn test_push_never_shows_photo_edges() {
+        let now = Instant::now();
+        let duration = Duration::from_secs(1);
+
+        let mut incoming = AnimatedSlideProperties::default();
+        incoming.set_zoom_no_ease(1.5);
+        PushTransition.ease_in(now, duration, &mut incoming);
+        for step in 0..=10 {
+            let time = now + duration * step / 10;
+            expect_that!(incoming.get_zoom(time), le(1.));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use log::error;
+
+/// Exit code of the process when the watchdog fires, any failure gets it restarted by systemd.
+const STALL_EXIT_CODE: i32 = 2;
+
+/// Exits the process when the render loop stops for too long, e.g. on a GPU driver hang in
+/// `swap_buffers`, so that systemd restarts it. A last resort for unattended frames.
+pub struct Watchdog {
+    /// Last turn of the render loop, `None` while paused.
+    heartbeat: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Watchdog {
+    pub fn start(timeout: Duration) -> Result<Self> {
+        let heartbeat = Arc::new(Mutex::new(Some(Instant::now())));
+        let watched = Arc::clone(&heartbeat);
+        thread::Builder::new()
+            .name("watchdog".to_string())
+            .spawn(move || loop {
+                thread::sleep(timeout / 4);
+                let last = *watched.lock().unwrap();
+                if let Some(stalled) = stalled_for(last, Instant::now(), timeout) {
+                    error!(
+                        "Render loop stalled for {:?}, exiting to be restarted",
+                        stalled
+                    );
+                    std::process::exit(STALL_EXIT_CODE);
+                }
+            })
+            .context("Cannot start watchdog thread")?;
+        Ok(Self { heartbeat })
+    }
+
+    /// Called on each turn of the render loop, whether a frame is drawn or not.
+    pub fn beat(&self) {
+        *self.heartbeat.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Stops watching until the next beat, e.g. while the display is off.
+    pub fn pause(&self) {
+        *self.heartbeat.lock().unwrap() = None;
+    }
+}
+
+/// How long the render loop has been stuck, if longer than `timeout`.
+fn stalled_for(last: Option<Instant>, now: Instant, timeout: Duration) -> Option<Duration> {
+    let elapsed = now.saturating_duration_since(last?);
+    (elapsed > timeout).then_some(elapsed)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, none, some},
+    };
+
+    use super::stalled_for;
+    use crate::configuration::WatchdogConfig;
+
+    #[gtest]
+    fn test_stall_detected_after_timeout() {
+        let now = Instant::now();
+        let timeout = Duration::from_secs(60);
+
+        expect_that!(stalled_for(Some(now), now + timeout, timeout), none());
+        expect_that!(
+            stalled_for(Some(now), now + Duration::from_secs(61), timeout),
+            some(eq(Duration::from_secs(61)))
+        );
+    }
+
+    #[gtest]
+    fn test_zero_timeout_rejected() {
+        let config = |timeout: &str| {
+            serde_json::from_value::<WatchdogConfig>(serde_json::json!({ "timeout": timeout }))
+        };
+        expect_that!(
+            config("30s").map(|config| config.timeout).ok(),
+            some(eq(Duration::from_secs(30)))
+        );
+        expect_that!(config("0s").is_err(), eq(true));
+    }
+
+    #[gtest]
+    fn test_paused_watchdog_never_fires() {
+        let now = Instant::now();
+        expect_that!(
+            stalled_for(
+                None,
+                now + Duration::from_secs(3600),
+                Duration::from_secs(60)
+            ),
+            none()
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+use vek::{Extent2, Vec2};
+
+use crate::{
+    configuration::{WatermarkPosition, WatermarkSettings},
+    gl::{
+        texture::{Texture, TextureFormat, TextureWrapMode},
+        GlContext,
+    },
+    graphics::{Drawable, Graphics, SharedTexture2d, Sprite},
+};
+
+/// Space between the logo and the edges of the screen, as a fraction of its smaller side.
+const MARGIN_FRACTION: f32 = 0.02;
+
+/// Logo drawn over the photos, see [`crate::configuration::Settings::watermark`].
+pub struct Watermark {
+    sprite: Sprite,
+}
+
+impl Watermark {
+    /// Loads the logo at `path`, keeping its transparency.
+    pub fn load(gl: &Rc<GlContext>, path: &str) -> Result<Self> {
+        let image = image::open(path).context("Cannot decode image")?;
+        let mut texture =
+            Texture::new_from_image_with_format(Rc::clone(gl), &image, TextureFormat::Rgba)
+                .context("Cannot create watermark texture")?;
+        // Repeating would bleed the opposite edge into the transparent border
+        texture.set_wrap(TextureWrapMode::ClampToEdge);
+        Ok(Self {
+            sprite: Sprite::new(SharedTexture2d::new(texture)),
+        })
+    }
+
+    /// Places the logo for the current screen and settings, returns whether it moved.
+    pub fn update(&mut self, graphics: &Graphics, settings: &WatermarkSettings) -> bool {
+        let logo = self.sprite.texture.size().as_::<f32>();
+        let screen = graphics.get_dimensions().as_::<f32>();
+        let (position, size) =
+            placement(screen, logo, settings.position, settings.max_size_fraction);
+        let changed = position != self.sprite.position
+            || size != self.sprite.size
+            || settings.opacity != self.sprite.opacity;
+        self.sprite.position = position;
+        self.sprite.size = size;
+        self.sprite.opacity = settings.opacity;
+        changed
+    }
+}
+
+impl Drawable for Watermark {
+    fn draw(&self, graphics: &Graphics) -> Result<()> {
+        self.sprite.draw(graphics)
+    }
+}
+
+/// Position and size of a `logo` in a corner of the `screen`, scaled down to at most
+/// `max_size_fraction` of its smaller side.
+fn placement(
+    screen: Extent2<f32>,
+    logo: Extent2<f32>,
+    position: WatermarkPosition,
+    max_size_fraction: f32,
+) -> (Vec2<f32>, Extent2<f32>) {
+    let smaller_side = screen.reduce_partial_min();
+    let max_side = smaller_side * max_size_fraction.max(0.);
+    let largest = logo.reduce_partial_max();
+    let scale = if largest > max_side {
+        max_side / largest
+    } else {
+        1.
+    };
+    let size = logo * scale;
+    let margin = smaller_side * MARGIN_FRACTION;
+    let x = match position {
+        WatermarkPosition::TopLeft | WatermarkPosition::BottomLeft => margin,
+        WatermarkPosition::TopRight | WatermarkPosition::BottomRight => screen.w - size.w - margin,
+    };
+    let y = match position {
+        WatermarkPosition::TopLeft | WatermarkPosition::TopRight => margin,
+        WatermarkPosition::BottomLeft | WatermarkPosition::BottomRight => {
+            screen.h - size.h - margin
+        }
+    };
+    (Vec2::new(x, y), size)
+}
+
+#[cfg(test)]
+mod test {
+    use googletest::{expect_that, gtest, prelude::eq};
+    use vek::{Extent2, Vec2};
+
+    use super::placement;
+    use crate::configuration::WatermarkPosition;
+
+    const LANDSCAPE: Extent2<f32> = Extent2::new(1920., 1080.);
+    // Same screen rotated by 90 or 270 degrees
+    const PORTRAIT: Extent2<f32> = Extent2::new(1080., 1920.);
+
+    #[gtest]
+    fn test_logo_scaled_down_preserving_aspect() {
+        let logo = Extent2::new(540., 270.);
+        // A quarter of 1080 pixels, whatever the rotation
+        for screen in [LANDSCAPE, PORTRAIT] {
+            let (_, size) = placement(screen, logo, WatermarkPosition::TopLeft, 0.25);
+            expect_that!(size, eq(Extent2::new(270., 135.)));
+        }
+        // Never scaled up
+        let (_, size) = placement(LANDSCAPE, logo, WatermarkPosition::TopLeft, 0.75);
+        expect_that!(size, eq(logo));
+    }
+
+    #[gtest]
+    fn test_logo_in_each_corner() {
+        let logo = Extent2::new(100., 50.);
+        let margin = 1080. * 0.02;
+        let cases = [
+            (
+                WatermarkPosition::TopLeft,
+                LANDSCAPE,
+                Vec2::new(margin, margin),
+            ),
+            (
+                WatermarkPosition::TopRight,
+                LANDSCAPE,
+                Vec2::new(1920. - 100. - margin, margin),
+            ),
+            (
+                WatermarkPosition::BottomLeft,
+                LANDSCAPE,
+                Vec2::new(margin, 1080. - 50. - margin),
+            ),
+            (
+                WatermarkPosition::BottomRight,
+                LANDSCAPE,
+                Vec2::new(1920. - 100. - margin, 1080. - 50. - margin),
+            ),
+            (
+                WatermarkPosition::TopLeft,
+                PORTRAIT,
+                Vec2::new(margin, margin),
+            ),
+            (
+                WatermarkPosition::TopRight,
+                PORTRAIT,
+                Vec2::new(1080. - 100. - margin, margin),
+            ),
+            (
+                WatermarkPosition::BottomLeft,
+                PORTRAIT,
+                Vec2::new(margin, 1920. - 50. - margin),
+            ),
+            (
+                WatermarkPosition::BottomRight,
+                PORTRAIT,
+                Vec2::new(1080. - 100. - margin, 1920. - 50. - margin),
+            ),
+        ];
+        for (position, screen, expected) in cases {
+            let (at, _) = placement(screen, logo, position, 0.15);
+            expect_that!(at, eq(expected), "{position:?} on {screen:?}");
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::cell::RefCell;
+
+use anyhow::{bail, Result};
+use config::{Config, ConfigError, Map, Value, ValueKind};
+use log::warn;
+use serde::{
+    de::{DeserializeOwned, Error},
+    Deserialize, Deserializer,
+};
+use serde_ignored::Path;
+
+/// Added by [`deserialize`] to the fields of tagged enums, holding their path in the
+/// configuration, see [`tagged`].
+const PATH_KEY: &str = "\0path";
+
+thread_local! {
+    /// Unknown fields found by [`tagged`], only collected during [`deserialize`].
+    static UNKNOWN_FIELDS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Deserializes `config`, whose structures don't deny unknown fields. Unless `strict`,
+/// unknown fields, like typos or options of a newer version, are ignored with a warning
+/// instead of failing, so that a frame still boots after a partial edit.
+pub fn deserialize<T: DeserializeOwned>(config: Config, strict: bool) -> Result<T> {
+    let mut unknown = Vec::new();
+    let mut tree = config.cache;
+    add_paths(&mut tree, "");
+    UNKNOWN_FIELDS.set(Some(Vec::new()));
+    let value = serde_ignored::deserialize(tree, |path| unknown.push(field_path(&path)));
+    unknown.extend(UNKNOWN_FIELDS.take().unwrap_or_default());
+    let value = value?;
+    if strict && !unknown.is_empty() {
+        bail!("Rejecting unknown field(s) {}", unknown.join(", "));
+    }
+    for path in unknown {
+        warn!("Ignoring unknown field {path}");
+    }
+    Ok(value)
+}
+
+/// Like "sources.0.instance.url", without the steps into options.
+fn field_path(path: &Path) -> String {
+    let path = path.to_string();
+    let steps: Vec<_> = path.split('.').filter(|step| *step != "?").collect();
+    steps.join(".")
+}
+
+/// Adds its path to each table tagged by a `type` field, like "sources.0.specs.0".
+fn add_paths(value: &mut Value, path: &str) {
+    let child = |key: &dyn std::fmt::Display| match path {
+        "" => key.to_string(),
+        _ => format!("{path}.{key}"),
+    };
+    match &mut value.kind {
+        ValueKind::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                add_paths(value, &child(key));
+            }
+            let tagged = table
+                .get("type")
+                .is_some_and(|kind| matches!(kind.kind, ValueKind::String(_)));
+            if tagged {
+                table.insert(PATH_KEY.to_owned(), Value::new(None, path));
+            }
+        }
+        ValueKind::Array(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                add_paths(value, &child(&index));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn record_unknown(path: String) {
+    UNKNOWN_FIELDS.with_borrow_mut(|unknown| {
+        if let Some(unknown) = unknown {
+            unknown.push(path);
+        }
+    });
+}
+
+/// Fields of a variant of an enum tagged by its `type` field, see [`tagged`]. They are
+/// kept as configuration values, so that strings of environment overrides are still
+/// converted to numbers and booleans.
+pub struct Fields {
+    /// Path of the variant in the configuration, or its kind when not known.
+    path: String,
+    map: Map<String, Value>,
+}
+
+impl Fields {
+    pub fn parse<T: DeserializeOwned>(self) -> Result<T, ConfigError> {
+        let Fields { path: prefix, map } = self;
+        serde_ignored::deserialize(Value::new(None, ValueKind::Table(map)), |path| {
+            record_unknown(format!("{prefix}.{}", field_path(&path)))
+        })
+    }
+
+    /// For variants without fields, all of them are unknown.
+    pub fn none(self) -> Result<(), ConfigError> {
+        for key in self.map.keys() {
+            record_unknown(format!("{}.{key}", self.path));
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes an enum tagged by its `type` field, one of `variants`, whose fields are
+/// read by `variant`. Serde buffers the fields of derived tagged enums, which hides the
+/// unknown ones from [`deserialize`], so they are tracked here.
+pub fn tagged<'de, D, T>(
+    deserializer: D,
+    variants: &'static [&'static str],
+    variant: impl FnOnce(&str, Fields) -> Result<T, ConfigError>,
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut map = Map::deserialize(deserializer)?;
+    let kind = map
+        .remove("type")
+        .ok_or_else(|| D::Error::missing_field("type"))?;
+    let kind = String::deserialize(kind).map_err(D::Error::custom)?;
+    if !variants.contains(&kind.as_str()) {
+        return Err(D::Error::unknown_variant(&kind, variants));
+    }
+    let path = match map.remove(PATH_KEY).map(String::deserialize) {
+        Some(path) => path.map_err(D::Error::custom)?,
+        None => kind.clone(),
+    };
+    let fields = Fields { path, map };
+    variant(&kind, fields).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use config::{Config, File, FileFormat};
+    use googletest::{
+        expect_that, gtest,
+        matchers::matches_pattern,
+        prelude::{contains_substring, eq, is_true},
+    };
+
+    use super::deserialize;
+    use crate::configuration::{
+        AppConfig, Background, BlurBackground, ImmichSource, Settings, Source, TakenDate,
+    };
+
+    fn config(yaml: &str) -> Config {
+        Config::builder()
+            .add_source(File::from_str(yaml, FileFormat::Yaml))
+            .build()
+            .unwrap()
+    }
+
+    const SETTINGS: &str = r#"
+display_duration: "12s"
+dispaly_duration: "15s"
+caption:
+  enabled: false
+  font_sise: 30
+"#;
+
+    #[gtest]
+    fn test_unknown_fields_are_dropped() {
+        let settings: Settings = deserialize(config(SETTINGS), false).unwrap();
+        expect_that!(settings.display_duration.as_secs(), eq(12));
+        expect_that!(settings.caption.enabled, eq(false));
+    }
+
+    #[gtest]
+    fn test_strict_mode_rejects_unknown_fields() {
+        let error = deserialize::<Settings>(config(SETTINGS), true).unwrap_err();
+        let message = format!("{error:#}");
+        expect_that!(message, contains_substring("caption.font_sise"));
+        expect_that!(message, contains_substring("dispaly_duration"));
+    }
+
+    #[gtest]
+    fn test_unknown_fields_in_sources_are_dropped() {
+        let app_config: AppConfig = deserialize(
+            config(
+                r#"
+sources:
+  - type: immich
+    instance:
+      url: "http://immich"
+      api_key: "key"
+      new_option: true
+"#,
+            ),
+            false,
+        )
+        .unwrap();
+        expect_that!(app_config.sources.len(), eq(1));
+    }
+
+    #[gtest]
+    fn test_unknown_fields_in_tagged_enums_are_rejected() {
+        let error = deserialize::<AppConfig>(
+            config(
+                r#"
+sources:
+  - type: immich
+    instance:
+      url: "http://immich"
+      api_key: "key"
+    specs:
+      - type: memory-lane
+        weight: 2
+        persons: []
+"#,
+            ),
+            true,
+        )
+        .unwrap_err();
+        expect_that!(
+            format!("{error:#}"),
+            eq("Rejecting unknown field(s) sources.0.specs.0.persons")
+        );
+    }
+
+    #[gtest]
+    fn test_former_option_names_are_read() {
+        let app_config: AppConfig = deserialize(
+            config(
+                r#"
+sources:
+  - type: immich
+    instance:
+      url: "http://immich"
+      api_key: "key"
+    caption_date: file
+"#,
+            ),
+            true,
+        )
+        .unwrap();
+        expect_that!(
+            app_config.sources[0],
+            matches_pattern!(Source::Immich(matches_pattern!(ImmichSource {
+                taken_date: eq(TakenDate::File),
+                ..
+            })))
+        );
+    }
+
+    #[gtest]
+    fn test_tagged_enums_convert_strings() {
+        let settings: Settings = deserialize(
+            config(
+                r#"
+background:
+  type: blur
+  darken: "0.5"
+"#,
+            ),
+            true,
+        )
+        .unwrap();
+        expect_that!(
+            settings.background,
+            matches_pattern!(Background::Blur(matches_pattern!(BlurBackground {
+                darken: eq(0.5),
+                ..
+            })))
+        );
+    }
+
+    #[gtest]
+    fn test_other_errors_are_kept() {
+        let result = deserialize::<Settings>(config("display_duration: 12"), false);
+        expect_that!(result.is_err(), is_true());
+    }
+}
@@ -1,4 +1,5 @@
 pub mod app_config;
+pub mod lenient;
 pub mod settings;
 
 pub use self::{app_config::*, settings::*};
@@ -1,15 +1,19 @@
-use std::time::Duration;
+use std::{fmt::Write, str::FromStr, sync::Mutex, time::Duration};
 
+use anyhow::{Context, Error};
 use better_default::Default;
-use chrono::Locale;
+use chrono::{Locale, NaiveDate, NaiveTime};
+use log::warn;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use struct_patch::Patch;
 
+use super::lenient;
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct BlurSettings {
     #[default(6.0)]
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
@@ -17,12 +21,59 @@ pub struct BlurSettings {
     #[default(3)]
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
     pub passes: u8,
+    /// Blend the photo in linear light, so that the edges between bright and dark areas
+    /// are not darkened. Each sample costs a little more to decode. Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub gamma_correct: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct SharpenSettings {
+    /// Strength of the unsharp mask, 0 disables it. Values around 0.5 bring back details
+    /// softened by downscaling. Defaults to 0.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub amount: f32,
+}
+
+/// Zoom-in of each photo from 90% of its size, as it appears.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct IntroSettings {
+    /// Show the first photo, replacing the initial slide, right away at its full size.
+    /// The next ones still zoom in. Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub skip_first: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct ColorAdjustmentSettings {
+    /// Factor of the colors, below 1 darkens and above 1 brightens. Defaults to 1.
+    #[default(1.0)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub brightness: f32,
+    /// Factor of the differences to mid gray, 0 gives a flat gray. Defaults to 1.
+    #[default(1.0)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub contrast: f32,
+    /// Factor of the differences to the gray of same luminance, 0 gives grayscale.
+    /// Defaults to 1.
+    #[default(1.0)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub saturation: f32,
 }
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct Settings {
     /// The minimum amount of time that photos are displayed before switching to the next.
     ///
@@ -48,12 +99,41 @@ pub struct Settings {
     #[patch(attribute(serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")))]
     pub transition_duration: Duration,
 
+    /// Transitions picked at random between two photos.
+    /// Defaults to all of them.
+    /// Possible values are "dissolve", "ease-in-out" and "push".
+    #[default(vec![TransitionKind::Dissolve, TransitionKind::EaseInOut, TransitionKind::Push])]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub transitions: Vec<TransitionKind>,
+
+    /// Fade photos and captions at an even pace to the eye, instead of lingering nearly
+    /// invisible and popping in at the end, by applying a gamma curve to their opacity.
+    /// Defaults to true.
+    #[default(true)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub perceptual_fades: bool,
+
     /// The options for the initial slide.
     /// Defaults to a loading circle.
     /// Possible values are "empty" and "loading-circle".
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
     pub init_slide: InitSlideOptions,
 
+    /// The zoom-in of the photos as they appear.
+    #[patch(
+        name = "IntroSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub intro: IntroSettings,
+
+    /// A small spinner shows in the bottom right corner when the next photo is late by
+    /// more than this, so that a slow frame doesn't look frozen.
+    /// Defaults to 10 seconds ("10s"), "0s" disables it.
+    #[default(Duration::from_secs(10))]
+    #[serde(with = "humantime_serde")]
+    #[patch(attribute(serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")))]
+    pub overdue_indicator_delay: Duration,
+
     /// The options for the blur effect.
     #[patch(
         name = "BlurSettingsPatch",
@@ -61,6 +141,21 @@ pub struct Settings {
     )]
     pub blur_options: BlurSettings,
 
+    /// Sharpening of the photos, the blurred background is left as is.
+    #[patch(
+        name = "SharpenSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub sharpen: SharpenSettings,
+
+    /// Color correction of the images on screen, e.g. for rooms with varying light. Applied
+    /// by the GPU, so it works without a controllable backlight.
+    #[patch(
+        name = "ColorAdjustmentSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub color_adjustment: ColorAdjustmentSettings,
+
     /// The options for the background, aka the area around the photos when they don't fill the screen.
     /// Defaults to a blurred version of the photo.
     /// Possible values are "black" and "blur".
@@ -73,6 +168,18 @@ pub struct Settings {
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
     pub rotation: OrientationName,
 
+    /// Rotation of the display by any angle in degrees, e.g. 45 for a square display
+    /// mounted as a diamond. Replaces "rotation" when set. Photos are laid out in the
+    /// largest rectangle fitting the rotated display, the corners stay black.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub rotation_degrees: Option<f32>,
+
+    /// How the picture is drawn when the display is rotated.
+    /// Defaults to "direct".
+    /// Possible values are "direct" and "supersampled".
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub rotated_rendering: RotatedRendering,
+
     /// The options for the caption (photo information displayed at the bottom of the screen).
     #[patch(
         name = "CaptionOptionsPatch",
@@ -84,6 +191,100 @@ pub struct Settings {
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
     pub downscaled_image_filter: ImageFilter,
 
+    /// How photos are filtered by the GPU when drawn scaled, like during the zoom animation.
+    #[patch(
+        name = "TextureFilteringSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub texture_filtering: TextureFilteringSettings,
+
+    /// Draw each new photo once offscreen before showing it, for drivers flashing white or
+    /// black the first time a texture is drawn. Its transition then starts one frame later.
+    /// Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub warm_up_textures: bool,
+
+    /// Maximum time spent downloading a photo. When exceeded, the photo is skipped
+    /// for an hour and the next one is prepared instead.
+    /// Defaults to 60 seconds ("60s").
+    #[default(Duration::from_secs(60))]
+    #[serde(with = "humantime_serde")]
+    #[patch(attribute(serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")))]
+    pub preparation_timeout: Duration,
+
+    /// Photos whose textures are in GPU memory at once, the ones shown and the ones prepared
+    /// ahead. At the limit, the next photo is only prepared once the display is done with
+    /// a previous one. Useful on boards with little GPU memory. At least 2, the photo shown
+    /// and the next one, the photos of a leader frame are prepared on time regardless.
+    /// Defaults to 0, no limit.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub max_live_slides: u32,
+
+    /// Message displayed when sources work but none of them match any photo.
+    #[default("No photos match your configuration, check your search query".into())]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub no_photos_message: String,
+
+    /// Show a title card naming the album before its photos, whenever the album of a source
+    /// changes: sources taking turns don't show it before each photo. Only sources listing
+    /// albums know them. Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub show_album_headers: bool,
+
+    /// Show the photos of each batch in capture order, with a title card naming the day
+    /// before the photos of a new day, e.g. to relive a trip. Only Immich sources fetch
+    /// photos in batches. Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub group_by_day: bool,
+
+    /// Show a single photo all day, replaced at midnight, local time, instead of after
+    /// [`Settings::display_duration`]. Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub daily_photo: bool,
+
+    /// When the display is turned off, show a mosaic of the photos of the day before, for
+    /// [`Settings::display_off_recap_duration`]. Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub display_off_recap: bool,
+
+    /// How long the mosaic of [`Settings::display_off_recap`] stays on screen.
+    /// Defaults to 10 seconds ("10s").
+    #[default(Duration::from_secs(10))]
+    #[serde(with = "humantime_serde")]
+    #[patch(attribute(serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")))]
+    pub display_off_recap_duration: Duration,
+
+    /// Radius of the rounded corners of the photo, in pixels. The blurred background
+    /// keeps square corners. Defaults to 0, square corners.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub corner_radius: f32,
+
+    /// Soft shadow drawn behind the photo, over the blurred background.
+    #[patch(
+        name = "ShadowSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub shadow: ShadowSettings,
+
+    /// Zooms towards a face while the photo is displayed, instead of zooming out to the
+    /// whole photo. Only photos from Immich know their faces.
+    #[patch(
+        name = "PortraitFramingSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub portrait_framing: PortraitFramingSettings,
+
+    /// Logo drawn over the photos at all times, e.g. for a frame in a shop window.
+    #[patch(
+        name = "WatermarkSettingsPatch",
+        attribute(serde(skip_serializing_if = "Option::is_none"))
+    )]
+    pub watermark: WatermarkSettings,
+
+    /// Profile of the static configuration applied over these settings, empty for none.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub active_profile: String,
+
     /// The options for the debug overlay.
     #[patch(
         name = "DebugSettingsPatch",
@@ -92,8 +293,20 @@ pub struct Settings {
     pub debug: DebugSettings,
 }
 
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransitionKind {
+    /// The photos fade into each other.
+    Dissolve,
+    /// The photo fades out, then the next one fades in.
+    EaseInOut,
+    /// The photo grows while fading out, as if passed through, and the next one settles
+    /// from slightly oversized.
+    Push,
+}
+
 #[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
-#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 pub enum ImageFilter {
     Nearest,
     Triangle,
@@ -103,19 +316,172 @@ pub enum ImageFilter {
     Lanczos3,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct TextureFilteringSettings {
+    /// Filter used when sampling photos. Defaults to "linear".
+    /// Possible values are "linear" and "nearest".
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub photo: TextureFilter,
+
+    /// Anisotropic filtering level of photos, 1 disables it. Only used when the GPU supports
+    /// GL_EXT_texture_filter_anisotropic, and clamped to its maximum. Defaults to 1.
+    #[default(1)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub photo_anisotropy: u8,
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct DebugSettings {
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
     pub show_fps: bool,
+
+    /// Display color bars and a gradient instead of photos.
+    /// Useful during installation to check for dead pixels and color accuracy.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub test_pattern: bool,
+
+    /// Log levels, with the RUST_LOG syntax (e.g. "info,memocadre::gallery=trace").
+    /// Empty to use the RUST_LOG environment variable.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub log_filter: String,
+
+    /// Lower the quality of the blurred background of the next photos when frames take
+    /// too long to draw, first with fewer blur passes then without it, and restore it
+    /// once they are fast again.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub adaptive_quality: bool,
+}
+
+/// How much of the blurred background is prepared for the next photos.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum QualityLevel {
+    #[default]
+    Full,
+    /// A single blur pass.
+    FewerBlurPasses,
+    /// No blurred background, photos are shown over black.
+    NoBlur,
+}
+
+impl QualityLevel {
+    /// Lowers the settings of `settings` to this level.
+    pub fn apply(self, settings: &mut Settings) {
+        match self {
+            QualityLevel::Full => {}
+            QualityLevel::FewerBlurPasses => {
+                settings.blur_options.passes = settings.blur_options.passes.min(1);
+            }
+            QualityLevel::NoBlur => settings.background = Background::Black,
+        }
+    }
+
+    /// Next level when frames are too slow, `None` at the lowest.
+    pub fn lower(self) -> Option<Self> {
+        match self {
+            QualityLevel::Full => Some(QualityLevel::FewerBlurPasses),
+            QualityLevel::FewerBlurPasses => Some(QualityLevel::NoBlur),
+            QualityLevel::NoBlur => None,
+        }
+    }
+
+    /// Next level when frames are fast again, `None` at the highest.
+    pub fn raise(self) -> Option<Self> {
+        match self {
+            QualityLevel::Full => None,
+            QualityLevel::FewerBlurPasses => Some(QualityLevel::Full),
+            QualityLevel::NoBlur => Some(QualityLevel::FewerBlurPasses),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct PortraitFramingSettings {
+    /// Defaults to false.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub enabled: bool,
+    /// Share of the height of the photo on screen covered by the face once zoomed, from 0
+    /// to 1. Photos are zoomed in by 2.5 times at most. Defaults to 0.3.
+    #[default(0.3)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub face_size: f32,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct WatermarkSettings {
+    /// PNG image to draw, transparency is kept. Empty for none, the default.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub path: String,
+    /// Corner of the screen the logo is drawn in. Defaults to "bottom-right".
+    /// Possible values are "top-left", "top-right", "bottom-left" and "bottom-right".
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub position: WatermarkPosition,
+    /// From 0 (invisible) to 1 (as is). Defaults to 0.5.
+    #[default(0.5)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub opacity: f32,
+    /// Largest side of the logo, as a fraction of the smaller side of the screen. Larger
+    /// logos are scaled down, smaller ones are left as is. Defaults to 0.15.
+    #[default(0.15)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub max_size_fraction: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
+#[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
+#[patch(attribute(serde(default)))]
+#[serde(default)]
+pub struct ShadowSettings {
+    /// Defaults to false. Never drawn on a black background.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub enabled: bool,
+    /// Width of the soft edge of the shadow, in pixels. Defaults to 24.
+    #[default(24.0)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub blur: f32,
+    /// From 0 (invisible) to 1 (black). Defaults to 0.6.
+    #[default(0.6)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub opacity: f32,
+    /// Offset of the shadow from the photo, in pixels, right and down. Defaults to [0, 8].
+    #[default([0.0, 8.0])]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub offset: [f32; 2],
 }
 
 #[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct CaptionOptions {
     /// Whether the caption is enabled.
     #[default(true)]
@@ -133,29 +499,301 @@ pub struct CaptionOptions {
     #[default(28.)]
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
     pub font_size: f32,
+
+    /// Local hours during which the caption is shown, e.g. "08:00-22:00".
+    /// The range may wrap around midnight ("22:00-06:00").
+    /// Defaults to always shown.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub hours: Option<TimeRange>,
+
+    /// Moves the caption to the top or a corner when it would hide a face.
+    /// Faces are only known for Immich photos.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub avoid_faces: bool,
+
+    /// Static text added as the last line of every caption, like "The Smith Family".
+    /// Shown even on photos without city nor date.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub label: Option<String>,
+
+    /// What is drawn behind the caption to keep it legible: "box" (a dark rounded box),
+    /// "scrim" (a dark gradient across the screen from its edge) or "none".
+    /// Defaults to "box".
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub background_style: CaptionBackground,
+
+    /// Widest the caption may be, in percent of the screen width. Longer lines wrap,
+    /// centered. Defaults to 80.
+    #[default(80.)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub max_width: f32,
+
+    /// Date shown in the caption: "taken" (when the photo was taken) or "added" (when it
+    /// was uploaded, only known for Immich photos). Defaults to "taken".
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub date_source: CaptionDateSource,
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionDateSource {
+    #[default]
+    Taken,
+    Added,
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionBackground {
+    #[default]
+    Box,
+    Scrim,
+    None,
+}
+
+impl CaptionOptions {
+    pub fn is_shown_at(&self, time: NaiveTime) -> bool {
+        self.enabled && self.hours.is_none_or(|hours| hours.contains(time))
+    }
+
+    /// Whether the caption changes while the photo is on screen, see
+    /// [`DateFormat::relative`].
+    pub fn is_time_sensitive(&self) -> bool {
+        self.enabled && self.date_format.relative
+    }
+}
+
+/// A daily time window, written as "HH:MM-HH:MM".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeRange {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for TimeRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .context("Expected a range formatted as \"HH:MM-HH:MM\"")?;
+        let parse = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time {t:?}, expected HH:MM"))
+        };
+        Ok(TimeRange {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl Serialize for TimeRange {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        ser.serialize_str(&format!(
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        ))
+    }
+}
+
+impl<'d> Deserialize<'d> for TimeRange {
+    fn deserialize<D>(deser: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'d>,
+    {
+        let s = String::deserialize(deser)?;
+        s.parse()
+            .map_err(|e| serde::de::Error::custom(format!("Invalid time range: {:#}", e)))
+    }
 }
 
 #[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct DateFormat {
     /// The format of the date in the caption.
     /// Defaults to "%A, %e. %B %Y".
     /// See https://docs.rs/chrono/0.4.39/chrono/format/strftime/index.html for more information.
     #[default("%A, %e. %B %Y".into())]
-    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    #[serde(deserialize_with = "deserialize_date_format")]
+    #[patch(attribute(serde(
+        deserialize_with = "deserialize_date_format_patch",
+        skip_serializing_if = "Option::is_none"
+    )))]
     pub format: String,
 
-    /// The locale to use for the date.
+    /// The locale to use for the date, or a list of them, e.g. ["fr_FR", "de_DE"].
     /// Defaults to "en_US".
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
-    pub locale: ConfigLocale,
+    pub locale: ConfigLocales,
+
+    /// How the date is shown with several locales.
+    /// Defaults to "alternate".
+    /// Possible values are "alternate" (one locale per photo, in turn) and "all" (one line
+    /// per locale).
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub mode: LocaleMode,
+
+    /// Shows how long ago the photo was taken instead, like "3 years ago", in English.
+    /// Kept up to date while the photo is on screen.
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub relative: bool,
+}
+
+/// Rejects formats that cannot format a date, chrono would fail on each caption otherwise.
+fn check_date_format(format: &str) -> Result<(), String> {
+    let sample = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+    let mut formatted = String::new();
+    write!(
+        formatted,
+        "{}",
+        sample.format_localized(format, Locale::en_US)
+    )
+    .map_err(|_| format!("Invalid date format {format:?}: unknown or time specifier"))
+}
+
+fn deserialize_date_format<'d, D>(deser: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'d>,
+{
+    let format = String::deserialize(deser)?;
+    check_date_format(&format).map_err(serde::de::Error::custom)?;
+    Ok(format)
+}
+
+fn deserialize_date_format_patch<'d, D>(deser: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'d>,
+{
+    deserialize_date_format(deser).map(Some)
+}
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocaleMode {
+    #[default]
+    Alternate,
+    All,
+}
+
+/// One or more locales, never empty. A single locale is written as a string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigLocales(pub Vec<ConfigLocale>);
+
+impl ConfigLocales {
+    /// The first locale, used where only one fits.
+    pub fn first(&self) -> &ConfigLocale {
+        &self.0[0]
+    }
+}
+
+impl Default for ConfigLocales {
+    fn default() -> Self {
+        ConfigLocales(vec![ConfigLocale::default()])
+    }
+}
+
+impl Serialize for ConfigLocales {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.0.as_slice() {
+            [locale] => locale.serialize(ser),
+            locales => locales.serialize(ser),
+        }
+    }
+}
+
+impl<'d> Deserialize<'d> for ConfigLocales {
+    fn deserialize<D>(deser: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'d>,
+    {
+        // Parsed afterwards, untagged enums hide the errors of their variants
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(String),
+            Many(Vec<String>),
+        }
+        let locales = match OneOrMany::deserialize(deser)? {
+            OneOrMany::One(locale) => vec![locale],
+            OneOrMany::Many(locales) if locales.is_empty() => {
+                return Err(serde::de::Error::custom(
+                    "Invalid locale: at least one is needed",
+                ))
+            }
+            OneOrMany::Many(locales) => locales,
+        };
+        locales
+            .iter()
+            .map(|locale| ConfigLocale::parse(locale))
+            .collect::<Result<_, _>>()
+            .map(ConfigLocales)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConfigLocale(pub Locale);
 
+/// Locales already reported as missing month names.
+static LOCALES_WITHOUT_MONTHS: Mutex<Vec<Locale>> = Mutex::new(Vec::new());
+
+impl ConfigLocale {
+    fn parse<E: serde::de::Error>(s: &str) -> Result<Self, E> {
+        s.parse()
+            .map(ConfigLocale)
+            .map_err(|e| E::custom(format!("Invalid locale: {:?}", e)))
+    }
+
+    /// Locale to format dates with. Falls back to en_US when chrono has no month names for
+    /// this one, with a warning the first time.
+    pub fn for_dates(&self) -> Locale {
+        Self::date_locale(self.0, has_month_names(self.0))
+    }
+
+    fn date_locale(locale: Locale, has_month_names: bool) -> Locale {
+        if has_month_names {
+            return locale;
+        }
+        let mut reported = LOCALES_WITHOUT_MONTHS.lock().unwrap();
+        if !reported.contains(&locale) {
+            warn!("No month names for locale {locale}, dates are shown in en_US");
+            reported.push(locale);
+        }
+        Locale::en_US
+    }
+}
+
+fn has_month_names(locale: Locale) -> bool {
+    (1..=12).all(|month| {
+        NaiveDate::from_ymd_opt(2000, month, 1).is_some_and(|date| {
+            !date
+                .format_localized("%B", locale)
+                .to_string()
+                .trim()
+                .is_empty()
+        })
+    })
+}
+
 impl Default for ConfigLocale {
     fn default() -> Self {
         ConfigLocale(Locale::en_US)
@@ -176,43 +814,174 @@ impl<'d> Deserialize<'d> for ConfigLocale {
     where
         D: Deserializer<'d>,
     {
-        let s = String::deserialize(deser)?;
-        s.parse()
-            .map(ConfigLocale)
-            .map_err(|e| serde::de::Error::custom(format!("Invalid locale: {:?}", e)))
+        ConfigLocale::parse(&String::deserialize(deser)?)
     }
 }
 
-#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
-#[serde(deny_unknown_fields, tag = "type", rename_all = "kebab-case")]
+#[derive(Serialize, Default, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
 pub enum Background {
     Black,
     #[default]
     Blur(BlurBackground),
 }
 
+impl<'de> Deserialize<'de> for Background {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        lenient::tagged(
+            deserializer,
+            &["black", "blur"],
+            |kind, fields| match kind {
+                "blur" => fields.parse().map(Self::Blur),
+                _ => fields.none().map(|()| Self::Black),
+            },
+        )
+    }
+}
+
 #[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct BlurBackground {
-    #[default(50)]
+    /// Free space around the photo above which it's filled with the blurred photo rather
+    /// than left black, in pixels (50) or as a percentage of the screen side ("10%").
+    /// Defaults to 50 pixels.
+    #[default(FreeSpace::Pixels(50))]
     #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
-    pub min_free_space: u16,
+    pub min_free_space: FreeSpace,
+    /// Darken the blurred background, from 0 (unchanged) to 1 (black).
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub darken: f32,
+    /// Desaturate the blurred background, from 0 (unchanged) to 1 (grayscale).
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub desaturate: f32,
+    /// Photos leaving less free space than this, in percent of the screen side, are
+    /// stretched to fill the screen rather than surrounded by thin strips.
+    /// Defaults to 2.
+    #[default(2.0)]
+    #[patch(attribute(serde(skip_serializing_if = "Option::is_none")))]
+    pub snap_tolerance: f32,
+}
+
+/// A length in pixels, or relative to a side of the screen when written as "10%".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreeSpace {
+    Pixels(u16),
+    Percent(f32),
+}
+
+impl FreeSpace {
+    /// Length in pixels, percentages being relative to `side`.
+    pub fn to_pixels(self, side: f32) -> f32 {
+        match self {
+            FreeSpace::Pixels(pixels) => pixels as f32,
+            FreeSpace::Percent(percent) => side * percent / 100.,
+        }
+    }
+}
+
+impl FromStr for FreeSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => {
+                let percent: f32 = percent
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid percentage {s:?}"))?;
+                anyhow::ensure!(
+                    (0. ..=100.).contains(&percent),
+                    "Percentage {s:?} is not between 0% and 100%"
+                );
+                Ok(FreeSpace::Percent(percent))
+            }
+            None => s
+                .strip_suffix("px")
+                .unwrap_or(s)
+                .trim()
+                .parse()
+                .map(FreeSpace::Pixels)
+                .with_context(|| format!("Invalid length {s:?}, expected pixels or \"10%\"")),
+        }
+    }
+}
+
+impl Serialize for FreeSpace {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            FreeSpace::Pixels(pixels) => ser.serialize_u16(*pixels),
+            FreeSpace::Percent(percent) => ser.serialize_str(&format!("{percent}%")),
+        }
+    }
+}
+
+impl<'d> Deserialize<'d> for FreeSpace {
+    fn deserialize<D>(deser: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'d>,
+    {
+        struct FreeSpaceVisitor;
+
+        impl serde::de::Visitor<'_> for FreeSpaceVisitor {
+            type Value = FreeSpace;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a number of pixels or a percentage like \"10%\"")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<FreeSpace, E> {
+                u16::try_from(v)
+                    .map(FreeSpace::Pixels)
+                    .map_err(|_| E::custom(format!("{v} pixels is too large")))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<FreeSpace, E> {
+                u16::try_from(v)
+                    .map(FreeSpace::Pixels)
+                    .map_err(|_| E::custom(format!("Invalid number of pixels {v}")))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<FreeSpace, E> {
+                v.parse()
+                    .map_err(|e| E::custom(format!("Invalid free space: {:#}", e)))
+            }
+        }
+
+        deser.deserialize_any(FreeSpaceVisitor)
+    }
 }
 
-#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
-#[serde(deny_unknown_fields, tag = "type", rename_all = "kebab-case")]
+#[derive(Serialize, Default, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
 pub enum InitSlideOptions {
     Empty,
     #[default]
     LoadingCircle(LoadingCircleOptions),
 }
 
+impl<'de> Deserialize<'de> for InitSlideOptions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        lenient::tagged(
+            deserializer,
+            &["empty", "loading-circle"],
+            |kind, fields| match kind {
+                "loading-circle" => fields.parse().map(Self::LoadingCircle),
+                _ => fields.none().map(|()| Self::Empty),
+            },
+        )
+    }
+}
+
 #[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Patch)]
 #[patch(attribute(derive(Debug, Default, Deserialize, Serialize, Clone)))]
 #[patch(attribute(serde(default)))]
-#[serde(deny_unknown_fields, default)]
+#[serde(default)]
 pub struct LoadingCircleOptions {
     /// Number of rotations per second for the circle.
     #[default(1.5)]
@@ -221,7 +990,6 @@ pub struct LoadingCircleOptions {
 }
 
 #[derive(Clone, Copy, Deserialize_repr, Debug, Default, PartialEq, Serialize_repr)]
-#[serde(deny_unknown_fields)]
 #[repr(u16)]
 pub enum OrientationName {
     #[default]
@@ -230,3 +998,161 @@ pub enum OrientationName {
     Angle180 = 180,
     Angle270 = 270,
 }
+
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RotatedRendering {
+    /// Drawn straight to the screen with a rotated projection, which costs nothing. Right
+    /// angle rotations map pixels exactly, and multisampling of the display surface, when
+    /// available, applies as without rotation.
+    #[default]
+    Direct,
+    /// Drawn into an offscreen texture twice the size of the screen, then downscaled to
+    /// it rotated with linear filtering. Smooths diagonal edges and text, at the cost of
+    /// four times the pixels to draw and a texture of 12 bytes per screen pixel.
+    Supersampled,
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Locale, NaiveTime};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true},
+    };
+
+    use super::{
+        Background, ConfigLocale, DateFormat, DateFormatPatch, FreeSpace, LocaleMode, QualityLevel,
+        Settings, TimeRange,
+    };
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[gtest]
+    fn test_time_range_parse() {
+        let range: TimeRange = "08:00-22:30".parse().unwrap();
+        expect_that!(range.start, eq(time(8, 0)));
+        expect_that!(range.end, eq(time(22, 30)));
+        expect_that!("08:00".parse::<TimeRange>().is_err(), is_true());
+        expect_that!("8h-22h".parse::<TimeRange>().is_err(), is_true());
+    }
+
+    #[gtest]
+    fn test_time_range_contains() {
+        let range: TimeRange = "08:00-22:00".parse().unwrap();
+        expect_that!(range.contains(time(8, 0)), is_true());
+        expect_that!(range.contains(time(21, 59)), is_true());
+        expect_that!(range.contains(time(22, 0)), is_false());
+        expect_that!(range.contains(time(3, 0)), is_false());
+    }
+
+    #[gtest]
+    fn test_time_range_wraps_midnight() {
+        let range: TimeRange = "22:00-06:00".parse().unwrap();
+        expect_that!(range.contains(time(23, 0)), is_true());
+        expect_that!(range.contains(time(5, 59)), is_true());
+        expect_that!(range.contains(time(6, 0)), is_false());
+        expect_that!(range.contains(time(12, 0)), is_false());
+    }
+
+    #[gtest]
+    fn test_date_locales_deserialize() {
+        let single: DateFormat = serde_json::from_str(r#"{"locale": "fr_FR"}"#).unwrap();
+        expect_that!(single.locale.0, eq(&vec![ConfigLocale(Locale::fr_FR)]));
+        expect_that!(single.mode, eq(LocaleMode::Alternate));
+        expect_that!(
+            serde_json::to_value(&single).unwrap()["locale"],
+            eq(&serde_json::json!("fr_FR"))
+        );
+
+        let many: DateFormat =
+            serde_json::from_str(r#"{"locale": ["fr_FR", "de_DE"], "mode": "all"}"#).unwrap();
+        expect_that!(
+            many.locale.0,
+            eq(&vec![
+                ConfigLocale(Locale::fr_FR),
+                ConfigLocale(Locale::de_DE)
+            ])
+        );
+        expect_that!(many.mode, eq(LocaleMode::All));
+
+        let invalid = serde_json::from_str::<DateFormat>(r#"{"locale": ["fr_FR", "xx_XX"]}"#);
+        expect_that!(
+            invalid.unwrap_err().to_string().contains("Invalid locale"),
+            is_true()
+        );
+        let empty = serde_json::from_str::<DateFormat>(r#"{"locale": []}"#);
+        expect_that!(empty.is_err(), is_true());
+    }
+
+    #[gtest]
+    fn test_invalid_date_format_rejected() {
+        let valid: DateFormat = serde_json::from_str(r#"{"format": "%d/%m/%Y"}"#).unwrap();
+        expect_that!(valid.format, eq("%d/%m/%Y"));
+        expect_that!(DateFormat::default().format, eq("%A, %e. %B %Y"));
+
+        let invalid = serde_json::from_str::<DateFormat>(r#"{"format": "%A %Q"}"#);
+        expect_that!(
+            invalid
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid date format"),
+            is_true()
+        );
+        // A date has no hours
+        let time = serde_json::from_str::<DateFormat>(r#"{"format": "%H:%M"}"#);
+        expect_that!(time.is_err(), is_true());
+        let patch = serde_json::from_str::<DateFormatPatch>(r#"{"format": "%Q"}"#);
+        expect_that!(patch.is_err(), is_true());
+        let empty_patch: DateFormatPatch = serde_json::from_str("{}").unwrap();
+        expect_that!(empty_patch.format, eq(&None));
+    }
+
+    #[gtest]
+    fn test_date_locale_without_month_names_falls_back() {
+        expect_that!(ConfigLocale(Locale::de_DE).for_dates(), eq(Locale::de_DE));
+        expect_that!(
+            ConfigLocale::date_locale(Locale::de_DE, false),
+            eq(Locale::en_US)
+        );
+    }
+
+    #[gtest]
+    fn test_free_space_parse() {
+        expect_that!(
+            "10%".parse::<FreeSpace>().unwrap(),
+            eq(FreeSpace::Percent(10.))
+        );
+        expect_that!(
+            "50".parse::<FreeSpace>().unwrap(),
+            eq(FreeSpace::Pixels(50))
+        );
+        expect_that!(
+            "50px".parse::<FreeSpace>().unwrap(),
+            eq(FreeSpace::Pixels(50))
+        );
+        expect_that!("150%".parse::<FreeSpace>().is_err(), is_true());
+        expect_that!("wide".parse::<FreeSpace>().is_err(), is_true());
+    }
+
+    #[gtest]
+    fn test_free_space_deserialize() {
+        let pixels: FreeSpace = serde_json::from_str("50").unwrap();
+        expect_that!(pixels, eq(FreeSpace::Pixels(50)));
+        let percent: FreeSpace = serde_json::from_str(r#""12.5%""#).unwrap();
+        expect_that!(percent, eq(FreeSpace::Percent(12.5)));
+        expect_that!(percent.to_pixels(800.), eq(100.));
+        expect_that!(serde_json::to_string(&percent).unwrap(), eq(r#""12.5%""#));
+    }
+
+    #[gtest]
+    fn test_apply_quality_levels() {
+        let mut settings = Settings::default();
+        QualityLevel::FewerBlurPasses.apply(&mut settings);
+        expect_that!(settings.blur_options.passes, eq(1));
+        QualityLevel::NoBlur.apply(&mut settings);
+        expect_that!(settings.background, eq(Background::Black));
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use anyhow::{Context, Error, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use image::{DynamicImage, Rgb, RgbImage};
+
+use super::{Gallery, GalleryProvider, GeoPoint, ImageDetails, ImageWithDetails};
+use crate::random;
+
+const ID_PREFIX: &str = "demo:";
+/// Size of the demo photos, 3:2 like most cameras, resized by the worker like any photo.
+const WIDTH: u32 = 1500;
+const HEIGHT: u32 = 1000;
+
+/// Built-in landscapes shown without any server, to try the frame out or test the
+/// rendering. They are drawn when fetched and cycle in order.
+pub struct DemoGalleryProvider {
+    next: usize,
+}
+
+/// Landscape drawn for a demo photo, with the details shown in its caption.
+struct DemoPhoto {
+    city: &'static str,
+    country: &'static str,
+    gps: (f64, f64),
+    date: (i32, u32, u32),
+    /// Colors of the sky at the top of the photo and at the horizon.
+    sky: [[u8; 3]; 2],
+    /// Position of the sun, as fractions of the width and height.
+    sun: (f32, f32),
+    /// Colors of the hills, from the farthest to the nearest.
+    hills: [[u8; 3]; 3],
+    /// Shifts the ridges of the hills, so that each landscape has its own.
+    phase: f32,
+}
+
+const DEMO_PHOTOS: [DemoPhoto; 5] = [
+    DemoPhoto {
+        city: "Étretat",
+        country: "FR",
+        gps: (49.7069, 0.2052),
+        date: (2023, 7, 14),
+        sky: [[70, 130, 200], [200, 225, 240]],
+        sun: (0.75, 0.2),
+        hills: [[120, 150, 170], [70, 120, 90], [40, 80, 50]],
+        phase: 0.,
+    },
+    DemoPhoto {
+        city: "Chamonix",
+        country: "FR",
+        gps: (45.9237, 6.8694),
+        date: (2024, 2, 10),
+        sky: [[40, 90, 170], [180, 210, 235]],
+        sun: (0.2, 0.15),
+        hills: [[230, 235, 245], [150, 160, 180], [60, 80, 90]],
+        phase: 1.3,
+    },
+    DemoPhoto {
+        city: "Bordeaux",
+        country: "FR",
+        gps: (44.8378, -0.5792),
+        date: (2022, 9, 23),
+        sky: [[90, 60, 130], [250, 170, 90]],
+        sun: (0.5, 0.5),
+        hills: [[150, 90, 110], [90, 60, 80], [40, 30, 50]],
+        phase: 2.1,
+    },
+    DemoPhoto {
+        city: "Biarritz",
+        country: "FR",
+        gps: (43.4832, -1.5586),
+        date: (2023, 8, 5),
+        sky: [[30, 110, 190], [160, 220, 240]],
+        sun: (0.35, 0.25),
+        hills: [[40, 120, 170], [30, 90, 140], [210, 190, 140]],
+        phase: 3.7,
+    },
+    DemoPhoto {
+        city: "Colmar",
+        country: "FR",
+        gps: (48.0794, 7.3585),
+        date: (2021, 12, 18),
+        sky: [[30, 40, 80], [220, 140, 120]],
+        sun: (0.65, 0.45),
+        hills: [[110, 90, 120], [70, 60, 90], [35, 35, 55]],
+        phase: 5.2,
+    },
+];
+
+impl DemoGalleryProvider {
+    pub fn new() -> Self {
+        Self { next: 0 }
+    }
+
+    fn fetch(&self, index: usize) -> ImageWithDetails {
+        let photo = &DEMO_PHOTOS[index];
+        let (year, month, day) = photo.date;
+        ImageWithDetails {
+            image: DynamicImage::ImageRgb8(photo.draw()),
+            details: ImageDetails {
+                asset_id: Some(format!("{ID_PREFIX}{index}")),
+                album: None,
+                starts_album: false,
+                city: Some(photo.city.into()),
+                country: Some(photo.country.into()),
+                gps: Some(GeoPoint {
+                    latitude: photo.gps.0,
+                    longitude: photo.gps.1,
+                }),
+                date: Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).single(),
+                people: Vec::new(),
+            },
+            low_res: false,
+            background_image: None,
+            preparation: Default::default(),
+        }
+    }
+}
+
+impl DemoPhoto {
+    fn draw(&self) -> RgbImage {
+        let width = WIDTH as f32;
+        let height = HEIGHT as f32;
+        // Height of the ridge of each hill, for each column
+        let ridges: Vec<Vec<f32>> = (0..self.hills.len())
+            .map(|layer| {
+                let layer = layer as f32;
+                let base = height * (0.55 + 0.13 * layer);
+                let amplitude = height * (0.08 - 0.015 * layer);
+                let frequency = (1.5 + layer) / width * std::f32::consts::TAU;
+                (0..WIDTH)
+                    .map(|x| {
+                        let x = x as f32 * frequency + self.phase * (layer + 1.);
+                        base - amplitude * (0.6 * x.sin() + 0.4 * (2.3 * x + 1.).sin())
+                    })
+                    .collect()
+            })
+            .collect();
+        let horizon = ridges[0].iter().copied().fold(height, f32::min);
+        let sun = (self.sun.0 * width, self.sun.1 * height);
+        let sun_radius = height * 0.06;
+        RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+            let near_hill = ridges
+                .iter()
+                .rposition(|ridge| y as f32 >= ridge[x as usize]);
+            if let Some(layer) = near_hill {
+                return Rgb(self.hills[layer]);
+            }
+            let mut color = mix(self.sky[0], self.sky[1], y as f32 / horizon);
+            let distance = (x as f32 - sun.0).hypot(y as f32 - sun.1);
+            let glow = if distance <= sun_radius {
+                1.
+            } else {
+                0.6 * (sun_radius / distance).powi(2)
+            };
+            color = mix(color, [255, 240, 200], glow);
+            Rgb(color)
+        })
+    }
+}
+
+/// Color between `from` and `to`, `amount` being clamped to 0..1.
+fn mix(from: [u8; 3], to: [u8; 3], amount: f32) -> [u8; 3] {
+    let amount = amount.clamp(0., 1.);
+    std::array::from_fn(|channel| {
+        let from = from[channel] as f32;
+        let to = to[channel] as f32;
+        (from + (to - from) * amount).round() as u8
+    })
+}
+
+impl Gallery for DemoGalleryProvider {
+    fn get_next_image(&mut self, _timeout: Duration) -> Result<ImageWithDetails> {
+        let index = self.next;
+        self.next = (self.next + 1) % DEMO_PHOTOS.len();
+        Ok(self.fetch(index))
+    }
+
+    fn get_image_by_id(&mut self, id: &str, _timeout: Duration) -> Result<ImageWithDetails> {
+        let index = id
+            .strip_prefix(ID_PREFIX)
+            .and_then(|index| index.parse::<usize>().ok())
+            .filter(|&index| index < DEMO_PHOTOS.len())
+            .with_context(|| format!("Image {id} is not a demo photo"))?;
+        Ok(self.fetch(index))
+    }
+}
+
+impl GalleryProvider for DemoGalleryProvider {
+    fn is_retryable(&self, _error: &Error) -> bool {
+        false
+    }
+
+    fn randomize_start(&mut self) {
+        self.next = random::index(DEMO_PHOTOS.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_true, some},
+    };
+    use image::GenericImageView;
+
+    use super::{DemoGalleryProvider, DEMO_PHOTOS};
+    use crate::gallery::Gallery;
+
+    #[gtest]
+    fn test_demo_photos_cycle_in_order() {
+        let mut demo = DemoGalleryProvider::new();
+        let first = demo.get_next_image(Duration::ZERO).unwrap();
+        let ids: Vec<String> = (0..DEMO_PHOTOS.len())
+            .map(|_| {
+                let photo = demo.get_next_image(Duration::ZERO).unwrap();
+                photo.details.asset_id.unwrap()
+            })
+            .collect();
+        expect_that!(
+            ids,
+            eq(&vec!["demo:1", "demo:2", "demo:3", "demo:4", "demo:0"])
+        );
+
+        // Drawn the same each time
+        let again = demo.get_image_by_id("demo:0", Duration::ZERO).unwrap();
+        expect_that!(first.image.dimensions(), eq((1500, 1000)));
+        expect_that!(first.image == again.image, is_true());
+        expect_that!(first.details.city, some(eq("Étretat")));
+        expect_that!(
+            first.details.date,
+            some(eq(Utc.with_ymd_and_hms(2023, 7, 14, 12, 0, 0).unwrap()))
+        );
+    }
+
+    #[gtest]
+    fn test_demo_photo_by_id() {
+        let mut demo = DemoGalleryProvider::new();
+        let photo = demo.get_image_by_id("demo:2", Duration::ZERO).unwrap();
+        expect_that!(photo.details.city, some(eq("Bordeaux")));
+        expect_that!(
+            demo.get_image_by_id("demo:5", Duration::ZERO).is_err(),
+            is_true()
+        );
+        expect_that!(
+            demo.get_image_by_id("https://example.com/a.jpg", Duration::ZERO)
+                .is_err(),
+            is_true()
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    time::Instant,
+};
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+
+use super::{GeoPoint, ImageDetails};
+use crate::configuration::ReverseGeocodingConfig;
+
+const EARTH_RADIUS_KM: f64 = 6371.;
+/// Length of a degree of latitude.
+const KM_PER_DEGREE: f64 = 111.2;
+
+/// Completes the details of images once fetched, before they are displayed.
+pub trait ImageDetailsEnricher: Send {
+    fn enrich(&self, details: &mut ImageDetails);
+}
+
+/// Builds the enrichers enabled in the configuration, loading their data.
+pub fn build_enrichers(
+    reverse_geocoding: Option<&ReverseGeocodingConfig>,
+) -> Result<Vec<Box<dyn ImageDetailsEnricher>>> {
+    let mut enrichers: Vec<Box<dyn ImageDetailsEnricher>> = Vec::new();
+    if let Some(config) = reverse_geocoding {
+        let start = Instant::now();
+        let file = File::open(&config.path)
+            .with_context(|| format!("Cannot open cities file {}", config.path.display()))?;
+        let geocoder =
+            ReverseGeocoder::from_reader(BufReader::new(file), config.max_distance_km)
+                .with_context(|| format!("Cannot read cities file {}", config.path.display()))?;
+        info!(
+            "Loaded {} cities for reverse geocoding in {:?}",
+            geocoder.cities.len(),
+            start.elapsed()
+        );
+        enrichers.push(Box::new(geocoder));
+    }
+    Ok(enrichers)
+}
+
+struct City {
+    name: String,
+    country: String,
+    location: GeoPoint,
+}
+
+/// Names the city closest to the GPS coordinates of images, from an offline list of cities.
+pub struct ReverseGeocoder {
+    cities: Vec<City>,
+    /// Indices of the cities in each cell of one degree, by floored latitude and longitude.
+    grid: HashMap<(i16, i16), Vec<usize>>,
+    max_distance_km: f64,
+}
+
+impl ReverseGeocoder {
+    /// Reads a GeoNames cities file (e.g. cities1000.txt): tab separated, with the name in
+    /// the 2nd column, the latitude and longitude in the 5th and 6th and the country code
+    /// in the 9th.
+    pub fn from_reader(reader: impl BufRead, max_distance_km: f64) -> Result<Self> {
+        let mut cities = Vec::new();
+        let mut grid: HashMap<_, Vec<_>> = HashMap::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.context("Cannot read line")?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let city = Self::parse_city(&line).with_context(|| format!("Line {}", number + 1))?;
+            grid.entry(cell(&city.location))
+                .or_default()
+                .push(cities.len());
+            cities.push(city);
+        }
+        Ok(Self {
+            cities,
+            grid,
+            max_distance_km,
+        })
+    }
+
+    fn parse_city(line: &str) -> Result<City> {
+        let columns: Vec<_> = line.split('\t').collect();
+        let column = |index: usize| {
+            columns
+                .get(index)
+                .copied()
+                .with_context(|| format!("Missing column {}", index + 1))
+        };
+        Ok(City {
+            name: column(1)?.to_owned(),
+            country: column(8)?.to_owned(),
+            location: GeoPoint {
+                latitude: column(4)?.parse().context("Invalid latitude")?,
+                longitude: column(5)?.parse().context("Invalid longitude")?,
+            },
+        })
+    }
+
+    fn nearest(&self, point: &GeoPoint) -> Option<&City> {
+        let (lat_cell, lon_cell) = cell(point);
+        let lat_cells = (self.max_distance_km / KM_PER_DEGREE).ceil() as i32;
+        // Degrees of longitude shrink towards the poles
+        let lon_km = KM_PER_DEGREE * point.latitude.to_radians().cos().max(0.01);
+        let lon_cells = ((self.max_distance_km / lon_km).ceil() as i32).min(180);
+        let cells = (-lat_cells..=lat_cells).flat_map(|lat| {
+            (-lon_cells..=lon_cells).map(move |lon| {
+                let lon = (lon_cell as i32 + lon + 180).rem_euclid(360) - 180;
+                (lat_cell + lat as i16, lon as i16)
+            })
+        });
+        cells
+            .filter_map(|cell| self.grid.get(&cell))
+            .flatten()
+            .map(|&index| {
+                let city = &self.cities[index];
+                (city, distance_km(point, &city.location))
+            })
+            .filter(|(_, distance)| *distance <= self.max_distance_km)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(city, _)| city)
+    }
+}
+
+impl ImageDetailsEnricher for ReverseGeocoder {
+    fn enrich(&self, details: &mut ImageDetails) {
+        if details.city.is_some() && details.country.is_some() {
+            return;
+        }
+        let Some(point) = &details.gps else {
+            return;
+        };
+        match self.nearest(point) {
+            Some(city) => {
+                details.city.get_or_insert_with(|| city.name.clone());
+                details.country.get_or_insert_with(|| city.country.clone());
+            }
+            None => debug!("No city within {} km of {:?}", self.max_distance_km, point),
+        }
+    }
+}
+
+fn cell(point: &GeoPoint) -> (i16, i16) {
+    (
+        point.latitude.floor() as i16,
+        point.longitude.floor() as i16,
+    )
+}
+
+/// Great-circle distance, with the haversine formula.
+fn distance_km(a: &GeoPoint, b: &GeoPoint) -> f64 {
+    let d_lat = (b.latitude - a.latitude).to_radians();
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.).sin().powi(2)
+        + a.latitude.to_radians().cos()
+            * b.latitude.to_radians().cos()
+            * (d_lon / 2.).sin().powi(2);
+    2. * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+#[cfg(test)]
+mod test {
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, none, some},
+    };
+
+    use super::{ImageDetailsEnricher, ReverseGeocoder};
+    use crate::gallery::{GeoPoint, ImageDetails};
+
+    const CITIES: &str = "\
+2988507\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR
+2995469\tMarseille\tMarseille\t\t43.29695\t5.38107\tP\tPPLA\tFR
+2643743\tLondon\tLondon\t\t51.50853\t-0.12574\tP\tPPLC\tGB
+2193733\tAuckland\tAuckland\t\t-36.84853\t174.76349\tP\tPPLA\tNZ
+4032402\tApia\tApia\t\t-13.83333\t-171.76666\tP\tPPLC\tWS
+";
+
+    fn geocoder() -> ReverseGeocoder {
+        ReverseGeocoder::from_reader(CITIES.as_bytes(), 100.).unwrap()
+    }
+
+    fn details(gps: Option<(f64, f64)>) -> ImageDetails {
+        ImageDetails {
+            asset_id: None,
+            album: None,
+            starts_album: false,
+            city: None,
+            country: None,
+            gps: gps.map(|(latitude, longitude)| GeoPoint {
+                latitude,
+                longitude,
+            }),
+            date: None,
+            people: Vec::new(),
+        }
+    }
+
+    #[gtest]
+    fn test_nearest_city() {
+        let geocoder = geocoder();
+
+        // Versailles
+        let mut versailles = details(Some((48.8049, 2.1204)));
+        geocoder.enrich(&mut versailles);
+        expect_that!(versailles.city, some(eq("Paris")));
+        expect_that!(versailles.country, some(eq("FR")));
+
+        // Avignon, in another cell than Marseille
+        let mut avignon = details(Some((43.9493, 4.8055)));
+        geocoder.enrich(&mut avignon);
+        expect_that!(avignon.city, some(eq("Marseille")));
+    }
+
+    #[gtest]
+    fn test_nearest_city_across_antimeridian() {
+        let geocoder = ReverseGeocoder::from_reader(CITIES.as_bytes(), 1000.).unwrap();
+        let mut tonga = details(Some((-15.5, 179.9)));
+        geocoder.enrich(&mut tonga);
+        expect_that!(tonga.city, some(eq("Apia")));
+    }
+
+    #[gtest]
+    fn test_far_from_cities() {
+        let mut atlantic = details(Some((30., -40.)));
+        geocoder().enrich(&mut atlantic);
+        expect_that!(atlantic.city, none());
+        expect_that!(atlantic.country, none());
+    }
+
+    #[gtest]
+    fn test_without_gps() {
+        let mut photo = details(None);
+        geocoder().enrich(&mut photo);
+        expect_that!(photo.city, none());
+        expect_that!(photo.country, none());
+    }
+
+    #[gtest]
+    fn test_known_city_kept() {
+        let mut photo = details(Some((48.8049, 2.1204)));
+        photo.city = Some("Versailles".into());
+        geocoder().enrich(&mut photo);
+        expect_that!(photo.city, some(eq("Versailles")));
+        expect_that!(photo.country, some(eq("FR")));
+    }
+}
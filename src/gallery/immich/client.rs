@@ -1,11 +1,26 @@
-use std::num::NonZeroU32;
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, ErrorKind, Read, Write},
+    num::NonZeroU32,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
-use log::trace;
+use log::{debug, trace, warn};
 use minreq::{Method, Request, Response};
 use serde::{Deserialize, Serialize};
 
+use crate::network;
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
@@ -14,7 +29,12 @@ pub struct AssetResponse {
     pub exif_info: Option<ExifInfo>,
     pub local_date_time: DateTime<Utc>,
     pub file_created_at: DateTime<Utc>,
+    /// When the asset was uploaded to Immich.
+    pub created_at: Option<DateTime<Utc>>,
+    /// Stack the asset belongs to, not listed by older versions of Immich.
+    pub stack: Option<AssetStack>,
     pub r#type: AssetType,
+    pub original_mime_type: Option<String>,
     pub people: Vec<Person>,
     #[serde(default = "Vec::default")]
     pub unassigned_faces: Vec<Face>,
@@ -29,6 +49,26 @@ pub struct AlbumInfo {
     pub assets: Vec<AssetResponse>,
 }
 
+/// Album or assets exposed by a shared link.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedLinkResponse {
+    pub description: Option<String>,
+    /// Set for album links, without its assets.
+    pub album: Option<SharedLinkAlbum>,
+    /// Assets of individual links.
+    #[serde(default)]
+    pub assets: Vec<AssetResponse>,
+    /// Unlocks the next requests of a password protected link.
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedLinkAlbum {
+    pub id: String,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
@@ -62,11 +102,23 @@ pub struct Face {
     pub bounding_box_y2: i32,
 }
 
+/// Group of assets shown as one by Immich, like the shots of a burst.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct AssetStack {
+    pub id: String,
+    pub primary_asset_id: String,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct ExifInfo {
     pub city: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
     pub date_time_original: Option<DateTime<Utc>>,
 }
 
@@ -79,6 +131,27 @@ pub enum AssetType {
     Other,
 }
 
+/// Versions of an asset available from Immich.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssetSize {
+    /// Small and heavily compressed, quick to download.
+    Thumbnail,
+    /// Large enough to be displayed full screen.
+    Preview,
+    /// The uploaded file, in its original format.
+    Original,
+}
+
+impl AssetSize {
+    pub fn name(self) -> &'static str {
+        match self {
+            AssetSize::Thumbnail => "thumbnail",
+            AssetSize::Preview => "preview",
+            AssetSize::Original => "original",
+        }
+    }
+}
+
 #[derive(Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchRandomRequest {
@@ -129,9 +202,150 @@ pub struct SmartSearchAssets {
     pub items: Vec<AssetResponse>,
 }
 
+/// Immich answered with an error status code.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status_code: i32,
+    pub reason_phrase: String,
+}
+
+impl StatusError {
+    /// Client errors come from the configuration (wrong API key, unknown album...) and
+    /// won't go away by themselves, except for timeouts and rate limiting.
+    pub fn is_fatal(&self) -> bool {
+        (400..500).contains(&self.status_code) && !matches!(self.status_code, 408 | 429)
+    }
+}
+
+impl Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Response error: status code {} ({})",
+            self.status_code, self.reason_phrase
+        )
+    }
+}
+
+impl std::error::Error for StatusError {}
+
 pub struct ImmichClient {
     base_url: String,
-    api_key: String,
+    auth: Auth,
+    limits: DownloadLimits,
+}
+
+/// How requests are authenticated.
+enum Auth {
+    /// Key of a user, giving access to all its assets.
+    ApiKey(String),
+    /// Key of a shared link, only giving access to what it shares.
+    SharedLink {
+        key: String,
+        /// Received once the password of a protected link is checked.
+        token: OnceLock<String>,
+    },
+}
+
+/// Sizes deciding where downloaded assets are kept, in bytes.
+#[derive(Debug, Clone)]
+pub struct DownloadLimits {
+    /// Larger assets are streamed to a temporary file rather than kept in memory.
+    pub in_memory: u64,
+    /// Larger assets are rejected.
+    pub max: u64,
+    /// Where temporary files are created.
+    pub temp_dir: PathBuf,
+}
+
+impl Default for DownloadLimits {
+    fn default() -> Self {
+        Self {
+            in_memory: 8 * 1024 * 1024,
+            max: 200 * 1024 * 1024,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+/// Body of a downloaded asset.
+pub enum AssetData {
+    Memory(Vec<u8>),
+    /// Too large to be kept in memory, removed once dropped.
+    File(TempFile),
+}
+
+/// File removed when dropped.
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Creates a file only readable by the frame, the assets may be private.
+    fn create(dir: &Path) -> Result<(Self, File)> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = dir.join(format!(
+            "memocadre-{}-{}.download",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Cannot create temporary file {}", path.display()))?;
+        Ok((Self { path }, file))
+    }
+
+    pub fn open(&self) -> Result<File> {
+        File::open(&self.path)
+            .with_context(|| format!("Cannot open temporary file {}", self.path.display()))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!(
+                "Cannot remove temporary file {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Removes the temporary files of downloads left in `dir` by processes that are gone, e.g.
+/// after a crash or a power cut. Failures are only logged.
+pub fn remove_stale_downloads(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("Cannot list {} for stale downloads: {}", dir.display(), err);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = download_pid(&entry.file_name()) else {
+            continue;
+        };
+        if Path::new("/proc").join(pid.to_string()).exists() {
+            continue;
+        }
+        let path = entry.path();
+        match fs::remove_file(&path) {
+            Ok(()) => debug!("Removed stale download {}", path.display()),
+            Err(err) => warn!("Cannot remove stale download {}: {}", path.display(), err),
+        }
+    }
+}
+
+/// Process that downloaded to the temporary file `name`, see [`TempFile::create`].
+fn download_pid(name: &OsStr) -> Option<u32> {
+    let name = name.to_str()?.strip_prefix("memocadre-")?;
+    let (pid, _) = name.strip_suffix(".download")?.split_once('-')?;
+    pid.parse().ok()
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -141,16 +355,50 @@ pub struct PersonResponse {
     pub id: String,
     pub birth_date: Option<String>,
     pub name: String,
+    /// Starred in Immich.
+    #[serde(default)]
+    pub is_favorite: bool,
+}
+
+/// Page of the persons of the account.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PeopleResponse {
+    people: Vec<PersonResponse>,
+    #[serde(default)]
+    has_next_page: bool,
 }
 
 impl ImmichClient {
     pub fn new(base_url: impl AsRef<str>, api_key: impl AsRef<str>) -> Self {
         Self {
             base_url: base_url.as_ref().into(),
-            api_key: api_key.as_ref().into(),
+            auth: Auth::ApiKey(api_key.as_ref().into()),
+            limits: DownloadLimits::default(),
+        }
+    }
+
+    /// Client only accessing what the shared link of `key` exposes.
+    pub fn shared_link(base_url: impl AsRef<str>, key: impl AsRef<str>) -> Self {
+        Self {
+            base_url: base_url.as_ref().into(),
+            auth: Auth::SharedLink {
+                key: key.as_ref().into(),
+                token: OnceLock::new(),
+            },
+            limits: DownloadLimits::default(),
         }
     }
 
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn with_download_limits(mut self, limits: DownloadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn smart_search(&self, query: SmartSearchRequest) -> Result<SmartSearchResponse> {
         self.handle_response_error(
             self.post("search/smart")
@@ -186,6 +434,52 @@ impl ImmichClient {
             .context("Cannot read immich person response")
     }
 
+    /// Persons of the account, only the starred ones with `favorites_only`.
+    pub fn list_people(&self, favorites_only: bool) -> Result<Vec<PersonResponse>> {
+        const PAGE_SIZE: u32 = 500;
+        let mut people = Vec::new();
+        for page in 1.. {
+            let response: PeopleResponse = self
+                .handle_response_error(
+                    self.get("people")
+                        .with_param("page", page.to_string())
+                        .with_param("size", PAGE_SIZE.to_string())
+                        .send(),
+                )?
+                .json()
+                .context("Cannot read immich people response")?;
+            let last = !response.has_next_page || response.people.is_empty();
+            people.extend(
+                response
+                    .people
+                    .into_iter()
+                    .filter(|person| person.is_favorite || !favorites_only),
+            );
+            if last {
+                break;
+            }
+        }
+        Ok(people)
+    }
+
+    /// What the shared link of the client exposes. Protected links are unlocked with
+    /// `password` for the next requests.
+    pub fn get_shared_link(&self, password: Option<&str>) -> Result<SharedLinkResponse> {
+        let mut request = self.get("shared-links/me");
+        if let Some(password) = password {
+            request = request.with_param("password", password);
+        }
+        let link: SharedLinkResponse = self
+            .handle_response_error(request.send())?
+            .json()
+            .context("Cannot read immich shared link response")?;
+        if let (Auth::SharedLink { token, .. }, Some(received)) = (&self.auth, &link.token) {
+            // The token of a link doesn't change
+            let _ = token.set(received.clone());
+        }
+        Ok(link)
+    }
+
     pub fn get_memory_lane(&self, day: u8, month: u8) -> Result<Vec<MemoryLaneElement>> {
         self.handle_response_error(
             self.get("assets/memory-lane")
@@ -197,19 +491,49 @@ impl ImmichClient {
         .context("Cannot read immich memory lane response")
     }
 
+    /// Cheap authenticated request, checking that the instance is reachable.
+    pub fn check_access(&self, timeout: Duration) -> Result<()> {
+        self.handle_response_error(
+            self.get("users/me")
+                .with_timeout(timeout.as_secs().max(1))
+                .send(),
+        )?;
+        Ok(())
+    }
+
     pub fn get_asset_details(&self, id: &str) -> Result<AssetResponse> {
         self.handle_response_error(self.get(format!("assets/{id}")).send())?
             .json()
             .context("Cannot read immich asset response")
     }
 
-    pub fn view_assets(&self, id: &str) -> Result<Vec<u8>> {
-        Ok(self
-            .handle_response_error(
-                self.get(format!("assets/{id}/thumbnail?size=preview"))
-                    .send(),
-            )?
-            .into_bytes())
+    /// Download a version of the asset, giving up once `deadline` is reached. Large assets
+    /// are streamed to a temporary file.
+    pub fn view_assets(&self, id: &str, size: AssetSize, deadline: Instant) -> Result<AssetData> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let path = match size {
+            AssetSize::Thumbnail | AssetSize::Preview => {
+                format!("assets/{id}/thumbnail?size={}", size.name())
+            }
+            AssetSize::Original => format!("assets/{id}/original"),
+        };
+        let response = self
+            .get(path)
+            .with_timeout(remaining.as_secs().max(1))
+            .send_lazy()
+            .context("Cannot send request")?;
+        Self::check_status(response.status_code, &response.reason_phrase)?;
+        let length = response
+            .headers
+            .get("content-length")
+            .and_then(|length| length.parse::<u64>().ok());
+        if let Some(length) = length.filter(|length| *length > self.limits.max) {
+            bail!(
+                "Asset of {length} bytes is larger than the {} bytes limit",
+                self.limits.max
+            );
+        }
+        read_body_before(response, deadline, &self.limits)
     }
 
     fn handle_response_error(
@@ -217,14 +541,19 @@ impl ImmichClient {
         response: core::result::Result<Response, minreq::Error>,
     ) -> Result<Response> {
         let response = response.context("Cannot send request")?;
-        if response.status_code >= 400 {
-            Err(anyhow!(
-                "Response error: status code {} ({})",
-                response.status_code,
-                response.reason_phrase
-            ))
+        Self::check_status(response.status_code, &response.reason_phrase)?;
+        Ok(response)
+    }
+
+    fn check_status(status_code: i32, reason_phrase: &str) -> Result<()> {
+        if status_code >= 400 {
+            Err(StatusError {
+                status_code,
+                reason_phrase: reason_phrase.to_owned(),
+            }
+            .into())
         } else {
-            Ok(response)
+            Ok(())
         }
     }
 
@@ -239,8 +568,227 @@ impl ImmichClient {
     fn request(&self, method: Method, path: impl AsRef<str>) -> Request {
         let url = format!("{}/api/{}", self.base_url, path.as_ref());
         trace!("Requesting Immich with {} {}", method, url);
-        Request::new(method, url)
-            .with_header("x-api-key", &self.api_key)
-            .with_timeout(60)
+        let request = network::request(method, url).with_timeout(60);
+        match &self.auth {
+            Auth::ApiKey(api_key) => request.with_header("x-api-key", api_key),
+            Auth::SharedLink { key, token } => {
+                let request = request.with_param("key", key);
+                match token.get() {
+                    Some(token) => {
+                        request.with_header("Cookie", format!("immich_shared_link_token={token}"))
+                    }
+                    None => request,
+                }
+            }
+        }
+    }
+}
+
+/// Read `reader` to the end chunk by chunk, failing as soon as `deadline` is reached.
+/// Once larger than `limits.in_memory`, the data is moved to a temporary file.
+fn read_body_before(
+    mut reader: impl Read,
+    deadline: Instant,
+    limits: &DownloadLimits,
+) -> Result<AssetData> {
+    let mut data = Vec::new();
+    let mut file: Option<(BufWriter<File>, TempFile)> = None;
+    let mut total = 0;
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        if Instant::now() >= deadline {
+            bail!("Deadline reached after reading {} bytes", total);
+        }
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err).context("Cannot read response body"),
+        };
+        total += read as u64;
+        if total > limits.max {
+            bail!("Asset is larger than the {} bytes limit", limits.max);
+        }
+        match &mut file {
+            Some((writer, _)) => writer
+                .write_all(&chunk[..read])
+                .context("Cannot write to temporary file")?,
+            None => {
+                data.extend_from_slice(&chunk[..read]);
+                if total > limits.in_memory {
+                    trace!(
+                        "Asset larger than {} bytes, streaming it to a file",
+                        limits.in_memory
+                    );
+                    let (temp, out) = TempFile::create(&limits.temp_dir)?;
+                    let mut writer = BufWriter::new(out);
+                    writer
+                        .write_all(&data)
+                        .context("Cannot write to temporary file")?;
+                    data = Vec::new();
+                    file = Some((writer, temp));
+                }
+            }
+        }
+    }
+    match file {
+        Some((mut writer, temp)) => {
+            writer.flush().context("Cannot write to temporary file")?;
+            Ok(AssetData::File(temp))
+        }
+        None => Ok(AssetData::Memory(data)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ffi::OsStr,
+        fs,
+        io::Read,
+        os::unix::fs::PermissionsExt,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true, none, some},
+    };
+    use temp_dir::TempDir;
+
+    use super::{
+        download_pid, read_body_before, remove_stale_downloads, AssetData, DownloadLimits,
+        StatusError,
+    };
+
+    fn status(status_code: i32) -> StatusError {
+        StatusError {
+            status_code,
+            reason_phrase: String::new(),
+        }
+    }
+
+    #[gtest]
+    fn test_fatal_status() {
+        expect_that!(status(401).is_fatal(), is_true());
+        expect_that!(status(404).is_fatal(), is_true());
+        expect_that!(status(429).is_fatal(), is_false());
+        expect_that!(status(500).is_fatal(), is_false());
+        expect_that!(status(503).is_fatal(), is_false());
+    }
+
+    /// Serves `remaining` bytes, a few at a time, waiting `delay` before each read.
+    struct SlowReader {
+        remaining: usize,
+        delay: Duration,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            thread::sleep(self.delay);
+            let read = self.remaining.min(buf.len()).min(4);
+            buf[..read].fill(42);
+            self.remaining -= read;
+            Ok(read)
+        }
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(10)
+    }
+
+    fn fast_reader(remaining: usize) -> SlowReader {
+        SlowReader {
+            remaining,
+            delay: Duration::ZERO,
+        }
+    }
+
+    fn limits(dir: &TempDir) -> DownloadLimits {
+        DownloadLimits {
+            in_memory: 16,
+            max: 64,
+            temp_dir: dir.path().to_owned(),
+        }
+    }
+
+    fn temp_files(dir: &TempDir) -> usize {
+        fs::read_dir(dir.path()).unwrap().count()
+    }
+
+    #[gtest]
+    fn test_read_before_deadline() {
+        let data = read_body_before(fast_reader(10), deadline(), &Default::default()).unwrap();
+        let AssetData::Memory(data) = data else {
+            panic!("Small assets should stay in memory");
+        };
+        expect_that!(data, eq(vec![42; 10]));
+    }
+
+    #[gtest]
+    fn test_large_asset_streamed_to_file() {
+        let dir = TempDir::new().unwrap();
+        let data = read_body_before(fast_reader(40), deadline(), &limits(&dir)).unwrap();
+        let AssetData::File(file) = data else {
+            panic!("Large assets should be written to a file");
+        };
+        let mut content = Vec::new();
+        file.open().unwrap().read_to_end(&mut content).unwrap();
+        expect_that!(content, eq(vec![42; 40]));
+        expect_that!(temp_files(&dir), eq(1));
+        let mode = file
+            .open()
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .permissions()
+            .mode();
+        expect_that!(mode & 0o777, eq(0o600));
+
+        drop(file);
+        expect_that!(temp_files(&dir), eq(0));
+    }
+
+    #[gtest]
+    fn test_stale_downloads_removed() {
+        let dir = TempDir::new().unwrap();
+        let running = format!("memocadre-{}-0.download", std::process::id());
+        // Above the largest pid of Linux
+        let gone = format!("memocadre-{}-3.download", u32::MAX);
+        for name in [&running, &gone, &"memocadre.yaml".to_string()] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        remove_stale_downloads(dir.path());
+        expect_that!(dir.path().join(&running).exists(), is_true());
+        expect_that!(dir.path().join(&gone).exists(), is_false());
+        expect_that!(temp_files(&dir), eq(2));
+
+        expect_that!(download_pid(OsStr::new(&gone)), some(eq(u32::MAX)));
+        expect_that!(download_pid(OsStr::new("memocadre-12.download")), none());
+    }
+
+    #[gtest]
+    fn test_asset_over_limit_rejected() {
+        let dir = TempDir::new().unwrap();
+        let result = read_body_before(fast_reader(100), deadline(), &limits(&dir));
+        expect_that!(result.is_err(), is_true());
+        expect_that!(temp_files(&dir), eq(0));
+    }
+
+    #[gtest]
+    fn test_read_timeout() {
+        let reader = SlowReader {
+            remaining: 1000,
+            delay: Duration::from_millis(10),
+        };
+        let start = Instant::now();
+        let result = read_body_before(
+            reader,
+            start + Duration::from_millis(50),
+            &Default::default(),
+        );
+        expect_that!(result.is_err(), is_true());
+        expect_that!(start.elapsed() < Duration::from_secs(1), is_true());
     }
 }
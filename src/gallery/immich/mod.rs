@@ -1,32 +1,120 @@
-use std::{io::Cursor, num::NonZeroU32, ops::Deref, rc::Rc, time::Instant};
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    io::{BufReader, Cursor},
+    num::NonZeroU32,
+    ops::Deref,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Error, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
 use client::SmartSearchRequest;
-use image::ImageReader;
+use image::{DynamicImage, ImageFormat, ImageReader};
 use itertools::Itertools;
-use log::debug;
+use log::{debug, info, warn};
+use rand::seq::SliceRandom;
+use tokio::sync::watch;
+use vek::Extent2;
 
-use self::client::{AssetResponse, AssetType, ImmichClient, SearchRandomRequest};
-use super::{Gallery, GalleryProvider};
+use self::client::{
+    AssetData, AssetResponse, AssetSize, AssetType, DownloadLimits, Face, ImmichClient,
+    SearchRandomRequest, StatusError,
+};
+#[cfg(feature = "weather")]
+use super::weather::{WeatherGallery, WeatherQuery};
+use super::{
+    decode_image, skip_list::SkipList, Gallery, GalleryEmpty, GalleryProvider, PreparationInfo,
+};
 use crate::{
-    configuration::{ImmichPerson, ImmichSearchQuery, ImmichSource, ImmichSpec, PrivateAlbum},
-    gallery::{ImageDetails, ImageWithDetails},
+    configuration::{
+        AssetQuality, CaptionDateSource, ImmichInstance, ImmichPerson, ImmichSearchQuery,
+        ImmichSharedLinkSource, ImmichSmartSearchQuery, ImmichSource, ImmichSpec, MissingExif,
+        PrivateAlbum, Stacks, TakenDate, WeightedImmichSpec,
+    },
+    gallery::{BoxInImage, GeoPoint, ImageDetails, ImageWithDetails, Person},
+    random,
 };
 
 mod client;
+mod person_cache;
+
+/// How long an asset that timed out is left aside before being tried again.
+const SKIP_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Size of the shorter side of thumbnails and previews with the default Immich settings.
+const THUMBNAIL_RESOLUTION: u32 = 250;
+const PREVIEW_RESOLUTION: u32 = 1440;
+/// Batches loaded for a single photo before giving up, when all their assets are skipped.
+const MAX_SKIPPED_BATCHES: usize = 3;
+/// Instances slower than this at startup are left to the regular retries.
+const PREWARM_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest weight of a spec, so that the rotation of the sources stays short.
+const MAX_SPEC_WEIGHT: u32 = 100;
 
 struct ImmichGalleryProvider {
+    /// Human readable description of the instance and spec, used in status reports.
+    name: String,
     client: Rc<ImmichClient>,
+    sizes: AssetSizeSelector,
+    /// Downloads the thumbnail along with the photo, for the blurred background.
+    background_thumbnail: bool,
     search: ImmichRequest,
+    /// Taken from the end, see [`capture_order`].
     next_assets: Vec<AssetResponse>,
+    /// Sorts each batch in capture order.
+    group_by_day: bool,
+    policy: AssetPolicy,
+    skipped: SkipList,
+    /// Persons the search is limited to, resolved again when Immich rejects their ids.
+    persons: Vec<ImmichPerson>,
+    /// Ids of the persons each photo shows all of, searched in turn and merged, see
+    /// [`expand_persons`].
+    person_sets: Option<Vec<Vec<String>>>,
+    person_names_ttl: Duration,
+    /// Whether some of the person ids were cached, see [`person_cache::resolve`].
+    cached_person_ids: bool,
+}
+
+/// Which assets are shown and how their details are read, see [`ImmichSource::stacks`],
+/// [`ImmichSource::taken_date`] and [`ImmichSource::missing_exif`].
+#[derive(Clone, Copy, Default)]
+struct AssetPolicy {
+    stacks: Stacks,
+    taken_date: TakenDate,
+    missing: MissingExif,
+    /// Set by the slideshow, see [`Gallery::set_date_source`].
+    date_source: CaptionDateSource,
+}
+
+impl AssetPolicy {
+    fn of(source: &ImmichSource) -> Self {
+        Self {
+            stacks: source.stacks,
+            taken_date: source.taken_date,
+            missing: source.missing_exif,
+            date_source: CaptionDateSource::default(),
+        }
+    }
 }
 
 #[derive(Debug)]
 enum ImmichRequest {
     RandomSearch(SearchRandomRequest),
     SmartSearch(SmartSearchRequest),
-    PrivateAlbum { id: String },
+    PrivateAlbum {
+        id: String,
+        /// Known once the album is loaded.
+        name: Option<String>,
+    },
     MemoryLane,
+    /// Album or assets of the shared link of the client.
+    SharedLink {
+        password: Option<String>,
+        /// Album name or description of the link, known once loaded.
+        name: Option<String>,
+    },
 }
 
 impl ImmichRequest {
@@ -36,161 +124,1440 @@ impl ImmichRequest {
                 .search_random(SearchRandomRequest {
                     r#type: Some(AssetType::Image),
                     with_exif: Some(true),
+                    // Faces, used to frame the photos
+                    with_people: Some(true),
                     ..search_random_request.clone()
                 })
                 .context("Error while search next assets batch")?),
             ImmichRequest::SmartSearch(ref mut request) => {
-                request.page = request.page.map(|p| p.saturating_add(1));
-                Ok(client
+                let assets = client
                     .smart_search(SmartSearchRequest {
                         r#type: Some(AssetType::Image),
                         with_exif: Some(true),
+                        // Faces, used to frame the photos
+                        with_people: Some(true),
                         ..request.clone()
                     })
                     .context("Error while smart searching next assets batch")?
                     .assets
-                    .items)
-            }
-            ImmichRequest::PrivateAlbum { id } => Ok(client
-                .get_album(id)
-                .context("Cannot get album for next batch")?
-                .assets),
-            ImmichRequest::MemoryLane => Ok(client
-                .get_memory_lane(29, 1)?
-                .into_iter()
-                .flat_map(|l| l.assets)
-                .collect()),
+                    .items;
+                // Start over from the first page once all results have been seen
+                request.page = if assets.is_empty() {
+                    NonZeroU32::new(1)
+                } else {
+                    request.page.map(|p| p.saturating_add(1))
+                };
+                Ok(assets)
+            }
+            ImmichRequest::PrivateAlbum { id, name } => {
+                let album = client
+                    .get_album(id)
+                    .context("Cannot get album for next batch")?;
+                *name = Some(album.album_name);
+                Ok(album.assets)
+            }
+            ImmichRequest::MemoryLane => {
+                let (day, month) = memory_lane_day(Local::now().date_naive());
+                Ok(client
+                    .get_memory_lane(day, month)?
+                    .into_iter()
+                    .flat_map(|l| l.assets)
+                    .collect())
+            }
+            ImmichRequest::SharedLink { password, name } => {
+                let link = client
+                    .get_shared_link(password.as_deref())
+                    .context("Cannot get shared link")?;
+                match link.album {
+                    Some(album) => {
+                        let album = client
+                            .get_album(&album.id)
+                            .context("Cannot get shared album")?;
+                        *name = Some(album.album_name);
+                        Ok(album.assets)
+                    }
+                    None => {
+                        *name = link
+                            .description
+                            .filter(|description| !description.is_empty());
+                        Ok(link.assets)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Loads the next batch of the search for each set of person ids, merged without
+    /// duplicates and shuffled, not to show the photos of each set in a row. A smart search
+    /// moves to its next page once for all of them.
+    fn load_next_of_each(
+        &mut self,
+        client: &ImmichClient,
+        person_sets: &[Vec<String>],
+    ) -> Result<Vec<AssetResponse>> {
+        let page = match self {
+            ImmichRequest::SmartSearch(request) => request.page,
+            _ => None,
+        };
+        let mut ids = HashSet::new();
+        let mut assets = Vec::new();
+        for person_ids in person_sets {
+            if let ImmichRequest::SmartSearch(request) = self {
+                request.page = page;
+            }
+            self.set_person_ids(Some(person_ids.clone()));
+            let batch = self.load_next(client)?;
+            assets.extend(
+                batch
+                    .into_iter()
+                    .filter(|asset| ids.insert(asset.id.clone())),
+            );
+        }
+        if let ImmichRequest::SmartSearch(request) = self {
+            // Start over from the first page once all results have been seen
+            request.page = if assets.is_empty() {
+                NonZeroU32::new(1)
+            } else {
+                page.map(|p| p.saturating_add(1))
+            };
+        }
+        random::with_rng(|rng| assets.shuffle(rng));
+        Ok(assets)
+    }
+
+    fn set_person_ids(&mut self, person_ids: Option<Vec<String>>) {
+        match self {
+            ImmichRequest::RandomSearch(request) => request.person_ids = person_ids,
+            ImmichRequest::SmartSearch(request) => request.person_ids = person_ids,
+            ImmichRequest::PrivateAlbum { .. }
+            | ImmichRequest::MemoryLane
+            | ImmichRequest::SharedLink { .. } => {}
+        }
+    }
+
+    fn album_name(&self) -> Option<String> {
+        match self {
+            ImmichRequest::PrivateAlbum { name, .. } | ImmichRequest::SharedLink { name, .. } => {
+                name.clone()
+            }
+            _ => None,
         }
     }
 }
 
 impl Gallery for ImmichGalleryProvider {
-    fn get_next_image(&mut self) -> Result<ImageWithDetails> {
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        let asset = self.get_next_asset()?;
+        let size = self.sizes.select(&asset);
+        let mut image = if self.background_thumbnail && size != AssetSize::Thumbnail {
+            self.fetch_image_with_background(asset, size, timeout)?
+        } else {
+            self.fetch_image(asset, size, timeout)?
+        };
+        image.details.album = self.search.album_name();
+        Ok(image)
+    }
+
+    fn get_next_thumbnail(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
         let asset = self.get_next_asset()?;
-        let start = Instant::now();
-        let img_data = self
+        let size = self.sizes.select(&asset);
+        let mut image = self.fetch_image(asset, AssetSize::Thumbnail, timeout)?;
+        // Nothing to replace it with when thumbnails are shown anyway
+        image.low_res = size != AssetSize::Thumbnail;
+        image.details.album = self.search.album_name();
+        Ok(image)
+    }
+
+    fn get_image_by_id(&mut self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        let asset = self
             .client
-            .view_assets(&asset.id)
-            .context("Cannot fetch image data")?;
-        let image = ImageReader::new(Cursor::new(&img_data))
-            .with_guessed_format()
-            .context("Cannot guess image format")?
-            .decode()
-            .context("Cannot decode image")?;
-        debug!("Asset downloaded and decoded in {:?}", start.elapsed());
-        Ok(ImageWithDetails {
-            image,
-            details: ImageDetails {
-                city: asset.exif_info.as_ref().and_then(|i| i.city.clone()),
-                date: Some(asset.file_created_at),
-                people: Vec::new(),
-            },
+            .get_asset_details(id)
+            .context("Cannot fetch asset with details")?;
+        let size = self.sizes.select(&asset);
+        // Asked for, shown even when it would be skipped
+        self.fetch_image(asset, size, timeout)
+    }
+
+    fn set_group_by_day(&mut self, enabled: bool) {
+        self.group_by_day = enabled;
+        if enabled {
+            capture_order(&mut self.next_assets, self.policy);
+        }
+    }
+
+    fn set_date_source(&mut self, source: CaptionDateSource) {
+        self.policy.date_source = source;
+        if self.group_by_day {
+            capture_order(&mut self.next_assets, self.policy);
+        }
+    }
+}
+impl GalleryProvider for ImmichGalleryProvider {
+    fn is_retryable(&self, error: &Error) -> bool {
+        !error.chain().any(|cause| {
+            cause
+                .downcast_ref::<StatusError>()
+                .is_some_and(StatusError::is_fatal)
         })
     }
+
+    #[cfg(feature = "weather")]
+    fn set_search_query(&mut self, query: &str) {
+        if let ImmichRequest::SmartSearch(request) = &mut self.search {
+            request.query = query.to_owned();
+            request.page = NonZeroU32::new(1);
+            self.next_assets.clear();
+        }
+    }
 }
-impl GalleryProvider for ImmichGalleryProvider {}
 
 impl ImmichGalleryProvider {
-    fn new(client: &Rc<ImmichClient>, search: &ImmichSpec) -> Result<Self> {
+    fn new(
+        name: String,
+        client: &Rc<ImmichClient>,
+        sizes: AssetSizeSelector,
+        background_thumbnail: bool,
+        search: &ImmichSpec,
+        person_names_ttl: Duration,
+        policy: AssetPolicy,
+    ) -> Result<Self> {
+        let persons = match search {
+            ImmichSpec::RandomSearch(ImmichSearchQuery { persons })
+            | ImmichSpec::SmartSearch(ImmichSmartSearchQuery { persons, .. }) => persons.clone(),
+            ImmichSpec::PrivateAlbum(_) | ImmichSpec::MemoryLane => None,
+        };
+        let (person_sets, cached_person_ids) =
+            Self::get_persons_ids(client.deref(), persons.as_deref(), person_names_ttl)
+                .context("While building search request")?;
+        let person_ids = person_sets.as_ref().and_then(|sets| sets.first().cloned());
         let immich_request = match search {
-            ImmichSpec::RandomSearch(immich_search_query) => {
-                let req = Self::build_random_search(client.deref(), immich_search_query)
-                    .context("While building search request")?;
-                ImmichRequest::RandomSearch(req)
-            }
+            ImmichSpec::RandomSearch(_) => ImmichRequest::RandomSearch(SearchRandomRequest {
+                person_ids,
+                ..Default::default()
+            }),
             ImmichSpec::SmartSearch(search) => ImmichRequest::SmartSearch(SmartSearchRequest {
-                person_ids: Self::get_persons_ids(client.deref(), &search.persons)?,
+                person_ids,
                 city: search.city.clone(),
                 query: search.query.clone(),
                 page: NonZeroU32::new(1),
                 ..Default::default()
             }),
-            ImmichSpec::PrivateAlbum(PrivateAlbum { id }) => {
-                ImmichRequest::PrivateAlbum { id: id.clone() }
-            }
+            ImmichSpec::PrivateAlbum(PrivateAlbum { id }) => ImmichRequest::PrivateAlbum {
+                id: id.clone(),
+                name: None,
+            },
             ImmichSpec::MemoryLane => ImmichRequest::MemoryLane,
         };
-        let search = immich_request;
         Ok(Self {
+            persons: persons.unwrap_or_default(),
+            person_sets,
+            person_names_ttl,
+            cached_person_ids,
+            policy,
+            ..Self::with_request(name, client, sizes, background_thumbnail, immich_request)
+        })
+    }
+
+    fn with_request(
+        name: String,
+        client: &Rc<ImmichClient>,
+        sizes: AssetSizeSelector,
+        background_thumbnail: bool,
+        search: ImmichRequest,
+    ) -> Self {
+        Self {
+            name,
             client: client.clone(),
+            sizes,
+            background_thumbnail,
             next_assets: Vec::new(),
+            group_by_day: false,
+            policy: AssetPolicy::default(),
             search,
-        })
+            skipped: SkipList::new(SKIP_DURATION),
+            persons: Vec::new(),
+            person_sets: None,
+            person_names_ttl: Duration::ZERO,
+            cached_person_ids: false,
+        }
     }
 
-    fn build_random_search(
+    /// Sets of ids of `persons`, see [`expand_persons`], with whether some of them were
+    /// cached. Names are only searched again once their cached ids are older than `ttl`.
+    fn get_persons_ids(
         client: &ImmichClient,
-        search: &ImmichSearchQuery,
-    ) -> Result<SearchRandomRequest> {
-        let person_ids = Self::get_persons_ids(client, &search.persons)?;
-        Ok(SearchRandomRequest {
-            person_ids,
-            ..Default::default()
-        })
+        persons: Option<&[ImmichPerson]>,
+        ttl: Duration,
+    ) -> Result<(Option<Vec<Vec<String>>>, bool)> {
+        let Some(persons) = persons else {
+            return Ok((None, false));
+        };
+        let names: Vec<&str> = persons
+            .iter()
+            .filter_map(|person| match person {
+                ImmichPerson::Name(name) => Some(name.as_str()),
+                ImmichPerson::Id(_) | ImmichPerson::Favorites => None,
+            })
+            .collect();
+        let resolved = person_cache::resolve(
+            person_cache::person_ids(),
+            client.base_url(),
+            &names,
+            ttl,
+            |name| {
+                Ok(client
+                    .search_person(name)
+                    .context("Cannot list persons")?
+                    .into_iter()
+                    .map(|p| p.id)
+                    .collect())
+            },
+        )?;
+        // Listed again on each build, so that newly starred persons are included
+        let favorites = if persons
+            .iter()
+            .any(|person| matches!(person, ImmichPerson::Favorites))
+        {
+            client
+                .list_people(true)
+                .context("Cannot list favorite persons")?
+                .into_iter()
+                .map(|p| p.id)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Ok((
+            expand_persons(persons, resolved.ids, &favorites),
+            resolved.cached,
+        ))
     }
 
-    fn get_persons_ids(
-        client: &ImmichClient,
-        persons: &Option<Vec<ImmichPerson>>,
-    ) -> Result<Option<Vec<String>>> {
-        persons
-            .as_ref()
-            .map(|persons| -> Result<_> {
-                persons
-                    .iter()
-                    .map(|p| -> Result<_> {
-                        Ok(match p {
-                            // FIXME handle non-existing
-                            ImmichPerson::Id(id) => vec![id.to_owned()],
-                            ImmichPerson::Name(name) => client
-                                .search_person(name)
-                                .context("Cannot list persons")?
-                                .into_iter()
-                                .map(|p| p.id)
-                                .collect(),
-                        })
-                    })
-                    .flatten_ok()
-                    .collect::<Result<Vec<_>>>()
+    /// Whether `error` may come from cached person ids that no longer exist, e.g. after
+    /// persons were merged in Immich.
+    fn rejects_cached_persons(&self, error: &Error) -> bool {
+        self.cached_person_ids
+            && error.chain().any(|cause| {
+                cause
+                    .downcast_ref::<StatusError>()
+                    .is_some_and(|status| matches!(status.status_code, 400 | 404))
             })
-            .transpose()
     }
 
+    /// Forgets the cached ids of the person names and searches them again.
+    fn resolve_persons_again(&mut self) -> Result<()> {
+        {
+            let mut cache = person_cache::person_ids().lock().unwrap();
+            for person in &self.persons {
+                if let ImmichPerson::Name(name) = person {
+                    cache.invalidate(self.client.base_url(), name);
+                }
+            }
+        }
+        let (person_sets, cached) =
+            Self::get_persons_ids(&self.client, Some(&self.persons), self.person_names_ttl)?;
+        self.search
+            .set_person_ids(person_sets.as_ref().and_then(|sets| sets.first().cloned()));
+        self.person_sets = person_sets;
+        self.cached_person_ids = cached;
+        Ok(())
+    }
+
+    /// Loads the next batch of the search, searching the person names again once when
+    /// Immich rejects their cached ids.
+    fn load_search(&mut self) -> Result<Vec<AssetResponse>> {
+        match self.load_batch() {
+            Err(err) if self.rejects_cached_persons(&err) => {
+                warn!(
+                    "Search of {} rejected, searching the person names again: {:?}",
+                    self.name, err
+                );
+                self.resolve_persons_again()
+                    .context("Cannot search the person names again")?;
+                self.load_batch()
+            }
+            res => res,
+        }
+    }
+
+    /// Loads the next batch of the search, once per set of persons when there are several.
+    fn load_batch(&mut self) -> Result<Vec<AssetResponse>> {
+        match &self.person_sets {
+            Some(sets) if sets.len() > 1 => self.search.load_next_of_each(&self.client, sets),
+            _ => self.search.load_next(&self.client),
+        }
+    }
+
+    fn fetch_image(
+        &mut self,
+        asset: AssetResponse,
+        size: AssetSize,
+        timeout: Duration,
+    ) -> Result<ImageWithDetails> {
+        let deadline = Instant::now() + timeout;
+        let asset_id = asset.id.clone();
+        let result = download_asset(&self.client, asset, size, deadline, self.policy);
+        self.skip_if_late(asset_id, timeout, deadline, result)
+    }
+
+    /// Leaves the asset aside for a while when its download failed after `deadline`, most
+    /// likely for being too slow, so that the next photos are tried instead.
+    fn skip_if_late<T>(
+        &mut self,
+        asset_id: String,
+        timeout: Duration,
+        deadline: Instant,
+        result: Result<T>,
+    ) -> Result<T> {
+        match result {
+            Err(err) if Instant::now() >= deadline => {
+                warn!(
+                    "Asset {} took more than {:?} to download, skipping it for {:?}",
+                    asset_id, timeout, SKIP_DURATION
+                );
+                self.skipped.insert(asset_id, Instant::now());
+                Err(err.context("Timeout while fetching image data"))
+            }
+            res => res,
+        }
+    }
+
+    /// Like [`Self::fetch_image`], downloading the thumbnail in parallel. The photo is
+    /// still returned when the thumbnail cannot be fetched.
+    fn fetch_image_with_background(
+        &mut self,
+        asset: AssetResponse,
+        size: AssetSize,
+        timeout: Duration,
+    ) -> Result<ImageWithDetails> {
+        let client = Rc::clone(&self.client);
+        let client: &ImmichClient = &client;
+        let id = asset.id.clone();
+        let deadline = Instant::now() + timeout;
+        thread::scope(|scope| {
+            let background =
+                scope.spawn(move || download_image(client, &id, AssetSize::Thumbnail, deadline));
+            let mut image = self.fetch_image(asset, size, timeout)?;
+            image.background_image = match background.join() {
+                Ok(Ok(background)) => Some(background),
+                Ok(Err(err)) => {
+                    warn!("Cannot fetch thumbnail for the background: {:?}", err);
+                    None
+                }
+                Err(_) => {
+                    warn!("Thumbnail download panicked");
+                    None
+                }
+            };
+            Ok(image)
+        })
+    }
+
+    /// Next asset with its details, leaving aside the hidden members of stacks and the
+    /// assets without EXIF when they are skipped.
     fn get_next_asset(&mut self) -> Result<AssetResponse> {
-        let asset = if let Some(next) = self.next_assets.pop() {
-            next
-        } else {
+        let mut batches = 0;
+        let mut skipped = 0;
+        loop {
+            let asset = if let Some(next) = self.pop_next_asset() {
+                next
+            } else {
+                if batches == MAX_SKIPPED_BATCHES {
+                    bail!("Skipped all {skipped} assets of the last {batches} batches");
+                }
+                batches += 1;
+                self.load_next_batch()?;
+                self.pop_next_asset()
+                    .context("Should have at least one asset that is not skipped")?
+            };
+            if hidden_in_stack(&asset, self.policy.stacks) {
+                debug!("Asset {} is not the primary of its stack", asset.id);
+                skipped += 1;
+                continue;
+            }
+            let mut details = self
+                .client
+                .get_asset_details(&asset.id)
+                .context("Cannot fetch assets with details")?;
+            // Searched with their EXIF, in case the details lack it
+            if details.exif_info.is_none() {
+                details.exif_info = asset.exif_info;
+            }
+            // Searches may list assets without their stack
+            if hidden_in_stack(&details, self.policy.stacks) {
+                debug!("Asset {} is not the primary of its stack", details.id);
+                skipped += 1;
+                continue;
+            }
+            if details.exif_info.is_some() || self.policy.missing != MissingExif::Skip {
+                return Ok(details);
+            }
+            info!(
+                "Asset {} has no EXIF yet, skipping it for {:?}",
+                details.id, SKIP_DURATION
+            );
+            self.skipped.insert(details.id, Instant::now());
+            skipped += 1;
+        }
+    }
+
+    /// Load the next batch of assets, failing with [`GalleryEmpty`] when the spec matches nothing.
+    fn load_next_batch(&mut self) -> Result<()> {
+        // A paged search returns an empty page once exhausted, so give it a second chance to
+        // start over before considering it empty.
+        for _ in 0..2 {
             self.next_assets = self
-                .search
-                .load_next(&self.client)
+                .load_search()
                 .context("Error while loading next asset batch")?;
-            self.next_assets
-                .pop()
-                .context("Should have at least one asset")?
-        };
-        self.client
-            .get_asset_details(&asset.id)
-            .context("Cannot fetch assets with details")
+            if !self.next_assets.is_empty() {
+                if self.group_by_day {
+                    capture_order(&mut self.next_assets, self.policy);
+                }
+                return Ok(());
+            }
+        }
+        Err(GalleryEmpty {
+            sources: vec![self.name.clone()],
+        }
+        .into())
+    }
+
+    fn pop_next_asset(&mut self) -> Option<AssetResponse> {
+        let now = Instant::now();
+        while let Some(asset) = self.next_assets.pop() {
+            if !self.skipped.contains(&asset.id, now) {
+                return Some(asset);
+            }
+            debug!("Asset {} recently timed out, skipping it", asset.id);
+        }
+        None
+    }
+}
+
+/// Sets of ids of `persons`, each name taking the next ids of `name_ids`. Immich matches the
+/// photos showing all the persons of a set, so "@favorites" makes a set for each person in
+/// `favorites`, along with the other persons, for photos showing any of them. Without any
+/// favorite, they are left out of the filter, which is `None` when nothing else is in it. A
+/// favorite also listed matches the photos of the others: only that set is left.
+fn expand_persons(
+    persons: &[ImmichPerson],
+    name_ids: Vec<Vec<String>>,
+    favorites: &[String],
+) -> Option<Vec<Vec<String>>> {
+    let mut name_ids = name_ids.into_iter();
+    let mut with_favorites = false;
+    let mut only_favorites = true;
+    let mut ids = Vec::new();
+    for person in persons {
+        match person {
+            // FIXME handle non-existing
+            ImmichPerson::Id(id) => ids.push(id.to_owned()),
+            ImmichPerson::Name(_) => ids.extend(name_ids.next().unwrap_or_default()),
+            ImmichPerson::Favorites => {
+                if favorites.is_empty() {
+                    warn!("No favorite person in Immich, \"@favorites\" is ignored");
+                }
+                with_favorites = true;
+                continue;
+            }
+        }
+        only_favorites = false;
+    }
+    // A person listed by name and by id is only searched once
+    let ids = ids.into_iter().unique().collect_vec();
+    if !with_favorites || favorites.is_empty() {
+        return (!(only_favorites && ids.is_empty())).then_some(vec![ids]);
+    }
+    if favorites.iter().any(|favorite| ids.contains(favorite)) {
+        return Some(vec![ids]);
+    }
+    let sets = favorites
+        .iter()
+        .unique()
+        .map(|favorite| {
+            let mut set = ids.clone();
+            set.push(favorite.clone());
+            set
+        })
+        .collect_vec();
+    Some(sets)
+}
+
+/// Whether `asset` is a member of a stack other than its primary, e.g. a shot of a burst,
+/// and only primaries are shown. Versions of Immich without stacks list none of them.
+fn hidden_in_stack(asset: &AssetResponse, stacks: Stacks) -> bool {
+    stacks == Stacks::PrimaryOnly
+        && asset
+            .stack
+            .as_ref()
+            .is_some_and(|stack| stack.primary_asset_id != asset.id)
+}
+
+/// Sorts `assets` for the oldest to be taken first from the end, by the date shown in their
+/// captions, see [`Gallery::set_group_by_day`].
+fn capture_order(assets: &mut [AssetResponse], policy: AssetPolicy) {
+    assets.sort_by_cached_key(|asset| Reverse(caption_date(asset, policy)));
+}
+
+/// Downloads and decodes a version of the asset `id`, giving up once `deadline` is reached.
+fn download_image(
+    client: &ImmichClient,
+    id: &str,
+    size: AssetSize,
+    deadline: Instant,
+) -> Result<DynamicImage> {
+    let start = Instant::now();
+    let img_data = client
+        .view_assets(id, size, deadline)
+        .context("Cannot fetch image data")?;
+    // A temporary file is removed once decoded, with `img_data`
+    let image = match &img_data {
+        AssetData::Memory(data) => decode_image(ImageReader::new(Cursor::new(data)))?,
+        AssetData::File(file) => decode_image(ImageReader::new(BufReader::new(file.open()?)))?,
+    };
+    debug!(
+        "Asset {} downloaded and decoded in {:?}",
+        size.name(),
+        start.elapsed()
+    );
+    Ok(image)
+}
+
+/// Downloads and decodes `asset`, giving up once `deadline` is reached.
+fn download_asset(
+    client: &ImmichClient,
+    asset: AssetResponse,
+    size: AssetSize,
+    deadline: Instant,
+    policy: AssetPolicy,
+) -> Result<ImageWithDetails> {
+    let image = download_image(client, &asset.id, size, deadline)?;
+    Ok(ImageWithDetails {
+        image,
+        details: details_of(asset, policy),
+        low_res: false,
+        background_image: None,
+        preparation: PreparationInfo {
+            variant: Some(size.name()),
+        },
+    })
+}
+
+/// Details of `asset` shown in its caption, without any date when its EXIF is missing and
+/// the caption hidden.
+fn details_of(asset: AssetResponse, policy: AssetPolicy) -> ImageDetails {
+    let people = people_of(&asset);
+    let exif = asset.exif_info.as_ref();
+    let date = match exif {
+        None if policy.missing == MissingExif::HideCaption => {
+            debug!("Asset {} has no EXIF yet, hiding its caption", asset.id);
+            None
+        }
+        _ => Some(caption_date(&asset, policy)),
+    };
+    ImageDetails {
+        city: exif.and_then(|i| i.city.clone()),
+        country: exif.and_then(|i| i.country.clone()),
+        gps: exif.and_then(|i| {
+            Some(GeoPoint {
+                latitude: i.latitude?,
+                longitude: i.longitude?,
+            })
+        }),
+        asset_id: Some(asset.id),
+        album: None,
+        starts_album: false,
+        date,
+        people,
+    }
+}
+
+/// Date of `asset` shown in its caption. The EXIF date, when asked for, falls back to the
+/// file creation date, which Immich sets from the EXIF when it can. Assets listed without
+/// their upload date show their file creation date.
+fn caption_date(asset: &AssetResponse, policy: AssetPolicy) -> DateTime<Utc> {
+    if policy.date_source == CaptionDateSource::Added {
+        return asset.created_at.unwrap_or(asset.file_created_at);
     }
+    let exif_date = asset
+        .exif_info
+        .as_ref()
+        .and_then(|exif| exif.date_time_original);
+    match policy.taken_date {
+        TakenDate::Exif => exif_date.unwrap_or(asset.file_created_at),
+        TakenDate::File => asset.file_created_at,
+    }
+}
+
+/// Recognized people and unassigned faces of `asset`, one entry per face.
+fn people_of(asset: &AssetResponse) -> Vec<Person> {
+    let named = asset.people.iter().flat_map(|person| {
+        let name = Some(person.name.clone()).filter(|name| !name.is_empty());
+        person.faces.iter().map(move |face| Person {
+            name: name.clone(),
+            face: Some(face.into()),
+        })
+    });
+    let unassigned = asset.unassigned_faces.iter().map(|face| Person {
+        name: None,
+        face: Some(face.into()),
+    });
+    named.chain(unassigned).collect()
+}
+
+impl From<&Face> for BoxInImage {
+    fn from(face: &Face) -> Self {
+        let clamp = |value: i32| value.max(0) as u32;
+        Self {
+            height: clamp(face.image_height),
+            width: clamp(face.image_width),
+            box_x_start: clamp(face.bounding_box_x1),
+            box_y_start: clamp(face.bounding_box_y1),
+            box_x_end: clamp(face.bounding_box_x2),
+            box_y_end: clamp(face.bounding_box_y2),
+        }
+    }
+}
+
+/// Picks the version of the assets to download from the configured quality.
+#[derive(Clone)]
+struct AssetSizeSelector {
+    quality: AssetQuality,
+    /// Size photos are resized to by the worker, usually the screen size.
+    ideal_max_size: watch::Receiver<Extent2<u32>>,
+}
+
+impl AssetSizeSelector {
+    fn select(&self, asset: &AssetResponse) -> AssetSize {
+        let size = select_size(self.quality, *self.ideal_max_size.borrow());
+        let decodable = asset
+            .original_mime_type
+            .as_deref()
+            .and_then(ImageFormat::from_mime_type)
+            .is_some_and(|format| format.reading_enabled());
+        if size == AssetSize::Original && !decodable {
+            debug!(
+                "Cannot decode original of asset {} ({:?}), using the preview",
+                asset.id, asset.original_mime_type
+            );
+            return AssetSize::Preview;
+        }
+        size
+    }
+}
+
+/// Immich resizes thumbnails and previews so that their shorter side has a given size.
+/// A photo fitted in `ideal_size` has its shorter side at most as long as the shorter
+/// side of `ideal_size`, so that's what decides whether a version is large enough.
+fn select_size(quality: AssetQuality, ideal_size: Extent2<u32>) -> AssetSize {
+    match quality {
+        AssetQuality::Thumbnail => AssetSize::Thumbnail,
+        AssetQuality::Preview => AssetSize::Preview,
+        AssetQuality::Original => AssetSize::Original,
+        AssetQuality::Auto => match ideal_size.reduce_min() {
+            side if side <= THUMBNAIL_RESOLUTION => AssetSize::Thumbnail,
+            side if side <= PREVIEW_RESOLUTION => AssetSize::Preview,
+            _ => AssetSize::Original,
+        },
+    }
+}
+
+/// Fetches Immich assets by id, for sources listing them explicitly.
+pub struct ImmichAssets {
+    client: ImmichClient,
+    sizes: AssetSizeSelector,
+    policy: AssetPolicy,
+}
+
+impl ImmichAssets {
+    pub fn new(
+        instance: &ImmichInstance,
+        taken_date: TakenDate,
+        ideal_max_size: watch::Receiver<Extent2<u32>>,
+    ) -> Self {
+        Self {
+            client: new_client(instance),
+            sizes: AssetSizeSelector {
+                quality: instance.asset_quality,
+                ideal_max_size,
+            },
+            policy: AssetPolicy {
+                taken_date,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Date shown in the captions, see [`Gallery::set_date_source`].
+    pub fn set_date_source(&mut self, source: CaptionDateSource) {
+        self.policy.date_source = source;
+    }
+
+    pub fn get_image(&self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        let deadline = Instant::now() + timeout;
+        let asset = self
+            .client
+            .get_asset_details(id)
+            .context("Cannot fetch asset with details")?;
+        let size = self.sizes.select(&asset);
+        download_asset(&self.client, asset, size, deadline, self.policy)
+    }
+}
+
+/// Removes the downloads a previous run left in the temporary directory, e.g. after a crash.
+pub fn remove_stale_downloads() {
+    client::remove_stale_downloads(&DownloadLimits::default().temp_dir);
+}
+
+fn new_client(instance: &ImmichInstance) -> ImmichClient {
+    const MB: u64 = 1024 * 1024;
+    ImmichClient::new(&instance.url, &instance.api_key).with_download_limits(DownloadLimits {
+        in_memory: instance.in_memory_download_mb.saturating_mul(MB),
+        max: instance.max_download_mb.saturating_mul(MB),
+        ..Default::default()
+    })
 }
 
-pub fn build_immich_providers(source: &ImmichSource) -> Result<Vec<Box<dyn GalleryProvider>>> {
-    source
-        .instance
+/// Sends a request to each instance in the background, logging how long it took, while
+/// the sources are built and the first photo fetched. Failures are only logged, the regular
+/// requests retry on their own.
+fn prewarm(instances: &[&ImmichInstance]) -> Vec<thread::JoinHandle<()>> {
+    instances
         .iter()
-        .chain(source.instances.iter())
+        .map(|instance| {
+            let instance = (*instance).clone();
+            thread::spawn(move || {
+                let start = Instant::now();
+                let client = ImmichClient::new(&instance.url, &instance.api_key);
+                match client.check_access(PREWARM_TIMEOUT) {
+                    Ok(()) => info!(
+                        "Immich instance {} reachable in {:?}",
+                        instance.url,
+                        start.elapsed()
+                    ),
+                    Err(err) => warn!(
+                        "Immich instance {} not reachable after {:?}: {:?}",
+                        instance.url,
+                        start.elapsed(),
+                        err
+                    ),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Builds a provider for each spec of each instance, with the weight of its spec.
+pub fn build_immich_providers(
+    source: &ImmichSource,
+    ideal_max_size: &watch::Receiver<Extent2<u32>>,
+) -> Result<Vec<(Box<dyn GalleryProvider>, u32)>> {
+    if let Some(spec) = source
+        .specs
+        .iter()
+        .find(|spec| !(1..=MAX_SPEC_WEIGHT).contains(&spec.weight))
+    {
+        bail!(
+            "Weight of spec {} must be between 1 and {MAX_SPEC_WEIGHT}",
+            describe_spec(&spec.spec)
+        );
+    }
+    let instances: Vec<_> = instances(source).collect();
+    if source.prewarm {
+        // Detached, not to delay the startup
+        drop(prewarm(&instances));
+    }
+    if source.persist_person_names {
+        match person_cache::person_ids_file() {
+            Some(path) => person_cache::person_ids().lock().unwrap().persist(path),
+            None => warn!("Cannot find cache directory, person names are searched on restart"),
+        }
+    }
+    let person_names_ttl = source.person_names_ttl;
+    let policy = AssetPolicy::of(source);
+    let clients: Vec<_> = instances
+        .iter()
+        .map(|instance| Rc::new(new_client(instance)))
+        .collect();
+    provider_specs(source)
+        .map(
+            |((id, instance), WeightedImmichSpec { spec, weight })| -> Result<_> {
+                let sizes = AssetSizeSelector {
+                    quality: instance.asset_quality,
+                    ideal_max_size: ideal_max_size.clone(),
+                };
+                let name = format!("{} {}", instance.url, describe_spec(spec));
+                let provider = ImmichGalleryProvider::new(
+                    name,
+                    &clients[id],
+                    sizes,
+                    instance.background_thumbnail,
+                    spec,
+                    person_names_ttl,
+                    policy,
+                )
+                .with_context(|| format!("Cannot build for client {id}"))?;
+                Ok((with_weather(Box::new(provider), spec)?, *weight))
+            },
+        )
+        .collect()
+}
+
+/// Wraps `provider` so that its query follows the weather, when its spec asks for it.
+#[cfg(feature = "weather")]
+fn with_weather(
+    provider: Box<dyn GalleryProvider>,
+    spec: &ImmichSpec,
+) -> Result<Box<dyn GalleryProvider>> {
+    Ok(match spec {
+        ImmichSpec::SmartSearch(ImmichSmartSearchQuery {
+            query,
+            weather: Some(config),
+            ..
+        }) => Box::new(WeatherGallery::new(
+            provider,
+            WeatherQuery::new(query.clone(), config.clone()),
+        )),
+        _ => provider,
+    })
+}
+
+#[cfg(not(feature = "weather"))]
+fn with_weather(
+    provider: Box<dyn GalleryProvider>,
+    spec: &ImmichSpec,
+) -> Result<Box<dyn GalleryProvider>> {
+    anyhow::ensure!(
+        !matches!(spec, ImmichSpec::SmartSearch(search) if search.weather.is_some()),
+        "Smart search \"weather\" option requires building with the \"weather\" feature"
+    );
+    Ok(provider)
+}
+
+fn instances(source: &ImmichSource) -> impl Iterator<Item = &ImmichInstance> + Clone {
+    source.instance.iter().chain(&source.instances)
+}
+
+/// Instance, with its index, and spec of each gallery built from `source`, in the order of
+/// [`build_immich_providers`].
+pub fn provider_specs(
+    source: &ImmichSource,
+) -> impl Iterator<Item = ((usize, &ImmichInstance), &WeightedImmichSpec)> {
+    instances(source)
         .enumerate()
-        .flat_map(|(id, instance)| {
-            let client = ImmichClient::new(&instance.url, &instance.api_key);
-            let client = Rc::new(client);
-            source
-                .specs
-                .iter()
-                .map(move |search| ImmichGalleryProvider::new(&client, search))
-                .map(move |p| match p {
-                    Ok(p) => Ok(Box::new(p) as Box<dyn GalleryProvider>),
-                    Err(err) => Err(err).context(format!("Cannot build for client {id}")),
-                })
+        .cartesian_product(&source.specs)
+}
+
+pub fn build_shared_link_provider(
+    source: &ImmichSharedLinkSource,
+    ideal_max_size: &watch::Receiver<Extent2<u32>>,
+) -> Result<Box<dyn GalleryProvider>> {
+    let (base_url, key) = parse_shared_link(&source.url)?;
+    let client = Rc::new(ImmichClient::shared_link(base_url, key));
+    let sizes = AssetSizeSelector {
+        quality: source.asset_quality,
+        ideal_max_size: ideal_max_size.clone(),
+    };
+    let request = ImmichRequest::SharedLink {
+        password: source.password.clone(),
+        name: None,
+    };
+    // The key is left out of status reports
+    let name = format!("{base_url} shared-link");
+    Ok(Box::new(ImmichGalleryProvider::with_request(
+        name, &client, sizes, true, request,
+    )))
+}
+
+/// Splits a link like "https://immich.example.com/share/<key>" into the URL of the instance
+/// and the key of the link.
+fn parse_shared_link(url: &str) -> Result<(&str, &str)> {
+    let (base_url, key) = url
+        .trim_end_matches('/')
+        .rsplit_once("/share/")
+        .with_context(|| {
+            format!("Shared link {url:?} should look like https://<host>/share/<key>")
+        })?;
+    anyhow::ensure!(
+        !key.is_empty() && !key.contains('/'),
+        "Shared link {url:?} should end with the key of the link"
+    );
+    Ok((base_url, key))
+}
+
+/// Assets returned by a single request of a spec, see [`check_spec`].
+pub struct SpecCheck {
+    /// Instance and spec, as named in status reports.
+    pub name: String,
+    /// Sets of ids the configured persons resolved to, when the spec filters on persons,
+    /// each one searched on its own.
+    pub person_sets: Option<Vec<Vec<String>>>,
+    /// Ids and cities of the assets, in the order Immich returned them.
+    pub assets: Vec<(String, Option<String>)>,
+}
+
+/// Loads a single batch of assets of the spec at `index` of `source`, counting the specs of
+/// each instance in turn like the slideshow does.
+pub fn check_spec(source: &ImmichSource, index: usize) -> Result<SpecCheck> {
+    let ((_, instance), WeightedImmichSpec { spec, .. }) = provider_specs(source)
+        .nth(index)
+        .with_context(|| format!("No spec {index} in this source"))?;
+    let client = Rc::new(new_client(instance));
+    let sizes = AssetSizeSelector {
+        quality: instance.asset_quality,
+        // Nothing is downloaded
+        ideal_max_size: watch::channel(Extent2::zero()).1,
+    };
+    let name = format!("{} {}", instance.url, describe_spec(spec));
+    let mut provider = ImmichGalleryProvider::new(
+        name,
+        &client,
+        sizes,
+        false,
+        spec,
+        source.person_names_ttl,
+        AssetPolicy::of(source),
+    )
+    .context("Cannot build the spec request")?;
+    let assets = provider.load_batch().context("Cannot load assets")?;
+    Ok(SpecCheck {
+        person_sets: provider.person_sets.clone(),
+        assets: assets
+            .into_iter()
+            .map(|asset| {
+                let city = asset.exif_info.and_then(|exif| exif.city);
+                (asset.id, city)
+            })
+            .collect(),
+        name: provider.name,
+    })
+}
+
+fn describe_spec(spec: &ImmichSpec) -> String {
+    match spec {
+        ImmichSpec::RandomSearch(_) => "random-search".into(),
+        ImmichSpec::SmartSearch(search) => format!("smart-search \"{}\"", search.query),
+        ImmichSpec::PrivateAlbum(PrivateAlbum { id }) => format!("private-album {id}"),
+        ImmichSpec::MemoryLane => "memory-lane".into(),
+    }
+}
+
+/// Whether `id` is the id of an Immich asset, which other frames can fetch from the same
+/// instance, unlike the ids of the demo photos, playlists and feeds.
+pub fn is_immich_asset_id(id: &str) -> bool {
+    // Immich identifies assets with UUIDs
+    id.len() == 36
+        && id.char_indices().all(|(index, c)| match index {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
         })
-        .try_collect()
+}
+
+/// Day and month whose memories are shown on `date`. Read at each batch, the date changes
+/// while running and the clock may only be set after startup.
+fn memory_lane_day(date: NaiveDate) -> (u8, u8) {
+    (date.day() as u8, date.month() as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use anyhow::anyhow;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true, none, some},
+    };
+    use serde_json::Value;
+    use tokio::sync::watch;
+    use vek::Extent2;
+
+    use super::{
+        build_immich_providers, caption_date, capture_order,
+        client::{AssetResponse, AssetSize, SearchRandomRequest},
+        details_of, expand_persons, hidden_in_stack, is_immich_asset_id, memory_lane_day,
+        new_client, parse_shared_link, prewarm, select_size, AssetPolicy, AssetSizeSelector,
+        ImmichGalleryProvider, ImmichRequest,
+    };
+    use crate::configuration::{
+        AssetQuality, CaptionDateSource, ImmichInstance, ImmichPerson, ImmichSource, ImmichSpec,
+        MissingExif, Stacks, TakenDate, WeightedImmichSpec,
+    };
+
+    #[gtest]
+    fn test_spec_weights_are_bounded() {
+        let (_, ideal_max_size) = watch::channel(Extent2::new(1920, 1080));
+        for weight in [0, 101] {
+            let source = ImmichSource {
+                specs: vec![WeightedImmichSpec {
+                    spec: ImmichSpec::MemoryLane,
+                    weight,
+                }],
+                ..Default::default()
+            };
+            let error = build_immich_providers(&source, &ideal_max_size).err();
+            expect_that!(
+                error.map(|error| error.to_string()),
+                some(eq("Weight of spec memory-lane must be between 1 and 100"))
+            );
+        }
+    }
+
+    fn auto(w: u32, h: u32) -> AssetSize {
+        select_size(AssetQuality::Auto, Extent2::new(w, h))
+    }
+
+    fn asset_json(id: &str, created_at: &str) -> Value {
+        serde_json::json!({
+            "id": id,
+            "localDateTime": created_at,
+            "fileCreatedAt": created_at,
+            "type": "IMAGE",
+            "people": [],
+        })
+    }
+
+    fn asset(id: &str, created_at: &str) -> AssetResponse {
+        serde_json::from_value(asset_json(id, created_at)).unwrap()
+    }
+
+    #[gtest]
+    fn test_timed_out_asset_skipped() {
+        let instance = ImmichInstance {
+            url: "http://immich".into(),
+            api_key: "key".into(),
+            asset_quality: AssetQuality::Preview,
+            in_memory_download_mb: 8,
+            max_download_mb: 200,
+            background_thumbnail: false,
+        };
+        let (_, ideal_max_size) = watch::channel(Extent2::new(1920, 1080));
+        let mut provider = ImmichGalleryProvider::with_request(
+            "test".into(),
+            &Rc::new(new_client(&instance)),
+            AssetSizeSelector {
+                quality: AssetQuality::Preview,
+                ideal_max_size,
+            },
+            false,
+            ImmichRequest::RandomSearch(SearchRandomRequest::default()),
+        );
+        let timeout = Duration::from_millis(500);
+        let failed = || Err::<(), _>(anyhow!("Connection reset"));
+        let late = provider.skip_if_late("slow".into(), timeout, Instant::now(), failed());
+        expect_that!(late.is_err(), is_true());
+        let in_time = Instant::now() + Duration::from_secs(60 * 60);
+        let early = provider.skip_if_late("broken".into(), timeout, in_time, failed());
+        expect_that!(early.is_err(), is_true());
+
+        let date = "2024-07-01T12:00:00Z";
+        // Taken from the end
+        provider.next_assets = vec![asset("fast", date), asset("slow", date)];
+        let next = provider.pop_next_asset();
+        expect_that!(next.map(|asset| asset.id), some(eq("fast")));
+        // Only the late ones are left aside
+        provider.next_assets = vec![asset("broken", date)];
+        let next = provider.pop_next_asset();
+        expect_that!(next.map(|asset| asset.id), some(eq("broken")));
+    }
+
+    #[gtest]
+    fn test_capture_order_takes_oldest_first() {
+        let mut assets = vec![
+            asset("second", "2024-07-02T09:00:00Z"),
+            asset("third", "2024-07-02T18:30:00Z"),
+            asset("first", "2024-07-01T12:00:00Z"),
+        ];
+        capture_order(&mut assets, AssetPolicy::default());
+        // Taken from the end
+        let taken: Vec<_> = assets.iter().rev().map(|asset| asset.id.as_str()).collect();
+        expect_that!(taken, eq(&vec!["first", "second", "third"]));
+    }
+
+    #[gtest]
+    fn test_parse_shared_link() {
+        expect_that!(
+            parse_shared_link("https://immich.example.com/share/AbC-123").unwrap(),
+            eq(("https://immich.example.com", "AbC-123"))
+        );
+        expect_that!(
+            parse_shared_link("http://nas:2283/immich/share/key/").unwrap(),
+            eq(("http://nas:2283/immich", "key"))
+        );
+        expect_that!(
+            parse_shared_link("https://immich.example.com/albums/id").is_err(),
+            eq(true)
+        );
+        expect_that!(
+            parse_shared_link("https://immich.example.com/share/").is_err(),
+            eq(true)
+        );
+    }
+
+    #[gtest]
+    fn test_memory_lane_day() {
+        let date = NaiveDate::from_ymd_opt(2025, 7, 14).unwrap();
+        expect_that!(memory_lane_day(date), eq((14, 7)));
+        let date = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        expect_that!(memory_lane_day(date), eq((31, 12)));
+    }
+
+    #[gtest]
+    fn test_auto_quality_thresholds() {
+        expect_that!(auto(320, 240), eq(AssetSize::Thumbnail));
+        expect_that!(auto(250, 250), eq(AssetSize::Thumbnail));
+        expect_that!(auto(800, 480), eq(AssetSize::Preview));
+        expect_that!(auto(1920, 1080), eq(AssetSize::Preview));
+        expect_that!(auto(2560, 1440), eq(AssetSize::Preview));
+        expect_that!(auto(2560, 1441), eq(AssetSize::Original));
+        expect_that!(auto(3840, 2160), eq(AssetSize::Original));
+        // Rotated screens
+        expect_that!(auto(1080, 1920), eq(AssetSize::Preview));
+        expect_that!(auto(2160, 3840), eq(AssetSize::Original));
+    }
+
+    #[gtest]
+    fn test_prewarm_failure_is_not_fatal() {
+        let instance = ImmichInstance {
+            // Nothing listens on port 1
+            url: "http://127.0.0.1:1".into(),
+            api_key: "key".into(),
+            asset_quality: AssetQuality::Preview,
+            in_memory_download_mb: 8,
+            max_download_mb: 200,
+            background_thumbnail: true,
+        };
+        for handle in prewarm(&[&instance]) {
+            expect_that!(handle.join().is_ok(), is_true());
+        }
+    }
+
+    #[gtest]
+    fn test_fixed_quality_ignores_screen_size() {
+        let screen = Extent2::new(3840, 2160);
+        expect_that!(
+            select_size(AssetQuality::Preview, screen),
+            eq(AssetSize::Preview)
+        );
+        expect_that!(
+            select_size(AssetQuality::Thumbnail, screen),
+            eq(AssetSize::Thumbnail)
+        );
+        expect_that!(
+            select_size(AssetQuality::Original, Extent2::new(800, 480)),
+            eq(AssetSize::Original)
+        );
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[gtest]
+    fn test_favorite_persons_expanded() {
+        let persons: Vec<ImmichPerson> = serde_json::from_value(serde_json::json!([
+            { "name": "Alice" },
+            "@favorites",
+            { "id": "id-3" },
+        ]))
+        .unwrap();
+        expect_that!(
+            expand_persons(&persons, vec![ids(&["id-1"])], &ids(&["id-2", "id-4"])),
+            some(eq(&vec![
+                ids(&["id-1", "id-3", "id-2"]),
+                ids(&["id-1", "id-3", "id-4"])
+            ]))
+        );
+        // Alice being a favorite, the photos of her with id-3 show a favorite already
+        expect_that!(
+            expand_persons(&persons, vec![ids(&["id-2"])], &ids(&["id-2", "id-4"])),
+            some(eq(&vec![ids(&["id-2", "id-3"])]))
+        );
+        // Alice also listed by id
+        expect_that!(
+            expand_persons(
+                &[
+                    ImmichPerson::Name("Alice".into()),
+                    ImmichPerson::Id("id-1".into())
+                ],
+                vec![ids(&["id-1"])],
+                &[]
+            ),
+            some(eq(&vec![ids(&["id-1"])]))
+        );
+    }
+
+    #[gtest]
+    fn test_no_favorite_persons() {
+        // Not filtering rather than matching nothing
+        expect_that!(
+            expand_persons(&[ImmichPerson::Favorites], Vec::new(), &[]),
+            none()
+        );
+        let persons = [ImmichPerson::Favorites, ImmichPerson::Id("id-1".into())];
+        expect_that!(
+            expand_persons(&persons, Vec::new(), &[]),
+            some(eq(&vec![ids(&["id-1"])]))
+        );
+    }
+
+    /// Asset as listed by Immich, with `stack` unless `None`.
+    fn stacked(id: &str, stack: Option<serde_json::Value>) -> AssetResponse {
+        let mut json = serde_json::json!({
+            "id": id,
+            "localDateTime": "2024-07-02T09:00:00Z",
+            "fileCreatedAt": "2024-07-02T09:00:00Z",
+            "type": "IMAGE",
+            "people": [],
+        });
+        if let Some(stack) = stack {
+            json["stack"] = stack;
+        }
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[gtest]
+    fn test_stacked_assets_hidden() {
+        let burst = || {
+            Some(serde_json::json!({
+                "id": "stack-1",
+                "primaryAssetId": "shot-1",
+                "assetCount": 3,
+            }))
+        };
+        let primary = stacked("shot-1", burst());
+        let member = stacked("shot-2", burst());
+        expect_that!(hidden_in_stack(&primary, Stacks::PrimaryOnly), is_false());
+        expect_that!(hidden_in_stack(&member, Stacks::PrimaryOnly), is_true());
+        expect_that!(hidden_in_stack(&member, Stacks::All), is_false());
+
+        let unstacked = stacked("single", Some(serde_json::Value::Null));
+        expect_that!(hidden_in_stack(&unstacked, Stacks::PrimaryOnly), is_false());
+        // Older versions of Immich, without stacks
+        let legacy = stacked("legacy", None);
+        expect_that!(hidden_in_stack(&legacy, Stacks::PrimaryOnly), is_false());
+    }
+
+    fn with_exif(mut asset: AssetResponse, exif: serde_json::Value) -> AssetResponse {
+        asset.exif_info = Some(serde_json::from_value(exif).unwrap());
+        asset
+    }
+
+    fn dates(taken_date: TakenDate, date_source: CaptionDateSource) -> AssetPolicy {
+        AssetPolicy {
+            taken_date,
+            date_source,
+            ..Default::default()
+        }
+    }
+
+    #[gtest]
+    fn test_caption_date_source() {
+        let created = "2024-07-02T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let taken = "2024-06-30T18:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let photo = with_exif(
+            asset("photo", "2024-07-02T09:00:00Z"),
+            serde_json::json!({ "dateTimeOriginal": "2024-06-30T18:00:00Z" }),
+        );
+        let exif = dates(TakenDate::Exif, CaptionDateSource::Taken);
+        expect_that!(caption_date(&photo, exif), eq(taken));
+        let file = dates(TakenDate::File, CaptionDateSource::Taken);
+        expect_that!(caption_date(&photo, file), eq(created));
+
+        // Screenshots and scans have EXIF without date
+        let scan = with_exif(asset("scan", "2024-07-02T09:00:00Z"), serde_json::json!({}));
+        expect_that!(caption_date(&scan, exif), eq(created));
+        let unprocessed = asset("unprocessed", "2024-07-02T09:00:00Z");
+        expect_that!(caption_date(&unprocessed, exif), eq(created));
+    }
+
+    #[gtest]
+    fn test_capture_order_follows_caption_date() {
+        // Uploaded in the wrong order, their EXIF tells otherwise
+        let mut assets = vec![
+            with_exif(
+                asset("first", "2024-07-02T09:00:00Z"),
+                serde_json::json!({ "dateTimeOriginal": "2024-06-30T18:00:00Z" }),
+            ),
+            asset("second", "2024-07-01T12:00:00Z"),
+        ];
+        let taken = |assets: &[AssetResponse]| -> Vec<String> {
+            assets.iter().rev().map(|asset| asset.id.clone()).collect()
+        };
+        capture_order(
+            &mut assets,
+            dates(TakenDate::Exif, CaptionDateSource::Taken),
+        );
+        expect_that!(taken(&assets), eq(&vec!["first", "second"]));
+        capture_order(
+            &mut assets,
+            dates(TakenDate::File, CaptionDateSource::Taken),
+        );
+        expect_that!(taken(&assets), eq(&vec!["second", "first"]));
+    }
+
+    #[gtest]
+    fn test_caption_date_added() {
+        let mut photo = with_exif(
+            asset("photo", "2024-07-02T09:00:00Z"),
+            serde_json::json!({ "dateTimeOriginal": "2024-06-30T18:00:00Z" }),
+        );
+        photo.created_at = Some("2025-01-05T20:00:00Z".parse().unwrap());
+        let added = dates(TakenDate::Exif, CaptionDateSource::Added);
+        expect_that!(
+            caption_date(&photo, added),
+            eq("2025-01-05T20:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        photo.created_at = None;
+        expect_that!(
+            caption_date(&photo, added),
+            eq("2024-07-02T09:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+    }
+
+    #[gtest]
+    fn test_missing_exif_caption() {
+        let policy = |missing| AssetPolicy {
+            missing,
+            ..Default::default()
+        };
+        let unprocessed = || asset("unprocessed", "2024-07-02T09:00:00Z");
+        let shown = details_of(unprocessed(), policy(MissingExif::Show));
+        expect_that!(shown.date.is_some(), is_true());
+        expect_that!(shown.city, none());
+        let hidden = details_of(unprocessed(), policy(MissingExif::HideCaption));
+        expect_that!(hidden.date, none());
+        expect_that!(hidden.asset_id, some(eq("unprocessed")));
+
+        // Only photos without EXIF lose their caption
+        let photo = with_exif(
+            asset("photo", "2024-07-02T09:00:00Z"),
+            serde_json::json!({ "city": "Bordeaux" }),
+        );
+        let details = details_of(photo, policy(MissingExif::HideCaption));
+        expect_that!(details.city, some(eq("Bordeaux")));
+        expect_that!(details.date.is_some(), is_true());
+    }
+
+    #[gtest]
+    fn test_only_immich_asset_ids_recognized() {
+        expect_that!(
+            is_immich_asset_id("1f3a0c2e-9b4d-4e6f-8a7b-0c1d2e3f4a5b"),
+            is_true()
+        );
+        expect_that!(is_immich_asset_id("demo:3"), is_false());
+        expect_that!(
+            is_immich_asset_id("immich:1f3a0c2e-9b4d-4e6f-8a7b-0c1d2e3f4a5b"),
+            is_false()
+        );
+        expect_that!(is_immich_asset_id("/photos/2024/beach.jpg"), is_false());
+        expect_that!(
+            is_immich_asset_id("1f3a0c2e_9b4d_4e6f_8a7b_0c1d2e3f4a5b"),
+            is_false()
+        );
+    }
 }
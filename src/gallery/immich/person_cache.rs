@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// Saved in the cache directory, see [`PersonCache::persist`].
+const PERSON_IDS_FILE: &str = "person_ids.json";
+/// Names matching no person are searched again sooner, they may just have been added to
+/// Immich, or tagged on a photo.
+const EMPTY_IDS_TTL: Duration = Duration::from_secs(60);
+
+static PERSON_IDS: OnceLock<Mutex<PersonCache>> = OnceLock::new();
+
+/// Ids that person names resolved to, by instance, shared by all the providers so that
+/// building them again doesn't search the same names.
+pub fn person_ids() -> &'static Mutex<PersonCache> {
+    PERSON_IDS.get_or_init(|| Mutex::new(PersonCache::default()))
+}
+
+/// File the resolved ids are saved to, `None` when the cache directory is unknown.
+pub fn person_ids_file() -> Option<PathBuf> {
+    ProjectDirs::from("com", "xabufr", "memocadre")
+        .map(|dirs| dirs.cache_dir().join(PERSON_IDS_FILE))
+}
+
+#[derive(Default)]
+pub struct PersonCache {
+    /// Ids and resolution time, by instance URL and person name.
+    entries: HashMap<(String, String), (Vec<String>, SystemTime)>,
+    /// File the entries are saved to on each change, once loaded from it.
+    path: Option<PathBuf>,
+}
+
+/// Entry of the saved file, JSON maps only have string keys.
+#[derive(Serialize, Deserialize)]
+struct SavedEntry {
+    instance: String,
+    name: String,
+    ids: Vec<String>,
+    resolved_at: SystemTime,
+}
+
+/// Ids resolved from person names, see [`resolve`].
+pub struct ResolvedNames {
+    /// Ids of each name, in the same order.
+    pub ids: Vec<Vec<String>>,
+    /// Whether some of the ids were cached, they may be outdated.
+    pub cached: bool,
+}
+
+impl PersonCache {
+    /// Loads the entries saved in `path`, and saves them there from then on. Done once,
+    /// the entries already resolved are kept.
+    pub fn persist(&mut self, path: PathBuf) {
+        if self.path.is_some() {
+            return;
+        }
+        match load_entries(&path) {
+            Ok(entries) => {
+                debug!("Loaded {} cached person names", entries.len());
+                for entry in entries {
+                    self.entries
+                        .entry((entry.instance, entry.name))
+                        .or_insert((entry.ids, entry.resolved_at));
+                }
+            }
+            Err(err) => warn!("Cannot load cached person names: {:?}", err),
+        }
+        self.path = Some(path);
+    }
+
+    /// Ids of `name` resolved less than `ttl` before `now`, or less than [`EMPTY_IDS_TTL`]
+    /// when it matched no person.
+    pub fn get(
+        &self,
+        instance: &str,
+        name: &str,
+        now: SystemTime,
+        ttl: Duration,
+    ) -> Option<Vec<String>> {
+        self.entries
+            .get(&(instance.to_owned(), name.to_owned()))
+            .filter(|(ids, resolved_at)| {
+                let ttl = if ids.is_empty() {
+                    ttl.min(EMPTY_IDS_TTL)
+                } else {
+                    ttl
+                };
+                // Resolved in the future when the clock went back, not to be trusted
+                now.duration_since(*resolved_at)
+                    .is_ok_and(|elapsed| elapsed < ttl)
+            })
+            .map(|(ids, _)| ids.clone())
+    }
+
+    pub fn insert(&mut self, instance: &str, name: &str, ids: Vec<String>, now: SystemTime) {
+        self.entries
+            .insert((instance.to_owned(), name.to_owned()), (ids, now));
+        self.save();
+    }
+
+    /// Forgets the ids of `name`, e.g. when Immich rejects them.
+    pub fn invalidate(&mut self, instance: &str, name: &str) {
+        if self
+            .entries
+            .remove(&(instance.to_owned(), name.to_owned()))
+            .is_some()
+        {
+            self.save();
+        }
+    }
+
+    /// Failures are only logged, the names are searched again after a restart.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let entries: Vec<SavedEntry> = self
+            .entries
+            .iter()
+            .map(|((instance, name), (ids, resolved_at))| SavedEntry {
+                instance: instance.clone(),
+                name: name.clone(),
+                ids: ids.clone(),
+                resolved_at: *resolved_at,
+            })
+            .collect();
+        if let Err(err) = save_entries(path, &entries) {
+            warn!("Cannot save cached person names: {:?}", err);
+        }
+    }
+}
+
+fn load_entries(path: &Path) -> Result<Vec<SavedEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = fs::File::open(path).context("Cannot open person names cache")?;
+    serde_json::from_reader(file).context("Cannot parse person names cache")
+}
+
+fn save_entries(path: &Path, entries: &[SavedEntry]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Cannot create cache directory")?;
+    }
+    let writer = fs::File::create(path).context("Cannot create person names cache")?;
+    serde_json::to_writer(writer, entries).context("Cannot serialize person names cache")
+}
+
+/// Ids of the persons named `names` on `instance`, reusing the ones resolved less than
+/// `ttl` ago. The other names are searched with `lookup`, concurrently.
+pub fn resolve(
+    cache: &Mutex<PersonCache>,
+    instance: &str,
+    names: &[&str],
+    ttl: Duration,
+    lookup: impl Fn(&str) -> Result<Vec<String>> + Sync,
+) -> Result<ResolvedNames> {
+    let now = SystemTime::now();
+    let mut ids: Vec<Option<Vec<String>>> = {
+        let cache = cache.lock().unwrap();
+        names
+            .iter()
+            .map(|name| cache.get(instance, name, now, ttl))
+            .collect()
+    };
+    let cached = ids.iter().any(Option::is_some);
+    let missing: Vec<&str> = names
+        .iter()
+        .zip(&ids)
+        .filter(|(_, ids)| ids.is_none())
+        .map(|(name, _)| *name)
+        .collect();
+    let lookup = &lookup;
+    let found: Vec<Result<Vec<String>>> = match missing.as_slice() {
+        [] => Vec::new(),
+        [name] => vec![lookup(name)],
+        _ => thread::scope(|scope| {
+            let searches: Vec<_> = missing
+                .iter()
+                .map(|name| scope.spawn(move || lookup(name)))
+                .collect();
+            searches
+                .into_iter()
+                .map(|search| search.join().expect("Person search panicked"))
+                .collect()
+        }),
+    };
+    let mut found = missing.into_iter().zip(found);
+    let mut cache = cache.lock().unwrap();
+    for (name, name_ids) in names.iter().zip(ids.iter_mut()) {
+        if name_ids.is_some() {
+            debug!("Using cached ids of {name}");
+            continue;
+        }
+        let (_, result) = found.next().expect("Every missing name is searched");
+        let result = result.with_context(|| format!("Cannot find person {name}"))?;
+        cache.insert(instance, name, result.clone(), now);
+        *name_ids = Some(result);
+    }
+    Ok(ResolvedNames {
+        ids: ids.into_iter().flatten().collect(),
+        cached,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::{Duration, SystemTime},
+    };
+
+    use anyhow::Result;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true, none, some},
+    };
+    use temp_dir::TempDir;
+
+    use super::{resolve, PersonCache};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[gtest]
+    fn test_person_ids_expire() {
+        let now = SystemTime::now();
+        let mut cache = PersonCache::default();
+        cache.insert("http://immich", "Alice", vec!["id-1".into()], now);
+
+        let ttl = Duration::from_secs(3600);
+        expect_that!(
+            cache.get(
+                "http://immich",
+                "Alice",
+                now + Duration::from_secs(3599),
+                ttl
+            ),
+            some(eq(&vec!["id-1".to_string()]))
+        );
+        expect_that!(cache.get("http://other", "Alice", now, ttl), none());
+        expect_that!(cache.get("http://immich", "Bob", now, ttl), none());
+        expect_that!(
+            cache.get(
+                "http://immich",
+                "Alice",
+                now + Duration::from_secs(3600),
+                ttl
+            ),
+            none()
+        );
+        // The clock went back
+        expect_that!(
+            cache.get("http://immich", "Alice", now - Duration::from_secs(60), ttl),
+            none()
+        );
+    }
+
+    #[gtest]
+    fn test_names_matching_nobody_expire_sooner() {
+        let now = SystemTime::now();
+        let mut cache = PersonCache::default();
+        cache.insert("http://immich", "Nobody", Vec::new(), now);
+
+        expect_that!(
+            cache.get(
+                "http://immich",
+                "Nobody",
+                now + Duration::from_secs(59),
+                DAY
+            ),
+            some(eq(&Vec::<String>::new()))
+        );
+        expect_that!(
+            cache.get(
+                "http://immich",
+                "Nobody",
+                now + Duration::from_secs(60),
+                DAY
+            ),
+            none()
+        );
+    }
+
+    /// Server answering searches with an id derived from the name, counting them.
+    fn lookup(searches: &AtomicUsize) -> impl Fn(&str) -> Result<Vec<String>> + Sync + '_ {
+        move |name| {
+            searches.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![format!("id-{name}")])
+        }
+    }
+
+    #[gtest]
+    fn test_names_searched_once_across_providers() {
+        let cache = Mutex::new(PersonCache::default());
+        let searches = AtomicUsize::new(0);
+        let first = resolve(
+            &cache,
+            "http://immich",
+            &["Alice", "Bob"],
+            DAY,
+            lookup(&searches),
+        )
+        .unwrap();
+        expect_that!(
+            first.ids,
+            eq(&vec![
+                vec!["id-Alice".to_string()],
+                vec!["id-Bob".to_string()]
+            ])
+        );
+        expect_that!(first.cached, is_false());
+        expect_that!(searches.load(Ordering::Relaxed), eq(2));
+
+        // Built again, e.g. after a configuration change
+        let second = resolve(
+            &cache,
+            "http://immich",
+            &["Bob", "Carol"],
+            DAY,
+            lookup(&searches),
+        )
+        .unwrap();
+        expect_that!(
+            second.ids,
+            eq(&vec![
+                vec!["id-Bob".to_string()],
+                vec!["id-Carol".to_string()]
+            ])
+        );
+        expect_that!(second.cached, is_true());
+        expect_that!(searches.load(Ordering::Relaxed), eq(3));
+
+        // Names of another instance are other persons
+        resolve(&cache, "http://other", &["Bob"], DAY, lookup(&searches)).unwrap();
+        expect_that!(searches.load(Ordering::Relaxed), eq(4));
+    }
+
+    #[gtest]
+    fn test_invalidated_name_searched_again() {
+        let cache = Mutex::new(PersonCache::default());
+        let searches = AtomicUsize::new(0);
+        resolve(&cache, "http://immich", &["Alice"], DAY, lookup(&searches)).unwrap();
+        cache.lock().unwrap().invalidate("http://immich", "Alice");
+        let resolved =
+            resolve(&cache, "http://immich", &["Alice"], DAY, lookup(&searches)).unwrap();
+        expect_that!(resolved.cached, is_false());
+        expect_that!(searches.load(Ordering::Relaxed), eq(2));
+    }
+
+    #[gtest]
+    fn test_failed_search_not_cached() {
+        let cache = Mutex::new(PersonCache::default());
+        let failing = |_: &str| -> Result<Vec<String>> { anyhow::bail!("Server down") };
+        expect_that!(
+            resolve(&cache, "http://immich", &["Alice"], DAY, failing).is_err(),
+            is_true()
+        );
+        let searches = AtomicUsize::new(0);
+        resolve(&cache, "http://immich", &["Alice"], DAY, lookup(&searches)).unwrap();
+        expect_that!(searches.load(Ordering::Relaxed), eq(1));
+    }
+
+    #[gtest]
+    fn test_resolved_names_survive_restart() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.child("cache").join("person_ids.json");
+        let searches = AtomicUsize::new(0);
+        let cache = Mutex::new(PersonCache::default());
+        cache.lock().unwrap().persist(path.clone());
+        resolve(&cache, "http://immich", &["Alice"], DAY, lookup(&searches)).unwrap();
+
+        let restarted = Mutex::new(PersonCache::default());
+        restarted.lock().unwrap().persist(path);
+        let resolved = resolve(
+            &restarted,
+            "http://immich",
+            &["Alice"],
+            DAY,
+            lookup(&searches),
+        )
+        .unwrap();
+        expect_that!(resolved.ids, eq(&vec![vec!["id-Alice".to_string()]]));
+        expect_that!(searches.load(Ordering::Relaxed), eq(1));
+    }
+}
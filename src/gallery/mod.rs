@@ -1,36 +1,163 @@
-use anyhow::{bail, Context, Result};
+use std::{
+    fmt::Display,
+    io::{BufRead, Cursor, Seek, SeekFrom},
+    ops::Range,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Error, Result};
 use chrono::{DateTime, Utc};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, DynamicImage, GenericImageView, ImageFormat, ImageReader,
+};
 use itertools::Itertools;
-use log::error;
+use log::{debug, error, info, warn};
+use tokio::sync::watch;
+use vek::Extent2;
+mod demo;
+mod enricher;
 mod immich;
+mod playlist;
+mod rss;
+mod skip_list;
+#[cfg(feature = "weather")]
+mod weather;
+
+pub use self::{
+    demo::DemoGalleryProvider,
+    enricher::{build_enrichers, ImageDetailsEnricher},
+    immich::{is_immich_asset_id, remove_stale_downloads, SpecCheck},
+};
+use self::{playlist::PlaylistGalleryProvider, rss::RssFeedGalleryProvider};
+use crate::{
+    configuration::{CaptionDateSource, Source},
+    network, random,
+};
 
-use crate::configuration::Source;
+/// Time before trying again a source that matched no photo.
+pub const EMPTY_RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Largest image downloaded from an URL, so that a wrong link cannot fill the memory.
+const MAX_IMAGE_DOWNLOAD_SIZE: usize = 50 * 1024 * 1024;
 
 pub trait Gallery {
-    fn get_next_image(&mut self) -> Result<ImageWithDetails>;
+    /// Fetch the next image, giving up on it when it cannot be downloaded within `timeout`.
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails>;
+
+    /// Fetch the next image as a small thumbnail, quicker to download, to show something
+    /// as soon as possible. The full image can then be fetched by id. Sources without
+    /// thumbnails return the full image.
+    fn get_next_thumbnail(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.get_next_image(timeout)
+    }
+
+    /// Fetch a specific image, e.g. the pinned photo.
+    fn get_image_by_id(&mut self, id: &str, _timeout: Duration) -> Result<ImageWithDetails> {
+        bail!("Cannot fetch image {id}: not supported by this source")
+    }
+
+    /// Descriptions of the sources that currently match no photo.
+    fn empty_sources(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Only fetch from the galleries in `range`, numbered like [`check_immich_spec`], taking
+    /// turns, or from all of them when `None`. See [`source_galleries`] for the galleries of a
+    /// configured source.
+    fn set_active_source(&mut self, range: Option<Range<usize>>) -> Result<()> {
+        match range {
+            None => Ok(()),
+            Some(range) => bail!("Cannot select sources {range:?}: not supported by this source"),
+        }
+    }
+
+    /// Fetch the photos of each batch in capture order, for sources fetching them in
+    /// batches, see [`crate::configuration::Settings::group_by_day`].
+    fn set_group_by_day(&mut self, _enabled: bool) {}
+
+    /// Date set on the details of the photos, for sources knowing both, see
+    /// [`crate::configuration::CaptionOptions::date_source`].
+    fn set_date_source(&mut self, _source: CaptionDateSource) {}
+}
+
+/// Returned when sources work but none of them match any photo. This is not a failure and
+/// must not be retried right away.
+#[derive(Debug)]
+pub struct GalleryEmpty {
+    pub sources: Vec<String>,
+}
+
+impl Display for GalleryEmpty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No photo matches {}", self.sources.join(", "))
+    }
 }
 
-trait GalleryProvider: Gallery {}
+impl std::error::Error for GalleryEmpty {}
+
+trait GalleryProvider: Gallery {
+    /// Whether trying again may fix `error`, like a network failure. Misconfigurations
+    /// are not retryable.
+    fn is_retryable(&self, error: &Error) -> bool;
+
+    /// Starts from a random photo, for sources showing them in order.
+    fn randomize_start(&mut self) {}
+
+    /// Replaces the smart search query, restarting the search from its first results.
+    #[cfg(feature = "weather")]
+    fn set_search_query(&mut self, _query: &str) {}
+}
 
 pub struct ImageWithDetails {
     pub image: image::DynamicImage,
     pub details: ImageDetails,
+    /// Set when `image` is a thumbnail, see [`Gallery::get_next_thumbnail`].
+    pub low_res: bool,
+    /// Smaller version of `image`, blurred for the background instead of it when set.
+    pub background_image: Option<DynamicImage>,
+    pub preparation: PreparationInfo,
+}
+
+/// How an image was obtained, for debugging.
+#[derive(Debug, Clone, Default)]
+pub struct PreparationInfo {
+    /// Version downloaded from sources offering several, e.g. "preview".
+    pub variant: Option<&'static str>,
 }
 
 pub struct ImageDetails {
+    /// Identifier of the image in its source, to fetch it again later.
+    pub asset_id: Option<String>,
+    /// Album the image was listed from, when the source knows it.
+    pub album: Option<String>,
+    /// Whether the image is the first of its album after images of another one from the
+    /// same source, whatever the sources taking turns in between.
+    pub starts_album: bool,
     pub city: Option<String>,
-    pub date: Option<DateTime<Utc>>,
+    /// Country code, e.g. "FR".
     #[allow(dead_code)]
+    pub country: Option<String>,
+    /// Where the photo was taken.
+    pub gps: Option<GeoPoint>,
+    pub date: Option<DateTime<Utc>>,
     pub people: Vec<Person>,
 }
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
 pub struct Person {
+    #[allow(dead_code)]
     pub name: Option<String>,
     pub face: Option<BoxInImage>,
 }
 
-#[allow(dead_code)]
+/// Area of an image, in the coordinates of an image of `width` by `height` pixels which may
+/// be another version of it.
 pub struct BoxInImage {
     pub height: u32,
     pub width: u32,
@@ -40,34 +167,883 @@ pub struct BoxInImage {
     pub box_y_end: u32,
 }
 
+/// Decodes an image, rejecting empty ones that cannot be laid out. Only the first frame of
+/// animated images is decoded.
+fn decode_image(reader: ImageReader<impl BufRead + Seek>) -> Result<DynamicImage> {
+    let reader = reader
+        .with_guessed_format()
+        .context("Cannot guess image format")?;
+    let reader = match reader.format() {
+        Some(format) => {
+            let mut inner = reader.into_inner();
+            let start = inner.stream_position().context("Cannot read image")?;
+            if is_animated(format, &mut inner) {
+                warn!("Animated {format:?} image, only its first frame is shown");
+            }
+            inner
+                .seek(SeekFrom::Start(start))
+                .context("Cannot read image")?;
+            ImageReader::with_format(inner, format)
+        }
+        None => reader,
+    };
+    let image = reader.decode().context("Cannot decode image")?;
+    check_dimensions(&image)?;
+    Ok(image)
+}
+
+/// Whether the image has several frames. Formats without animations, and images that
+/// cannot be read, are not animated.
+fn is_animated(format: ImageFormat, reader: &mut (impl BufRead + Seek)) -> bool {
+    let animated = match format {
+        ImageFormat::Gif => {
+            GifDecoder::new(reader).map(|decoder| decoder.into_frames().take(2).count() > 1)
+        }
+        ImageFormat::Png => PngDecoder::new(reader).and_then(|decoder| decoder.is_apng()),
+        ImageFormat::WebP => WebPDecoder::new(reader).map(|decoder| decoder.has_animation()),
+        _ => return false,
+    };
+    animated.unwrap_or_else(|err| {
+        debug!("Cannot check whether the {format:?} image is animated: {err}");
+        false
+    })
+}
+
+/// Downloads and decodes the image at `url`.
+fn download_image(url: &str, timeout: Duration) -> Result<DynamicImage> {
+    let bytes =
+        download_limited(url, timeout, MAX_IMAGE_DOWNLOAD_SIZE).context("Cannot download image")?;
+    decode_image(ImageReader::new(Cursor::new(bytes)))
+}
+
+/// Body of the response to `url`, given up on once larger than `max_size` bytes.
+fn download_limited(url: &str, timeout: Duration, max_size: usize) -> Result<Vec<u8>> {
+    let response = network::get(url)
+        .with_timeout(timeout.as_secs().max(1))
+        .send_lazy()?;
+    if response.status_code >= 400 {
+        bail!("{} {}", response.status_code, response.reason_phrase);
+    }
+    let announced = response
+        .headers
+        .get("content-length")
+        .and_then(|length| length.trim().parse::<usize>().ok());
+    if let Some(length) = announced.filter(|length| *length > max_size) {
+        bail!("{length} bytes, more than {max_size}");
+    }
+    let mut bytes = Vec::with_capacity(announced.unwrap_or_default());
+    for byte in response {
+        let (byte, _) = byte?;
+        if bytes.len() == max_size {
+            bail!("More than {max_size} bytes");
+        }
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+fn check_dimensions(image: &DynamicImage) -> Result<()> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        bail!("Image has no pixels ({width}x{height})");
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+struct RetryPolicy {
+    retries: u8,
+    delay: Duration,
+}
+
+struct RetryingGallery {
+    gallery: Box<dyn GalleryProvider>,
+    /// Turns taken by the gallery in the rotation, see [`GalleryImpl::rotation`].
+    weight: u32,
+    retry: RetryPolicy,
+    /// Set when the gallery matched no photo, it's skipped until `retry_at`.
+    empty: Option<EmptyGallery>,
+    /// Album of its last photo, see [`ImageDetails::starts_album`].
+    last_album: Option<String>,
+}
+
+struct EmptyGallery {
+    sources: Vec<String>,
+    retry_at: Instant,
+}
+
 struct GalleryImpl {
-    galleries: Vec<Box<dyn GalleryProvider>>,
+    galleries: Vec<RetryingGallery>,
+    /// Next turn in [`GalleryImpl::rotation`].
     next: usize,
+    /// Galleries taking turns instead of all of them, see [`Gallery::set_active_source`].
+    active: Option<Range<usize>>,
 }
 
-pub fn build_sources(sources: &[Source]) -> Result<Box<dyn Gallery>> {
-    let galleries = sources
+/// Builds the galleries, `ideal_max_size` being the size photos are resized to. With
+/// `randomize_start`, the first photo is from a random source rather than the first one.
+/// The demo photos are shown when no source is configured.
+pub fn build_sources(
+    sources: &[Source],
+    randomize_start: bool,
+    ideal_max_size: watch::Receiver<Extent2<u32>>,
+) -> Result<Box<dyn Gallery>> {
+    let demo = [Source::Demo];
+    let sources = if sources.is_empty() {
+        info!("No source configured, showing the built-in demo photos");
+        &demo[..]
+    } else {
+        sources
+    };
+    let mut galleries: Vec<RetryingGallery> = sources
         .iter()
         .enumerate()
-        .map(|(id, source)| match source {
-            Source::Immich(immich_source) => immich::build_immich_providers(immich_source)
-                .context(format!("Cannot build source {id}")),
+        .map(|(id, source)| -> Result<Vec<RetryingGallery>> {
+            match source {
+                Source::Immich(immich_source) => {
+                    let retry = RetryPolicy {
+                        retries: immich_source.retries,
+                        delay: immich_source.retry_delay,
+                    };
+                    let providers = immich::build_immich_providers(immich_source, &ideal_max_size)
+                        .context(format!("Cannot build source {id}"))?;
+                    Ok(providers
+                        .into_iter()
+                        .map(|(gallery, weight)| RetryingGallery {
+                            gallery,
+                            weight,
+                            retry,
+                            empty: None,
+                            last_album: None,
+                        })
+                        .collect())
+                }
+                Source::ImmichSharedLink(link_source) => {
+                    let gallery = immich::build_shared_link_provider(link_source, &ideal_max_size)
+                        .context(format!("Cannot build source {id}"))?;
+                    Ok(vec![RetryingGallery {
+                        gallery,
+                        weight: 1,
+                        retry: RetryPolicy {
+                            retries: 0,
+                            delay: Duration::ZERO,
+                        },
+                        empty: None,
+                        last_album: None,
+                    }])
+                }
+                Source::Playlist(playlist_source) => {
+                    let gallery =
+                        PlaylistGalleryProvider::new(playlist_source, ideal_max_size.clone())
+                            .context(format!("Cannot build source {id}"))?;
+                    Ok(vec![RetryingGallery {
+                        gallery: Box::new(gallery),
+                        weight: 1,
+                        retry: RetryPolicy {
+                            retries: 0,
+                            delay: Duration::ZERO,
+                        },
+                        empty: None,
+                        last_album: None,
+                    }])
+                }
+                Source::RssFeed(feed_source) => Ok(vec![RetryingGallery {
+                    gallery: Box::new(RssFeedGalleryProvider::new(feed_source)),
+                    weight: 1,
+                    retry: RetryPolicy {
+                        retries: 0,
+                        delay: Duration::ZERO,
+                    },
+                    empty: None,
+                    last_album: None,
+                }]),
+                Source::Demo => Ok(vec![RetryingGallery {
+                    gallery: Box::new(DemoGalleryProvider::new()),
+                    weight: 1,
+                    retry: RetryPolicy {
+                        retries: 0,
+                        delay: Duration::ZERO,
+                    },
+                    empty: None,
+                    last_album: None,
+                }]),
+            }
         })
         .flatten_ok()
         .try_collect()?;
-    Ok(Box::new(GalleryImpl { galleries, next: 0 }))
+    let mut next = 0;
+    if randomize_start && !galleries.is_empty() {
+        let turns: u32 = galleries.iter().map(|gallery| gallery.weight).sum();
+        next = random::index(turns as usize);
+        for gallery in &mut galleries {
+            gallery.gallery.randomize_start();
+        }
+    }
+    Ok(Box::new(GalleryImpl {
+        galleries,
+        next,
+        active: None,
+    }))
+}
+
+/// Number of galleries built from `source`, each selectable on its own.
+fn gallery_count(source: &Source) -> usize {
+    match source {
+        Source::Immich(immich_source) => immich::provider_specs(immich_source).count(),
+        Source::ImmichSharedLink(_) | Source::Playlist(_) | Source::RssFeed(_) | Source::Demo => 1,
+    }
+}
+
+/// Galleries built from the source at `index` in `sources`, each Immich instance and spec
+/// being a gallery of its own.
+pub fn source_galleries(sources: &[Source], index: usize) -> Option<Range<usize>> {
+    let start = sources.get(..index)?.iter().map(gallery_count).sum();
+    Some(start..start + gallery_count(sources.get(index)?))
+}
+
+/// Number of galleries built from `sources`, each one selectable with
+/// [`Gallery::set_active_source`].
+pub fn gallery_total(sources: &[Source]) -> usize {
+    sources.iter().map(gallery_count).sum()
+}
+
+/// Source of the gallery at `index`, numbered like [`Gallery::set_active_source`], with the
+/// index of the gallery within the source.
+fn locate_gallery(sources: &[Source], mut index: usize) -> Option<(&Source, usize)> {
+    for source in sources {
+        let count = gallery_count(source);
+        if index < count {
+            return Some((source, index));
+        }
+        index -= count;
+    }
+    None
+}
+
+/// Runs the search of the Immich gallery at `index` once, numbered like
+/// [`Gallery::set_active_source`], to check what it matches.
+pub fn check_immich_spec(sources: &[Source], index: usize) -> Result<SpecCheck> {
+    match locate_gallery(sources, index) {
+        Some((Source::Immich(immich_source), index)) => immich::check_spec(immich_source, index),
+        Some(_) => bail!("Source {index} is not an Immich spec"),
+        None => bail!("No source {index}"),
+    }
+}
+
+impl Gallery for RetryingGallery {
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        let mut attempt = 0;
+        loop {
+            match self.gallery.get_next_image(timeout) {
+                Err(error)
+                    if attempt < self.retry.retries
+                        && !error.is::<GalleryEmpty>()
+                        && self.gallery.is_retryable(&error) =>
+                {
+                    attempt += 1;
+                    warn!(
+                        "Cannot get next image, retrying in {:?} ({attempt}/{}): {:?}",
+                        self.retry.delay, self.retry.retries, error
+                    );
+                    thread::sleep(self.retry.delay);
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn get_next_thumbnail(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        // Only used to speed up the first photo, the full image path retries on failure
+        self.gallery.get_next_thumbnail(timeout)
+    }
+
+    fn get_image_by_id(&mut self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        self.gallery.get_image_by_id(id, timeout)
+    }
+
+    fn set_group_by_day(&mut self, enabled: bool) {
+        self.gallery.set_group_by_day(enabled);
+    }
+
+    fn set_date_source(&mut self, source: CaptionDateSource) {
+        self.gallery.set_date_source(source);
+    }
+}
+
+impl GalleryImpl {
+    /// Indices of the galleries in the order they take turns, each one as many times as
+    /// its weight, spread out by smooth weighted round-robin: each gallery earns its
+    /// weight on each turn, the richest one takes it and pays the total weight. Equal
+    /// weights take turns in order.
+    fn rotation(&self) -> Vec<usize> {
+        let total: i64 = self
+            .galleries
+            .iter()
+            .map(|gallery| i64::from(gallery.weight))
+            .sum();
+        let mut credits = vec![0; self.galleries.len()];
+        (0..total)
+            .filter_map(|_| {
+                for (credit, gallery) in credits.iter_mut().zip(&self.galleries) {
+                    *credit += i64::from(gallery.weight);
+                }
+                // The first one wins ties
+                let (index, _) = credits
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .max_by_key(|(_, credit)| **credit)?;
+                credits[index] -= total;
+                Some(index)
+            })
+            .collect()
+    }
+
+    /// Galleries taking turns, the active ones or else all of them.
+    fn selected(&self) -> Range<usize> {
+        self.active.clone().unwrap_or(0..self.galleries.len())
+    }
+
+    /// Fetches from the next source in turn, skipping the empty ones.
+    fn next_image(
+        &mut self,
+        timeout: Duration,
+        fetch: impl Fn(&mut RetryingGallery, Duration) -> Result<ImageWithDetails>,
+    ) -> Result<ImageWithDetails> {
+        let selected = self.selected();
+        let now = Instant::now();
+        let mut failed = false;
+        let rotation = self.rotation();
+        let mut tried = vec![false; self.galleries.len()];
+        for _ in 0..rotation.len() {
+            let index = rotation[self.next % rotation.len()];
+            self.next = (self.next + 1) % rotation.len();
+            // The others keep their turn for when the selection ends
+            if !selected.contains(&index) {
+                continue;
+            }
+            // Each gallery is tried once per photo, whatever its weight
+            if std::mem::replace(&mut tried[index], true) {
+                continue;
+            }
+            let gallery = &mut self.galleries[index];
+            if gallery
+                .empty
+                .as_ref()
+                .is_some_and(|empty| empty.retry_at > now)
+            {
+                continue;
+            }
+            match fetch(gallery, timeout) {
+                Ok(mut res) => {
+                    gallery.empty = None;
+                    res.details.starts_album =
+                        res.details.album.is_some() && res.details.album != gallery.last_album;
+                    gallery.last_album = res.details.album.clone();
+                    return Ok(res);
+                }
+                Err(error) => match error.downcast::<GalleryEmpty>() {
+                    Ok(GalleryEmpty { sources }) => {
+                        warn!("No photo matches {}", sources.join(", "));
+                        gallery.empty = Some(EmptyGallery {
+                            sources,
+                            retry_at: now + EMPTY_RETRY_INTERVAL,
+                        });
+                    }
+                    Err(error) => {
+                        error!("Cannot get next image: {:?}", error);
+                        failed = true;
+                    }
+                },
+            }
+        }
+        if !failed
+            && self.galleries[selected]
+                .iter()
+                .all(|gallery| gallery.empty.is_some())
+        {
+            return Err(GalleryEmpty {
+                sources: self.empty_sources(),
+            }
+            .into());
+        }
+        bail!("All sources have failed")
+    }
 }
 
 impl Gallery for GalleryImpl {
-    fn get_next_image(&mut self) -> Result<ImageWithDetails> {
-        for _ in 0..self.galleries.len() {
-            let res = self.galleries[self.next].get_next_image();
-            self.next = (self.next + 1) % self.galleries.len();
-            match res {
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.next_image(timeout, RetryingGallery::get_next_image)
+    }
+
+    fn get_next_thumbnail(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.next_image(timeout, RetryingGallery::get_next_thumbnail)
+    }
+
+    fn get_image_by_id(&mut self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        for gallery in self.galleries.iter_mut() {
+            match gallery.get_image_by_id(id, timeout) {
                 Ok(res) => return Ok(res),
-                Err(error) => error!("Cannot get next image: {:?}", error),
+                Err(error) => debug!("Image {id} not found in source: {:?}", error),
             }
         }
-        bail!("All sources have failed")
+        bail!("Image {id} not found in any source")
+    }
+
+    fn empty_sources(&self) -> Vec<String> {
+        self.galleries
+            .iter()
+            .filter_map(|gallery| gallery.empty.as_ref())
+            .flat_map(|empty| empty.sources.iter().cloned())
+            .collect()
+    }
+
+    fn set_active_source(&mut self, range: Option<Range<usize>>) -> Result<()> {
+        if let Some(range) = &range {
+            ensure!(
+                !range.is_empty() && range.end <= self.galleries.len(),
+                "Cannot select sources {range:?}, there are only {} sources",
+                self.galleries.len()
+            );
+        }
+        self.active = range;
+        Ok(())
+    }
+
+    fn set_group_by_day(&mut self, enabled: bool) {
+        for gallery in &mut self.galleries {
+            gallery.set_group_by_day(enabled);
+        }
+    }
+
+    fn set_date_source(&mut self, source: CaptionDateSource) {
+        for gallery in &mut self.galleries {
+            gallery.set_date_source(source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        io::{BufRead, BufReader, Cursor, Write},
+        net::TcpListener,
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{anyhow, Error, Result};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true},
+    };
+    use image::{codecs::gif::GifEncoder, Frame, ImageFormat, ImageReader, RgbaImage};
+
+    use super::{
+        check_dimensions, decode_image, download_limited, gallery_total, is_animated,
+        locate_gallery, source_galleries, Gallery, GalleryEmpty, GalleryImpl, GalleryProvider,
+        ImageDetails, ImageWithDetails, RetryPolicy, RetryingGallery,
+    };
+    use crate::configuration::{ImmichInstance, ImmichSource, ImmichSpec, RssFeedSource, Source};
+
+    /// Result returned by [`ScriptedGallery`]: an image, a retryable or fatal error, or no
+    /// matching photo.
+    enum Outcome {
+        Image,
+        /// Image listed from an album.
+        Album(&'static str),
+        Transient,
+        Fatal,
+        Empty,
+    }
+
+    /// Returns its outcomes in order, then fails with a fatal error.
+    struct ScriptedGallery {
+        outcomes: VecDeque<Outcome>,
+    }
+
+    #[derive(Debug)]
+    struct FatalError;
+
+    impl std::fmt::Display for FatalError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fatal")
+        }
+    }
+
+    impl std::error::Error for FatalError {}
+
+    impl Gallery for ScriptedGallery {
+        fn get_next_image(&mut self, _timeout: Duration) -> Result<ImageWithDetails> {
+            let album = match self.outcomes.pop_front() {
+                Some(Outcome::Image) => None,
+                Some(Outcome::Album(album)) => Some(album.to_owned()),
+                Some(Outcome::Transient) => return Err(anyhow!("network blip")),
+                Some(Outcome::Empty) => {
+                    return Err(GalleryEmpty {
+                        sources: vec!["empty spec".into()],
+                    }
+                    .into())
+                }
+                Some(Outcome::Fatal) | None => return Err(FatalError.into()),
+            };
+            Ok(ImageWithDetails {
+                image: image::DynamicImage::new_rgb8(1, 1),
+                details: ImageDetails {
+                    asset_id: None,
+                    album,
+                    starts_album: false,
+                    city: None,
+                    country: None,
+                    gps: None,
+                    date: None,
+                    people: Vec::new(),
+                },
+                low_res: false,
+                background_image: None,
+                preparation: Default::default(),
+            })
+        }
+    }
+
+    impl GalleryProvider for ScriptedGallery {
+        fn is_retryable(&self, error: &Error) -> bool {
+            !error.is::<FatalError>()
+        }
+    }
+
+    fn gallery(retries: u8, outcomes: impl IntoIterator<Item = Outcome>) -> RetryingGallery {
+        RetryingGallery {
+            gallery: Box::new(ScriptedGallery {
+                outcomes: outcomes.into_iter().collect(),
+            }),
+            weight: 1,
+            retry: RetryPolicy {
+                retries,
+                delay: Duration::ZERO,
+            },
+            empty: None,
+            last_album: None,
+        }
+    }
+
+    fn remaining(gallery: &mut RetryingGallery) -> usize {
+        // Count the images left in the script, skipping the retry logic.
+        let mut remaining = 0;
+        while gallery.gallery.get_next_image(Duration::ZERO).is_ok() {
+            remaining += 1;
+        }
+        remaining
+    }
+
+    #[gtest]
+    fn test_transient_errors_are_retried() {
+        let mut gallery = gallery(2, [Outcome::Transient, Outcome::Transient, Outcome::Image]);
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+    }
+
+    #[gtest]
+    fn test_retries_are_limited() {
+        let mut gallery = gallery(
+            1,
+            [
+                Outcome::Transient,
+                Outcome::Transient,
+                Outcome::Image,
+                Outcome::Image,
+            ],
+        );
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_err(), is_true());
+        expect_that!(remaining(&mut gallery), eq(2));
+    }
+
+    #[gtest]
+    fn test_fatal_errors_are_not_retried() {
+        let mut gallery = gallery(3, [Outcome::Fatal, Outcome::Image]);
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_err(), is_true());
+        expect_that!(remaining(&mut gallery), eq(1));
+    }
+
+    #[gtest]
+    fn test_moves_to_next_source_once_retries_exhausted() {
+        let mut gallery = GalleryImpl {
+            galleries: vec![
+                gallery(1, [Outcome::Transient, Outcome::Transient]),
+                gallery(0, [Outcome::Image]),
+            ],
+            next: 0,
+            active: None,
+        };
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        expect_that!(gallery.next, eq(0));
+    }
+
+    #[gtest]
+    fn test_empty_sources_are_skipped() {
+        let mut gallery = GalleryImpl {
+            galleries: vec![
+                gallery(2, [Outcome::Empty, Outcome::Image]),
+                gallery(0, [Outcome::Image, Outcome::Image]),
+            ],
+            next: 0,
+            active: None,
+        };
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        expect_that!(gallery.empty_sources(), eq(&vec!["empty spec".to_string()]));
+
+        // The empty source is not queried again before EMPTY_RETRY_INTERVAL.
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        expect_that!(remaining(&mut gallery.galleries[0]), eq(1));
+    }
+
+    #[gtest]
+    fn test_all_sources_empty() {
+        let mut gallery = GalleryImpl {
+            galleries: vec![gallery(2, [Outcome::Empty]), gallery(2, [Outcome::Empty])],
+            next: 0,
+            active: None,
+        };
+        let error = gallery.get_next_image(Duration::ZERO).unwrap_err();
+        expect_that!(error.is::<GalleryEmpty>(), is_true());
+        expect_that!(gallery.empty_sources().len(), eq(2));
+    }
+
+    #[gtest]
+    fn test_failure_is_not_reported_as_empty() {
+        let mut gallery = GalleryImpl {
+            galleries: vec![gallery(0, [Outcome::Empty]), gallery(0, [Outcome::Fatal])],
+            next: 0,
+            active: None,
+        };
+        let error = gallery.get_next_image(Duration::ZERO).unwrap_err();
+        expect_that!(error.is::<GalleryEmpty>(), is_false());
+    }
+
+    #[gtest]
+    fn test_weighted_rotation() {
+        let weighted = |weights: &[u32]| GalleryImpl {
+            galleries: weights
+                .iter()
+                .map(|weight| RetryingGallery {
+                    weight: *weight,
+                    ..gallery(0, (0..20).map(|_| Outcome::Image))
+                })
+                .collect(),
+            next: 0,
+            active: None,
+        };
+        expect_that!(weighted(&[1, 1, 1]).rotation(), eq(&vec![0, 1, 2]));
+        // 70% from the first spec, spread out
+        expect_that!(
+            weighted(&[7, 3]).rotation(),
+            eq(&vec![0, 1, 0, 0, 0, 1, 0, 0, 1, 0])
+        );
+
+        let mut gallery = weighted(&[3, 1]);
+        for _ in 0..8 {
+            expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        }
+        expect_that!(remaining(&mut gallery.galleries[0]), eq(14));
+        expect_that!(remaining(&mut gallery.galleries[1]), eq(18));
+    }
+
+    #[gtest]
+    fn test_album_starts_within_its_source() {
+        let mut gallery = GalleryImpl {
+            galleries: vec![
+                gallery(0, [Outcome::Album("Holidays"), Outcome::Album("Holidays")]),
+                gallery(
+                    0,
+                    [
+                        Outcome::Album("Birthday"),
+                        Outcome::Album("Wedding"),
+                        Outcome::Image,
+                    ],
+                ),
+            ],
+            next: 0,
+            active: None,
+        };
+        let starts = |gallery: &mut GalleryImpl| {
+            gallery
+                .get_next_image(Duration::ZERO)
+                .unwrap()
+                .details
+                .starts_album
+        };
+        expect_that!(starts(&mut gallery), is_true());
+        expect_that!(starts(&mut gallery), is_true());
+        // The sources take turns, each one still in its album
+        expect_that!(starts(&mut gallery), is_false());
+        expect_that!(starts(&mut gallery), is_true());
+        // No album
+        gallery.galleries.remove(0);
+        expect_that!(starts(&mut gallery), is_false());
+    }
+
+    #[gtest]
+    fn test_empty_images_are_rejected() {
+        expect_that!(
+            check_dimensions(&image::DynamicImage::new_rgb8(1, 1)).is_ok(),
+            is_true()
+        );
+        expect_that!(
+            check_dimensions(&image::DynamicImage::new_rgb8(10, 0)).is_err(),
+            is_true()
+        );
+    }
+
+    /// GIF with a frame of `width` pixels by 1 for each width.
+    fn gif(widths: &[u32]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = GifEncoder::new(&mut data);
+        for width in widths {
+            encoder
+                .encode_frame(Frame::new(RgbaImage::new(*width, 1)))
+                .unwrap();
+        }
+        drop(encoder);
+        data
+    }
+
+    #[gtest]
+    fn test_animated_images_are_detected() {
+        let animated = gif(&[2, 2]);
+        expect_that!(
+            is_animated(ImageFormat::Gif, &mut Cursor::new(&animated)),
+            is_true()
+        );
+        expect_that!(
+            is_animated(ImageFormat::Gif, &mut Cursor::new(gif(&[2]))),
+            is_false()
+        );
+
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(2, 1)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        expect_that!(
+            is_animated(ImageFormat::Png, &mut Cursor::new(&png)),
+            is_false()
+        );
+        expect_that!(
+            is_animated(ImageFormat::Png, &mut Cursor::new(b"not a png")),
+            is_false()
+        );
+
+        // The first frame is still shown
+        let image = decode_image(ImageReader::new(Cursor::new(&animated))).unwrap();
+        expect_that!(image.width(), eq(2));
+    }
+
+    #[gtest]
+    fn test_active_source() {
+        let mut gallery = GalleryImpl {
+            galleries: vec![
+                gallery(0, [Outcome::Image, Outcome::Image]),
+                gallery(0, [Outcome::Image, Outcome::Image, Outcome::Image]),
+            ],
+            next: 0,
+            active: None,
+        };
+        expect_that!(gallery.set_active_source(Some(2..3)).is_err(), is_true());
+        expect_that!(gallery.set_active_source(Some(1..2)).is_ok(), is_true());
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        expect_that!(remaining(&mut gallery.galleries[1]), eq(1));
+
+        // Back to taking turns, from where it stopped
+        gallery.set_active_source(None).unwrap();
+        expect_that!(gallery.get_next_image(Duration::ZERO).is_ok(), is_true());
+        expect_that!(remaining(&mut gallery.galleries[0]), eq(1));
+    }
+
+    /// A feed, 2 Immich instances of 2 specs each, and another feed.
+    fn sources() -> [Source; 3] {
+        let instance = ImmichInstance {
+            url: "http://immich".into(),
+            api_key: "key".into(),
+            asset_quality: Default::default(),
+            in_memory_download_mb: 8,
+            max_download_mb: 200,
+            background_thumbnail: true,
+        };
+        let feed = Source::RssFeed(RssFeedSource {
+            url: "http://feed".into(),
+            refresh_interval: Duration::from_secs(60),
+        });
+        [
+            feed.clone(),
+            Source::Immich(ImmichSource {
+                instance: Some(instance.clone()),
+                instances: vec![instance],
+                specs: vec![ImmichSpec::MemoryLane.into(), ImmichSpec::MemoryLane.into()],
+                ..Default::default()
+            }),
+            feed,
+        ]
+    }
+
+    #[gtest]
+    fn test_locate_gallery() {
+        let sources = sources();
+        let located = |index| {
+            locate_gallery(&sources, index)
+                .map(|(source, index)| (matches!(source, Source::Immich(_)), index))
+        };
+        expect_that!(located(0), eq(Some((false, 0))));
+        expect_that!(located(1), eq(Some((true, 0))));
+        expect_that!(located(4), eq(Some((true, 3))));
+        expect_that!(located(5), eq(Some((false, 0))));
+        expect_that!(located(6), eq(None));
+    }
+
+    #[gtest]
+    fn test_source_galleries() {
+        let sources = sources();
+        expect_that!(source_galleries(&sources, 0), eq(Some(0..1)));
+        expect_that!(source_galleries(&sources, 1), eq(Some(1..5)));
+        // After all the galleries of the Immich source
+        expect_that!(source_galleries(&sources, 2), eq(Some(5..6)));
+        expect_that!(source_galleries(&sources, 3), eq(None));
+        expect_that!(gallery_total(&sources), eq(6));
+    }
+
+    /// URL answering `response` once, on a local server.
+    fn serve_once(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/image", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            // Fails when the client gave up early
+            let _ = stream.write_all(response.as_bytes());
+        });
+        url
+    }
+
+    #[gtest]
+    fn test_download_size_limited() {
+        let timeout = Duration::from_secs(5);
+        let sized = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789";
+        expect_that!(
+            download_limited(&serve_once(sized), timeout, 10).unwrap(),
+            eq(b"0123456789")
+        );
+        expect_that!(
+            download_limited(&serve_once(sized), timeout, 4).is_err(),
+            is_true()
+        );
+        // Without announced length, stopped while it's read
+        let streamed = "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n0123456789";
+        expect_that!(
+            download_limited(&serve_once(streamed), timeout, 4).is_err(),
+            is_true()
+        );
     }
 }
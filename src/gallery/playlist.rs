@@ -0,0 +1,399 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Error, Result};
+use image::ImageReader;
+use log::{debug, info, warn};
+use tokio::sync::watch;
+use vek::Extent2;
+
+use super::{
+    decode_image, download_image, immich::ImmichAssets, Gallery, GalleryEmpty, GalleryProvider,
+    ImageDetails, ImageWithDetails,
+};
+use crate::{
+    configuration::{CaptionDateSource, PlaylistSource},
+    random,
+};
+
+const IMMICH_PREFIX: &str = "immich:";
+
+/// Shows the photos listed in a file, in order. The file is read again whenever it's
+/// modified, resuming after the last photo shown when it's still listed.
+pub struct PlaylistGalleryProvider {
+    path: PathBuf,
+    looping: bool,
+    immich: Option<ImmichAssets>,
+    entries: Vec<Entry>,
+    /// Modification time of the file when it was last read.
+    modified: Option<SystemTime>,
+    next: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Entry {
+    File(PathBuf),
+    Url(String),
+    Immich(String),
+}
+
+impl Entry {
+    /// Parses a playlist line, `None` for blank lines and comments. Relative paths are
+    /// resolved from `base`.
+    fn parse(line: &str, base: &Path) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        Some(if let Some(id) = line.strip_prefix(IMMICH_PREFIX) {
+            Entry::Immich(id.trim().to_owned())
+        } else if line.starts_with("http://") || line.starts_with("https://") {
+            Entry::Url(line.to_owned())
+        } else {
+            Entry::File(base.join(line))
+        })
+    }
+
+    /// Identifier given to the image, parsed back to fetch it again.
+    fn id(&self) -> String {
+        match self {
+            Entry::File(path) => path.display().to_string(),
+            Entry::Url(url) => url.clone(),
+            Entry::Immich(id) => format!("{IMMICH_PREFIX}{id}"),
+        }
+    }
+}
+
+impl PlaylistGalleryProvider {
+    pub fn new(
+        source: &PlaylistSource,
+        ideal_max_size: watch::Receiver<Extent2<u32>>,
+    ) -> Result<Self> {
+        let mut playlist = Self {
+            path: source.path.clone(),
+            looping: source.looping,
+            immich: source
+                .immich
+                .as_ref()
+                .map(|instance| ImmichAssets::new(instance, source.taken_date, ideal_max_size)),
+            entries: Vec::new(),
+            modified: None,
+            next: 0,
+        };
+        playlist.load()?;
+        Ok(playlist)
+    }
+
+    fn load(&mut self) -> Result<()> {
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Cannot read playlist {}", self.path.display()))?;
+        let base = self.path.parent().unwrap_or(Path::new(""));
+        let entries: Vec<_> = content
+            .lines()
+            .filter_map(|line| Entry::parse(line, base))
+            .filter(|entry| self.validate(entry))
+            .collect();
+        info!(
+            "Loaded {} photos from playlist {}",
+            entries.len(),
+            self.path.display()
+        );
+
+        let last = self
+            .next
+            .checked_sub(1)
+            .and_then(|last| self.entries.get(last));
+        self.next = last
+            .and_then(|last| entries.iter().position(|entry| entry == last))
+            .map_or(0, |position| position + 1);
+        self.entries = entries;
+        self.modified = modified;
+        Ok(())
+    }
+
+    /// Whether `entry` can be shown, logging why it's skipped otherwise.
+    fn validate(&self, entry: &Entry) -> bool {
+        let problem = match entry {
+            Entry::File(path) if !path.is_file() => "file not found",
+            Entry::Immich(id) if id.is_empty() => "missing asset id",
+            Entry::Immich(_) if self.immich.is_none() => "no Immich instance configured",
+            _ => return true,
+        };
+        warn!(
+            "Skipping playlist entry {} of {}: {problem}",
+            entry.id(),
+            self.path.display()
+        );
+        false
+    }
+
+    fn reload_if_changed(&mut self) {
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        match modified {
+            Ok(modified) if Some(modified) == self.modified => {}
+            Ok(_) => {
+                if let Err(err) = self.load() {
+                    warn!(
+                        "Cannot reload playlist, keeping the previous one: {:?}",
+                        err
+                    );
+                }
+            }
+            Err(err) => debug!("Cannot check playlist modification time: {:?}", err),
+        }
+    }
+
+    fn fetch(&self, entry: &Entry, timeout: Duration) -> Result<ImageWithDetails> {
+        let image = match entry {
+            Entry::File(path) => {
+                decode_image(ImageReader::open(path).context("Cannot open image")?)?
+            }
+            Entry::Url(url) => download_image(url, timeout)?,
+            Entry::Immich(id) => {
+                let immich = self
+                    .immich
+                    .as_ref()
+                    .context("No Immich instance configured")?;
+                let mut image = immich.get_image(id, timeout)?;
+                // Looked up again by the playlist, not by the Immich source
+                image.details.asset_id = Some(entry.id());
+                return Ok(image);
+            }
+        };
+        Ok(ImageWithDetails {
+            image,
+            details: ImageDetails {
+                asset_id: Some(entry.id()),
+                album: None,
+                starts_album: false,
+                city: None,
+                country: None,
+                gps: None,
+                date: None,
+                people: Vec::new(),
+            },
+            low_res: false,
+            background_image: None,
+            preparation: Default::default(),
+        })
+    }
+}
+
+impl Gallery for PlaylistGalleryProvider {
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.reload_if_changed();
+        if self.next >= self.entries.len() && self.looping {
+            self.next = 0;
+        }
+        let Some(entry) = self.entries.get(self.next) else {
+            // Skipped for a while by the caller, the file is then checked again
+            let state = if self.entries.is_empty() {
+                "empty"
+            } else {
+                info!(
+                    "Playlist {} played to the end, it starts again when modified",
+                    self.path.display()
+                );
+                "played to the end"
+            };
+            return Err(GalleryEmpty {
+                sources: vec![format!("playlist {} ({state})", self.path.display())],
+            }
+            .into());
+        };
+        self.next += 1;
+        self.fetch(entry, timeout)
+            .with_context(|| format!("Cannot load playlist entry {}", entry.id()))
+    }
+
+    fn get_image_by_id(&mut self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        // Only listed entries are served, this is not a way to read any file
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.id() == id)
+            .with_context(|| format!("Image {id} is not in the playlist"))?;
+        self.fetch(entry, timeout)
+    }
+
+    fn set_date_source(&mut self, source: CaptionDateSource) {
+        if let Some(immich) = &mut self.immich {
+            immich.set_date_source(source);
+        }
+    }
+}
+
+impl GalleryProvider for PlaylistGalleryProvider {
+    fn is_retryable(&self, _error: &Error) -> bool {
+        // A failing entry is skipped, the next call moves on to the following one
+        false
+    }
+
+    fn randomize_start(&mut self) {
+        if !self.entries.is_empty() {
+            self.next = random::index(self.entries.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        fs,
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{elements_are, ends_with, eq, none, some},
+    };
+    use temp_dir::TempDir;
+    use tokio::sync::watch;
+    use vek::Extent2;
+
+    use super::{Entry, PlaylistGalleryProvider};
+    use crate::{
+        configuration::PlaylistSource,
+        gallery::{Gallery, GalleryEmpty, GalleryProvider},
+    };
+
+    /// Writes a 1 pixel high image of `width` pixels, to tell images apart.
+    fn write_image(dir: &TempDir, name: &str, width: u32) {
+        image::RgbImage::new(width, 1)
+            .save(dir.path().join(name))
+            .unwrap();
+    }
+
+    /// Writes the playlist, `version` setting its modification time so that a rewrite is
+    /// noticed even within the file system time resolution.
+    fn write_playlist(dir: &TempDir, content: &str, version: u64) {
+        let path = dir.path().join("playlist.txt");
+        fs::write(&path, content).unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(version);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn playlist(dir: &TempDir, looping: bool) -> PlaylistGalleryProvider {
+        PlaylistGalleryProvider::new(
+            &PlaylistSource {
+                path: dir.path().join("playlist.txt"),
+                looping,
+                immich: None,
+                ..Default::default()
+            },
+            watch::channel(Extent2::new(800, 600)).1,
+        )
+        .unwrap()
+    }
+
+    fn next_width(playlist: &mut PlaylistGalleryProvider) -> Option<u32> {
+        playlist
+            .get_next_image(Duration::from_secs(1))
+            .ok()
+            .map(|image| image.image.width())
+    }
+
+    #[gtest]
+    fn test_parse_entries() {
+        let base = Path::new("/photos");
+        expect_that!(Entry::parse("  # comment", base), none());
+        expect_that!(Entry::parse("", base), none());
+        expect_that!(
+            Entry::parse("immich:1234-abcd", base),
+            some(eq(&Entry::Immich("1234-abcd".into())))
+        );
+        expect_that!(
+            Entry::parse("https://example.com/a.jpg", base),
+            some(eq(&Entry::Url("https://example.com/a.jpg".into())))
+        );
+        expect_that!(
+            Entry::parse("holidays/a.jpg", base),
+            some(eq(&Entry::File("/photos/holidays/a.jpg".into())))
+        );
+        expect_that!(
+            Entry::parse("/other/b.jpg", base),
+            some(eq(&Entry::File("/other/b.jpg".into())))
+        );
+    }
+
+    #[gtest]
+    fn test_invalid_entries_are_skipped() {
+        let dir = TempDir::new().unwrap();
+        write_image(&dir, "a.png", 1);
+        write_playlist(&dir, "a.png\nmissing.png\nimmich:1234\n", 1);
+        let playlist = playlist(&dir, true);
+        expect_that!(
+            playlist.entries,
+            elements_are![eq(&Entry::File(dir.path().join("a.png")))]
+        );
+    }
+
+    #[gtest]
+    fn test_plays_in_order() {
+        let dir = TempDir::new().unwrap();
+        write_image(&dir, "a.png", 1);
+        write_image(&dir, "b.png", 2);
+        write_playlist(&dir, "a.png\nb.png\n", 1);
+
+        let mut looping = playlist(&dir, true);
+        let widths: Vec<_> = (0..3).map(|_| next_width(&mut looping)).collect();
+        expect_that!(widths, eq(vec![Some(1), Some(2), Some(1)]));
+
+        let mut once = playlist(&dir, false);
+        expect_that!(next_width(&mut once), some(eq(1)));
+        expect_that!(next_width(&mut once), some(eq(2)));
+        let error = once.get_next_image(Duration::from_secs(1)).unwrap_err();
+        expect_that!(
+            error
+                .downcast_ref::<GalleryEmpty>()
+                .map(|empty| &empty.sources[0]),
+            some(ends_with("(played to the end)"))
+        );
+    }
+
+    #[gtest]
+    fn test_reload_resumes_after_last_shown() {
+        let dir = TempDir::new().unwrap();
+        write_image(&dir, "a.png", 1);
+        write_image(&dir, "b.png", 2);
+        write_image(&dir, "c.png", 3);
+        write_playlist(&dir, "a.png\nb.png\n", 1);
+
+        let mut playlist = playlist(&dir, false);
+        expect_that!(next_width(&mut playlist), some(eq(1)));
+        write_playlist(&dir, "c.png\na.png\nb.png\n", 2);
+        expect_that!(next_width(&mut playlist), some(eq(2)));
+        expect_that!(next_width(&mut playlist), none());
+    }
+
+    #[gtest]
+    fn test_random_start() {
+        let dir = TempDir::new().unwrap();
+        write_image(&dir, "a.png", 1);
+        write_image(&dir, "b.png", 2);
+        write_image(&dir, "c.png", 3);
+        write_playlist(&dir, "a.png\nb.png\nc.png\n", 1);
+
+        let mut playlist = playlist(&dir, true);
+        let mut starts = HashSet::new();
+        // Missing one of them is very unlikely after that many tries
+        for _ in 0..50 {
+            playlist.randomize_start();
+            starts.insert(playlist.next);
+        }
+        expect_that!(starts, eq(HashSet::from([0, 1, 2])));
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    borrow::Cow,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Error, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use quick_xml::{
+    escape::resolve_xml_entity,
+    events::{BytesStart, Event},
+    name::{Namespace, ResolveResult},
+    NsReader,
+};
+
+use super::{
+    download_image, Gallery, GalleryEmpty, GalleryProvider, ImageDetails, ImageWithDetails,
+};
+use crate::{configuration::RssFeedSource, network};
+
+/// Shows the images attached to the entries of an RSS or Atom feed, newest first as
+/// listed in the feed. The feed is downloaded again every `refresh_interval`.
+pub struct RssFeedGalleryProvider {
+    url: String,
+    refresh_interval: Duration,
+    entries: Vec<FeedEntry>,
+    /// When the feed was last downloaded, successfully or not.
+    fetched_at: Option<Instant>,
+    next: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FeedEntry {
+    image_url: String,
+    date: Option<DateTime<Utc>>,
+}
+
+impl RssFeedGalleryProvider {
+    pub fn new(source: &RssFeedSource) -> Self {
+        Self {
+            url: source.url.clone(),
+            refresh_interval: source.refresh_interval,
+            entries: Vec::new(),
+            fetched_at: None,
+            next: 0,
+        }
+    }
+
+    fn refresh_if_due(&mut self, timeout: Duration) -> Result<()> {
+        if self
+            .fetched_at
+            .is_some_and(|fetched_at| fetched_at.elapsed() < self.refresh_interval)
+        {
+            return Ok(());
+        }
+        self.fetched_at = Some(Instant::now());
+        match self.download(timeout) {
+            Ok(entries) => {
+                info!("Loaded {} photos from feed {}", entries.len(), self.url);
+                self.replace_entries(entries);
+                Ok(())
+            }
+            Err(err) if !self.entries.is_empty() => {
+                warn!("Cannot refresh feed, keeping the previous one: {:?}", err);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn download(&self, timeout: Duration) -> Result<Vec<FeedEntry>> {
+        let response = network::get(&self.url)
+            .with_timeout(timeout.as_secs().max(1))
+            .send()
+            .with_context(|| format!("Cannot download feed {}", self.url))?;
+        if response.status_code >= 400 {
+            bail!(
+                "Cannot download feed {}: {} {}",
+                self.url,
+                response.status_code,
+                response.reason_phrase
+            );
+        }
+        let content = response.as_str().context("Feed is not valid UTF-8")?;
+        Ok(parse_feed(content))
+    }
+
+    /// Replaces the entries, resuming after the last image shown when it's still listed.
+    fn replace_entries(&mut self, entries: Vec<FeedEntry>) {
+        let last = self
+            .next
+            .checked_sub(1)
+            .and_then(|last| self.entries.get(last));
+        self.next = last
+            .and_then(|last| entries.iter().position(|entry| entry == last))
+            .map_or(0, |position| position + 1);
+        self.entries = entries;
+    }
+
+    fn fetch(&self, entry: &FeedEntry, timeout: Duration) -> Result<ImageWithDetails> {
+        Ok(ImageWithDetails {
+            image: download_image(&entry.image_url, timeout)?,
+            details: ImageDetails {
+                asset_id: Some(entry.image_url.clone()),
+                album: None,
+                starts_album: false,
+                city: None,
+                country: None,
+                gps: None,
+                date: entry.date,
+                people: Vec::new(),
+            },
+            low_res: false,
+            background_image: None,
+            preparation: Default::default(),
+        })
+    }
+}
+
+impl Gallery for RssFeedGalleryProvider {
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.refresh_if_due(timeout)?;
+        if self.entries.is_empty() {
+            // Skipped for a while by the caller, the feed may have images by then
+            return Err(GalleryEmpty {
+                sources: vec![format!("feed {}", self.url)],
+            }
+            .into());
+        }
+        if self.next >= self.entries.len() {
+            self.next = 0;
+        }
+        let entry = &self.entries[self.next];
+        self.next += 1;
+        self.fetch(entry, timeout)
+            .with_context(|| format!("Cannot load feed image {}", entry.image_url))
+    }
+
+    fn get_image_by_id(&mut self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        // Only images of the feed are served, this is not a way to download any URL
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.image_url == id)
+            .with_context(|| format!("Image {id} is not in the feed"))?;
+        self.fetch(entry, timeout)
+    }
+}
+
+impl GalleryProvider for RssFeedGalleryProvider {
+    fn is_retryable(&self, _error: &Error) -> bool {
+        // A failing image is skipped, the next call moves on to the following one
+        false
+    }
+}
+
+/// Media RSS and Dublin Core namespaces, whatever the prefix they are bound to in the feed,
+/// without their trailing "/" that feeds don't all write.
+const MEDIA_RSS: &[u8] = b"http://search.yahoo.com/mrss";
+const DUBLIN_CORE: &[u8] = b"http://purl.org/dc/elements/1.1";
+
+/// Elements of a feed entry telling its image and date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Element {
+    /// RSS item or Atom entry.
+    Entry,
+    Enclosure,
+    MediaContent,
+    /// Atom link, an image when it's an enclosure.
+    Link,
+    /// RSS pubDate, Atom published or Dublin Core date.
+    Date,
+    Other,
+}
+
+impl Element {
+    fn resolve(namespace: &ResolveResult, local_name: &[u8]) -> Self {
+        let namespace = match namespace {
+            ResolveResult::Bound(Namespace(namespace)) => {
+                Some(namespace.strip_suffix(b"/").unwrap_or(*namespace))
+            }
+            ResolveResult::Unbound | ResolveResult::Unknown(_) => None,
+        };
+        match (namespace, local_name) {
+            (Some(MEDIA_RSS), b"content") => Element::MediaContent,
+            (Some(DUBLIN_CORE), b"date") => Element::Date,
+            (Some(MEDIA_RSS | DUBLIN_CORE), _) => Element::Other,
+            (_, b"item" | b"entry") => Element::Entry,
+            (_, b"enclosure") => Element::Enclosure,
+            (_, b"link") => Element::Link,
+            (_, b"pubDate" | b"published") => Element::Date,
+            _ => Element::Other,
+        }
+    }
+}
+
+/// Entry being read, until its end.
+#[derive(Default)]
+struct PartialEntry {
+    image_url: Option<String>,
+    date: Option<DateTime<Utc>>,
+    /// Text of the date element being read.
+    date_text: Option<String>,
+}
+
+/// Lists the first image of each item of an RSS feed or entry of an Atom feed, skipping
+/// those without any. Images are enclosures, Media RSS contents or Atom enclosure links.
+/// The entries read before an XML error are kept.
+fn parse_feed(content: &str) -> Vec<FeedEntry> {
+    let mut reader = NsReader::from_str(content);
+    // Unescaped HTML in descriptions is not worth dropping the feed for
+    reader.config_mut().check_end_names = false;
+    let mut entries = Vec::new();
+    let mut entry: Option<PartialEntry> = None;
+    loop {
+        let (namespace, event) = match reader.read_resolved_event() {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                warn!("Invalid feed, keeping the entries read before: {}", err);
+                break;
+            }
+        };
+        match event {
+            Event::Start(start) => {
+                match Element::resolve(&namespace, start.local_name().as_ref()) {
+                    Element::Entry => entry = Some(PartialEntry::default()),
+                    Element::Date => {
+                        if let Some(entry) = entry.as_mut().filter(|entry| entry.date.is_none()) {
+                            entry.date_text = Some(String::new());
+                        }
+                    }
+                    element => read_image(entry.as_mut(), element, &start),
+                }
+            }
+            Event::Empty(empty) => {
+                let element = Element::resolve(&namespace, empty.local_name().as_ref());
+                read_image(entry.as_mut(), element, &empty);
+            }
+            Event::Text(text) => append_date_text(entry.as_mut(), text.decode().ok()),
+            Event::CData(text) => append_date_text(entry.as_mut(), text.decode().ok()),
+            Event::GeneralRef(reference) => {
+                let resolved: Option<Cow<str>> = match reference.resolve_char_ref() {
+                    Ok(Some(char)) => Some(char.to_string().into()),
+                    _ => reference
+                        .decode()
+                        .ok()
+                        .and_then(|name| resolve_xml_entity(&name))
+                        .map(Into::into),
+                };
+                append_date_text(entry.as_mut(), resolved);
+            }
+            Event::End(end) => match Element::resolve(&namespace, end.local_name().as_ref()) {
+                Element::Entry => match entry.take() {
+                    Some(PartialEntry {
+                        image_url: Some(image_url),
+                        date,
+                        ..
+                    }) => entries.push(FeedEntry { image_url, date }),
+                    Some(_) => debug!("Skipping feed entry without image"),
+                    None => {}
+                },
+                Element::Date => {
+                    if let Some(entry) = entry.as_mut() {
+                        if let Some(text) = entry.date_text.take() {
+                            entry.date = parse_date(&text);
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Keeps the URL of `element` as the image of `entry` when it's the first image in it.
+fn read_image(entry: Option<&mut PartialEntry>, element: Element, tag: &BytesStart) {
+    let Some(entry) = entry.filter(|entry| entry.image_url.is_none()) else {
+        return;
+    };
+    let attribute = |name: &[u8]| {
+        tag.attributes()
+            .flatten()
+            .find(|attribute| attribute.key.prefix().is_none() && attribute.key.as_ref() == name)
+            .and_then(|attribute| attribute.unescape_value().ok())
+            .map(Cow::into_owned)
+    };
+    let image_type = attribute(b"type").is_some_and(|mime| mime.starts_with("image/"));
+    let url = match element {
+        Element::Enclosure if image_type => attribute(b"url"),
+        Element::MediaContent
+            if image_type || attribute(b"medium").is_some_and(|medium| medium == "image") =>
+        {
+            attribute(b"url")
+        }
+        Element::Link if image_type && attribute(b"rel").is_some_and(|rel| rel == "enclosure") => {
+            attribute(b"href")
+        }
+        _ => None,
+    };
+    entry.image_url = url;
+}
+
+fn append_date_text(entry: Option<&mut PartialEntry>, text: Option<Cow<str>>) {
+    if let (Some(date_text), Some(text)) = (entry.and_then(|entry| entry.date_text.as_mut()), text)
+    {
+        date_text.push_str(&text);
+    }
+}
+
+fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    DateTime::parse_from_rfc2822(text)
+        .or_else(|_| DateTime::parse_from_rfc3339(text))
+        .map(|date| date.to_utc())
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{elements_are, eq, is_empty, none, some},
+    };
+
+    use super::{parse_feed, FeedEntry, RssFeedGalleryProvider};
+    use crate::configuration::RssFeedSource;
+
+    fn entry(image_url: &str) -> FeedEntry {
+        FeedEntry {
+            image_url: image_url.into(),
+            date: None,
+        }
+    }
+
+    #[gtest]
+    fn test_parse_rss() {
+        let feed = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Photo blog</title>
+    <image><url>https://example.com/logo.png</url></image>
+    <item>
+      <title>Sunset</title>
+      <pubDate>Tue, 03 Jun 2025 18:30:00 +0200</pubDate>
+      <enclosure url="https://example.com/sunset.jpg?size=l&amp;v=2" length="1234" type="image/jpeg"/>
+    </item>
+    <item>
+      <title>Podcast</title>
+      <enclosure url="https://example.com/episode.mp3" type="audio/mpeg"/>
+    </item>
+    <item>
+      <media:content url='https://example.com/lake.jpg' medium='image'/>
+    </item>
+  </channel>
+</rss>"#;
+        expect_that!(
+            parse_feed(feed),
+            elements_are![
+                eq(&FeedEntry {
+                    image_url: "https://example.com/sunset.jpg?size=l&v=2".into(),
+                    date: Some(Utc.with_ymd_and_hms(2025, 6, 3, 16, 30, 0).unwrap()),
+                }),
+                eq(&entry("https://example.com/lake.jpg")),
+            ]
+        );
+    }
+
+    #[gtest]
+    fn test_parse_atom() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <entry>
+    <published>2025-06-03T18:30:00Z</published>
+    <link rel="alternate" href="https://example.com/post"/>
+    <link rel="enclosure" type="image/png" href="https://example.com/photo.png"/>
+  </entry>
+  <entry>
+    <link href="https://example.com/text-only"/>
+  </entry>
+</feed>"#;
+        let entries = parse_feed(feed);
+        expect_that!(
+            entries,
+            elements_are![eq(&FeedEntry {
+                image_url: "https://example.com/photo.png".into(),
+                date: Some(Utc.with_ymd_and_hms(2025, 6, 3, 18, 30, 0).unwrap()),
+            })]
+        );
+    }
+
+    #[gtest]
+    fn test_parse_namespaces_and_escapes() {
+        let feed = r#"<rss xmlns:m="http://search.yahoo.com/mrss/"
+     xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel>
+    <item>
+      <description><![CDATA[<p>Some <b>HTML</p>]]></description>
+      <dc:date><![CDATA[2025-06-03T18:30:00Z]]></dc:date>
+      <m:group>
+        <m:content url="https://example.com/a&#46;jpg?x=1&#x26;y=2" medium="image"/>
+      </m:group>
+    </item>
+    <item>
+      <media:content url="https://example.com/unbound.jpg" medium="image"/>
+    </item>
+  </channel>
+</rss>"#;
+        // The unbound "media" prefix is not Media RSS
+        expect_that!(
+            parse_feed(feed),
+            elements_are![eq(&FeedEntry {
+                image_url: "https://example.com/a.jpg?x=1&y=2".into(),
+                date: Some(Utc.with_ymd_and_hms(2025, 6, 3, 18, 30, 0).unwrap()),
+            })]
+        );
+    }
+
+    #[gtest]
+    fn test_feed_without_images() {
+        expect_that!(parse_feed("<rss><channel></channel></rss>"), is_empty());
+        expect_that!(parse_feed("not a feed"), is_empty());
+    }
+
+    #[gtest]
+    fn test_refresh_resumes_after_last_shown() {
+        let mut feed = RssFeedGalleryProvider::new(&RssFeedSource {
+            url: "https://example.com/feed.xml".into(),
+            refresh_interval: Default::default(),
+        });
+        feed.replace_entries(vec![entry("a"), entry("b")]);
+        feed.next = 1;
+        feed.replace_entries(vec![entry("c"), entry("a"), entry("b")]);
+        expect_that!(feed.entries.get(feed.next).cloned(), some(eq(&entry("b"))));
+
+        feed.replace_entries(vec![entry("d")]);
+        expect_that!(feed.entries.get(feed.next).cloned(), some(eq(&entry("d"))));
+        feed.next = 1;
+        feed.replace_entries(Vec::new());
+        expect_that!(feed.entries.get(feed.next).cloned(), none());
+    }
+}
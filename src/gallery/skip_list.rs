@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Remembers assets that recently failed, so they are not retried before `ttl` elapsed.
+pub struct SkipList {
+    entries: HashMap<String, Instant>,
+    ttl: Duration,
+}
+
+impl SkipList {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            ttl,
+        }
+    }
+
+    pub fn insert(&mut self, id: impl Into<String>, now: Instant) {
+        self.entries.insert(id.into(), now);
+    }
+
+    pub fn contains(&mut self, id: &str, now: Instant) -> bool {
+        self.entries
+            .retain(|_, skipped_at| now.saturating_duration_since(*skipped_at) < self.ttl);
+        self.entries.contains_key(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{
+        expect_that, gtest,
+        prelude::{is_false, is_true},
+    };
+
+    use super::SkipList;
+
+    #[gtest]
+    fn test_skipped_until_expiry() {
+        let now = Instant::now();
+        let mut skip_list = SkipList::new(Duration::from_secs(3600));
+        skip_list.insert("slow", now);
+
+        expect_that!(skip_list.contains("slow", now), is_true());
+        expect_that!(skip_list.contains("other", now), is_false());
+        expect_that!(
+            skip_list.contains("slow", now + Duration::from_secs(3599)),
+            is_true()
+        );
+        expect_that!(
+            skip_list.contains("slow", now + Duration::from_secs(3600)),
+            is_false()
+        );
+    }
+
+    #[gtest]
+    fn test_skipped_again_after_expiry() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(7200);
+        let mut skip_list = SkipList::new(Duration::from_secs(3600));
+        skip_list.insert("slow", now);
+        skip_list.insert("slow", later);
+
+        expect_that!(skip_list.contains("slow", later), is_true());
+    }
+}
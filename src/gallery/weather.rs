@@ -0,0 +1,319 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Error, Result};
+use chrono::{Datelike, Local, NaiveDate};
+use itertools::Itertools;
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+use super::{Gallery, GalleryProvider, ImageWithDetails};
+use crate::{
+    configuration::{CaptionDateSource, WeatherConfig},
+    network,
+};
+
+const OPEN_METEO_URL: &str = "https://api.open-meteo.com/v1/forecast";
+/// Host of the commercial plans, the free one rejects API keys.
+const OPEN_METEO_CUSTOMER_URL: &str = "https://customer-api.open-meteo.com/v1/forecast";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Weather, simplified to what photos can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Condition {
+    Sunny,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+#[derive(Deserialize)]
+struct ForecastResponse {
+    current: CurrentWeather,
+}
+
+#[derive(Deserialize)]
+struct CurrentWeather {
+    weather_code: u8,
+}
+
+impl Condition {
+    /// Maps a WMO weather interpretation code, as returned by Open-Meteo.
+    fn from_wmo_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 | 1 => Condition::Sunny,
+            2 | 3 => Condition::Cloudy,
+            45 | 48 => Condition::Fog,
+            51..=67 | 80..=82 => Condition::Rain,
+            71..=77 | 85 | 86 => Condition::Snow,
+            95..=99 => Condition::Storm,
+            _ => return None,
+        })
+    }
+
+    fn keywords(self) -> &'static str {
+        match self {
+            Condition::Sunny => "sunny",
+            Condition::Cloudy => "cloudy",
+            Condition::Fog => "fog",
+            Condition::Rain => "rain",
+            Condition::Snow => "snow",
+            Condition::Storm => "storm",
+        }
+    }
+}
+
+impl Season {
+    /// Meteorological season, reversed in the southern hemisphere.
+    fn at(date: NaiveDate, latitude: f64) -> Self {
+        let northern = match date.month() {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        };
+        if latitude >= 0. {
+            return northern;
+        }
+        match northern {
+            Season::Spring => Season::Autumn,
+            Season::Summer => Season::Winter,
+            Season::Autumn => Season::Spring,
+            Season::Winter => Season::Summer,
+        }
+    }
+
+    fn keywords(self) -> &'static str {
+        match self {
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Autumn => "autumn",
+            Season::Winter => "winter",
+        }
+    }
+}
+
+/// Smart search query following the weather and season, e.g. "beach" becomes
+/// "beach sunny summer".
+pub struct WeatherQuery {
+    base: String,
+    config: WeatherConfig,
+    last_update: Option<Instant>,
+    query: String,
+}
+
+impl WeatherQuery {
+    pub fn new(base: String, config: WeatherConfig) -> Self {
+        Self {
+            query: base.clone(),
+            base,
+            config,
+            last_update: None,
+        }
+    }
+
+    /// Fetches the weather when the refresh interval elapsed, returning the new query
+    /// when it changed. The previous query is kept when the weather cannot be fetched.
+    pub fn refresh(&mut self, now: Instant) -> Option<&str> {
+        if self
+            .last_update
+            .is_some_and(|last| now.duration_since(last) < self.config.refresh_interval)
+        {
+            return None;
+        }
+        self.last_update = Some(now);
+        let condition = match fetch_condition(&self.config) {
+            Ok(condition) => condition,
+            Err(err) => {
+                warn!("Cannot fetch the weather: {:?}", err);
+                return None;
+            }
+        };
+        let season = Season::at(Local::now().date_naive(), self.config.latitude);
+        let query = build_query(&self.base, condition, season);
+        if query == self.query {
+            debug!("Weather unchanged, keeping query \"{query}\"");
+            return None;
+        }
+        info!("Smart search query follows the weather: \"{query}\"");
+        self.query = query;
+        Some(&self.query)
+    }
+}
+
+/// Smart search gallery whose query follows the weather, see [`WeatherQuery`].
+pub struct WeatherGallery {
+    gallery: Box<dyn GalleryProvider>,
+    query: WeatherQuery,
+}
+
+impl WeatherGallery {
+    pub fn new(gallery: Box<dyn GalleryProvider>, query: WeatherQuery) -> Self {
+        Self { gallery, query }
+    }
+
+    /// Restarts the search of the gallery when the weather changed the query.
+    fn follow_weather(&mut self) {
+        if let Some(query) = self.query.refresh(Instant::now()) {
+            self.gallery.set_search_query(query);
+        }
+    }
+}
+
+impl Gallery for WeatherGallery {
+    fn get_next_image(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.follow_weather();
+        self.gallery.get_next_image(timeout)
+    }
+
+    fn get_next_thumbnail(&mut self, timeout: Duration) -> Result<ImageWithDetails> {
+        self.follow_weather();
+        self.gallery.get_next_thumbnail(timeout)
+    }
+
+    fn get_image_by_id(&mut self, id: &str, timeout: Duration) -> Result<ImageWithDetails> {
+        self.gallery.get_image_by_id(id, timeout)
+    }
+
+    fn empty_sources(&self) -> Vec<String> {
+        self.gallery.empty_sources()
+    }
+
+    fn set_active_source(&mut self, range: Option<Range<usize>>) -> Result<()> {
+        self.gallery.set_active_source(range)
+    }
+
+    fn set_group_by_day(&mut self, enabled: bool) {
+        self.gallery.set_group_by_day(enabled);
+    }
+
+    fn set_date_source(&mut self, source: CaptionDateSource) {
+        self.gallery.set_date_source(source);
+    }
+}
+
+impl GalleryProvider for WeatherGallery {
+    fn is_retryable(&self, error: &Error) -> bool {
+        self.gallery.is_retryable(error)
+    }
+
+    fn randomize_start(&mut self) {
+        self.gallery.randomize_start();
+    }
+
+    fn set_search_query(&mut self, query: &str) {
+        self.gallery.set_search_query(query);
+    }
+}
+
+fn build_query(base: &str, condition: Condition, season: Season) -> String {
+    [base.trim(), condition.keywords(), season.keywords()]
+        .into_iter()
+        .filter(|word| !word.is_empty())
+        .join(" ")
+}
+
+fn fetch_condition(config: &WeatherConfig) -> Result<Condition> {
+    let mut request = network::get(forecast_url(config))
+        .with_param("latitude", config.latitude.to_string())
+        .with_param("longitude", config.longitude.to_string())
+        .with_param("current", "weather_code")
+        .with_timeout(REQUEST_TIMEOUT.as_secs());
+    if let Some(api_key) = &config.api_key {
+        request = request.with_param("apikey", api_key);
+    }
+    let response = request.send().context("Cannot send weather request")?;
+    if response.status_code >= 400 {
+        bail!(
+            "Weather request failed: {} {}",
+            response.status_code,
+            response.reason_phrase
+        );
+    }
+    let forecast: ForecastResponse = response.json().context("Cannot read weather response")?;
+    let code = forecast.current.weather_code;
+    Condition::from_wmo_code(code).with_context(|| format!("Unknown weather code {code}"))
+}
+
+/// Forecast endpoint for `config`: API keys are only accepted by the customer host.
+fn forecast_url(config: &WeatherConfig) -> &'static str {
+    match config.api_key {
+        Some(_) => OPEN_METEO_CUSTOMER_URL,
+        None => OPEN_METEO_URL,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, none, some},
+    };
+
+    use super::{
+        build_query, forecast_url, Condition, Season, OPEN_METEO_CUSTOMER_URL, OPEN_METEO_URL,
+    };
+    use crate::configuration::WeatherConfig;
+
+    fn date(month: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, month, 15).unwrap()
+    }
+
+    #[gtest]
+    fn test_wmo_codes() {
+        expect_that!(Condition::from_wmo_code(0), some(eq(Condition::Sunny)));
+        expect_that!(Condition::from_wmo_code(3), some(eq(Condition::Cloudy)));
+        expect_that!(Condition::from_wmo_code(61), some(eq(Condition::Rain)));
+        expect_that!(Condition::from_wmo_code(73), some(eq(Condition::Snow)));
+        expect_that!(Condition::from_wmo_code(95), some(eq(Condition::Storm)));
+        expect_that!(Condition::from_wmo_code(42), none());
+    }
+
+    #[gtest]
+    fn test_seasons_by_hemisphere() {
+        expect_that!(Season::at(date(1), 48.8), eq(Season::Winter));
+        expect_that!(Season::at(date(7), 48.8), eq(Season::Summer));
+        expect_that!(Season::at(date(1), -33.9), eq(Season::Summer));
+        expect_that!(Season::at(date(4), -33.9), eq(Season::Autumn));
+    }
+
+    #[gtest]
+    fn test_customer_host_with_api_key() {
+        let mut config = WeatherConfig {
+            latitude: 48.8,
+            longitude: 2.3,
+            api_key: None,
+            refresh_interval: Duration::from_secs(60),
+        };
+        expect_that!(forecast_url(&config), eq(OPEN_METEO_URL));
+        config.api_key = Some("key".to_owned());
+        expect_that!(forecast_url(&config), eq(OPEN_METEO_CUSTOMER_URL));
+    }
+
+    #[gtest]
+    fn test_query() {
+        expect_that!(
+            build_query("landscape", Condition::Snow, Season::Winter),
+            eq("landscape snow winter")
+        );
+        expect_that!(
+            build_query(" ", Condition::Sunny, Season::Summer),
+            eq("sunny summer")
+        );
+    }
+}
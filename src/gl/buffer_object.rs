@@ -1,4 +1,4 @@
-use std::{marker::PhantomData, rc::Rc};
+use std::{cell::Cell, marker::PhantomData, rc::Rc};
 
 use anyhow::{bail, Error, Result};
 use bytemuck::NoUninit;
@@ -44,12 +44,19 @@ pub struct BufferObject<Type> {
     gl: Rc<GlContext>,
     /// Size of the buffer in elements
     size: usize,
+    /// Size of the store in elements, kept when smaller data is written
+    capacity: usize,
+    /// Set when drawn since the last write, the GPU may still be reading the store
+    drawn: Cell<bool>,
     _data_type: PhantomData<Type>,
 }
 
 impl<Type: NoUninit> BufferObject<Type> {
+    /// Allocates a store of the size of `data`.
     pub fn write(&mut self, data: &[Type]) {
         self.size = data.len();
+        self.capacity = data.len();
+        self.drawn.set(false);
         unsafe {
             self.gl.bind_buffer(self.target.to_gl(), Some(self.object));
             self.gl.buffer_data_u8_slice(
@@ -75,6 +82,42 @@ impl<Type: NoUninit> BufferObject<Type> {
         }
         Ok(())
     }
+
+    /// Replaces the content, reallocating only when it grows. The store is orphaned when
+    /// the size changes or when it was drawn since the last write, it is written in place
+    /// otherwise.
+    pub fn update(&mut self, data: &[Type]) {
+        if data.len() > self.capacity {
+            self.write(data);
+        } else if data.len() != self.size || self.drawn.get() {
+            self.orphan_and_write(data);
+        } else {
+            self.write_sub(0, data)
+                .expect("Should never happen: the buffer has the size of the data");
+        }
+    }
+
+    /// Specifies the store again before writing `data`, so that the driver gives a fresh
+    /// one instead of waiting for the GPU to be done with the previous frame. Only dynamic
+    /// buffers are orphaned, others are written in place.
+    pub fn orphan_and_write(&mut self, data: &[Type]) {
+        if data.len() > self.capacity {
+            self.write(data);
+            return;
+        }
+        let capacity = self.capacity * std::mem::size_of::<Type>();
+        unsafe {
+            self.gl.bind_buffer(self.target.to_gl(), Some(self.object));
+            if matches!(self.usage, BufferUsage::Dynamic) {
+                self.gl
+                    .buffer_data_size(self.target.to_gl(), capacity as _, self.usage.to_gl());
+            }
+            self.gl
+                .buffer_sub_data_u8_slice(self.target.to_gl(), 0, bytemuck::cast_slice(data));
+        }
+        self.size = data.len();
+        self.drawn.set(false);
+    }
 }
 
 impl<Type> BufferObject<Type> {
@@ -86,6 +129,8 @@ impl<Type> BufferObject<Type> {
             usage,
             gl,
             size: 0,
+            capacity: 0,
+            drawn: Cell::new(false),
             _data_type: PhantomData,
         })
     }
@@ -110,6 +155,16 @@ impl<Type> BufferObject<Type> {
     pub fn size(&self) -> usize {
         self.size
     }
+
+    /// Size of the store in elements, at least [`BufferObject::size`]
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn mark_drawn(&self) {
+        self.drawn.set(true);
+    }
 }
 
 impl BufferObject<u32> {
@@ -125,3 +180,90 @@ impl<T> Drop for BufferObject<T> {
 }
 
 pub type ElementBufferObject = BufferObject<u32>;
+
+#[cfg(test)]
+mod test {
+    use std::{
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use faux::when;
+    use googletest::{expect_that, gtest, prelude::eq};
+
+    use super::{BufferObject, BufferUsage};
+    use crate::gl::{wrapper::mocked_gl, GlContext};
+
+    /// Writes `initial` then updates the buffer with `next`, returns the GL calls of the
+    /// update and the buffer.
+    fn update_calls(
+        usage: BufferUsage,
+        initial: &[u32],
+        drawn: bool,
+        next: &[u32],
+    ) -> (Vec<String>, BufferObject<u32>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut gl = mocked_gl();
+        let record = |calls: &Arc<Mutex<Vec<String>>>| {
+            let calls = Arc::clone(calls);
+            move |call: String| calls.lock().unwrap().push(call)
+        };
+        let data = record(&calls);
+        when!(gl.buffer_data_u8_slice)
+            .then(move |(_, bytes, _)| data(format!("data {}", bytes.len())));
+        let orphan = record(&calls);
+        when!(gl.buffer_data_size).then(move |(_, size, _)| orphan(format!("orphan {size}")));
+        let sub = record(&calls);
+        when!(gl.buffer_sub_data_u8_slice)
+            .then(move |(_, offset, bytes)| sub(format!("sub {offset} {}", bytes.len())));
+        let gl = Rc::new(GlContext::mocked(gl));
+
+        let mut buffer = BufferObject::new_index_buffer(gl, usage).unwrap();
+        buffer.write(initial);
+        if drawn {
+            buffer.mark_drawn();
+        }
+        calls.lock().unwrap().clear();
+        buffer.update(next);
+        let recorded = calls.lock().unwrap().clone();
+        (recorded, buffer)
+    }
+
+    #[gtest]
+    fn test_grown_buffer_reallocated() {
+        let (calls, buffer) = update_calls(BufferUsage::Dynamic, &[1, 2], false, &[1, 2, 3]);
+        expect_that!(calls, eq(&["data 12".to_string()]));
+        expect_that!(buffer.capacity(), eq(3));
+    }
+
+    #[gtest]
+    fn test_same_size_written_in_place() {
+        let (calls, _) = update_calls(BufferUsage::Dynamic, &[1, 2], false, &[3, 4]);
+        expect_that!(calls, eq(&["sub 0 8".to_string()]));
+    }
+
+    #[gtest]
+    fn test_drawn_buffer_orphaned() {
+        let (calls, _) = update_calls(BufferUsage::Dynamic, &[1, 2], true, &[3, 4]);
+        expect_that!(calls, eq(&["orphan 8".to_string(), "sub 0 8".into()]));
+    }
+
+    #[gtest]
+    fn test_shrunk_buffer_keeps_its_store() {
+        let (calls, mut buffer) = update_calls(BufferUsage::Dynamic, &[1, 2, 3], false, &[1]);
+        expect_that!(calls, eq(&["orphan 12".to_string(), "sub 0 4".into()]));
+        // Only the new content is drawn
+        expect_that!(buffer.size(), eq(1));
+        expect_that!(buffer.capacity(), eq(3));
+
+        buffer.update(&[1, 2]);
+        expect_that!(buffer.size(), eq(2));
+        expect_that!(buffer.capacity(), eq(3));
+    }
+
+    #[gtest]
+    fn test_static_buffer_never_orphaned() {
+        let (calls, _) = update_calls(BufferUsage::Static, &[1, 2, 3], true, &[1]);
+        expect_that!(calls, eq(&["sub 0 4".to_string()]));
+    }
+}
@@ -1,6 +1,7 @@
 use std::rc::Rc;
 
-use anyhow::{Error, Result};
+use anyhow::{Context, Error, Result};
+use image::{imageops, RgbaImage};
 use vek::Rect;
 
 use super::{texture::Texture, GlContext};
@@ -12,12 +13,8 @@ pub struct FramebufferGuard<'a> {
 
 impl Drop for FramebufferGuard<'_> {
     fn drop(&mut self) {
-        unsafe {
-            self.framebuffer
-                .gl
-                .bind_framebuffer(glow::FRAMEBUFFER, None);
-            self.framebuffer.gl.set_viewport(self.previous_viewport);
-        }
+        self.framebuffer.gl.bind_screen_framebuffer();
+        self.framebuffer.gl.set_viewport(self.previous_viewport);
     }
 }
 
@@ -39,7 +36,7 @@ impl FramebufferObject {
                 Some(texture.get()),
                 0,
             );
-            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.bind_screen_framebuffer();
             Ok(Self {
                 framebuffer: fbo,
                 texture: Some(texture),
@@ -71,6 +68,15 @@ impl FramebufferObject {
         }
     }
 
+    /// Draws the frames into this framebuffer rather than to the surface, see
+    /// [`GlContext::set_screen_framebuffer`].
+    pub fn use_as_screen(&self) {
+        self.gl.set_screen_framebuffer(Some(self.framebuffer));
+        let size = self.get_texture().size();
+        self.gl
+            .set_viewport(Rect::new(0, 0, size.w as i32, size.h as i32));
+    }
+
     pub fn into_texture(mut self) -> Texture {
         self.texture.take().expect("Texture should be present")
     }
@@ -78,6 +84,30 @@ impl FramebufferObject {
     pub fn get_texture(&self) -> &Texture {
         self.texture.as_ref().expect("Texture should be present")
     }
+
+    /// Reads the texture back, top row first. Binds the screen back, so it must not be
+    /// called while drawing into another framebuffer.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn read_pixels(&self) -> Result<RgbaImage> {
+        let size = self.get_texture().size();
+        let mut data = vec![0; size.product() as usize * 4];
+        unsafe {
+            self.bind();
+            self.gl.read_pixels(
+                0,
+                0,
+                size.w as i32,
+                size.h as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(Some(&mut data)),
+            );
+        }
+        self.gl.bind_screen_framebuffer();
+        let image = RgbaImage::from_raw(size.w, size.h, data).context("Invalid pixels size")?;
+        // Rows start at the bottom in GL
+        Ok(imageops::flip_vertical(&image))
+    }
 }
 
 impl Drop for FramebufferObject {
@@ -1,11 +1,21 @@
-use std::{cell::RefCell, num::NonZeroU32, ops::Deref, rc::Rc};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    ffi::c_void,
+    fmt::Display,
+    num::NonZeroU32,
+    ops::Deref,
+    rc::Rc,
+};
 
 use anyhow::{Context as _, Result};
 use glutin::{
-    context::{NotCurrentContext, PossiblyCurrentContext},
+    api::egl::{device::Device, display::Display as EglDisplay},
+    config::{Api, ConfigSurfaceTypes, ConfigTemplateBuilder},
+    context::{ContextApi, ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext},
     prelude::{GlDisplay as _, NotCurrentGlContext},
     surface::{GlSurface as _, Surface, WindowSurface},
 };
+use image::RgbaImage;
 use vao::VaoBindGuard;
 use vek::{Extent2, Rect, Vec2};
 
@@ -24,16 +34,48 @@ pub mod wrapper;
 pub struct GlContext {
     gl: GlowContext,
     capacities: Capabilities,
+    /// Queried on first use, see [`GlContext::max_anisotropy`].
+    max_anisotropy: OnceCell<Option<f32>>,
     info: RefCell<GlContextInfo>,
+    /// Set once the context is lost, see [`GlContext::mark_lost`].
+    lost: Cell<bool>,
+    /// Set for contexts lost on GPU resets, see [`GlContext::is_lost`].
+    reset_status: Option<ResetStatusFn>,
     surface: Option<Surface<WindowSurface>>,
+    /// Drawn to instead of the surface when there's none, see
+    /// [`GlContext::set_screen_framebuffer`].
+    screen_framebuffer: Cell<Option<glow::NativeFramebuffer>>,
     #[cfg(not(test))]
     context: PossiblyCurrentContext,
+    /// Only set for tests drawing with a real GPU, kept alive until the end of the test.
+    #[cfg(test)]
+    #[allow(dead_code)]
+    context: Option<PossiblyCurrentContext>,
+}
+
+/// Returned when the GPU driver was reset or the compositor restarted: the context and all
+/// its objects are gone, a new one must be created.
+#[derive(Debug)]
+pub struct ContextLost;
+
+impl Display for ContextLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GL context lost")
+    }
 }
 
+impl std::error::Error for ContextLost {}
+
+/// `glGetGraphicsResetStatus`, not exposed by glow.
+type ResetStatusFn = unsafe extern "system" fn() -> u32;
+
 pub struct FutureGlThreadContext {
     display: glutin::display::Display,
     surface: Option<Surface<WindowSurface>>,
     context: NotCurrentContext,
+    /// Created with [`Robustness::RobustLoseContextOnReset`](glutin::context::Robustness), see
+    /// [`FutureGlThreadContext::with_reset_notification`].
+    reset_notification: bool,
 }
 
 impl FutureGlThreadContext {
@@ -46,7 +88,39 @@ impl FutureGlThreadContext {
             display,
             surface,
             context,
+            reset_notification: false,
+        }
+    }
+
+    /// Context without any surface, on the first EGL device, to draw offscreen. It shares
+    /// its objects with `share_with`, if any.
+    pub fn surfaceless(share_with: Option<&NotCurrentContext>) -> Result<Self> {
+        let device = Device::query_devices()
+            .context("Cannot list EGL devices")?
+            .next()
+            .context("No EGL device")?;
+        let display =
+            unsafe { EglDisplay::with_device(&device, None) }.context("Cannot open EGL display")?;
+        let template = ConfigTemplateBuilder::new()
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .with_api(Api::GLES2)
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .context("Cannot list EGL configs")?
+            .next()
+            .context("No EGL config")?;
+        let mut attributes =
+            ContextAttributesBuilder::new().with_context_api(ContextApi::Gles(None));
+        if let Some(share_context) = share_with {
+            attributes = attributes.with_sharing(share_context);
         }
+        let context = unsafe { display.create_context(&config, &attributes.build(None)) }
+            .context("Cannot create GL context")?;
+        Ok(Self::new(
+            None,
+            NotCurrentContext::Egl(context),
+            glutin::display::Display::Egl(display),
+        ))
     }
 
     pub fn activate(self) -> Result<Rc<GlContext>> {
@@ -78,8 +152,21 @@ impl FutureGlThreadContext {
         let gl = unsafe {
             glow::Context::from_loader_function_cstr(|s| self.display.get_proc_address(s))
         };
+        let reset_status = if self.reset_notification {
+            load_reset_status(&self.display)
+        } else {
+            None
+        };
+
+        GlContext::new(self.surface, context, gl.into(), reset_status)
+    }
 
-        GlContext::new(self.surface, context, gl.into())
+    /// The context was created with
+    /// [`Robustness::RobustLoseContextOnReset`](glutin::context::Robustness), its reset
+    /// status tells when it's lost.
+    pub fn with_reset_notification(mut self) -> Self {
+        self.reset_notification = true;
+        self
     }
 
     pub fn get_context(&self) -> &NotCurrentContext {
@@ -87,6 +174,23 @@ impl FutureGlThreadContext {
     }
 }
 
+/// Loads `glGetGraphicsResetStatus`, core in OpenGL ES 3.2 and from the robustness
+/// extensions before.
+fn load_reset_status(display: &glutin::display::Display) -> Option<ResetStatusFn> {
+    let address = [
+        c"glGetGraphicsResetStatusEXT",
+        c"glGetGraphicsResetStatusKHR",
+        c"glGetGraphicsResetStatus",
+    ]
+    .into_iter()
+    .map(|name| display.get_proc_address(name))
+    .find(|address| !address.is_null());
+    if address.is_none() {
+        log::warn!("No glGetGraphicsResetStatus, GPU resets are noticed when presenting");
+    }
+    address.map(|address| unsafe { std::mem::transmute::<*const c_void, ResetStatusFn>(address) })
+}
+
 impl Deref for GlContext {
     type Target = GlowContext;
 
@@ -110,6 +214,23 @@ pub struct Capabilities {
 #[derive(Default)]
 pub struct DrawParameters {
     pub blend: Option<BlendMode>,
+    /// Only pixels of this rectangle of the framebuffer are drawn, origin at the bottom left.
+    pub scissor: Option<Rect<i32, i32>>,
+    /// Viewport used for this draw only, the current one is restored afterwards.
+    pub viewport_override: Option<Rect<i32, i32>>,
+}
+
+impl DrawParameters {
+    pub fn alpha_blended() -> Self {
+        Self {
+            blend: Some(BlendMode::alpha()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_scissor(self, scissor: Option<Rect<i32, i32>>) -> Self {
+        Self { scissor, ..self }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -209,21 +330,26 @@ impl GlContext {
             capacities: Capabilities {
                 max_texture_size: 2048,
             },
+            max_anisotropy: OnceCell::new(),
             info: RefCell::new(GlContextInfo {
                 viewport: Rect::new(0, 0, 800, 600),
                 bound_shader: None,
                 blend_mode: None,
             }),
+            lost: Cell::new(false),
+            reset_status: None,
             gl,
             surface: None,
+            screen_framebuffer: Cell::new(None),
+            context: None,
         }
     }
 
-    #[cfg_attr(test, allow(unused_variables))]
     fn new(
         surface: Option<Surface<WindowSurface>>,
         context: PossiblyCurrentContext,
         gl: GlowContext,
+        reset_status: Option<ResetStatusFn>,
     ) -> Result<Rc<Self>> {
         let dimensions = if let Some(surface) = &surface {
             let width = surface.width().context("cannot get surface width")?;
@@ -237,21 +363,27 @@ impl GlContext {
             capacities: Capabilities {
                 max_texture_size: unsafe { gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) } as u32,
             },
+            max_anisotropy: OnceCell::new(),
             info: RefCell::new(GlContextInfo {
                 viewport,
                 bound_shader: None,
                 blend_mode: None,
             }),
+            lost: Cell::new(false),
+            reset_status,
             gl,
             surface,
+            screen_framebuffer: Cell::new(None),
             #[cfg(not(test))]
             context,
+            #[cfg(test)]
+            context: Some(context),
         }))
     }
 
     pub fn draw<T>(
         &self,
-        _vao: &VaoBindGuard<T>,
+        vao: &VaoBindGuard<T>,
         _program: &ProgramGuard,
         count: i32,
         offset: i32,
@@ -273,8 +405,25 @@ impl GlContext {
                     self.gl.disable(glow::BLEND);
                 }
             }
+            if let Some(scissor) = draw_parameters.scissor {
+                self.gl.enable(glow::SCISSOR_TEST);
+                self.gl.scissor(scissor.x, scissor.y, scissor.w, scissor.h);
+            }
+            if let Some(viewport) = draw_parameters.viewport_override {
+                self.gl
+                    .viewport(viewport.x, viewport.y, viewport.w, viewport.h);
+            }
             self.gl
                 .draw_elements(glow::TRIANGLES, count, glow::UNSIGNED_INT, offset);
+            vao.mark_drawn();
+            if draw_parameters.scissor.is_some() {
+                self.gl.disable(glow::SCISSOR_TEST);
+            }
+            if draw_parameters.viewport_override.is_some() {
+                let viewport = self.info.borrow().viewport;
+                self.gl
+                    .viewport(viewport.x, viewport.y, viewport.w, viewport.h);
+            }
         }
     }
 
@@ -296,6 +445,41 @@ impl GlContext {
         self.info.borrow_mut().viewport = viewport;
     }
 
+    /// Makes `framebuffer` the screen, drawn to when no other framebuffer is bound, for
+    /// backends reading the frames back rather than showing a surface.
+    pub fn set_screen_framebuffer(&self, framebuffer: Option<glow::NativeFramebuffer>) {
+        self.screen_framebuffer.set(framebuffer);
+        self.bind_screen_framebuffer();
+    }
+
+    /// Binds the screen back, once done drawing into another framebuffer.
+    pub fn bind_screen_framebuffer(&self) {
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, self.screen_framebuffer.get())
+        };
+    }
+
+    /// Reads the screen being drawn back, before it is swapped. Rows start at the bottom,
+    /// as in GL.
+    pub fn read_screen(&self) -> Result<RgbaImage> {
+        let viewport = self.current_viewport();
+        let mut data = vec![0; viewport.w as usize * viewport.h as usize * 4];
+        unsafe {
+            self.gl.read_pixels(
+                viewport.x,
+                viewport.y,
+                viewport.w,
+                viewport.h,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(Some(&mut data)),
+            );
+        }
+        RgbaImage::from_raw(viewport.w as u32, viewport.h as u32, data)
+            .context("Invalid pixels size")
+    }
+
     pub fn capabilities(&self) -> &Capabilities {
         &self.capacities
     }
@@ -303,9 +487,16 @@ impl GlContext {
     pub fn swap_buffers(&self) -> Result<()> {
         #[cfg(not(test))]
         if let Some(surface) = &self.surface {
-            surface
-                .swap_buffers(&self.context)
-                .context("Cannot swap buffers")
+            match surface.swap_buffers(&self.context) {
+                Err(err) if err.error_kind() == glutin::error::ErrorKind::ContextLost => {
+                    self.mark_lost();
+                    Err(ContextLost.into())
+                }
+                result => result.context("Cannot swap buffers"),
+            }
+        } else if self.screen_framebuffer.get().is_some() {
+            // Read back by the backend
+            Ok(())
         } else {
             anyhow::bail!("Cannot swap buffers on offscreen surface")
         }
@@ -313,10 +504,76 @@ impl GlContext {
         Ok(())
     }
 
+    /// Maximum level of anisotropic filtering, `None` when the GPU doesn't support
+    /// GL_EXT_texture_filter_anisotropic.
+    pub fn max_anisotropy(&self) -> Option<f32> {
+        *self.max_anisotropy.get_or_init(|| {
+            let extensions = self.gl.supported_extensions();
+            let supported = extensions.contains("GL_EXT_texture_filter_anisotropic")
+                || extensions.contains("EXT_texture_filter_anisotropic");
+            log::debug!("Anisotropic filtering supported: {supported}");
+            supported.then(|| unsafe {
+                self.gl
+                    .get_parameter_f32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+            })
+        })
+    }
+
     pub fn is_background(&self) -> bool {
         self.surface.is_none()
     }
 
+    /// Whether the context was lost, checked with the reset status of the contexts created
+    /// to be lost on GPU resets. The others are known lost once swapping buffers failed.
+    pub fn is_lost(&self) -> bool {
+        if let Some(reset_status) = self.reset_status.filter(|_| !self.lost.get()) {
+            let status = unsafe { reset_status() };
+            if status != glow::NO_ERROR {
+                log::warn!("GPU reset, status {status:#x}");
+                self.mark_lost();
+            }
+        }
+        self.lost.get()
+    }
+
+    /// Objects of a lost context are gone with it: they are not deleted anymore, their names
+    /// may be those of the objects of the context replacing it.
+    pub fn mark_lost(&self) {
+        self.lost.set(true);
+    }
+
+    // Used instead of the ones of GlowContext when dropping GL objects, see `mark_lost`
+
+    pub unsafe fn delete_texture(&self, texture: glow::NativeTexture) {
+        if !self.lost.get() {
+            self.gl.delete_texture(texture);
+        }
+    }
+
+    pub unsafe fn delete_buffer(&self, buffer: glow::NativeBuffer) {
+        if !self.lost.get() {
+            self.gl.delete_buffer(buffer);
+        }
+    }
+
+    pub unsafe fn delete_vertex_array(&self, vertex_array: glow::NativeVertexArray) {
+        if !self.lost.get() {
+            self.gl.delete_vertex_array(vertex_array);
+        }
+    }
+
+    pub unsafe fn delete_program(&self, program: glow::NativeProgram) {
+        if !self.lost.get() {
+            self.gl.delete_program(program);
+        }
+    }
+
+    pub unsafe fn delete_framebuffer(&self, framebuffer: glow::NativeFramebuffer) {
+        if !self.lost.get() {
+            self.gl.delete_framebuffer(framebuffer);
+        }
+    }
+
     pub fn wait(&self) {
         unsafe {
             self.gl.finish();
@@ -328,3 +585,130 @@ impl GlContext {
         self.info.borrow_mut().bound_shader.replace(shader)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use faux::when;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true},
+    };
+    use vek::{Extent2, Rect};
+
+    use super::{
+        buffer_object::{BufferObject, BufferUsage, ElementBufferObject},
+        shader::{Program, ProgramGuard},
+        texture::Texture,
+        vao::VertexArrayObject,
+        wrapper::mocked_gl,
+        DrawParameters, GlContext,
+    };
+
+    /// Draws once with `parameters`, returns the GL calls made.
+    fn draw_calls(parameters: DrawParameters) -> Vec<String> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut gl = mocked_gl();
+        let record = |calls: &Arc<Mutex<Vec<String>>>| {
+            let calls = Arc::clone(calls);
+            move |call: String| calls.lock().unwrap().push(call)
+        };
+        let enable = record(&calls);
+        when!(gl.enable).then(move |cap| enable(format!("enable {cap:#x}")));
+        let disable = record(&calls);
+        when!(gl.disable).then(move |cap| disable(format!("disable {cap:#x}")));
+        let scissor = record(&calls);
+        when!(gl.scissor).then(move |(x, y, w, h)| scissor(format!("scissor {x} {y} {w} {h}")));
+        let viewport = record(&calls);
+        when!(gl.viewport).then(move |(x, y, w, h)| viewport(format!("viewport {x} {y} {w} {h}")));
+        let draw = record(&calls);
+        when!(gl.draw_elements).then(move |_| draw("draw".into()));
+        let gl = Rc::new(GlContext::mocked(gl));
+
+        let program = Program::new(Rc::clone(&gl), "", "").unwrap();
+        let vbo = BufferObject::<[f32; 2]>::new_vertex_buffer(Rc::clone(&gl), BufferUsage::Static)
+            .unwrap();
+        let ebo =
+            ElementBufferObject::new_index_buffer(Rc::clone(&gl), BufferUsage::Static).unwrap();
+        let vao = VertexArrayObject::new(Rc::clone(&gl), vbo, ebo, vec![]).unwrap();
+        let program = ProgramGuard::bind(&program);
+        gl.draw(&vao.bind_guard(), &program, 6, 0, &parameters);
+        let mut recorded = Vec::new();
+        recorded.append(&mut calls.lock().unwrap());
+        recorded
+    }
+
+    #[gtest]
+    fn test_draw_without_clipping() {
+        expect_that!(
+            draw_calls(DrawParameters::default()),
+            eq(&["draw".to_string()])
+        );
+    }
+
+    #[gtest]
+    fn test_scissor_is_restored() {
+        let parameters = DrawParameters {
+            blend: None,
+            scissor: Some(Rect::new(10, 20, 100, 50)),
+            viewport_override: Some(Rect::new(0, 0, 400, 300)),
+        };
+        expect_that!(
+            draw_calls(parameters),
+            eq(&[
+                "enable 0xc11".to_string(),
+                "scissor 10 20 100 50".into(),
+                "viewport 0 0 400 300".into(),
+                "draw".into(),
+                "disable 0xc11".into(),
+                "viewport 0 0 800 600".into(),
+            ])
+        );
+    }
+
+    #[gtest]
+    fn test_lost_context_objects_not_deleted() {
+        let deleted = Arc::new(Mutex::new(0));
+        let mut gl = mocked_gl();
+        let count = Arc::clone(&deleted);
+        when!(gl.delete_texture).then(move |_| *count.lock().unwrap() += 1);
+        let gl = Rc::new(GlContext::mocked(gl));
+
+        drop(Texture::mocked(Rc::clone(&gl), Extent2::new(10, 10)));
+        expect_that!(*deleted.lock().unwrap(), eq(1));
+
+        // The name may be the one of a texture of the new context
+        gl.mark_lost();
+        drop(Texture::mocked(Rc::clone(&gl), Extent2::new(10, 10)));
+        expect_that!(*deleted.lock().unwrap(), eq(1));
+    }
+
+    unsafe extern "system" fn reset() -> u32 {
+        glow::GUILTY_CONTEXT_RESET
+    }
+
+    unsafe extern "system" fn no_reset() -> u32 {
+        glow::NO_ERROR
+    }
+
+    #[gtest]
+    fn test_context_loss_detected_from_reset_status() {
+        let mut robust = GlContext::mocked(mocked_gl());
+        robust.reset_status = Some(no_reset);
+        expect_that!(robust.is_lost(), is_false());
+        robust.reset_status = Some(reset);
+        expect_that!(robust.is_lost(), is_true());
+
+        // Without robustness, only a failed swap tells, the error flag isn't polled
+        let mut gl = mocked_gl();
+        when!(gl.get_error).then(|_| panic!("Error flag polled"));
+        let context = GlContext::mocked(gl);
+        expect_that!(context.is_lost(), is_false());
+        context.mark_lost();
+        expect_that!(context.is_lost(), is_true());
+    }
+}
@@ -1,7 +1,9 @@
-use std::rc::Rc;
+use std::{cell::RefCell, rc::Rc};
 
 use anyhow::{Context, Error, Result};
 use glow::NativeProgram;
+use itertools::Itertools;
+use log::warn;
 use micromap::Map;
 use vek::{Extent2, Mat4, Vec2};
 
@@ -9,6 +11,9 @@ use super::{wrapper::GlowContext, GlContext};
 
 type UniformLocation = glow::NativeUniformLocation;
 
+/// Lines of the source added to compilation errors.
+const SOURCE_EXCERPT_LINES: usize = 10;
+
 pub struct ProgramGuard<'a> {
     program: &'a Program,
 }
@@ -16,9 +21,12 @@ pub struct ProgramGuard<'a> {
 pub struct Program {
     program: NativeProgram,
     gl: Rc<GlContext>,
-    uniforms: Map<String, UniformLocation, 10>,
+    uniforms: Map<String, UniformLocation, 16>,
+    /// Last value set for each uniform location, to skip setting it again.
+    values: RefCell<Map<u32, UniformValue, 16>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UniformValue {
     Float(f32),
     SignedInt(i32),
@@ -92,10 +100,20 @@ impl<'a> ProgramGuard<'a> {
 impl Program {
     pub fn new(gl: Rc<GlContext>, vertex: &str, fragment: &str) -> Result<Self> {
         let (program, uniforms) = unsafe {
-            let vertex = Self::compile_shader(&gl, glow::VERTEX_SHADER, vertex)
-                .context("Cannot compile vertex shader")?;
-            let fragment = Self::compile_shader(&gl, glow::FRAGMENT_SHADER, fragment)
-                .context("Cannot compile fragment shader")?;
+            let (vertex, fragment) = match Self::compile_shaders(&gl, vertex, fragment) {
+                Ok(shaders) => shaders,
+                // Some drivers of GLES 3 contexts fail on GLSL ES 1.00
+                Err(err) if supports_glsl_es3(&gl) => {
+                    warn!("Compiling shaders as GLSL ES 3.00 after: {:#}", err);
+                    Self::compile_shaders(
+                        &gl,
+                        &to_glsl_es3(vertex, glow::VERTEX_SHADER),
+                        &to_glsl_es3(fragment, glow::FRAGMENT_SHADER),
+                    )
+                    .with_context(|| format!("Also failed as GLSL ES 3.00, after: {err:#}"))?
+                }
+                Err(err) => return Err(err),
+            };
             let program = Self::link_program(&gl, &[vertex, fragment])
                 .context("Cannot link shader program")?;
             gl.delete_shader(vertex);
@@ -108,13 +126,14 @@ impl Program {
                         .with_context(|| format!("Cannot get uniform #{l}"))?;
                     Ok((info.name.to_owned(), glow::NativeUniformLocation(l as _)))
                 })
-                .collect::<Result<Map<String, UniformLocation, 10>>>()
+                .collect::<Result<Map<String, UniformLocation, 16>>>()
                 .context("While creating uniforms cache")?;
             (program, uniforms)
         };
         Ok(Self {
             program,
             uniforms,
+            values: RefCell::new(Map::new()),
             gl,
         })
     }
@@ -125,6 +144,8 @@ impl Program {
             unsafe {
                 self.gl.use_program(Some(self.program));
             }
+            // Values are kept by the program, but don't rely on it once another one was used
+            self.values.borrow_mut().clear();
         }
     }
 
@@ -136,6 +157,23 @@ impl Program {
         })
     }
 
+    unsafe fn compile_shaders(
+        gl: &GlowContext,
+        vertex: &str,
+        fragment: &str,
+    ) -> Result<(glow::Shader, glow::Shader)> {
+        unsafe {
+            let vertex = Self::compile_shader(gl, glow::VERTEX_SHADER, vertex)?;
+            match Self::compile_shader(gl, glow::FRAGMENT_SHADER, fragment) {
+                Ok(fragment) => Ok((vertex, fragment)),
+                Err(err) => {
+                    gl.delete_shader(vertex);
+                    Err(err)
+                }
+            }
+        }
+    }
+
     unsafe fn compile_shader(
         gl: &GlowContext,
         shader_type: u32,
@@ -151,7 +189,14 @@ impl Program {
             if gl.get_shader_compile_status(shader) {
                 Ok(shader)
             } else {
-                Err(Error::msg(gl.get_shader_info_log(shader)))
+                let log = gl.get_shader_info_log(shader);
+                gl.delete_shader(shader);
+                Err(Error::msg(format!(
+                    "Cannot compile {} shader: {}\n{}",
+                    shader_kind(shader_type),
+                    log.trim(),
+                    source_excerpt(source)
+                )))
             }
         }
     }
@@ -182,8 +227,15 @@ impl Program {
             .uniforms
             .get(name)
             .with_context(|| format!("Uniform {name} doesn't exists"))?;
-        let location = Some(location);
         let value = value.to_uniform_value();
+        {
+            let mut values = self.values.borrow_mut();
+            if values.get(&location.0) == Some(&value) {
+                return Ok(());
+            }
+            values.insert(location.0, value);
+        }
+        let location = Some(location);
         let gl = &self.gl;
         unsafe {
             match value {
@@ -206,3 +258,242 @@ impl Drop for Program {
         }
     }
 }
+
+fn shader_kind(shader_type: u32) -> &'static str {
+    match shader_type {
+        glow::VERTEX_SHADER => "vertex",
+        glow::FRAGMENT_SHADER => "fragment",
+        _ => "unknown",
+    }
+}
+
+/// First lines of `source`, numbered as in the info logs.
+fn source_excerpt(source: &str) -> String {
+    source
+        .lines()
+        .take(SOURCE_EXCERPT_LINES)
+        .enumerate()
+        .map(|(index, line)| format!("{:>3} | {line}", index + 1))
+        .join("\n")
+}
+
+fn supports_glsl_es3(gl: &GlowContext) -> bool {
+    let version = unsafe { gl.get_parameter_string(glow::VERSION) };
+    version.contains("OpenGL ES 3")
+}
+
+/// Rewrites a GLSL ES 1.00 shader, which all shaders are written in, as GLSL ES 3.00.
+fn to_glsl_es3(source: &str, shader_type: u32) -> String {
+    let fragment = shader_type == glow::FRAGMENT_SHADER;
+    let mut output_declared = !fragment;
+    let mut translated = String::with_capacity(source.len() + 64);
+    for line in source.lines() {
+        let directive = line.trim_start();
+        if directive.starts_with("#version") {
+            translated.push_str("#version 300 es\n");
+            continue;
+        }
+        // Declared after the directives, extensions must come first
+        if !output_declared && !directive.is_empty() && !directive.starts_with('#') {
+            translated.push_str("out mediump vec4 frag_color;\n");
+            output_declared = true;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find(is_identifier_char) {
+            translated.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+            translated.push_str(glsl_es3_identifier(&rest[..end], fragment));
+            rest = &rest[end..];
+        }
+        translated.push_str(rest);
+        translated.push('\n');
+    }
+    translated
+}
+
+fn glsl_es3_identifier(identifier: &str, fragment: bool) -> &str {
+    match identifier {
+        "attribute" => "in",
+        "varying" if fragment => "in",
+        "varying" => "out",
+        "texture2D" => "texture",
+        "gl_FragColor" => "frag_color",
+        _ => identifier,
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        num::NonZeroU32,
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use faux::when;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{contains_substring, eq, is_true, not, some, starts_with},
+    };
+
+    use super::{to_glsl_es3, Program, UniformValue};
+    use crate::gl::{wrapper::mocked_gl, GlContext};
+
+    const VERTEX: &str = "#version 100
+attribute vec2 pos;
+varying lowp vec2 texcoord;
+void main() {
+    texcoord = pos;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}";
+
+    const FRAGMENT: &str = "#version 100
+#extension GL_OES_standard_derivatives : enable
+precision mediump float;
+varying lowp vec2 texcoord;
+uniform sampler2D tex;
+void main() {
+    gl_FragColor = texture2D(tex, texcoord);
+}";
+
+    /// `amount` is a float uniform of the mocked programs.
+    fn cached_amount(program: &Program) -> Option<UniformValue> {
+        let location = program.uniforms.get("amount").unwrap().0;
+        program.values.borrow().get(&location).copied()
+    }
+
+    #[gtest]
+    fn test_identical_value_set_once() {
+        let mut gl = mocked_gl();
+        // Any other call would panic
+        when!(gl.uniform_1_f32(_, 0.5)).once().then_return(());
+        let gl = Rc::new(GlContext::mocked(gl));
+        let program = Program::new(gl, "", "").unwrap();
+        program.bind();
+
+        for _ in 0..3 {
+            program.set_uniform("amount", 0.5).unwrap();
+        }
+        expect_that!(cached_amount(&program), some(eq(UniformValue::Float(0.5))));
+    }
+
+    #[gtest]
+    fn test_different_values_are_set() {
+        let mut gl = mocked_gl();
+        when!(gl.uniform_1_f32(_, 0.5)).times(2).then_return(());
+        when!(gl.uniform_1_f32(_, 0.25)).once().then_return(());
+        let gl = Rc::new(GlContext::mocked(gl));
+        let program = Program::new(gl, "", "").unwrap();
+        program.bind();
+
+        program.set_uniform("amount", 0.5).unwrap();
+        program.set_uniform("amount", 0.25).unwrap();
+        expect_that!(cached_amount(&program), some(eq(UniformValue::Float(0.25))));
+        program.set_uniform("amount", 0.5).unwrap();
+        expect_that!(cached_amount(&program), some(eq(UniformValue::Float(0.5))));
+    }
+
+    #[gtest]
+    fn test_rebinding_invalidates_cache() {
+        let mut gl = mocked_gl();
+        when!(gl.uniform_1_f32(_, 0.5)).times(2).then_return(());
+        let gl = Rc::new(GlContext::mocked(gl));
+        let program = Program::new(Rc::clone(&gl), "", "").unwrap();
+        program.bind();
+        program.set_uniform("amount", 0.5).unwrap();
+
+        // Binding the same program again keeps the cache
+        program.bind();
+        program.set_uniform("amount", 0.5).unwrap();
+
+        // Another program was used in between
+        gl.set_bound_shader(NonZeroU32::new(2).unwrap());
+        program.bind();
+        expect_that!(program.values.borrow().is_empty(), is_true());
+        program.set_uniform("amount", 0.5).unwrap();
+        program.set_uniform("amount", 0.5).unwrap();
+    }
+
+    #[gtest]
+    fn test_compile_error_details() {
+        let mut gl = mocked_gl();
+        when!(gl.get_shader_compile_status).then_return(false);
+        when!(gl.get_shader_info_log).then_return("0:2: 'attribute' : syntax error\n".into());
+        // No GLSL ES 3.00 to fall back to
+        when!(gl.get_parameter_string).then_return("OpenGL ES 2.0".into());
+        let gl = Rc::new(GlContext::mocked(gl));
+        let source = (0..20)
+            .map(|line| format!("// line {line}\n"))
+            .collect::<String>();
+
+        let err = Program::new(gl, &source, FRAGMENT).err().unwrap();
+        let message = format!("{err:#}");
+        expect_that!(
+            message,
+            contains_substring("Cannot compile vertex shader: 0:2: 'attribute' : syntax error")
+        );
+        expect_that!(message, contains_substring("  1 | // line 0"));
+        expect_that!(message, contains_substring(" 10 | // line 9"));
+        expect_that!(message, not(contains_substring("// line 10")));
+    }
+
+    #[gtest]
+    fn test_glsl_es3_fallback() {
+        let mut gl = mocked_gl();
+        let sources = Arc::new(Mutex::new(Vec::<String>::new()));
+        when!(gl.shader_source).then({
+            let sources = Arc::clone(&sources);
+            move |(_, source)| sources.lock().unwrap().push(source.to_owned())
+        });
+        // Only GLSL ES 3.00 compiles
+        when!(gl.get_shader_compile_status).then({
+            let sources = Arc::clone(&sources);
+            move |_| {
+                let sources = sources.lock().unwrap();
+                sources.last().unwrap().starts_with("#version 300 es")
+            }
+        });
+        when!(gl.get_shader_info_log).then_return("version 100 unsupported".into());
+        let gl = Rc::new(GlContext::mocked(gl));
+
+        Program::new(gl, VERTEX, FRAGMENT).unwrap();
+        let sources = sources.lock().unwrap();
+        expect_that!(sources.len(), eq(3));
+        expect_that!(sources[0], starts_with("#version 100"));
+        expect_that!(sources[1], starts_with("#version 300 es"));
+        expect_that!(sources[2], starts_with("#version 300 es"));
+    }
+
+    #[gtest]
+    fn test_glsl_es3_translation() {
+        expect_that!(
+            to_glsl_es3(VERTEX, glow::VERTEX_SHADER),
+            eq("#version 300 es
+in vec2 pos;
+out lowp vec2 texcoord;
+void main() {
+    texcoord = pos;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
+")
+        );
+        expect_that!(
+            to_glsl_es3(FRAGMENT, glow::FRAGMENT_SHADER),
+            eq("#version 300 es
+#extension GL_OES_standard_derivatives : enable
+out mediump vec4 frag_color;
+precision mediump float;
+in lowp vec2 texcoord;
+uniform sampler2D tex;
+void main() {
+    frag_color = texture(tex, texcoord);
+}
+")
+        );
+    }
+}
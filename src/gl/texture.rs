@@ -1,4 +1,8 @@
-use std::rc::Rc;
+use std::{
+    mem::ManuallyDrop,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use anyhow::{Error, Result};
 use image::{DynamicImage, GenericImageView};
@@ -6,6 +10,19 @@ use vek::{Extent2, Rect};
 
 use super::{wrapper::GlowContext, GlContext};
 
+/// GPU memory taken by the textures alive, in bytes, whatever the thread that created them.
+/// Detached textures are still counted, until dropped, attached again or not.
+static TEXTURE_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+/// GPU memory taken by all the textures, in bytes, as uploaded without mipmaps.
+pub fn texture_memory() -> usize {
+    TEXTURE_MEMORY.load(Ordering::Relaxed)
+}
+
+fn memory_size(format: TextureFormat, size: Extent2<u32>) -> usize {
+    size.product() as usize * format.bytes_per_pixel()
+}
+
 #[derive(Debug)]
 pub struct Texture {
     texture: glow::Texture,
@@ -27,6 +44,7 @@ pub struct DetachedTexture {
 impl DetachedTexture {
     pub fn mock(size: Extent2<u32>) -> Self {
         use std::num::NonZeroU32;
+        TEXTURE_MEMORY.fetch_add(memory_size(TextureFormat::Rgb, size), Ordering::Relaxed);
         Self {
             texture: glow::NativeTexture(NonZeroU32::new(1).unwrap()),
             size,
@@ -45,6 +63,9 @@ pub struct TextureOptions {
     pub mag: TextureFiltering,
     pub min: TextureFiltering,
     pub wrap: TextureWrapMode,
+    /// Anisotropic filtering level, 0 or 1 disables it. Ignored when not supported by the
+    /// GPU and clamped to its maximum.
+    pub anisotropy: u8,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -57,7 +78,8 @@ pub enum TextureFiltering {
     Nearest,
     Linear,
 }
-#[derive(Debug, Copy, Clone)]
+/// How a texture is sampled beyond its edges, e.g. by linear filtering at the edge texels.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TextureWrapMode {
     ClampToEdge,
     MirroredRepeat,
@@ -115,6 +137,7 @@ impl Texture {
 
         use glow::NativeTexture;
 
+        TEXTURE_MEMORY.fetch_add(memory_size(TextureFormat::Rgb, size), Ordering::Relaxed);
         Self {
             size,
             gl,
@@ -126,10 +149,24 @@ impl Texture {
     }
 
     pub fn new_from_image(gl: Rc<GlContext>, image: &DynamicImage) -> Result<Self> {
+        Self::new_from_image_with_format(gl, image, TextureFormat::Rgb)
+    }
+
+    /// Like [`Self::new_from_image`], keeping the alpha channel with [`TextureFormat::Rgba`].
+    pub fn new_from_image_with_format(
+        gl: Rc<GlContext>,
+        image: &DynamicImage,
+        format: TextureFormat,
+    ) -> Result<Self> {
+        let texture = unsafe { Self::load_texture(&gl, image, format)? };
+        TEXTURE_MEMORY.fetch_add(
+            memory_size(format, image.dimensions().into()),
+            Ordering::Relaxed,
+        );
         let mut tex = Self {
             size: image.dimensions().into(),
-            texture: unsafe { Self::load_texture(&gl, image)? },
-            format: TextureFormat::Rgb,
+            texture,
+            format,
             options: Default::default(),
             gl,
             detached: false,
@@ -158,6 +195,7 @@ impl Texture {
                 glow::PixelUnpackData::Slice(None),
             );
             gl.bind_texture(TARGET, None);
+            TEXTURE_MEMORY.fetch_add(memory_size(format, dimensions), Ordering::Relaxed);
             Self {
                 size: dimensions,
                 gl,
@@ -172,6 +210,8 @@ impl Texture {
     }
 
     pub fn from_detached(gl: Rc<GlContext>, detached: DetachedTexture) -> Self {
+        // Counted by the texture from now on
+        let detached = ManuallyDrop::new(detached);
         Self {
             size: detached.size,
             texture: detached.texture,
@@ -204,10 +244,38 @@ impl Texture {
                 .tex_parameter_i32(TARGET, glow::TEXTURE_WRAP_S, options.wrap.to_gl());
             self.gl
                 .tex_parameter_i32(TARGET, glow::TEXTURE_WRAP_T, options.wrap.to_gl());
+            if options.anisotropy > 1 {
+                if let Some(max) = self.gl.max_anisotropy() {
+                    let anisotropy = f32::from(options.anisotropy);
+                    if anisotropy > max {
+                        log::debug!(
+                            "Anisotropy {anisotropy} clamped to the hardware maximum {max}"
+                        );
+                    }
+                    self.gl.tex_parameter_f32(
+                        TARGET,
+                        glow::TEXTURE_MAX_ANISOTROPY_EXT,
+                        anisotropy.min(max),
+                    );
+                }
+            }
             self.gl.bind_texture(TARGET, None);
         }
     }
 
+    /// Changes how the texture is sampled beyond its edges, keeping the other options.
+    pub fn set_wrap(&mut self, wrap: TextureWrapMode) {
+        self.set_options(TextureOptions {
+            wrap,
+            ..self.options
+        });
+    }
+
+    #[cfg(test)]
+    pub fn wrap(&self) -> TextureWrapMode {
+        self.options.wrap
+    }
+
     pub fn write(&mut self, format: TextureFormat, dimensions: Extent2<u32>, data: &[u8]) {
         assert_eq!(
             (dimensions.w * dimensions.h) as usize * format.bytes_per_pixel(),
@@ -228,6 +296,8 @@ impl Texture {
             );
             self.gl.bind_texture(TARGET, None);
         }
+        TEXTURE_MEMORY.fetch_sub(memory_size(self.format, self.size), Ordering::Relaxed);
+        TEXTURE_MEMORY.fetch_add(memory_size(format, dimensions), Ordering::Relaxed);
         self.format = format;
         self.size = dimensions;
     }
@@ -262,20 +332,27 @@ impl Texture {
         self.size
     }
 
-    unsafe fn load_texture(gl: &GlowContext, image: &DynamicImage) -> Result<glow::Texture> {
+    unsafe fn load_texture(
+        gl: &GlowContext,
+        image: &DynamicImage,
+        format: TextureFormat,
+    ) -> Result<glow::Texture> {
         let texture = gl.create_texture().map_err(Error::msg)?;
         gl.bind_texture(TARGET, Some(texture));
         // FIXME set in graphics init
         gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
-        let image_data = image.to_rgb8().into_raw();
+        let image_data = match format {
+            TextureFormat::Rgba => image.to_rgba8().into_raw(),
+            TextureFormat::Rgb => image.to_rgb8().into_raw(),
+        };
         gl.tex_image_2d(
             TARGET,
             0,
-            glow::RGB as _,
+            format.to_gl() as _,
             image.width() as i32,
             image.height() as i32,
             0,
-            glow::RGB,
+            format.to_gl(),
             glow::UNSIGNED_BYTE,
             glow::PixelUnpackData::Slice(Some(image_data.as_slice())),
         );
@@ -293,10 +370,100 @@ impl Texture {
     }
 }
 
+impl DetachedTexture {
+    /// Deletes the texture with `gl`, a context sharing the one it was created with.
+    pub fn delete(self, gl: Rc<GlContext>) {
+        drop(Texture::from_detached(gl, self));
+    }
+}
+
+/// Dropped without being attached nor deleted, e.g. prepared for a lost context. The
+/// texture itself is left to its context, there is none to delete it with.
+impl Drop for DetachedTexture {
+    fn drop(&mut self) {
+        TEXTURE_MEMORY.fetch_sub(memory_size(self.format, self.size), Ordering::Relaxed);
+    }
+}
+
 impl Drop for Texture {
     fn drop(&mut self) {
         if !self.detached {
             unsafe { self.gl.delete_texture(self.texture) };
+            TEXTURE_MEMORY.fetch_sub(memory_size(self.format, self.size), Ordering::Relaxed);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use faux::when;
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_empty},
+    };
+    use vek::Extent2;
+
+    use super::{Texture, TextureOptions};
+    use crate::gl::{
+        wrapper::{mocked_gl, GlowContext},
+        GlContext,
+    };
+
+    fn gl_with_extensions(extensions: &[&str], anisotropy: Arc<Mutex<Vec<f32>>>) -> GlowContext {
+        let mut gl = mocked_gl();
+        let extensions: &'static HashSet<String> =
+            Box::leak(Box::new(extensions.iter().map(|e| e.to_string()).collect()));
+        when!(gl.supported_extensions).then_return(extensions);
+        when!(gl.get_parameter_f32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT)).then_return(8.);
+        when!(gl.tex_parameter_f32(_, glow::TEXTURE_MAX_ANISOTROPY_EXT, _))
+            .then(move |(_, _, value)| anisotropy.lock().unwrap().push(value));
+        gl
+    }
+
+    fn set_anisotropy(gl: GlowContext, anisotropy: u8) {
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut texture = Texture::mocked(gl, Extent2::new(100, 100));
+        texture.set_options(TextureOptions {
+            anisotropy,
+            ..Default::default()
+        });
+    }
+
+    #[gtest]
+    fn test_anisotropy_set_when_supported() {
+        let anisotropy = Arc::new(Mutex::new(Vec::new()));
+        let gl = gl_with_extensions(&["GL_EXT_texture_filter_anisotropic"], anisotropy.clone());
+        set_anisotropy(gl, 4);
+        expect_that!(anisotropy.lock().unwrap().clone(), eq(vec![4.]));
+    }
+
+    #[gtest]
+    fn test_anisotropy_clamped_to_hardware_max() {
+        let anisotropy = Arc::new(Mutex::new(Vec::new()));
+        let gl = gl_with_extensions(&["GL_EXT_texture_filter_anisotropic"], anisotropy.clone());
+        set_anisotropy(gl, 16);
+        expect_that!(anisotropy.lock().unwrap().clone(), eq(vec![8.]));
+    }
+
+    #[gtest]
+    fn test_anisotropy_ignored_without_extension() {
+        let anisotropy = Arc::new(Mutex::new(Vec::new()));
+        let gl = gl_with_extensions(&["GL_OES_vertex_array_object"], anisotropy.clone());
+        set_anisotropy(gl, 4);
+        expect_that!(anisotropy.lock().unwrap().clone(), is_empty());
+    }
+
+    #[gtest]
+    fn test_anisotropy_disabled_by_default() {
+        let anisotropy = Arc::new(Mutex::new(Vec::new()));
+        let gl = gl_with_extensions(&["GL_EXT_texture_filter_anisotropic"], anisotropy.clone());
+        set_anisotropy(gl, 1);
+        expect_that!(anisotropy.lock().unwrap().clone(), is_empty());
+    }
+}
@@ -26,6 +26,14 @@ pub struct BufferInfo {
 pub struct VaoBindGuard<'a, V> {
     array_object: &'a VertexArrayObject<V>,
 }
+impl<V> VaoBindGuard<'_, V> {
+    /// The GPU may read the buffers until the frame is done, see [`BufferObject::update`].
+    pub fn mark_drawn(&self) {
+        self.array_object.vertex_buffer.mark_drawn();
+        self.array_object.element_buffer.mark_drawn();
+    }
+}
+
 impl<V> Drop for VaoBindGuard<'_, V> {
     fn drop(&mut self) {
         if self.array_object.vao.is_some() {
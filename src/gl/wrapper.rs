@@ -2,7 +2,7 @@ use std::collections::HashSet;
 
 use glow::{
     ActiveUniform, HasContext, NativeBuffer, NativeFramebuffer, NativeProgram, NativeShader,
-    NativeTexture, NativeUniformLocation, NativeVertexArray, PixelUnpackData,
+    NativeTexture, NativeUniformLocation, NativeVertexArray, PixelPackData, PixelUnpackData,
 };
 
 #[cfg_attr(test, faux::create)]
@@ -23,6 +23,11 @@ impl GlowContext {
         self.0.viewport(x, y, w, h)
     }
 
+    #[inline(always)]
+    pub unsafe fn scissor(&self, x: i32, y: i32, w: i32, h: i32) {
+        self.0.scissor(x, y, w, h)
+    }
+
     #[inline(always)]
     pub unsafe fn clear(&self, mask: u32) {
         self.0.clear(mask)
@@ -65,6 +70,11 @@ impl GlowContext {
         self.0.get_parameter_i32(parameter)
     }
 
+    #[inline(always)]
+    pub unsafe fn get_parameter_f32(&self, parameter: u32) -> f32 {
+        self.0.get_parameter_f32(parameter)
+    }
+
     #[inline(always)]
     pub unsafe fn delete_vertex_array(&self, vertex_array: NativeVertexArray) {
         self.0.delete_vertex_array(vertex_array)
@@ -165,6 +175,11 @@ impl GlowContext {
         self.0.tex_parameter_i32(target, parameter, value)
     }
 
+    #[inline(always)]
+    pub unsafe fn tex_parameter_f32(&self, target: u32, parameter: u32, value: f32) {
+        self.0.tex_parameter_f32(target, parameter, value)
+    }
+
     #[inline(always)]
     pub unsafe fn delete_program(&self, program: NativeProgram) {
         self.0.delete_program(program)
@@ -300,6 +315,11 @@ impl GlowContext {
         self.0.buffer_sub_data_u8_slice(target, offset, src_data)
     }
 
+    #[inline(always)]
+    pub unsafe fn buffer_data_size(&self, target: u32, size: i32, usage: u32) {
+        self.0.buffer_data_size(target, size, usage)
+    }
+
     #[inline(always)]
     pub unsafe fn buffer_data_u8_slice(&self, target: u32, data: &[u8], usage: u32) {
         self.0.buffer_data_u8_slice(target, data, usage)
@@ -374,6 +394,21 @@ impl GlowContext {
     pub unsafe fn finish(&self) {
         self.0.finish()
     }
+
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn read_pixels(
+        &self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        ty: u32,
+        pixels: PixelPackData,
+    ) {
+        self.0.read_pixels(x, y, width, height, format, ty, pixels)
+    }
 }
 
 #[cfg(test)]
@@ -382,7 +417,8 @@ mod test {
 
     use faux::when;
     use glow::{
-        ActiveUniform, NativeBuffer, NativeProgram, NativeShader, NativeTexture, NativeVertexArray,
+        ActiveUniform, NativeBuffer, NativeFramebuffer, NativeProgram, NativeShader, NativeTexture,
+        NativeVertexArray,
     };
 
     use super::GlowContext;
@@ -402,7 +438,7 @@ mod test {
         when!(gl.get_program_link_status).then_return(true);
         when!(gl.delete_shader).then_return(());
         when!(gl.delete_program).then_return(());
-        when!(gl.get_program_parameter_i32).then_return(8);
+        when!(gl.get_program_parameter_i32).then_return(12);
         when!(gl.get_attrib_location).then_return(Some(1));
         when!(gl.get_active_uniform).then(|(_, i)| {
             let n = match i {
@@ -414,6 +450,10 @@ mod test {
                 5 => "uv_offset_size",
                 6 => "tex_size",
                 7 => "dir",
+                8 => "darken",
+                9 => "desaturate",
+                10 => "amount",
+                11 => "opacity",
                 _ => return None,
             };
             Some(ActiveUniform {
@@ -426,6 +466,7 @@ mod test {
         when!(gl.bind_framebuffer).then_return(());
         when!(gl.bind_texture).then_return(());
         when!(gl.bind_vertex_array).then_return(());
+        when!(gl.buffer_data_size).then_return(());
         when!(gl.buffer_data_u8_slice).then_return(());
         when!(gl.buffer_sub_data_u8_slice).then_return(());
         when!(gl.framebuffer_texture_2d).then_return(());
@@ -439,6 +480,13 @@ mod test {
         when!(gl.tex_sub_image_2d).then_return(());
         when!(gl.vertex_attrib_pointer_f32).then_return(());
         when!(gl.enable_vertex_attrib_array).then_return(());
+        when!(gl.create_framebuffer)
+            .then_return(Ok(NativeFramebuffer(NonZeroU32::new(1).unwrap())));
+        when!(gl.delete_framebuffer).then_return(());
+        when!(gl.viewport).then_return(());
+        when!(gl.use_program).then_return(());
+        when!(gl.active_texture).then_return(());
+        when!(gl.uniform_1_i32).then_return(());
         gl
     }
 }
@@ -5,12 +5,12 @@ use serde::Deserialize;
 
 use super::Vertex2dUv;
 use crate::{
-    configuration::BlurSettings,
+    configuration::{Background, BlurSettings},
     gl::{
         buffer_object::{BufferObject, BufferUsage, ElementBufferObject},
         framebuffer::FramebufferObject,
         shader::{Program, ProgramGuard},
-        texture::{Texture, TextureFormat},
+        texture::{Texture, TextureFormat, TextureWrapMode},
         vao::{BufferInfo, VertexArrayObject},
         GlContext,
     },
@@ -19,6 +19,13 @@ use crate::{
 pub struct ImageBlurr {
     vertex_array: VertexArrayObject<Vertex2dUv>,
     program: Program,
+    gamma_vertex_array: VertexArrayObject<Vertex2dUv>,
+    /// Blurs in linear light, see [`BlurSettings::gamma_correct`].
+    gamma_program: Program,
+    tone_vertex_array: VertexArrayObject<Vertex2dUv>,
+    tone_program: Program,
+    sharpen_vertex_array: VertexArrayObject<Vertex2dUv>,
+    sharpen_program: Program,
     gl: Rc<GlContext>,
 }
 
@@ -27,6 +34,7 @@ pub struct ImageBlurr {
 pub struct BlurOptions {
     pub radius: f32,
     pub passes: u8,
+    pub gamma_correct: bool,
 }
 
 impl From<BlurSettings> for BlurOptions {
@@ -34,6 +42,34 @@ impl From<BlurSettings> for BlurOptions {
         Self {
             radius: options.radius,
             passes: options.passes,
+            gamma_correct: options.gamma_correct,
+        }
+    }
+}
+
+/// Color adjustments applied to the blurred background once blurred.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneOptions {
+    /// Fraction of brightness removed, 0 keeps the image as is and 1 makes it black.
+    pub darken: f32,
+    /// Fraction of saturation removed, 0 keeps the image as is and 1 makes it grayscale.
+    pub desaturate: f32,
+}
+
+impl ToneOptions {
+    fn is_neutral(&self) -> bool {
+        self.darken == 0. && self.desaturate == 0.
+    }
+}
+
+impl From<&Background> for ToneOptions {
+    fn from(background: &Background) -> Self {
+        match background {
+            Background::Blur(blur) => Self {
+                darken: blur.darken.clamp(0., 1.),
+                desaturate: blur.desaturate.clamp(0., 1.),
+            },
+            Background::Black => Self::default(),
         }
     }
 }
@@ -43,6 +79,7 @@ impl Default for BlurOptions {
         BlurOptions {
             passes: 3,
             radius: 6.,
+            gamma_correct: false,
         }
     }
 }
@@ -57,14 +94,53 @@ const VERTICES: [Vertex2dUv; 4] = [
 const INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
 impl ImageBlurr {
     pub fn new(gl: Rc<GlContext>) -> Result<Self> {
-        let mut vbo = BufferObject::new_vertex_buffer(Rc::clone(&gl), BufferUsage::Static)
+        let program = Program::new(
+            Rc::clone(&gl),
+            shader::VERTEX_BLUR,
+            &shader::fragment_blur(false),
+        )
+        .context("Cannot compile ImageBlurr shader")?;
+        let vertex_array = Self::create_vertex_array(&gl, &program)?;
+        let gamma_program = Program::new(
+            Rc::clone(&gl),
+            shader::VERTEX_BLUR,
+            &shader::fragment_blur(true),
+        )
+        .context("Cannot compile ImageBlurr gamma correct shader")?;
+        let gamma_vertex_array = Self::create_vertex_array(&gl, &gamma_program)?;
+        let tone_program = Program::new(Rc::clone(&gl), shader::VERTEX_BLUR, shader::FRAGMENT_TONE)
+            .context("Cannot compile ImageBlurr tone shader")?;
+        let tone_vertex_array = Self::create_vertex_array(&gl, &tone_program)?;
+        let sharpen_program = Program::new(
+            Rc::clone(&gl),
+            shader::VERTEX_BLUR,
+            shader::FRAGMENT_SHARPEN,
+        )
+        .context("Cannot compile ImageBlurr sharpen shader")?;
+        let sharpen_vertex_array = Self::create_vertex_array(&gl, &sharpen_program)?;
+
+        Ok(Self {
+            vertex_array,
+            program,
+            gamma_vertex_array,
+            gamma_program,
+            tone_vertex_array,
+            tone_program,
+            sharpen_vertex_array,
+            sharpen_program,
+            gl,
+        })
+    }
+
+    fn create_vertex_array(
+        gl: &Rc<GlContext>,
+        program: &Program,
+    ) -> Result<VertexArrayObject<Vertex2dUv>> {
+        let mut vbo = BufferObject::new_vertex_buffer(Rc::clone(gl), BufferUsage::Static)
             .context("Cannot create vertex buffer")?;
-        let mut ebo = ElementBufferObject::new_index_buffer(Rc::clone(&gl), BufferUsage::Static)
+        let mut ebo = ElementBufferObject::new_index_buffer(Rc::clone(gl), BufferUsage::Static)
             .context("Cannot create ElementArrayBuffer")?;
 
-        let program = Program::new(Rc::clone(&gl), shader::VERTEX_BLUR, shader::FRAGMENT_BLUR)
-            .context("Cannot compile ImageBlurr shader")?;
-        let program = program;
         let pos = program.get_attrib_location("pos")?;
         let uv = program.get_attrib_location("uv")?;
 
@@ -90,19 +166,16 @@ impl ImageBlurr {
 
         vbo.write(&VERTICES);
         ebo.write(&INDICES);
-        let vao = VertexArrayObject::new(Rc::clone(&gl), vbo, ebo, buffer_infos)
-            .context("Cannot create VAO")?;
-
-        Ok(Self {
-            vertex_array: vao,
-            program,
-            gl,
-        })
+        VertexArrayObject::new(Rc::clone(gl), vbo, ebo, buffer_infos).context("Cannot create VAO")
     }
 
     pub fn blur(
         &self,
-        BlurOptions { radius, passes }: BlurOptions,
+        BlurOptions {
+            radius,
+            passes,
+            gamma_correct,
+        }: BlurOptions,
         texture: &Texture,
     ) -> Result<Texture> {
         let textures = [
@@ -113,7 +186,9 @@ impl ImageBlurr {
         ];
         let fbos = textures
             .into_iter()
-            .map(|texture| {
+            .map(|mut texture| {
+                // Passes sample around each pixel, repeating would blend the opposite edge in
+                texture.set_wrap(TextureWrapMode::ClampToEdge);
                 FramebufferObject::with_texture(Rc::clone(&self.gl), texture)
                     .context("Cannot create blur framebuffer")
             })
@@ -121,8 +196,13 @@ impl ImageBlurr {
 
         let mut source_texture = texture;
 
-        let program_bind = ProgramGuard::bind(&self.program);
-        let _vao_guard = self.vertex_array.bind_guard();
+        let (program, vertex_array) = if gamma_correct {
+            (&self.gamma_program, &self.gamma_vertex_array)
+        } else {
+            (&self.program, &self.vertex_array)
+        };
+        let program_bind = ProgramGuard::bind(program);
+        let _vao_guard = vertex_array.bind_guard();
 
         program_bind.set_uniform("tex_size", texture.size().as_::<f32>())?;
         program_bind.set_uniform("tex", 0)?;
@@ -173,6 +253,71 @@ impl ImageBlurr {
             .expect("FBOs are not empty")
             .into_texture())
     }
+
+    /// Darkens and desaturates `texture` into a new texture. When `options` are neutral, the
+    /// texture is returned untouched so the output stays exactly the same as without toning.
+    pub fn tone(&self, options: ToneOptions, texture: Texture) -> Result<Texture> {
+        if options.is_neutral() {
+            return Ok(texture);
+        }
+        self.render_pass(
+            &self.tone_program,
+            &self.tone_vertex_array,
+            &texture,
+            |program_bind| {
+                program_bind.set_uniform("darken", options.darken)?;
+                program_bind.set_uniform("desaturate", options.desaturate)
+            },
+        )
+    }
+
+    /// Sharpens `texture` with an unsharp mask into a new texture, `amount` being the
+    /// strength of the effect. When it's 0, the texture is returned untouched.
+    pub fn sharpen(&self, amount: f32, texture: Texture) -> Result<Texture> {
+        if amount <= 0. {
+            return Ok(texture);
+        }
+        self.render_pass(
+            &self.sharpen_program,
+            &self.sharpen_vertex_array,
+            &texture,
+            |program_bind| {
+                program_bind.set_uniform("tex_size", texture.size().as_::<f32>())?;
+                program_bind.set_uniform("amount", amount)
+            },
+        )
+    }
+
+    /// Draws `texture` with `program` into a new texture of the same size.
+    fn render_pass(
+        &self,
+        program: &Program,
+        vertex_array: &VertexArrayObject<Vertex2dUv>,
+        texture: &Texture,
+        set_uniforms: impl FnOnce(&ProgramGuard) -> Result<()>,
+    ) -> Result<Texture> {
+        let mut target = Texture::empty(Rc::clone(&self.gl), TextureFormat::Rgb, texture.size())
+            .context("cannot create texture")?;
+        target.set_wrap(TextureWrapMode::ClampToEdge);
+        let fbo = FramebufferObject::with_texture(Rc::clone(&self.gl), target)
+            .context("Cannot create framebuffer")?;
+        {
+            let program_bind = ProgramGuard::bind(program);
+            let vao_guard = vertex_array.bind_guard();
+            program_bind.set_uniform("tex", 0)?;
+            set_uniforms(&program_bind)?;
+            let _guard = fbo.bind_guard();
+            texture.bind(Some(0));
+            self.gl.draw(
+                &vao_guard,
+                &program_bind,
+                INDICES.len() as _,
+                0,
+                &Default::default(),
+            );
+        }
+        Ok(fbo.into_texture())
+    }
 }
 
 mod shader {
@@ -186,7 +331,15 @@ mod shader {
         gl_Position = vec4(pos, 0, 1);
         texcoord = uv;
     }"#;
-    pub const FRAGMENT_BLUR: &str = r#"#version 100
+    /// Blur shader, decoding each sample to linear light and encoding the result back
+    /// when `gamma_correct`, which costs a `pow` per sample.
+    pub fn fragment_blur(gamma_correct: bool) -> String {
+        let gamma_correct = u8::from(gamma_correct);
+        format!("#version 100\n#define GAMMA_CORRECT {gamma_correct}\n{FRAGMENT_BLUR}")
+    }
+
+    /// Compiled with the header of [`fragment_blur`], `#version` being the first line.
+    const FRAGMENT_BLUR: &str = r#"
     precision mediump float;
 
     varying lowp vec2 texcoord;
@@ -195,23 +348,33 @@ mod shader {
     uniform lowp vec2 tex_size;
     uniform lowp vec2 dir;
 
+    // Blended in linear light when gamma correct, the textures being sRGB encoded
+    vec4 tap(sampler2D image, vec2 uv) {
+    #if GAMMA_CORRECT
+      vec4 color = texture2D(image, uv);
+      return vec4(pow(color.rgb, vec3(2.2)), color.a);
+    #else
+      return texture2D(image, uv);
+    #endif
+    }
+
     vec4 blur5(sampler2D image, vec2 uv, vec2 resolution, vec2 direction) {
       vec4 color = vec4(0.0);
       vec2 off1 = vec2(1.3333333333333333) * direction;
-      color += texture2D(image, uv) * 0.29411764705882354;
-      color += texture2D(image, uv + (off1 / resolution)) * 0.35294117647058826;
-      color += texture2D(image, uv - (off1 / resolution)) * 0.35294117647058826;
+      color += tap(image, uv) * 0.29411764705882354;
+      color += tap(image, uv + (off1 / resolution)) * 0.35294117647058826;
+      color += tap(image, uv - (off1 / resolution)) * 0.35294117647058826;
       return color;
     }
     vec4 blur9(sampler2D image, vec2 uv, vec2 resolution, vec2 direction) {
       vec4 color = vec4(0.0);
       vec2 off1 = vec2(1.3846153846) * direction;
       vec2 off2 = vec2(3.2307692308) * direction;
-      color += texture2D(image, uv) * 0.2270270270;
-      color += texture2D(image, uv + (off1 / resolution)) * 0.3162162162;
-      color += texture2D(image, uv - (off1 / resolution)) * 0.3162162162;
-      color += texture2D(image, uv + (off2 / resolution)) * 0.0702702703;
-      color += texture2D(image, uv - (off2 / resolution)) * 0.0702702703;
+      color += tap(image, uv) * 0.2270270270;
+      color += tap(image, uv + (off1 / resolution)) * 0.3162162162;
+      color += tap(image, uv - (off1 / resolution)) * 0.3162162162;
+      color += tap(image, uv + (off2 / resolution)) * 0.0702702703;
+      color += tap(image, uv - (off2 / resolution)) * 0.0702702703;
       return color;
     }
     vec4 blur13(sampler2D image, vec2 uv, vec2 resolution, vec2 direction) {
@@ -219,17 +382,171 @@ mod shader {
       vec2 off1 = vec2(1.411764705882353) * direction;
       vec2 off2 = vec2(3.2941176470588234) * direction;
       vec2 off3 = vec2(5.176470588235294) * direction;
-      color += texture2D(image, uv) * 0.1964825501511404;
-      color += texture2D(image, uv + (off1 / resolution)) * 0.2969069646728344;
-      color += texture2D(image, uv - (off1 / resolution)) * 0.2969069646728344;
-      color += texture2D(image, uv + (off2 / resolution)) * 0.09447039785044732;
-      color += texture2D(image, uv - (off2 / resolution)) * 0.09447039785044732;
-      color += texture2D(image, uv + (off3 / resolution)) * 0.010381362401148057;
-      color += texture2D(image, uv - (off3 / resolution)) * 0.010381362401148057;
+      color += tap(image, uv) * 0.1964825501511404;
+      color += tap(image, uv + (off1 / resolution)) * 0.2969069646728344;
+      color += tap(image, uv - (off1 / resolution)) * 0.2969069646728344;
+      color += tap(image, uv + (off2 / resolution)) * 0.09447039785044732;
+      color += tap(image, uv - (off2 / resolution)) * 0.09447039785044732;
+      color += tap(image, uv + (off3 / resolution)) * 0.010381362401148057;
+      color += tap(image, uv - (off3 / resolution)) * 0.010381362401148057;
       return color;
     }
 
     void main() {
-        gl_FragColor =  blur13(tex, texcoord, tex_size, dir);
+        vec4 color = blur13(tex, texcoord, tex_size, dir);
+    #if GAMMA_CORRECT
+        color.rgb = pow(color.rgb, vec3(1.0 / 2.2));
+    #endif
+        gl_FragColor = color;
+    }"#;
+    pub const FRAGMENT_TONE: &str = r#"#version 100
+    precision mediump float;
+
+    varying lowp vec2 texcoord;
+
+    uniform sampler2D tex;
+    uniform lowp float darken;
+    uniform lowp float desaturate;
+
+    void main() {
+        vec4 color = texture2D(tex, texcoord);
+        float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+        vec3 rgb = mix(color.rgb, vec3(luminance), desaturate) * (1.0 - darken);
+        gl_FragColor = vec4(rgb, color.a);
+    }"#;
+    pub const FRAGMENT_SHARPEN: &str = r#"#version 100
+    precision mediump float;
+
+    varying lowp vec2 texcoord;
+
+    uniform sampler2D tex;
+    uniform vec2 tex_size;
+    uniform float amount;
+
+    void main() {
+        vec2 texel = 1.0 / tex_size;
+        vec4 color = texture2D(tex, texcoord);
+        vec3 neighbours = texture2D(tex, texcoord + vec2(texel.x, 0.0)).rgb
+            + texture2D(tex, texcoord - vec2(texel.x, 0.0)).rgb
+            + texture2D(tex, texcoord + vec2(0.0, texel.y)).rgb
+            + texture2D(tex, texcoord - vec2(0.0, texel.y)).rgb;
+        // Unsharp mask: add back the difference with a cheap blur of the pixel
+        vec3 rgb = color.rgb + amount * (color.rgb - neighbours * 0.25);
+        gl_FragColor = vec4(clamp(rgb, 0.0, 1.0), color.a);
     }"#;
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        rc::Rc,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use faux::when;
+    use googletest::{expect_that, gtest, prelude::eq};
+    use vek::Extent2;
+
+    use super::{ImageBlurr, ToneOptions};
+    use crate::gl::{texture::Texture, wrapper::mocked_gl, GlContext};
+
+    #[gtest]
+    fn test_neutral_tone_is_skipped() {
+        // uniform_1_f32 and draw_elements are not mocked: calling them would panic.
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let blurr = ImageBlurr::new(gl.clone()).unwrap();
+        let texture = Texture::mocked(gl.clone(), Extent2::new(100, 100));
+
+        let toned = blurr.tone(ToneOptions::default(), texture).unwrap();
+        expect_that!(toned.size(), eq(Extent2::new(100, 100)));
+    }
+
+    #[gtest]
+    fn test_tone_sets_uniforms() {
+        let mut gl = mocked_gl();
+        let draws = Arc::new(AtomicUsize::new(0));
+        // Only the configured values are accepted, any other call would panic.
+        when!(gl.uniform_1_f32(_, 0.25)).then_return(());
+        when!(gl.uniform_1_f32(_, 0.5)).then_return(());
+        when!(gl.draw_elements).then({
+            let draws = Arc::clone(&draws);
+            move |_| {
+                draws.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let gl = Rc::new(GlContext::mocked(gl));
+        let blurr = ImageBlurr::new(gl.clone()).unwrap();
+        let texture = Texture::mocked(gl.clone(), Extent2::new(100, 100));
+
+        let options = ToneOptions {
+            darken: 0.25,
+            desaturate: 0.5,
+        };
+        let toned = blurr.tone(options, texture).unwrap();
+        expect_that!(toned.size(), eq(Extent2::new(100, 100)));
+        expect_that!(draws.load(Ordering::SeqCst), eq(1));
+    }
+
+    #[gtest]
+    fn test_blur_programs_compiled_with_gamma_define() {
+        let mut gl = mocked_gl();
+        let sources = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&sources);
+        // SAFETY: the source is copied before the call returns
+        unsafe {
+            when!(gl.shader_source).then_unchecked(move |(_, source)| {
+                recorded.lock().unwrap().push(source.to_owned());
+            });
+        }
+        let gl = Rc::new(GlContext::mocked(gl));
+        ImageBlurr::new(gl).unwrap();
+
+        let sources = sources.lock().unwrap();
+        let headers: Vec<_> = sources
+            .iter()
+            .filter(|source| source.contains("GAMMA_CORRECT"))
+            .map(|source| source.lines().take(2).collect::<Vec<_>>().join("\n"))
+            .collect();
+        expect_that!(
+            headers,
+            eq(&vec![
+                "#version 100\n#define GAMMA_CORRECT 0",
+                "#version 100\n#define GAMMA_CORRECT 1",
+            ])
+        );
+    }
+
+    #[gtest]
+    fn test_zero_sharpen_is_skipped() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let blurr = ImageBlurr::new(gl.clone()).unwrap();
+        let texture = Texture::mocked(gl.clone(), Extent2::new(100, 100));
+
+        let sharpened = blurr.sharpen(0., texture).unwrap();
+        expect_that!(sharpened.size(), eq(Extent2::new(100, 100)));
+    }
+
+    #[gtest]
+    fn test_sharpen_sets_uniforms() {
+        let mut gl = mocked_gl();
+        let draws = Arc::new(AtomicUsize::new(0));
+        when!(gl.uniform_1_f32(_, 0.5)).then_return(());
+        when!(gl.uniform_2_f32(_, 100., 50.)).then_return(());
+        when!(gl.draw_elements).then({
+            let draws = Arc::clone(&draws);
+            move |_| {
+                draws.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let gl = Rc::new(GlContext::mocked(gl));
+        let blurr = ImageBlurr::new(gl.clone()).unwrap();
+        let texture = Texture::mocked(gl.clone(), Extent2::new(100, 50));
+
+        let sharpened = blurr.sharpen(0.5, texture).unwrap();
+        expect_that!(sharpened.size(), eq(Extent2::new(100, 50)));
+        expect_that!(draws.load(Ordering::SeqCst), eq(1));
+    }
+}
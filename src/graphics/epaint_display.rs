@@ -1,6 +1,10 @@
 use std::{
     cell::RefCell,
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
     rc::{Rc, Weak},
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result};
@@ -8,8 +12,10 @@ use bytemuck::{Pod, Zeroable};
 use epaint::{
     image::AlphaFromCoverage,
     text::{FontDefinitions, LayoutJob},
-    Color32, Fonts, ImageData, Mesh, Shape, TessellationOptions, Tessellator, TextShape,
+    Color32, FontId, Fonts, ImageData, ImageDelta, Mesh, Shape, TessellationOptions, Tessellator,
+    TextShape,
 };
+use log::debug;
 use vek::{Extent2, Mat4, Rect, Vec2};
 
 use super::{Drawable, Graphics, SharedTexture2d};
@@ -18,11 +24,13 @@ use crate::gl::{
     shader::{Program, ProgramGuard},
     texture::{Texture, TextureFiltering, TextureFormat, TextureOptions, TextureWrapMode},
     vao::{BufferInfo, VertexArrayObject},
-    BlendMode, DrawParameters, GlContext,
+    DrawParameters, GlContext,
 };
 
 pub struct EpaintDisplay {
-    fonts: Fonts,
+    /// Loaded with the first text container or warm up, as it takes a while and shapes
+    /// don't need it. See [`Self::fonts`].
+    fonts: Option<Fonts>,
     pixels_per_point: f32,
     max_texture_size: usize,
     texture: Rc<RefCell<Texture>>,
@@ -30,7 +38,46 @@ pub struct EpaintDisplay {
     program: Rc<Program>,
     gl: Rc<GlContext>,
     containers: Vec<Weak<RefCell<TextContainerInner>>>,
+    /// Vertex arrays of the dropped containers, reused by the next ones rather than
+    /// creating buffers for each caption.
+    free_vaos: Rc<RefCell<Vec<VertexArrayObject<Vertex>>>>,
+    /// Changes of the font atlas from the layouts since the last update, uploaded with it.
+    font_deltas: Vec<ImageDelta>,
     atlas_updated: bool,
+    meshes: MeshCache,
+    /// Since the beginning of the frame.
+    stats: TessellationStats,
+}
+
+/// Number of text meshes kept for reuse, captions often repeat the same lines.
+const MESH_CACHE_SIZE: usize = 16;
+/// Vertex arrays of dropped containers kept for reuse, the next ones are deleted. A few
+/// slides are alive at once during transitions, each with a caption and its background.
+const FREE_VAOS: usize = 8;
+
+/// Glyphs added to the font atlas by [`EpaintDisplay::warm_up_fonts`], the ones of most
+/// captions: places and dates in the supported locales.
+const WARM_UP_GLYPHS: &str = "0123456789 \
+    abcdefghijklmnopqrstuvwxyz ABCDEFGHIJKLMNOPQRSTUVWXYZ \
+    àâäçéèêëîïôöùûüÿæœß ÀÂÄÇÉÈÊËÎÏÔÖÙÛÜŸÆŒ \
+    .,;:!?'’\"-–—()[]/&@#%+*";
+
+/// Text meshes by hash of their layout, least recently used first. They are only valid for
+/// the font atlas they were tessellated with, and cleared when it's replaced.
+#[derive(Default)]
+struct MeshCache {
+    entries: VecDeque<(u64, Mesh)>,
+}
+
+/// Text tessellation work, shown in the debug overlay.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TessellationStats {
+    /// Spent tessellating texts, or copying their cached mesh, and uploading them.
+    pub time: Duration,
+    /// Texts tessellated.
+    pub tessellated: u32,
+    /// Texts whose mesh was found in the cache.
+    pub cached: u32,
 }
 
 #[repr(C)]
@@ -46,18 +93,55 @@ pub struct TextContainer(Rc<RefCell<TextContainerInner>>);
 pub struct ShapeContainer {
     pub position: Vec2<f32>,
     pub opacity_factor: f32,
+    /// Only the part of the shape in this rectangle is drawn, in screen pixels.
+    pub clip: Option<Rect<i32, i32>>,
 
-    vao: VertexArrayObject<Vertex>,
+    vao: PooledVao,
     texture: Option<SharedTexture2d>,
 }
 
+/// Vertex array of a container, given back to [`EpaintDisplay`] for reuse when dropped.
+struct PooledVao {
+    /// Only taken when dropped.
+    vao: Option<VertexArrayObject<Vertex>>,
+    free_vaos: Weak<RefCell<Vec<VertexArrayObject<Vertex>>>>,
+}
+
+impl Deref for PooledVao {
+    type Target = VertexArrayObject<Vertex>;
+
+    fn deref(&self) -> &Self::Target {
+        self.vao.as_ref().expect("VAO is only taken when dropped")
+    }
+}
+
+impl DerefMut for PooledVao {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.vao.as_mut().expect("VAO is only taken when dropped")
+    }
+}
+
+impl Drop for PooledVao {
+    fn drop(&mut self) {
+        // Deleted when the display is gone or enough are kept already
+        let (Some(vao), Some(free_vaos)) = (self.vao.take(), self.free_vaos.upgrade()) else {
+            return;
+        };
+        let mut free_vaos = free_vaos.borrow_mut();
+        if free_vaos.len() < FREE_VAOS {
+            free_vaos.push(vao);
+        }
+    }
+}
+
 impl ShapeContainer {
-    fn new(vao: VertexArrayObject<Vertex>, texture: Option<SharedTexture2d>) -> Self {
+    fn new(vao: PooledVao, texture: Option<SharedTexture2d>) -> Self {
         Self {
             position: [0., 0.].into(),
             vao,
             texture,
             opacity_factor: 1f32,
+            clip: None,
         }
     }
 
@@ -138,8 +222,13 @@ impl TextContainer {
         self.0.borrow_mut().opacity_factor = opacity;
     }
 
+    /// Only draws the part of the text in `clip`, in screen pixels.
+    pub fn set_clip(&self, clip: Option<Rect<i32, i32>>) {
+        self.0.borrow_mut().clip = clip;
+    }
+
     pub fn force_update(&self, epaint: &mut EpaintDisplay) {
-        self.0.borrow_mut().update(epaint);
+        epaint.update_container(&mut self.0.borrow_mut());
     }
 
     #[cfg(test)]
@@ -160,49 +249,64 @@ impl Drawable for TextContainer {
 
 impl Drawable for ShapeContainer {
     fn draw(&self, graphics: &Graphics) -> Result<()> {
-        graphics.epaint_display().draw_shape(graphics.view(), self)
+        let scissor = self.clip.map(|clip| graphics.scissor_rect(clip));
+        graphics
+            .epaint_display()
+            .draw_shape(graphics.view(), self, scissor)
     }
 }
 
 struct TextContainerInner {
     position: Vec2<f32>,
     text_mesh: Mesh,
-    // TODO implement a pool for old VAOs
-    text_vao: VertexArrayObject<Vertex>,
+    text_vao: PooledVao,
     next_layout: Option<LayoutJob>,
+    /// See [`layout_hash`].
+    layout_hash: Option<u64>,
     shape: Option<TextShape>,
     opacity_factor: f32,
+    clip: Option<Rect<i32, i32>>,
     is_dirty: bool,
 }
 
 impl TextContainerInner {
     #[inline]
     fn draw(&self, graphics: &super::Graphics) -> Result<()> {
-        graphics.epaint_display().draw_text(graphics.view(), self)
+        let scissor = self.clip.map(|clip| graphics.scissor_rect(clip));
+        graphics
+            .epaint_display()
+            .draw_text(graphics.view(), self, scissor)
     }
+}
 
-    fn update(&mut self, epaint: &mut EpaintDisplay) {
-        if let Some(job) = self.next_layout.take() {
-            let galley = epaint
-                .fonts
-                .with_pixels_per_point(epaint.pixels_per_point)
-                .layout_job(job);
-            self.shape = Some(TextShape::new([0., 0.].into(), galley, Color32::WHITE));
-        }
-        if self.is_dirty || epaint.atlas_updated {
-            self.is_dirty = false;
-            self.text_mesh.clear();
-            if let Some(shape) = &self.shape {
-                epaint
-                    .tesselator
-                    .tessellate_text(shape, &mut self.text_mesh);
-
-                write_mesh_to_vao(&self.text_mesh, &mut self.text_vao);
-            }
+impl MeshCache {
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn get(&mut self, hash: u64) -> Option<&Mesh> {
+        let index = self.entries.iter().position(|(key, _)| *key == hash)?;
+        let entry = self.entries.remove(index)?;
+        self.entries.push_back(entry);
+        self.entries.back().map(|(_, mesh)| mesh)
+    }
+
+    fn insert(&mut self, hash: u64, mesh: Mesh) {
+        if self.entries.len() >= MESH_CACHE_SIZE {
+            self.entries.pop_front();
         }
+        self.entries.push_back((hash, mesh));
     }
 }
 
+/// Hash of a layout, identical for identical texts and formats. Uses a fixed key, unlike
+/// the hashers of `HashMap`.
+fn layout_hash(job: &LayoutJob) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    job.hash(&mut hasher);
+    hasher.finish()
+}
+
 impl From<epaint::Vertex> for Vertex {
     fn from(value: epaint::Vertex) -> Self {
         Self {
@@ -217,25 +321,23 @@ impl EpaintDisplay {
     pub fn new(gl: Rc<GlContext>) -> Result<Self> {
         let pixels_per_point: f32 = 1.;
         let max_texture_size = gl.capabilities().max_texture_size as usize;
-        let fonts = Fonts::new(
-            max_texture_size,
-            AlphaFromCoverage::TwoCoverageMinusCoverageSq,
-            FontDefinitions::default(),
-        );
+        // Shapes sample the top left texel of the font atlas, white. Until the fonts are
+        // loaded, it's the only texel of the texture.
         let tesselator = Tessellator::new(
             pixels_per_point,
             TessellationOptions::default(),
-            fonts.font_image_size(),
+            [1, 1],
             Vec::new(),
         );
 
         let program = Program::new(Rc::clone(&gl), shaders::VERTEX, shaders::FRAGMENT)
             .context("Cannot compile epaint shader")?;
-        let texture = Texture::empty(Rc::clone(&gl), TextureFormat::Rgba, (0, 0).into())
+        let mut texture = Texture::empty(Rc::clone(&gl), TextureFormat::Rgba, (0, 0).into())
             .context("Cannot create texture")?;
+        texture.write(TextureFormat::Rgba, (1, 1).into(), &[u8::MAX; 4]);
 
         Ok(Self {
-            fonts,
+            fonts: None,
             pixels_per_point,
             max_texture_size,
             texture: Rc::new(texture.into()),
@@ -243,16 +345,87 @@ impl EpaintDisplay {
             program: Rc::new(program),
             gl,
             containers: vec![],
+            free_vaos: Rc::default(),
+            font_deltas: vec![],
             atlas_updated: false,
+            meshes: MeshCache::default(),
+            stats: TessellationStats::default(),
         })
     }
 
     pub fn begin_frame(&mut self) {
         self.atlas_updated = false;
-        self.fonts.begin_pass(
-            self.max_texture_size,
-            AlphaFromCoverage::TwoCoverageMinusCoverageSq,
-        );
+        self.stats = TessellationStats::default();
+        if let Some(fonts) = &mut self.fonts {
+            fonts.begin_pass(
+                self.max_texture_size,
+                AlphaFromCoverage::TwoCoverageMinusCoverageSq,
+            );
+        }
+    }
+
+    /// Loads the fonts on first use. Their atlas replaces the texture on the next update.
+    fn fonts(&mut self) -> &mut Fonts {
+        self.fonts.get_or_insert_with(|| {
+            let start = Instant::now();
+            let fonts = Fonts::new(
+                self.max_texture_size,
+                AlphaFromCoverage::TwoCoverageMinusCoverageSq,
+                FontDefinitions::default(),
+            );
+            debug!("Loaded fonts in {:?}", start.elapsed());
+            fonts
+        })
+    }
+
+    /// Lays the common glyphs out at each font size, so that the font atlas is rebuilt by
+    /// the next update rather than while the first caption slides in. Other glyphs are
+    /// still added when first used.
+    pub fn warm_up_fonts(&mut self, sizes: &[f32]) {
+        let start = Instant::now();
+        let pixels_per_point = self.pixels_per_point;
+        for &size in sizes {
+            let job = LayoutJob::simple_singleline(
+                WARM_UP_GLYPHS.into(),
+                FontId::proportional(size),
+                Color32::WHITE,
+            );
+            self.fonts()
+                .with_pixels_per_point(pixels_per_point)
+                .layout_job(job);
+        }
+        self.take_font_delta();
+        debug!("Warmed up fonts {sizes:?} in {:?}", start.elapsed());
+    }
+
+    /// Whether the font atlas changed since the last update, e.g. for glyphs of a text
+    /// shown for the first time.
+    pub fn font_atlas_pending(&self) -> bool {
+        !self.font_deltas.is_empty()
+    }
+
+    /// Keeps the font atlas changes of the last layouts, for [`Self::font_atlas_pending`].
+    fn take_font_delta(&mut self) {
+        if let Some(delta) = self.fonts.as_mut().and_then(Fonts::font_image_delta) {
+            self.font_deltas.push(delta);
+        }
+    }
+
+    /// Text tessellation work since the beginning of the frame.
+    pub fn stats(&self) -> TessellationStats {
+        self.stats
+    }
+
+    #[cfg(test)]
+    pub fn fonts_loaded(&self) -> bool {
+        self.fonts.is_some()
+    }
+
+    /// Whether the font atlas was replaced by the last update, which tessellates every text
+    /// again.
+    #[cfg(test)]
+    pub fn atlas_updated(&self) -> bool {
+        self.atlas_updated
     }
 
     #[allow(dead_code)]
@@ -264,19 +437,17 @@ impl EpaintDisplay {
         let mut mesh = Mesh::default();
         self.tesselator.tessellate_shape(shape, &mut mesh);
 
-        let vbo_data = &[];
-        let ebo_data = &[];
-        // TODO avoid double buffer init
         let mut vao = self
-            .new_vao(vbo_data, ebo_data, BufferUsage::Static)
+            .pooled_vao(BufferUsage::Static)
             .context("Cannot create shape VAO")?;
         write_mesh_to_vao(&mesh, &mut vao);
         Ok(ShapeContainer::new(vao, texture))
     }
 
     pub fn create_text_container(&mut self) -> Result<TextContainer> {
+        self.fonts();
         let vao = self
-            .new_vao(&[], &[], BufferUsage::Dynamic)
+            .pooled_vao(BufferUsage::Dynamic)
             .context("Cannot create text VAO")?;
 
         let container = TextContainerInner {
@@ -284,8 +455,10 @@ impl EpaintDisplay {
             text_mesh: Mesh::default(),
             text_vao: vao,
             next_layout: None,
+            layout_hash: None,
             shape: None,
             opacity_factor: 1f32,
+            clip: None,
             is_dirty: false,
         };
         let container = Rc::new(RefCell::new(container));
@@ -295,26 +468,41 @@ impl EpaintDisplay {
 
     fn update_container(&mut self, container: &mut TextContainerInner) {
         if let Some(job) = container.next_layout.take() {
+            container.layout_hash = Some(layout_hash(&job));
+            let pixels_per_point = self.pixels_per_point;
             let galley = self
-                .fonts
-                .with_pixels_per_point(self.pixels_per_point)
+                .fonts()
+                .with_pixels_per_point(pixels_per_point)
                 .layout_job(job);
             container.shape = Some(TextShape::new([0., 0.].into(), galley, Color32::WHITE));
+            self.take_font_delta();
         }
         if container.is_dirty || self.atlas_updated {
             container.is_dirty = false;
             container.text_mesh.clear();
             if let Some(shape) = &container.shape {
-                self.tesselator
-                    .tessellate_text(shape, &mut container.text_mesh);
-
+                let start = Instant::now();
+                let cached = container.layout_hash.and_then(|hash| self.meshes.get(hash));
+                if let Some(mesh) = cached {
+                    container.text_mesh.clone_from(mesh);
+                    self.stats.cached += 1;
+                } else {
+                    self.tesselator
+                        .tessellate_text(shape, &mut container.text_mesh);
+                    if let Some(hash) = container.layout_hash {
+                        self.meshes.insert(hash, container.text_mesh.clone());
+                    }
+                    self.stats.tessellated += 1;
+                }
                 write_mesh_to_vao(&container.text_mesh, &mut container.text_vao);
+                self.stats.time += start.elapsed();
             }
         }
     }
 
     pub fn update(&mut self) {
-        if let Some(delta) = self.fonts.font_image_delta() {
+        self.take_font_delta();
+        for delta in std::mem::take(&mut self.font_deltas) {
             self.update_texture(delta);
         }
         let mut i = 0;
@@ -328,7 +516,12 @@ impl EpaintDisplay {
         }
     }
 
-    fn draw_text(&self, view: Mat4<f32>, text_container: &TextContainerInner) -> Result<()> {
+    fn draw_text(
+        &self,
+        view: Mat4<f32>,
+        text_container: &TextContainerInner,
+        scissor: Option<Rect<i32, i32>>,
+    ) -> Result<()> {
         if text_container.shape.is_none() {
             return Ok(());
         }
@@ -345,14 +538,17 @@ impl EpaintDisplay {
             &prog,
             text_container.text_mesh.indices.len() as _,
             0,
-            &DrawParameters {
-                blend: Some(BlendMode::alpha()),
-            },
+            &DrawParameters::alpha_blended().with_scissor(scissor),
         );
         Ok(())
     }
 
-    pub fn draw_shape(&self, view: Mat4<f32>, shape: &ShapeContainer) -> Result<()> {
+    pub fn draw_shape(
+        &self,
+        view: Mat4<f32>,
+        shape: &ShapeContainer,
+        scissor: Option<Rect<i32, i32>>,
+    ) -> Result<()> {
         let prog = ProgramGuard::bind(&self.program);
         prog.set_uniform("tex", 0)?;
         if let Some(texture) = shape.texture() {
@@ -370,9 +566,7 @@ impl EpaintDisplay {
             &prog,
             shape.vao().element_buffer.size() as _,
             0,
-            &DrawParameters {
-                blend: Some(BlendMode::alpha()),
-            },
+            &DrawParameters::alpha_blended().with_scissor(scissor),
         );
         Ok(())
     }
@@ -388,6 +582,7 @@ impl EpaintDisplay {
                     TextureWrapMode::MirroredRepeat
                 }
             },
+            ..Default::default()
         };
         self.texture.borrow_mut().set_options(options);
 
@@ -409,6 +604,8 @@ impl EpaintDisplay {
                 .borrow_mut()
                 .write(TextureFormat::Rgba, dimensions, &data);
             self.atlas_updated = true;
+            // Glyphs moved in the new atlas
+            self.meshes.clear();
         }
     }
 
@@ -422,6 +619,20 @@ impl EpaintDisplay {
         }
     }
 
+    /// Vertex array of a dropped container if any, `buffer_usage` only applies to new ones.
+    fn pooled_vao(&mut self, buffer_usage: BufferUsage) -> Result<PooledVao> {
+        let free = self.free_vaos.borrow_mut().pop();
+        let vao = match free {
+            Some(vao) => vao,
+            // TODO avoid double buffer init
+            None => self.new_vao(&[], &[], buffer_usage)?,
+        };
+        Ok(PooledVao {
+            vao: Some(vao),
+            free_vaos: Rc::downgrade(&self.free_vaos),
+        })
+    }
+
     fn new_vao(
         &mut self,
         vbo_data: &[Vertex],
@@ -474,20 +685,8 @@ fn write_mesh_to_vao(mesh: &Mesh, vao: &mut VertexArrayObject<Vertex>) {
         .map(Vertex::from)
         .collect::<Vec<_>>();
 
-    if vao.vertex_buffer.size() >= vertex.len() {
-        vao.vertex_buffer
-            .write_sub(0, &vertex)
-            .expect("Should never happen: vertex buffer has enough space");
-    } else {
-        vao.vertex_buffer.write(&vertex);
-    }
-    if vao.element_buffer.size() >= mesh.indices.len() {
-        vao.element_buffer
-            .write_sub(0, &mesh.indices)
-            .expect("Should never happen: element buffer has enough space");
-    } else {
-        vao.element_buffer.write(&mesh.indices);
-    }
+    vao.vertex_buffer.update(&vertex);
+    vao.element_buffer.update(&mesh.indices);
 }
 
 fn convert_filter_option(filter: epaint::textures::TextureFilter) -> TextureFiltering {
@@ -533,3 +732,141 @@ mod shaders {
         gl_FragColor = texture2D(tex, texcoord) * texcolor;
     }"#;
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        num::NonZeroU32,
+        rc::Rc,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use epaint::{
+        text::{LayoutJob, TextFormat},
+        vec2, Color32, FontId, Pos2, Shape,
+    };
+    use faux::when;
+    use glow::{NativeBuffer, NativeVertexArray};
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, gt, is_true, not},
+    };
+
+    use super::{EpaintDisplay, TextContainer};
+    use crate::gl::{wrapper::mocked_gl, GlContext};
+
+    fn text(display: &mut EpaintDisplay, text: &str) -> TextContainer {
+        let container = display.create_text_container().unwrap();
+        container.set_layout(LayoutJob::single_section(
+            text.into(),
+            TextFormat::simple(FontId::proportional(20.), Color32::WHITE),
+        ));
+        container
+    }
+
+    #[gtest]
+    fn test_dropped_containers_forgotten() {
+        let gl = Rc::new(GlContext::mocked(mocked_gl()));
+        let mut display = EpaintDisplay::new(gl).unwrap();
+        let first = text(&mut display, "Bordeaux");
+        let second = text(&mut display, "Lyon");
+        let third = text(&mut display, "Nantes");
+        let fourth = text(&mut display, "Saint-Malo");
+        drop(second);
+        drop(third);
+
+        display.begin_frame();
+        display.update();
+        expect_that!(display.containers.len(), eq(2));
+        expect_that!(
+            display
+                .containers
+                .iter()
+                .all(|container| container.strong_count() == 1),
+            is_true()
+        );
+        // Only the live ones were laid out
+        expect_that!(display.stats().tessellated, eq(2));
+        expect_that!(first.get_dimensions().w, gt(0.));
+        expect_that!(fourth.get_dimensions().w, gt(0.));
+
+        // Still updated once moved in the list, with glyphs already in the font atlas
+        let before = fourth.get_dimensions();
+        fourth.set_layout(LayoutJob::single_section(
+            "Bordeaux-Saint-Malo".into(),
+            TextFormat::simple(FontId::proportional(20.), Color32::WHITE),
+        ));
+        display.begin_frame();
+        display.update();
+        expect_that!(display.stats().tessellated, eq(1));
+        expect_that!(fourth.get_dimensions(), not(eq(before)));
+
+        drop(first);
+        drop(fourth);
+        display.update();
+        expect_that!(display.containers.len(), eq(0));
+    }
+
+    #[gtest]
+    fn test_vaos_of_dropped_containers_reused() {
+        let vaos = Arc::new(AtomicUsize::new(0));
+        let buffers = Arc::new(AtomicUsize::new(0));
+        let deleted = Arc::new(AtomicUsize::new(0));
+        let mut gl = mocked_gl();
+        when!(gl.create_vertex_array).then({
+            let vaos = Arc::clone(&vaos);
+            move |_| {
+                vaos.fetch_add(1, Ordering::SeqCst);
+                Ok(NativeVertexArray(NonZeroU32::new(1).unwrap()))
+            }
+        });
+        when!(gl.create_buffer).then({
+            let buffers = Arc::clone(&buffers);
+            move |_| {
+                buffers.fetch_add(1, Ordering::SeqCst);
+                Ok(NativeBuffer(NonZeroU32::new(1).unwrap()))
+            }
+        });
+        when!(gl.delete_vertex_array).then({
+            let deleted = Arc::clone(&deleted);
+            move |_| {
+                deleted.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut display = EpaintDisplay::new(gl).unwrap();
+        let rect = Shape::rect_filled(
+            epaint::Rect::from_min_size(Pos2::ZERO, vec2(100., 20.)),
+            0.,
+            Color32::BLACK,
+        );
+
+        // A caption and its background per slide, the previous slide being alive during
+        // transitions
+        let mut slides = VecDeque::new();
+        for _ in 0..50 {
+            let caption = text(&mut display, "Bordeaux");
+            let background = display.create_shape(rect.clone(), None).unwrap();
+            slides.push_back((caption, background));
+            if slides.len() > 2 {
+                slides.pop_front();
+            }
+        }
+        expect_that!(vaos.load(Ordering::SeqCst), eq(6));
+        expect_that!(buffers.load(Ordering::SeqCst), eq(12));
+        expect_that!(deleted.load(Ordering::SeqCst), eq(0));
+
+        // Beyond the ones kept for reuse, dropped VAOs are deleted
+        drop(slides);
+        let shapes: Vec<_> = (0..12)
+            .map(|_| display.create_shape(rect.clone(), None).unwrap())
+            .collect();
+        expect_that!(vaos.load(Ordering::SeqCst), eq(12));
+        drop(shapes);
+        expect_that!(deleted.load(Ordering::SeqCst), eq(4));
+    }
+}
@@ -4,11 +4,15 @@ use anyhow::{Context, Result};
 use vek::{num_traits::Inv, Extent2, Mat4, Rect, Vec2};
 
 use super::{Drawable, Graphics, SharedTexture2d, Vertex2dUv};
-use crate::gl::{
-    buffer_object::{BufferObject, BufferUsage, ElementBufferObject},
-    shader::{Program, ProgramGuard},
-    vao::{BufferInfo, VertexArrayObject},
-    BlendMode, DrawParameters, GlContext,
+use crate::{
+    configuration::ColorAdjustmentSettings,
+    gl::{
+        buffer_object::{BufferObject, BufferUsage, ElementBufferObject},
+        shader::{Program, ProgramGuard},
+        texture::Texture,
+        vao::{BufferInfo, VertexArrayObject},
+        DrawParameters, GlContext,
+    },
 };
 
 pub struct ImageDrawer {
@@ -37,6 +41,10 @@ pub struct Sprite {
     pub size: Extent2<f32>,
     //
     pub opacity: f32,
+    // Radius of the rounded corners in pixels, 0 for square corners
+    pub corner_radius: f32,
+    // Only the part of the sprite in this rectangle is drawn, in screen pixels
+    pub clip: Option<Rect<i32, i32>>,
 
     sub_rect: TextureRegion,
 }
@@ -52,15 +60,22 @@ impl Sprite {
             position: Vec2::zero(),
             size: texture.size().as_(),
             opacity: 1.,
+            corner_radius: 0.,
+            clip: None,
             texture,
             sub_rect: DEFAULT_SUB_RECT,
         }
     }
 
     // Scales the sprite to fit the given dimensions while maintaining aspect ratio
+    // An empty texture gets an empty sprite rather than an infinite one
     pub fn resize_respecting_ratio(&mut self, target_size: Extent2<u32>) {
         let target_size: Extent2<f32> = target_size.as_();
         let tex_size: Extent2<f32> = self.get_texture_size().as_();
+        if tex_size.product() == 0. {
+            self.size = Extent2::zero();
+            return;
+        }
         let ratio = target_size / tex_size;
         let ratio = ratio.reduce_partial_min();
         self.size = tex_size * ratio;
@@ -72,7 +87,11 @@ impl Sprite {
 
     pub fn set_sub_rect(&mut self, sub_rect: Rect<i32, i32>) {
         let sub_rect = sub_rect.as_::<f32, f32>();
-        let tr = Vec2::from(self.get_texture_size().as_::<f32>()).inv();
+        let tex_size = self.get_texture_size().as_::<f32>();
+        if tex_size.product() == 0. {
+            return;
+        }
+        let tr = Vec2::from(tex_size).inv();
         self.sub_rect.uv_center = sub_rect.center() * tr;
         self.sub_rect.uv_size = sub_rect.extent() * tr * 0.5;
     }
@@ -86,6 +105,13 @@ impl Sprite {
     pub fn get_sub_center_size(&self) -> TextureRegion {
         self.sub_rect
     }
+
+    /// Corner radius actually drawn, corners cannot be larger than half the sprite.
+    fn effective_corner_radius(&self) -> f32 {
+        self.corner_radius
+            .min(self.size.reduce_partial_min() * 0.5)
+            .max(0.)
+    }
 }
 
 impl Drawable for Sprite {
@@ -93,7 +119,12 @@ impl Drawable for Sprite {
     fn draw(&self, graphics: &Graphics) -> Result<()> {
         graphics
             .image_drawer()
-            .draw_sprite(graphics.view(), self)
+            .draw_sprite(
+                graphics.view(),
+                self,
+                self.clip.map(|clip| graphics.scissor_rect(clip)),
+                graphics.color_adjustment(),
+            )
             .context("Cannot draw sprite using ImageDrawer")
     }
 }
@@ -146,19 +177,77 @@ impl ImageDrawer {
         Ok(Self { vao, program, gl })
     }
 
-    pub fn draw_sprite(&self, view: Mat4<f32>, sprite: &Sprite) -> Result<()> {
+    pub fn draw_sprite(
+        &self,
+        view: Mat4<f32>,
+        sprite: &Sprite,
+        scissor: Option<Rect<i32, i32>>,
+        color: ColorAdjustmentSettings,
+    ) -> Result<()> {
         let model = Mat4::scaling_3d(Vec2::from(sprite.size)).translated_2d(sprite.position);
+        self.draw_texture(
+            view,
+            model,
+            &sprite.texture,
+            sprite.opacity,
+            sprite.sub_rect,
+            Corners {
+                size: sprite.size,
+                radius: sprite.effective_corner_radius(),
+            },
+            scissor,
+            color,
+        )
+    }
+
+    /// Draws the whole `texture` over a `size` rectangle at the origin, its colors as is.
+    pub fn draw_full_texture(
+        &self,
+        view: Mat4<f32>,
+        texture: &Texture,
+        size: Extent2<f32>,
+    ) -> Result<()> {
+        let model = Mat4::scaling_3d(Vec2::from(size));
+        let corners = Corners { size, radius: 0. };
+        self.draw_texture(
+            view,
+            model,
+            texture,
+            1.,
+            DEFAULT_SUB_RECT,
+            corners,
+            None,
+            ColorAdjustmentSettings::default(),
+        )
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    fn draw_texture(
+        &self,
+        view: Mat4<f32>,
+        model: Mat4<f32>,
+        texture: &Texture,
+        opacity: f32,
+        region: TextureRegion,
+        corners: Corners,
+        scissor: Option<Rect<i32, i32>>,
+        color: ColorAdjustmentSettings,
+    ) -> Result<()> {
         let prog_bind = ProgramGuard::bind(&self.program);
 
-        prog_bind.set_uniform("opacity", sprite.opacity)?;
+        prog_bind.set_uniform("opacity", opacity)?;
         prog_bind.set_uniform("model", model)?;
         prog_bind.set_uniform("view", view)?;
         prog_bind.set_uniform("tex", 0)?;
-        prog_bind.set_uniform("uv_offset_center", sprite.sub_rect.uv_center)?;
-        prog_bind.set_uniform("uv_offset_size", sprite.sub_rect.uv_size)?;
+        prog_bind.set_uniform("uv_offset_center", region.uv_center)?;
+        prog_bind.set_uniform("uv_offset_size", region.uv_size)?;
+        prog_bind.set_uniform("size", corners.size)?;
+        prog_bind.set_uniform("corner_radius", corners.radius)?;
+        prog_bind.set_uniform("brightness", color.brightness)?;
+        prog_bind.set_uniform("contrast", color.contrast)?;
+        prog_bind.set_uniform("saturation", color.saturation)?;
 
-        sprite.texture.bind(Some(0));
+        texture.bind(Some(0));
 
         let _guard = self.vao.bind_guard();
 
@@ -167,14 +256,18 @@ impl ImageDrawer {
             &prog_bind,
             INDICES.len() as _,
             0,
-            &DrawParameters {
-                blend: Some(BlendMode::alpha()),
-            },
+            &DrawParameters::alpha_blended().with_scissor(scissor),
         );
         Ok(())
     }
 }
 
+/// Rounded corners of a drawn rectangle, in pixels.
+struct Corners {
+    size: Extent2<f32>,
+    radius: f32,
+}
+
 mod shader {
     pub const VERTEX: &str = r#"#version 100
     attribute vec2 pos;
@@ -182,24 +275,46 @@ mod shader {
 
     uniform vec2 uv_offset_center;
     uniform vec2 uv_offset_size;
+    // Same precision as in the fragment shader, as required to share it
+    uniform mediump vec2 size;
     uniform mat4 model;
     uniform mat4 view;
 
     varying lowp vec2 texcoord;
+    // Position in the sprite relative to its center, in pixels
+    varying mediump vec2 centered;
 
     void main() {
         gl_Position = view * model * vec4(pos, 0, 1);
         texcoord = (2. * uv - 1.) * uv_offset_size + uv_offset_center;
+        centered = (uv - 0.5) * size;
         }"#;
 
     pub const FRAGMENT: &str = r#"#version 100
     varying lowp vec2 texcoord;
+    varying mediump vec2 centered;
 
     uniform sampler2D tex;
     uniform lowp float opacity;
+    uniform mediump vec2 size;
+    uniform mediump float corner_radius;
+    uniform mediump float brightness;
+    uniform mediump float contrast;
+    uniform mediump float saturation;
 
     void main() {
-        gl_FragColor = vec4(texture2D(tex, texcoord).rgb, opacity);
+        // Signed distance to the rounded rectangle, antialiased over one pixel
+        mediump vec2 q = abs(centered) - size * 0.5 + corner_radius;
+        mediump float distance = length(max(q, 0.)) + min(max(q.x, q.y), 0.) - corner_radius;
+        lowp float coverage = clamp(0.5 - distance, 0., 1.);
+        // Textures without alpha read 1, e.g. photos
+        lowp vec4 color = texture2D(tex, texcoord);
+        // Identity with the default adjustment
+        mediump float luma = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+        mediump vec3 rgb = mix(vec3(luma), color.rgb, saturation);
+        rgb = (rgb - 0.5) * contrast + 0.5;
+        rgb = clamp(rgb * brightness, 0., 1.);
+        gl_FragColor = vec4(rgb, color.a * opacity * coverage);
     }"#;
 }
 
@@ -246,6 +361,56 @@ mod test {
         );
     }
 
+    #[gtest]
+    fn test_sprite_resize_tiny_texture() {
+        let gl = mocked_gl();
+        let context = Rc::new(GlContext::mocked(gl));
+        let texture = Texture::mocked(context.clone(), Extent2::new(1, 1));
+        let mut sprite = Sprite::new(SharedTexture2d::new(texture));
+
+        sprite.resize_respecting_ratio(Extent2::new(50, 40));
+        expect_that!(
+            sprite.size,
+            matches_pattern!(Extent2 {
+                w: approx_eq(40.),
+                h: approx_eq(40.)
+            })
+        );
+    }
+
+    #[gtest]
+    fn test_sprite_resize_empty_texture() {
+        let gl = mocked_gl();
+        let context = Rc::new(GlContext::mocked(gl));
+        let texture = Texture::mocked(context.clone(), Extent2::new(100, 0));
+        let mut sprite = Sprite::new(SharedTexture2d::new(texture));
+
+        sprite.resize_respecting_ratio(Extent2::new(50, 40));
+        expect_that!(
+            sprite.size,
+            matches_pattern!(Extent2 {
+                w: approx_eq(0.),
+                h: approx_eq(0.)
+            })
+        );
+    }
+
+    #[gtest]
+    fn test_corner_radius_limited_to_half_sprite() {
+        let gl = mocked_gl();
+        let context = Rc::new(GlContext::mocked(gl));
+        let texture = Texture::mocked(context.clone(), Extent2::new(100, 100));
+        let mut sprite = Sprite::new(SharedTexture2d::new(texture));
+        sprite.size = Extent2::new(200., 40.);
+
+        sprite.corner_radius = 12.;
+        expect_that!(sprite.effective_corner_radius(), approx_eq(12.));
+        sprite.corner_radius = 50.;
+        expect_that!(sprite.effective_corner_radius(), approx_eq(20.));
+        sprite.corner_radius = -5.;
+        expect_that!(sprite.effective_corner_radius(), approx_eq(0.));
+    }
+
     #[gtest]
     fn test_sprite_set_sub_rect() {
         let gl = mocked_gl();
@@ -8,24 +8,30 @@ use anyhow::{Context, Result};
 use bytemuck::{Pod, Zeroable};
 use epaint::Shape;
 use image::DynamicImage;
-use vek::{Extent2, FrustumPlanes, Mat4};
+use vek::{Extent2, FrustumPlanes, Mat4, Rect, Vec2, Vec3, Vec4};
 
 use self::epaint_display::EpaintDisplay;
 #[cfg(test)]
 pub use self::image_display::TextureRegion;
 pub use self::{
-    blur::ImageBlurr,
-    epaint_display::{ShapeContainer, TextContainer},
+    blur::{BlurOptions, ImageBlurr},
+    epaint_display::{ShapeContainer, TessellationStats, TextContainer},
     image_display::{ImageDrawer, Sprite},
 };
 use crate::{
-    configuration::OrientationName,
+    configuration::{ColorAdjustmentSettings, OrientationName, RotatedRendering},
     gl::{
-        texture::{DetachedTexture, Texture},
+        framebuffer::FramebufferObject,
+        texture::{DetachedTexture, Texture, TextureFormat},
         GlContext,
     },
 };
 
+/// Size of the supersampled offscreen picture relative to the screen.
+const SUPERSAMPLING_FACTOR: u32 = 2;
+/// Side of the offscreen picture textures are warmed up into, see [`Graphics::warm_up`].
+const WARM_UP_SIZE: u32 = 4;
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct Vertex2dUv {
@@ -58,16 +64,43 @@ impl Deref for SharedTexture2d {
 
 struct Orientation {
     name: OrientationName,
+    /// Angle in degrees replacing `name` when it's not a right angle.
+    degrees: Option<f32>,
     value: Mat4<f32>,
 }
 
 impl Orientation {
-    fn create(name: OrientationName) -> Self {
-        Self {
-            name,
-            value: name.get_mat(),
+    fn create(name: OrientationName, degrees: Option<f32>) -> Self {
+        // Right angles keep the exact matrices and sizes
+        let right_angle = degrees.and_then(|degrees| {
+            let quarter_turns = degrees.rem_euclid(360.) / 90.;
+            (quarter_turns.fract() == 0.).then(|| match quarter_turns as u8 {
+                0 => OrientationName::Angle0,
+                1 => OrientationName::Angle90,
+                2 => OrientationName::Angle180,
+                _ => OrientationName::Angle270,
+            })
+        });
+        match (right_angle, degrees) {
+            (None, Some(degrees)) => Self {
+                name,
+                degrees: Some(degrees),
+                value: Mat4::rotation_z(degrees.to_radians()),
+            },
+            (right_angle, _) => {
+                let name = right_angle.unwrap_or(name);
+                Self {
+                    name,
+                    degrees: None,
+                    value: name.get_mat(),
+                }
+            }
         }
     }
+
+    fn is_rotated(&self) -> bool {
+        self.degrees.is_some() || self.name != OrientationName::Angle0
+    }
 }
 
 impl OrientationName {
@@ -89,6 +122,18 @@ pub struct Graphics {
     view: Mat4<f32>,
     orientation: Orientation,
     dimensions: Extent2<u32>,
+    /// Width of the column kept free on the right of the screen, e.g. for the agenda.
+    reserved_right: u32,
+    rotated_rendering: RotatedRendering,
+    /// Applied to the sprites, not to the texts.
+    color_adjustment: ColorAdjustmentSettings,
+    /// Offscreen picture drawn before being rotated to the screen, see
+    /// [`RotatedRendering::Supersampled`].
+    supersampled: Option<FramebufferObject>,
+    /// Whether the frame is being drawn into [`Self::supersampled`].
+    drawing_offscreen: bool,
+    /// Created on first use by [`Self::warm_up`].
+    warm_up_target: Option<FramebufferObject>,
     gl: Rc<GlContext>,
 }
 
@@ -108,15 +153,20 @@ impl Graphics {
             blurr,
             epaint_display,
             gl,
-            orientation: Orientation::create(orientation),
+            orientation: Orientation::create(orientation, None),
             dimensions: Extent2::default(),
+            reserved_right: 0,
+            rotated_rendering: RotatedRendering::default(),
+            color_adjustment: ColorAdjustmentSettings::default(),
+            supersampled: None,
+            drawing_offscreen: false,
+            warm_up_target: None,
             view: Mat4::zero(),
         };
         graphics.update_vp();
         Ok(graphics)
     }
 
-    #[allow(dead_code)]
     pub fn texture_from_image(&self, image: &DynamicImage) -> Result<Texture> {
         Texture::new_from_image(Rc::clone(&self.gl), image)
     }
@@ -130,6 +180,10 @@ impl Graphics {
         Texture::from_detached(Rc::clone(&self.gl), detached)
     }
 
+    pub fn gl(&self) -> &Rc<GlContext> {
+        &self.gl
+    }
+
     pub fn begin_frame(&mut self) {
         self.epaint_display.begin_frame();
 
@@ -140,10 +194,199 @@ impl Graphics {
         self.epaint_display.update();
     }
 
+    /// See [`EpaintDisplay::warm_up_fonts`].
+    pub fn warm_up_fonts(&mut self, sizes: &[f32]) {
+        self.epaint_display.warm_up_fonts(sizes);
+    }
+
+    /// Whether texts laid out since the last update added glyphs to the font atlas, which
+    /// is uploaded by the next one.
+    pub fn font_atlas_pending(&self) -> bool {
+        self.epaint_display.font_atlas_pending()
+    }
+
+    /// Text tessellation work since the beginning of the frame, before
+    /// [`Self::begin_frame`] the one of the last frame.
+    pub fn text_stats(&self) -> TessellationStats {
+        self.epaint_display.stats()
+    }
+
+    pub fn set_rotated_rendering(&mut self, rotated_rendering: RotatedRendering) {
+        self.rotated_rendering = rotated_rendering;
+    }
+
+    pub fn set_color_adjustment(&mut self, color_adjustment: ColorAdjustmentSettings) {
+        self.color_adjustment = color_adjustment;
+    }
+
+    /// Draws a frame with `draw`. When the screen is rotated and supersampling is enabled,
+    /// the frame is drawn unrotated into an offscreen texture, then drawn rotated to the
+    /// screen with linear filtering.
+    pub fn render(&mut self, draw: impl FnOnce(&Graphics) -> Result<()>) -> Result<()> {
+        let supersampled = self.rotated_rendering == RotatedRendering::Supersampled
+            && self.orientation.is_rotated();
+        if !supersampled {
+            self.supersampled = None;
+            return draw(self);
+        }
+        self.prepare_supersampled()
+            .context("Cannot create supersampled framebuffer")?;
+
+        let view = std::mem::replace(&mut self.view, self.offscreen_view());
+        self.drawing_offscreen = true;
+        let result = {
+            let framebuffer = self.supersampled.as_ref().expect("Framebuffer was created");
+            let _guard = framebuffer.bind_guard();
+            self.gl.clear();
+            draw(self)
+        };
+        self.drawing_offscreen = false;
+        self.view = view;
+        result?;
+
+        let framebuffer = self.supersampled.as_ref().expect("Framebuffer was created");
+        self.image_drawer
+            .draw_full_texture(self.view, framebuffer.get_texture(), self.dimensions.as_())
+            .context("Cannot draw supersampled frame")
+    }
+
+    /// Draws with `draw` into a tiny offscreen picture, so that drivers make the textures
+    /// used resident before they are first drawn to the screen, where some of them flash
+    /// for a frame otherwise.
+    pub fn warm_up(&mut self, draw: impl FnOnce(&Graphics) -> Result<()>) -> Result<()> {
+        if self.warm_up_target.is_none() {
+            let size = Extent2::broadcast(WARM_UP_SIZE);
+            let texture = Texture::empty(Rc::clone(&self.gl), TextureFormat::Rgb, size)?;
+            self.warm_up_target = Some(FramebufferObject::with_texture(
+                Rc::clone(&self.gl),
+                texture,
+            )?);
+        }
+        let target = self
+            .warm_up_target
+            .as_ref()
+            .expect("Framebuffer was created");
+        let _guard = target.bind_guard();
+        draw(self)
+    }
+
+    fn prepare_supersampled(&mut self) -> Result<()> {
+        let max_size = self.gl.capabilities().max_texture_size;
+        let size = (self.dimensions * SUPERSAMPLING_FACTOR).map(|side| side.min(max_size));
+        if self
+            .supersampled
+            .as_ref()
+            .is_some_and(|framebuffer| framebuffer.get_texture().size() == size)
+        {
+            return Ok(());
+        }
+        // Drop the previous one first, to not hold both in memory
+        self.supersampled = None;
+        let texture = Texture::empty(Rc::clone(&self.gl), TextureFormat::Rgb, size)?;
+        self.supersampled = Some(FramebufferObject::with_texture(
+            Rc::clone(&self.gl),
+            texture,
+        )?);
+        Ok(())
+    }
+
+    /// Projection drawing into the offscreen texture, unrotated. It's upside down, as
+    /// texture rows start at the bottom, so that it's drawn back to the screen upright.
+    fn offscreen_view(&self) -> Mat4<f32> {
+        Mat4::orthographic_without_depth_planes(FrustumPlanes {
+            left: 0.,
+            right: self.dimensions.w as _,
+            bottom: 0.,
+            top: self.dimensions.h as _,
+            far: -1.,
+            near: 1.,
+        })
+    }
+
+    /// Converts a rectangle of the screen as laid out, origin at the top left, to the
+    /// framebuffer rectangle to pass as scissor, which is unrotated with its origin at the
+    /// bottom left.
+    pub fn scissor_rect(&self, rect: Rect<i32, i32>) -> Rect<i32, i32> {
+        let vp = self.gl.current_viewport();
+        if self.drawing_offscreen {
+            // The offscreen view is unrotated and already upside down, only scaled
+            let scale = vp.extent().as_::<f32>() / self.dimensions.as_::<f32>();
+            return Rect::new(
+                vp.x + (rect.x as f32 * scale.w).round() as i32,
+                vp.y + (rect.y as f32 * scale.h).round() as i32,
+                (rect.w as f32 * scale.w).round() as i32,
+                (rect.h as f32 * scale.h).round() as i32,
+            );
+        }
+        if self.orientation.degrees.is_some() {
+            return self.rotated_bounding_rect(rect, vp);
+        }
+        let Rect { x, y, w, h } = rect;
+        let rotated = match self.orientation.name {
+            OrientationName::Angle0 => Rect::new(x, vp.h - y - h, w, h),
+            OrientationName::Angle90 => Rect::new(y, x, h, w),
+            OrientationName::Angle180 => Rect::new(vp.w - x - w, y, w, h),
+            OrientationName::Angle270 => Rect::new(vp.w - y - h, vp.h - x - w, h, w),
+        };
+        Rect::new(vp.x + rotated.x, vp.y + rotated.y, rotated.w, rotated.h)
+    }
+
+    /// Scissors are axis aligned: a rectangle rotated by any angle is replaced by its
+    /// bounding box, letting a bit more through.
+    fn rotated_bounding_rect(&self, rect: Rect<i32, i32>, vp: Rect<i32, i32>) -> Rect<i32, i32> {
+        let rect = rect.as_::<f32, f32>();
+        let vp = vp.as_::<f32, f32>();
+        let corners = [
+            Vec2::new(rect.x, rect.y),
+            Vec2::new(rect.x + rect.w, rect.y),
+            Vec2::new(rect.x, rect.y + rect.h),
+            Vec2::new(rect.x + rect.w, rect.y + rect.h),
+        ]
+        .map(|corner| {
+            let ndc = self.view * Vec4::new(corner.x, corner.y, 0., 1.);
+            Vec2::new(
+                vp.x + (ndc.x + 1.) * 0.5 * vp.w,
+                vp.y + (ndc.y + 1.) * 0.5 * vp.h,
+            )
+        });
+        let min = corners
+            .into_iter()
+            .reduce(Vec2::partial_min)
+            .unwrap()
+            .floor();
+        let max = corners
+            .into_iter()
+            .reduce(Vec2::partial_max)
+            .unwrap()
+            .ceil();
+        Rect::new(min.x, min.y, max.x - min.x, max.y - min.y).as_()
+    }
+
     pub fn get_dimensions(&self) -> Extent2<u32> {
         self.dimensions
     }
 
+    /// Area where slides are laid out: the screen without the reserved column.
+    pub fn get_slide_dimensions(&self) -> Extent2<u32> {
+        Extent2::new(
+            self.dimensions.w.saturating_sub(self.reserved_right),
+            self.dimensions.h,
+        )
+    }
+
+    /// Area of the slides to clip them to while a column is reserved, see
+    /// [`Self::get_slide_dimensions`]. `None` when they take the whole screen.
+    pub fn slide_clip(&self) -> Option<Rect<i32, i32>> {
+        let size = self.get_slide_dimensions().as_::<i32>();
+        (self.reserved_right > 0).then(|| Rect::new(0, 0, size.w, size.h))
+    }
+
+    /// Keeps a column of `width` pixels free on the right of the screen, slides created
+    /// afterwards are laid out next to it.
+    pub fn set_reserved_right(&mut self, width: u32) {
+        self.reserved_right = width;
+    }
+
     pub fn create_text_container(&mut self) -> Result<TextContainer> {
         self.epaint_display.create_text_container()
     }
@@ -161,9 +404,35 @@ impl Graphics {
         self.epaint_display.create_shape(shape, texture)
     }
 
-    fn update_vp(&mut self) {
+    /// Rotates the screen by any angle in degrees, replacing the current orientation, or
+    /// keeps it when `None`. Slides are laid out in the largest rectangle fitting the
+    /// rotated screen.
+    pub fn set_rotation_degrees(&mut self, degrees: Option<f32>) {
+        self.orientation = Orientation::create(self.orientation.name, degrees);
+        // Forces the update
+        self.dimensions = Extent2::zero();
+        self.update_vp();
+    }
+
+    /// Reads the screen size back from the viewport, returns whether it changed.
+    pub fn update_vp(&mut self) -> bool {
         // TODO: better way to get dims?
         let vp = self.gl.current_viewport();
+        if let Some(degrees) = self.orientation.degrees {
+            let viewport = vp.extent().as_::<f32>();
+            let dimensions = inscribed_size(viewport, degrees.to_radians());
+            if dimensions.map(|side| side.round() as u32) == self.dimensions {
+                return false;
+            }
+            self.dimensions = dimensions.map(|side| side.round() as u32);
+            // Rotated in pixels rather than in normalized coordinates, which are stretched
+            // on screens that aren't square
+            self.view = Mat4::scaling_3d(Vec3::new(2. / viewport.w, 2. / viewport.h, 1.))
+                * self.orientation.value
+                * Mat4::scaling_3d(Vec3::new(1., -1., 1.))
+                * Mat4::translation_2d(-Vec2::from(self.dimensions.as_::<f32>()) * 0.5);
+            return true;
+        }
         let mut dimensions = vp.extent().as_::<u32>();
         match self.orientation.name {
             OrientationName::Angle0 | OrientationName::Angle180 => {}
@@ -172,7 +441,7 @@ impl Graphics {
             }
         }
         if dimensions == self.dimensions {
-            return;
+            return false;
         }
         self.dimensions = dimensions;
         self.view = self.orientation.value
@@ -184,12 +453,17 @@ impl Graphics {
                 far: -1.,
                 near: 1.,
             });
+        true
     }
 
     fn view(&self) -> Mat4<f32> {
         self.view
     }
 
+    fn color_adjustment(&self) -> ColorAdjustmentSettings {
+        self.color_adjustment
+    }
+
     fn image_drawer(&self) -> &ImageDrawer {
         &self.image_drawer
     }
@@ -198,3 +472,318 @@ impl Graphics {
         &self.epaint_display
     }
 }
+
+/// Largest rectangle fitting in a rectangle of `size` rotated by `angle` in radians, with
+/// its sides parallel to the rotated axes.
+fn inscribed_size(size: Extent2<f32>, angle: f32) -> Extent2<f32> {
+    if size.product() <= 0. {
+        return Extent2::zero();
+    }
+    let (sin, cos) = (angle.sin().abs(), angle.cos().abs());
+    let (long, short) = (size.reduce_partial_max(), size.reduce_partial_min());
+    if short <= 2. * sin * cos * long || (sin - cos).abs() < 1e-6 {
+        // Only the short side constrains the rectangle, touching its two long sides
+        let half = short * 0.5;
+        if size.w >= size.h {
+            Extent2::new(half / sin, half / cos)
+        } else {
+            Extent2::new(half / cos, half / sin)
+        }
+    } else {
+        // Touching all four sides
+        let cos_2a = cos * cos - sin * sin;
+        Extent2::new(
+            (size.w * cos - size.h * sin) / cos_2a,
+            (size.h * cos - size.w * sin) / cos_2a,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        rc::Rc,
+        sync::{Arc, Mutex},
+    };
+
+    use anyhow::anyhow;
+    use epaint::{
+        text::{LayoutJob, TextFormat},
+        Color32, FontId, Rect as EpaintRect, Shape,
+    };
+    use faux::when;
+    use googletest::{
+        expect_that, gtest,
+        matchers::matches_pattern,
+        prelude::{eq, gt, is_empty, is_false, is_true, near, some},
+    };
+    use vek::{Extent2, Rect, Vec4};
+
+    use super::{inscribed_size, Drawable, Graphics};
+    use crate::{
+        configuration::{OrientationName, RotatedRendering},
+        gl::{wrapper::mocked_gl, GlContext},
+    };
+
+    fn graphics(orientation: OrientationName) -> (Rc<GlContext>, Graphics) {
+        let mut gl = mocked_gl();
+        when!(gl.clear).then_return(());
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(Rc::clone(&gl), orientation).unwrap();
+        graphics.set_rotated_rendering(RotatedRendering::Supersampled);
+        (gl, graphics)
+    }
+
+    #[gtest]
+    fn test_supersampled_texture_size() {
+        let (gl, mut graphics) = graphics(OrientationName::Angle90);
+        graphics.prepare_supersampled().unwrap();
+        expect_that!(
+            graphics
+                .supersampled
+                .as_ref()
+                .map(|fbo| fbo.get_texture().size()),
+            some(eq(Extent2::new(1200, 1600)))
+        );
+
+        // Clamped to the maximum texture size of the mocked context
+        gl.set_viewport(Rect::new(0, 0, 1920, 1080));
+        graphics.update_vp();
+        graphics.prepare_supersampled().unwrap();
+        expect_that!(
+            graphics
+                .supersampled
+                .as_ref()
+                .map(|fbo| fbo.get_texture().size()),
+            some(eq(Extent2::new(2048, 2048)))
+        );
+    }
+
+    #[gtest]
+    fn test_offscreen_view_is_flipped() {
+        let (_gl, graphics) = graphics(OrientationName::Angle90);
+        let view = graphics.offscreen_view();
+        // The top left corner lands on the first row of the texture
+        expect_that!(
+            view * Vec4::new(0., 0., 0., 1.),
+            eq(Vec4::new(-1., -1., 0., 1.))
+        );
+        expect_that!(
+            view * Vec4::new(600., 800., 0., 1.),
+            eq(Vec4::new(1., 1., 0., 1.))
+        );
+    }
+
+    #[gtest]
+    fn test_direct_rendering_draws_on_screen() {
+        let (gl, mut graphics) = graphics(OrientationName::Angle90);
+        graphics.set_rotated_rendering(RotatedRendering::Direct);
+        let view = graphics.view;
+        let drawn = Cell::new(false);
+        graphics
+            .render(|graphics| {
+                drawn.set(true);
+                expect_that!(graphics.view, eq(view));
+                expect_that!(gl.current_viewport(), eq(Rect::new(0, 0, 800, 600)));
+                Ok(())
+            })
+            .unwrap();
+        expect_that!(drawn.get(), is_true());
+        expect_that!(graphics.supersampled.is_none(), is_true());
+    }
+
+    #[gtest]
+    fn test_supersampled_rendering_draws_offscreen() {
+        let (gl, mut graphics) = graphics(OrientationName::Angle90);
+        let view = graphics.view;
+        let offscreen_view = graphics.offscreen_view();
+        // Fails after drawing, to stop before the frame is drawn to the screen
+        let result = graphics.render(|graphics| {
+            expect_that!(graphics.view, eq(offscreen_view));
+            expect_that!(gl.current_viewport(), eq(Rect::new(0, 0, 1200, 1600)));
+            Err(anyhow!("stop"))
+        });
+        expect_that!(result.is_err(), is_true());
+        expect_that!(graphics.view, eq(view));
+        expect_that!(gl.current_viewport(), eq(Rect::new(0, 0, 800, 600)));
+    }
+
+    #[gtest]
+    fn test_scissor_rect_follows_orientation() {
+        let rect = Rect::new(10, 20, 100, 50);
+        for (orientation, expected) in [
+            (OrientationName::Angle0, Rect::new(10, 530, 100, 50)),
+            (OrientationName::Angle90, Rect::new(20, 10, 50, 100)),
+            (OrientationName::Angle180, Rect::new(690, 20, 100, 50)),
+            (OrientationName::Angle270, Rect::new(730, 490, 50, 100)),
+        ] {
+            let (_gl, graphics) = graphics(orientation);
+            expect_that!(graphics.scissor_rect(rect), eq(expected), "{orientation:?}");
+        }
+    }
+
+    #[gtest]
+    fn test_text_clipped() {
+        let scissors = Arc::new(Mutex::new(Vec::new()));
+        let mut gl = mocked_gl();
+        when!(gl.uniform_1_f32).then_return(());
+        when!(gl.uniform_matrix_4_f32_slice).then_return(());
+        when!(gl.enable).then_return(());
+        when!(gl.disable).then_return(());
+        when!(gl.blend_equation_separate).then_return(());
+        when!(gl.blend_func_separate).then_return(());
+        when!(gl.draw_elements).then_return(());
+        let recorded = Arc::clone(&scissors);
+        when!(gl.scissor).then(move |rect| recorded.lock().unwrap().push(rect));
+        let gl = Rc::new(GlContext::mocked(gl));
+        let mut graphics = Graphics::new(gl, OrientationName::Angle0).unwrap();
+        let text = graphics.create_text_container().unwrap();
+        text.set_layout(LayoutJob::single_section(
+            "Bordeaux".into(),
+            TextFormat::simple(FontId::proportional(20.), Color32::WHITE),
+        ));
+        graphics.force_text_container_update(&text);
+
+        text.draw(&graphics).unwrap();
+        expect_that!(*scissors.lock().unwrap(), is_empty());
+
+        text.set_clip(Some(Rect::new(10, 20, 100, 50)));
+        text.draw(&graphics).unwrap();
+        expect_that!(*scissors.lock().unwrap(), eq(&vec![(10, 530, 100, 50)]));
+    }
+
+    #[gtest]
+    fn test_scissor_rect_when_supersampled() {
+        let (_gl, mut graphics) = graphics(OrientationName::Angle90);
+        let result = graphics.render(|graphics| {
+            expect_that!(
+                graphics.scissor_rect(Rect::new(10, 20, 100, 50)),
+                eq(Rect::new(20, 40, 200, 100))
+            );
+            Err(anyhow!("stop"))
+        });
+        expect_that!(result.is_err(), is_true());
+        expect_that!(graphics.drawing_offscreen, is_false());
+    }
+
+    #[gtest]
+    fn test_inscribed_size() {
+        let expect_size = |size: Extent2<f32>, degrees: f32, w: f32, h: f32| {
+            expect_that!(
+                inscribed_size(size, degrees.to_radians()),
+                matches_pattern!(Extent2 {
+                    w: near(w, 0.01),
+                    h: near(h, 0.01),
+                }),
+                "{degrees}°"
+            );
+        };
+        let screen = Extent2::new(800., 600.);
+        // Same as the right angle orientations
+        expect_size(screen, 0., 800., 600.);
+        expect_size(screen, 90., 600., 800.);
+        // Constrained by the short side
+        expect_size(screen, 30., 600., 346.41016);
+        expect_size(screen, 45., 424.26407, 424.26407);
+        // Square screen mounted as a diamond
+        expect_size(Extent2::new(600., 600.), 45., 424.26407, 424.26407);
+        // Touching the four sides
+        expect_size(screen, 10., 727.533, 480.972);
+    }
+
+    #[gtest]
+    fn test_arbitrary_rotation() {
+        let (_gl, mut graphics) = graphics(OrientationName::Angle0);
+        graphics.set_rotation_degrees(Some(45.));
+        expect_that!(graphics.get_dimensions(), eq(Extent2::new(424, 424)));
+        // The center stays at the center of the screen
+        expect_that!(
+            graphics.view * Vec4::new(212., 212., 0., 1.),
+            matches_pattern!(Vec4 {
+                x: near(0., 1e-4),
+                y: near(0., 1e-4),
+                z: near(0., 1e-4),
+                w: near(1., 1e-4),
+            })
+        );
+        // The whole layout fits in the screen
+        expect_that!(
+            graphics.scissor_rect(Rect::new(0, 0, 424, 424)),
+            eq(Rect::new(100, 0, 600, 600))
+        );
+
+        // Right angles keep the exact orientation
+        graphics.set_rotation_degrees(Some(-90.));
+        expect_that!(graphics.get_dimensions(), eq(Extent2::new(600, 800)));
+        expect_that!(graphics.orientation.name, eq(OrientationName::Angle270));
+        expect_that!(graphics.orientation.degrees, eq(None));
+    }
+
+    #[gtest]
+    fn test_unrotated_screen_is_drawn_directly() {
+        let (_gl, mut graphics) = graphics(OrientationName::Angle0);
+        graphics.render(|_| Ok(())).unwrap();
+        expect_that!(graphics.supersampled.is_some(), is_false());
+    }
+
+    #[gtest]
+    fn test_identical_texts_tessellated_once() {
+        let (_gl, mut graphics) = graphics(OrientationName::Angle0);
+        let job = LayoutJob::single_section(
+            "Bordeaux".into(),
+            TextFormat::simple(FontId::proportional(20.), Color32::WHITE),
+        );
+        let first = graphics.create_text_container().unwrap();
+        first.set_layout(job.clone());
+        graphics.force_text_container_update(&first);
+        let second = graphics.create_text_container().unwrap();
+        second.set_layout(job);
+        graphics.force_text_container_update(&second);
+
+        let stats = graphics.text_stats();
+        expect_that!(stats.tessellated, eq(1));
+        expect_that!(stats.cached, eq(1));
+        expect_that!(second.get_dimensions(), eq(first.get_dimensions()));
+    }
+
+    #[gtest]
+    fn test_warmed_up_glyphs_keep_font_atlas() {
+        let (_gl, mut graphics) = graphics(OrientationName::Angle0);
+        graphics.warm_up_fonts(&[28.]);
+        expect_that!(graphics.font_atlas_pending(), is_true());
+        graphics.begin_frame();
+        graphics.update();
+        expect_that!(graphics.epaint_display.atlas_updated(), is_true());
+
+        graphics.begin_frame();
+        let text = graphics.create_text_container().unwrap();
+        text.set_layout(LayoutJob::single_section(
+            "Saint-Émilion\n14 juillet 2024".into(),
+            TextFormat::simple(FontId::proportional(28.), Color32::WHITE),
+        ));
+        graphics.force_text_container_update(&text);
+        expect_that!(graphics.font_atlas_pending(), is_false());
+        graphics.update();
+        expect_that!(graphics.epaint_display.atlas_updated(), is_false());
+    }
+
+    #[gtest]
+    fn test_fonts_loaded_with_first_text() {
+        let (_gl, mut graphics) = graphics(OrientationName::Angle0);
+        let rect = EpaintRect::from_min_size([0., 0.].into(), [10., 10.].into());
+        graphics
+            .create_shape(Shape::rect_filled(rect, 0., Color32::BLACK), None)
+            .unwrap();
+        expect_that!(graphics.epaint_display.fonts_loaded(), is_false());
+
+        let text = graphics.create_text_container().unwrap();
+        text.set_layout(LayoutJob::single_section(
+            "Loading".into(),
+            TextFormat::simple(FontId::proportional(20.), Color32::WHITE),
+        ));
+        graphics.force_text_container_update(&text);
+        expect_that!(graphics.epaint_display.fonts_loaded(), is_true());
+        expect_that!(text.get_dimensions().w, gt(0.));
+    }
+}
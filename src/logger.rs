@@ -0,0 +1,184 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, OnceLock, RwLock},
+};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use env_filter::{Builder, Filter};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Number of log lines kept for `GET /logs`.
+const BUFFER_CAPACITY: usize = 500;
+
+static LOGGER: OnceLock<RuntimeLogger> = OnceLock::new();
+
+/// Logger whose filter can be replaced while running. Records are printed by env_logger
+/// and the last ones are kept in memory.
+///
+/// The global max level of the `log` crate follows the filter, so disabled levels are
+/// rejected by the logging macros before reaching the logger.
+struct RuntimeLogger {
+    filter: RwLock<Filter>,
+    output: env_logger::Logger,
+    buffer: Mutex<LogBuffer>,
+}
+
+/// Last log lines, the oldest ones being dropped once full.
+struct LogBuffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+}
+
+/// Parses a filter with the RUST_LOG syntax, e.g. "info,memocadre::gallery=trace". An
+/// empty filter falls back to the RUST_LOG environment variable.
+fn parse_filter(spec: &str) -> Result<Filter> {
+    if spec.trim().is_empty() {
+        return Ok(Builder::from_env("RUST_LOG").build());
+    }
+    Ok(Builder::new()
+        .try_parse(spec)
+        .with_context(|| format!("Invalid log filter {spec:?}"))?
+        .build())
+}
+
+/// Installs the logger, filtered by the RUST_LOG environment variable until
+/// [`set_filter`] is called.
+pub fn init() -> Result<()> {
+    let filter = parse_filter("")?;
+    let max_level = filter.filter();
+    let logger = LOGGER.get_or_init(|| RuntimeLogger {
+        filter: RwLock::new(filter),
+        // Filtering is done before records reach env_logger
+        output: env_logger::Builder::new()
+            .filter_level(LevelFilter::Trace)
+            .build(),
+        buffer: Mutex::new(LogBuffer::new(BUFFER_CAPACITY)),
+    });
+    log::set_logger(logger).context("Logger already installed")?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Replaces the filter of the logger, keeping the current one when `spec` is invalid.
+pub fn set_filter(spec: &str) -> Result<()> {
+    let filter = parse_filter(spec)?;
+    let Some(logger) = LOGGER.get() else {
+        return Ok(());
+    };
+    let max_level = filter.filter();
+    *logger.filter.write().unwrap_or_else(|err| err.into_inner()) = filter;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Last log lines, oldest first.
+pub fn recent_lines() -> Vec<String> {
+    LOGGER.get().map_or_else(Vec::new, |logger| {
+        logger
+            .buffer
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .lines()
+    })
+}
+
+impl Log for RuntimeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self
+            .filter
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .matches(record)
+        {
+            return;
+        }
+        self.output.log(record);
+        let line = format!(
+            "{} {:<5} {}: {}",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        self.buffer
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(line);
+    }
+
+    fn flush(&self) {
+        self.output.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use googletest::{
+        expect_that, gtest,
+        prelude::{eq, is_false, is_true},
+    };
+    use log::{Level, LevelFilter, Metadata};
+
+    use super::{parse_filter, LogBuffer};
+
+    fn enabled(filter: &env_filter::Filter, target: &str, level: Level) -> bool {
+        filter.enabled(&Metadata::builder().target(target).level(level).build())
+    }
+
+    #[gtest]
+    fn test_parse_per_module_filter() {
+        let filter = parse_filter("warn,memocadre::gallery=trace").unwrap();
+        expect_that!(filter.filter(), eq(LevelFilter::Trace));
+        expect_that!(
+            enabled(&filter, "memocadre::gallery::immich", Level::Trace),
+            is_true()
+        );
+        expect_that!(enabled(&filter, "memocadre::gl", Level::Info), is_false());
+        expect_that!(enabled(&filter, "memocadre::gl", Level::Warn), is_true());
+    }
+
+    #[gtest]
+    fn test_invalid_filter_is_rejected() {
+        expect_that!(parse_filter("memocadre=loud").is_err(), is_true());
+    }
+
+    #[gtest]
+    fn test_buffer_keeps_last_lines() {
+        let mut buffer = LogBuffer::new(2);
+        buffer.push("first".into());
+        buffer.push("second".into());
+        buffer.push("third".into());
+        expect_that!(
+            buffer.lines(),
+            eq(vec!["second".to_string(), "third".to_string()])
+        );
+    }
+}
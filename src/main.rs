@@ -1,17 +1,64 @@
+mod agenda;
 mod application;
 mod configuration;
 mod gallery;
 mod gl;
 mod graphics;
+mod logger;
+mod network;
+mod random;
 mod support;
 mod worker;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
-use self::application::Application;
+use self::application::{SimulationOptions, StartOptions};
 
 fn main() -> Result<()> {
-    env_logger::init();
-    support::start::<Application>()?;
-    Ok(())
+    logger::init()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("--test-spec") => {
+            let index = args
+                .get(1)
+                .context("Missing the index of the spec to test")?
+                .parse()
+                .context("Invalid spec index")?;
+            application::test_spec(index)
+        }
+        Some("--dump-effective-settings") => application::dump_effective_settings(),
+        Some("--check") => application::check_config(),
+        Some("--simulate") => {
+            application::simulate(SimulationOptions::parse(args.into_iter().skip(1))?)
+        }
+        _ => start(args),
+    }
+}
+
+/// Starts the slideshow, with its flags given in any order. The test pattern and the demo
+/// photos are only shown in the preview window, not instead of the photos of the frame.
+fn start(mut args: Vec<String>) -> Result<()> {
+    let options = StartOptions {
+        test_pattern: take_flag(&mut args, "--test-pattern"),
+        demo: take_flag(&mut args, "--demo"),
+        force: take_flag(&mut args, "--force"),
+    };
+    let preview = take_flag(&mut args, "--preview");
+    if let Some(arg) = args.first() {
+        bail!("Unknown argument {arg}");
+    }
+    if preview {
+        application::preview(options)
+    } else if options.test_pattern || options.demo {
+        bail!("--test-pattern and --demo only apply to --preview");
+    } else {
+        application::run(options)
+    }
+}
+
+/// Removes `flag` from `args`, returns whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != len
 }
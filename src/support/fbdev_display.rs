@@ -0,0 +1,261 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use image::{Rgba, RgbaImage};
+use log::{info, warn};
+use vek::Extent2;
+
+use super::{ApplicationContext, DrawResult};
+use crate::gl::{
+    framebuffer::FramebufferObject,
+    texture::{Texture, TextureFormat},
+    FutureGlThreadContext,
+};
+
+/// E-paper displays take a while to refresh and flash while doing so: the frames drawn
+/// meanwhile, like the ones of transitions, are skipped and the last one written after.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Layout of the pixels of the framebuffer, little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PixelFormat {
+    /// 5 bits of red, 6 of green and 5 of blue.
+    Rgb565,
+    /// Blue, green, red and an unused byte.
+    Xrgb8888,
+}
+
+/// Linux framebuffer device, described by its attributes in sysfs.
+struct Framebuffer {
+    device: File,
+    size: Extent2<u32>,
+    /// Bytes between the start of two rows.
+    stride: usize,
+    format: PixelFormat,
+    /// Blanks the screen, see [`Framebuffer::set_blank`].
+    blank: PathBuf,
+}
+
+impl PixelFormat {
+    fn from_bits_per_pixel(bits: u32) -> Result<Self> {
+        match bits {
+            16 => Ok(PixelFormat::Rgb565),
+            32 => Ok(PixelFormat::Xrgb8888),
+            _ => bail!("Unsupported framebuffer depth of {bits} bits per pixel"),
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Xrgb8888 => 4,
+        }
+    }
+
+    fn encode(self, Rgba([r, g, b, _]): Rgba<u8>, output: &mut Vec<u8>) {
+        match self {
+            PixelFormat::Rgb565 => {
+                let pixel =
+                    (u16::from(r) >> 3) << 11 | (u16::from(g) >> 2) << 5 | u16::from(b) >> 3;
+                output.extend_from_slice(&pixel.to_le_bytes());
+            }
+            PixelFormat::Xrgb8888 => output.extend_from_slice(&[b, g, r, 0xff]),
+        }
+    }
+}
+
+/// Bytes of the framebuffer showing `image`, read back from GL: its rows start at the
+/// bottom.
+fn encode_frame(image: &RgbaImage, format: PixelFormat, stride: usize) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(stride * image.height() as usize);
+    for row in image.rows().rev() {
+        let start = frame.len();
+        for pixel in row {
+            format.encode(*pixel, &mut frame);
+        }
+        frame.resize(start + stride, 0);
+    }
+    frame
+}
+
+impl Framebuffer {
+    /// Opens `device`, like /dev/fb0.
+    fn open(device: &Path) -> Result<Self> {
+        let name = device
+            .file_name()
+            .context("Invalid framebuffer device")?
+            .to_string_lossy();
+        let sysfs = Path::new("/sys/class/graphics").join(name.as_ref());
+        let attribute = |name: &str| -> Result<String> {
+            let path = sysfs.join(name);
+            let value = fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            Ok(value.trim().to_owned())
+        };
+        let virtual_size = attribute("virtual_size")?;
+        let (width, height) = virtual_size
+            .split_once(',')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .with_context(|| format!("Invalid framebuffer size {virtual_size:?}"))?;
+        let bits = attribute("bits_per_pixel")?
+            .parse()
+            .context("Invalid framebuffer depth")?;
+        let format = PixelFormat::from_bits_per_pixel(bits)?;
+        let stride = attribute("stride")?
+            .parse()
+            .context("Invalid framebuffer stride")?;
+        let size = Extent2::new(width, height);
+        if stride < size.w as usize * format.bytes_per_pixel() {
+            bail!("Framebuffer stride of {stride} bytes is shorter than its rows");
+        }
+        let device = OpenOptions::new()
+            .write(true)
+            .open(device)
+            .with_context(|| format!("Cannot open {}", device.display()))?;
+        Ok(Self {
+            device,
+            size,
+            stride,
+            format,
+            blank: sysfs.join("blank"),
+        })
+    }
+
+    fn write(&mut self, image: &RgbaImage) -> Result<()> {
+        let frame = encode_frame(image, self.format, self.stride);
+        self.device
+            .seek(SeekFrom::Start(0))
+            .context("Cannot rewind framebuffer")?;
+        self.device
+            .write_all(&frame)
+            .context("Cannot write framebuffer")
+    }
+
+    /// Failures are only logged, some drivers don't support blanking.
+    fn set_blank(&self, blank: bool) {
+        if let Err(err) = fs::write(&self.blank, if blank { "1" } else { "0" }) {
+            warn!("Cannot blank framebuffer: {:?}", err);
+        }
+    }
+}
+
+/// Draws offscreen and writes the frames to the framebuffer `device`, at most once per
+/// [`MIN_REFRESH_INTERVAL`]. Frames are only read back when written, the loop sleeps until
+/// then.
+pub fn start_fbdev<T>(device: &Path, options: T::Options) -> Result<()>
+where
+    T: ApplicationContext + 'static,
+{
+    let mut framebuffer = Framebuffer::open(device)?;
+    info!(
+        "Drawing to {} ({}x{}, {:?})",
+        device.display(),
+        framebuffer.size.w,
+        framebuffer.size.h,
+        framebuffer.format
+    );
+    let gl = FutureGlThreadContext::surfaceless(None)?;
+    let bg_gl = FutureGlThreadContext::surfaceless(Some(gl.get_context()))?;
+    let gl = gl.activate().context("Cannot activate main GL context")?;
+
+    let texture = Texture::empty(Rc::clone(&gl), TextureFormat::Rgba, framebuffer.size)
+        .context("Cannot create screen texture")?;
+    let screen = FramebufferObject::with_texture(Rc::clone(&gl), texture)
+        .context("Cannot create screen framebuffer")?;
+    screen.use_as_screen();
+
+    let mut app = T::new(Rc::clone(&gl), bg_gl, options).context("Cannot create application")?;
+    let mut last_refresh: Option<Instant> = None;
+    // A frame was drawn since the last one written
+    let mut pending = false;
+    loop {
+        match app.draw_frame().context("Error while drawing a frame")? {
+            DrawResult::Noop => {}
+            DrawResult::FrameDrawn => pending = true,
+            DrawResult::TurnDisplayOff => framebuffer.set_blank(true),
+            DrawResult::TurnDisplayOn => framebuffer.set_blank(false),
+            // The offscreen framebuffer would have to be created again, systemd restarts
+            // the frame
+            DrawResult::ContextLost => bail!("GL context lost"),
+        }
+        if !pending {
+            continue;
+        }
+        let wait = until_refresh(last_refresh, Instant::now());
+        if !wait.is_zero() {
+            // The frame drawn after the wait is the one written, with the animations ahead
+            std::thread::sleep(wait);
+            continue;
+        }
+        let frame = gl.read_screen().context("Cannot read the frame")?;
+        framebuffer.write(&frame)?;
+        let now = Instant::now();
+        last_refresh = Some(now);
+        pending = false;
+        app.frame_presented(now);
+    }
+}
+
+/// Time before the next frame can be written, after the one written at `last_refresh`.
+fn until_refresh(last_refresh: Option<Instant>, now: Instant) -> Duration {
+    last_refresh.map_or(Duration::ZERO, |last| {
+        (last + MIN_REFRESH_INTERVAL).saturating_duration_since(now)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{expect_that, gtest, prelude::eq};
+    use image::{Rgba, RgbaImage};
+
+    use super::{encode_frame, until_refresh, PixelFormat, MIN_REFRESH_INTERVAL};
+
+    #[gtest]
+    fn test_frames_written_every_refresh_interval() {
+        let now = Instant::now();
+        expect_that!(until_refresh(None, now), eq(Duration::ZERO));
+        expect_that!(
+            until_refresh(Some(now), now + Duration::from_millis(500)),
+            eq(MIN_REFRESH_INTERVAL - Duration::from_millis(500))
+        );
+        expect_that!(
+            until_refresh(Some(now), now + MIN_REFRESH_INTERVAL),
+            eq(Duration::ZERO)
+        );
+        expect_that!(
+            until_refresh(Some(now), now + MIN_REFRESH_INTERVAL * 3),
+            eq(Duration::ZERO)
+        );
+    }
+
+    #[gtest]
+    fn test_encode_rgb565() {
+        let mut output = Vec::new();
+        PixelFormat::Rgb565.encode(Rgba([255, 0, 0, 255]), &mut output);
+        PixelFormat::Rgb565.encode(Rgba([0, 255, 0, 255]), &mut output);
+        PixelFormat::Rgb565.encode(Rgba([0, 0, 255, 255]), &mut output);
+        expect_that!(output, eq(&vec![0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00]));
+    }
+
+    #[gtest]
+    fn test_frame_rows_flipped_and_padded() {
+        // Bottom row first, as read back from GL
+        let image = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8, y as u8, 7, 255]));
+        let frame = encode_frame(&image, PixelFormat::Xrgb8888, 12);
+        expect_that!(
+            frame,
+            eq(&vec![
+                7, 1, 0, 0xff, 7, 1, 1, 0xff, 0, 0, 0, 0, //
+                7, 0, 0, 0xff, 7, 0, 1, 0xff, 0, 0, 0, 0,
+            ])
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+/// Offset assumed until presentation times are known, one frame at 60 Hz.
+const FALLBACK_FRAME_OFFSET: Duration = Duration::from_micros(16_667);
+/// Longer gaps between two presentations are idle periods, not missed vblanks.
+const MAX_MISSED_FRAMES: u32 = 4;
+/// Presentations older than this don't tell the phase of the vblanks anymore.
+const STALE_PRESENTATION: Duration = Duration::from_secs(1);
+/// Weight of each new interval in the estimate, smoothing the jitter of the timestamps.
+const SMOOTHING: f64 = 0.2;
+
+/// Predicts when the next frame reaches the screen from the times the previous ones did,
+/// so that animations start on their first visible frame.
+#[derive(Debug, Default)]
+pub struct PresentationTiming {
+    last_present: Option<Instant>,
+    /// Estimated time between two vblanks.
+    interval: Option<Duration>,
+}
+
+impl PresentationTiming {
+    /// Records that a frame was shown at `at`, e.g. the time of a page flip event.
+    pub fn presented(&mut self, at: Instant) {
+        if let Some(last) = self.last_present {
+            let delta = at.saturating_duration_since(last);
+            self.interval = match self.interval {
+                None if !delta.is_zero() && delta < FALLBACK_FRAME_OFFSET * MAX_MISSED_FRAMES => {
+                    Some(delta)
+                }
+                None => None,
+                Some(interval) => {
+                    // Missed vblanks span several intervals
+                    let frames = (delta.as_secs_f64() / interval.as_secs_f64()).round();
+                    if frames >= 1. && frames <= MAX_MISSED_FRAMES as f64 {
+                        let sample = delta.as_secs_f64() / frames;
+                        let estimate =
+                            interval.as_secs_f64() * (1. - SMOOTHING) + sample * SMOOTHING;
+                        Some(Duration::from_secs_f64(estimate))
+                    } else {
+                        Some(interval)
+                    }
+                }
+            };
+        }
+        self.last_present = Some(at);
+    }
+
+    /// When a frame drawn at `now` should reach the screen: the first vblank after `now`,
+    /// or a fixed offset while the vblanks are unknown.
+    pub fn next_present(&self, now: Instant) -> Instant {
+        match (self.last_present, self.interval) {
+            (Some(last), Some(interval))
+                if now.saturating_duration_since(last) < STALE_PRESENTATION =>
+            {
+                let elapsed = now.saturating_duration_since(last);
+                let frames = (elapsed.as_secs_f64() / interval.as_secs_f64()).floor() + 1.;
+                last + interval.mul_f64(frames)
+            }
+            (_, interval) => now + interval.unwrap_or(FALLBACK_FRAME_OFFSET),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use googletest::{expect_that, gtest, prelude::near};
+
+    use super::PresentationTiming;
+
+    fn ms_between(from: Instant, to: Instant) -> f64 {
+        (to - from).as_secs_f64() * 1000.
+    }
+
+    /// Flips at 60 Hz, each shifted by up to half a millisecond.
+    fn timing_with_jitter(start: Instant) -> (PresentationTiming, Instant) {
+        let jitter = [0.3, -0.4, 0.1, 0.5, -0.2, -0.5, 0.4, 0., -0.1, 0.2];
+        let mut timing = PresentationTiming::default();
+        let mut last = start;
+        for (frame, jitter) in jitter.iter().enumerate() {
+            last = start + Duration::from_secs_f64((frame as f64 * 16.667 + jitter) / 1000.);
+            timing.presented(last);
+        }
+        (timing, last)
+    }
+
+    #[gtest]
+    fn test_predicts_next_vblank_despite_jitter() {
+        let start = Instant::now();
+        let (timing, last) = timing_with_jitter(start);
+
+        let predicted = timing.next_present(last + Duration::from_millis(5));
+        expect_that!(ms_between(last, predicted), near(16.667, 1.));
+        // A frame drawn too late for the next vblank is shown on the one after
+        let predicted = timing.next_present(last + Duration::from_millis(20));
+        expect_that!(ms_between(last, predicted), near(33.333, 2.));
+    }
+
+    #[gtest]
+    fn test_missed_vblanks_keep_interval() {
+        let start = Instant::now();
+        let (mut timing, last) = timing_with_jitter(start);
+        // Two vblanks missed by a slow frame
+        let late = last + Duration::from_secs_f64(0.05);
+        timing.presented(late);
+
+        let predicted = timing.next_present(late + Duration::from_millis(1));
+        expect_that!(ms_between(late, predicted), near(16.667, 1.));
+    }
+
+    #[gtest]
+    fn test_fixed_offset_without_presentation() {
+        let now = Instant::now();
+        let timing = PresentationTiming::default();
+        expect_that!(
+            ms_between(now, timing.next_present(now)),
+            near(16.667, 0.01)
+        );
+
+        // Idle for a while, the phase of the vblanks is lost
+        let (timing, last) = timing_with_jitter(now);
+        let later = last + Duration::from_secs(10);
+        expect_that!(
+            ms_between(later, timing.next_present(later)),
+            near(16.667, 1.)
+        );
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     ffi::CStr,
     fs::{File, OpenOptions},
     os::unix::io::{AsFd, BorrowedFd},
+    time::Duration,
 };
 
 use anyhow::{Context as _, Result};
@@ -209,7 +210,8 @@ impl DrmDevice {
         Ok(())
     }
 
-    pub fn flip_and_wait(&self, fb: FbHandle) -> Result<()> {
+    /// Returns the CLOCK_MONOTONIC time of the vblank at which the flip happened.
+    pub fn flip_and_wait(&self, fb: FbHandle) -> Result<Duration> {
         self.card
             .page_flip(self.crtc.handle(), fb, PageFlipFlags::EVENT, None)?;
 
@@ -218,7 +220,7 @@ impl DrmDevice {
             for event in &mut events {
                 if let control::Event::PageFlip(event) = event {
                     if event.crtc == self.crtc.handle() {
-                        return Ok(());
+                        return Ok(event.duration);
                     }
                 }
             }
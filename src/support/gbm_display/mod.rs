@@ -4,7 +4,7 @@ mod page_flip;
 
 use std::rc::Rc;
 
-use anyhow::{Context as _, Result};
+use anyhow::{bail, Context as _, Result};
 use drm_device::DpmsValue;
 use glutin::{
     context::{ContextAttributesBuilder, NotCurrentContext, Priority},
@@ -37,7 +37,7 @@ fn create_gl_context(
     }
 }
 
-pub fn start_gbm<T>() -> Result<()>
+pub fn start_gbm<T>(options: T::Options) -> Result<()>
 where
     T: ApplicationContext + 'static,
 {
@@ -65,13 +65,16 @@ where
     let mut page_flipper =
         PageFlipper::init(&gbm_data.device, &surface).context("Cannot create page flipper")?;
 
-    let mut app = T::new(Rc::clone(&gl), bg_gl).context("Cannot create application")?;
+    let mut app = T::new(Rc::clone(&gl), bg_gl, options).context("Cannot create application")?;
     loop {
         let result = app.draw_frame().context("Error while drawing a frame")?;
 
         match result {
             super::DrawResult::Noop => continue,
-            super::DrawResult::FrameDrawn => page_flipper.flip()?,
+            super::DrawResult::FrameDrawn => {
+                let presented_at = page_flipper.flip()?;
+                app.frame_presented(presented_at);
+            }
             super::DrawResult::TurnDisplayOff => {
                 gbm_data
                     .device
@@ -84,6 +87,8 @@ where
                     .set_dpms_property(DpmsValue::On)
                     .context("Cannot turn on display")?;
             }
+            // The GBM surface would have to be created again, systemd restarts the frame
+            super::DrawResult::ContextLost => bail!("GL context lost"),
         }
     }
 }
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use drm::control::Device as ControlDevice;
 
@@ -46,17 +48,33 @@ impl<'a> PageFlipper<'a> {
         })
     }
 
-    pub fn flip(&mut self) -> Result<()> {
+    /// Shows the last rendered buffer, returns when it reached the screen.
+    pub fn flip(&mut self) -> Result<Instant> {
         let next_bo = unsafe { self.surface.lock_front_buffer()? };
         let next_fb = FbWrapper {
             handle: self.device.add_framebuffer(&next_bo, self.bpp, self.bpp)?,
             device: self.device,
         };
 
-        self.device.flip_and_wait(next_fb.handle)?;
+        let flipped_at = self.device.flip_and_wait(next_fb.handle)?;
 
         drop(std::mem::replace(&mut self.bo, next_bo));
         drop(std::mem::replace(&mut self.fb, next_fb));
-        Ok(())
+        Ok(monotonic_to_instant(flipped_at))
+    }
+}
+
+/// Converts a CLOCK_MONOTONIC timestamp, like those of DRM events, to an [`Instant`].
+fn monotonic_to_instant(timestamp: Duration) -> Instant {
+    let now = Instant::now();
+    let mut clock = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut clock) } != 0 {
+        return now;
     }
+    let monotonic_now = Duration::new(clock.tv_sec as u64, clock.tv_nsec as u32);
+    now.checked_sub(monotonic_now.saturating_sub(timestamp))
+        .unwrap_or(now)
 }
@@ -1,12 +1,19 @@
+#[cfg(feature = "fbdev")]
+mod fbdev_display;
+mod frame_timing;
 #[cfg(feature = "drm")]
 mod gbm_display;
 #[cfg(feature = "winit")]
 mod window_display;
 
-use std::rc::Rc;
+use std::{rc::Rc, time::Instant};
 
 use anyhow::{Context, Result};
+use vek::Extent2;
 
+#[cfg(feature = "fbdev")]
+use self::fbdev_display::start_fbdev;
+pub use self::frame_timing::PresentationTiming;
 #[cfg(feature = "drm")]
 use self::gbm_display::start_gbm;
 #[cfg(feature = "winit")]
@@ -19,13 +26,37 @@ pub enum DrawResult {
     FrameDrawn,
     TurnDisplayOff,
     TurnDisplayOn,
+    /// The GL context is gone, new ones are given to
+    /// [`ApplicationContext::context_restored`].
+    ContextLost,
+}
+
+/// Where frames are shown, see [`start`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Backend {
+    /// A window when a window system runs, the framebuffer device named by `FRAMEBUFFER`
+    /// when set, the screen driven directly otherwise.
+    #[default]
+    Auto,
+    /// A window of this size, even when the screen could be driven directly.
+    Window(Extent2<u32>),
 }
 
 pub trait ApplicationContext: Sized {
+    /// Given to [`ApplicationContext::new`], each time the GL context is created.
+    type Options: Clone;
+
     fn draw_frame(&mut self) -> Result<DrawResult> {
         Ok(DrawResult::FrameDrawn)
     }
-    fn new(gl: Rc<GlContext>, bg_gl: FutureGlThreadContext) -> Result<Self>;
+    fn new(gl: Rc<GlContext>, bg_gl: FutureGlThreadContext, options: Self::Options)
+        -> Result<Self>;
+    /// Called with the new contexts after [`DrawResult::ContextLost`], the objects of the
+    /// lost ones must be created again.
+    fn context_restored(&mut self, gl: Rc<GlContext>, bg_gl: FutureGlThreadContext) -> Result<()>;
+    /// Called with the time the last drawn frame reached the screen, when the display
+    /// reports it.
+    fn frame_presented(&mut self, _at: Instant) {}
     #[cfg(feature = "winit")]
     fn resized(&mut self, _width: u32, _height: u32) {}
     #[cfg(feature = "winit")]
@@ -38,19 +69,35 @@ pub trait ApplicationContext: Sized {
     const WINDOW_TITLE: &'static str;
 }
 
-pub fn start<T: ApplicationContext + 'static>() -> Result<()> {
+pub fn start<T: ApplicationContext + 'static>(backend: Backend, options: T::Options) -> Result<()> {
     #[cfg(feature = "winit")]
     {
         let vars = ["WAYLAND_DISPLAY", "WAYLAND_SOCKET", "DISPLAY"];
         let has_window_system = vars.into_iter().any(|v| std::env::var_os(v).is_some());
-        if has_window_system {
-            return State::<T>::run_loop().context("While running application");
+        match backend {
+            Backend::Window(size) => {
+                return State::<T>::run_loop(Some(size), options)
+                    .context("While running application");
+            }
+            Backend::Auto if has_window_system => {
+                return State::<T>::run_loop(None, options).context("While running application");
+            }
+            Backend::Auto => {}
         }
     }
+    #[cfg(not(feature = "winit"))]
+    if let Backend::Window(_) = backend {
+        anyhow::bail!("Built without window support");
+    }
+    #[cfg(feature = "fbdev")]
+    if let Some(device) = std::env::var_os("FRAMEBUFFER") {
+        return start_fbdev::<T>(std::path::Path::new(&device), options)
+            .context("While running application");
+    }
     #[cfg(feature = "drm")]
     {
         #[allow(clippy::needless_return)]
-        return start_gbm::<T>().context("While running application");
+        return start_gbm::<T>(options).context("While running application");
     }
 
     #[cfg(not(feature = "drm"))]
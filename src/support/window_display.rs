@@ -2,16 +2,16 @@ use std::{num::NonZeroU32, rc::Rc};
 
 use anyhow::{Context, Result};
 use glutin::{
-    context::{self, Version},
+    context::{self, Robustness, Version},
     display::{GetGlDisplay, GlDisplay},
     surface::WindowSurface,
 };
-use log::warn;
+use log::{info, warn};
 use raw_window_handle::HasWindowHandle;
-use vek::Rect;
+use vek::{Extent2, Rect};
 use winit::{
-    application::ApplicationHandler, event::WindowEvent, event_loop::ActiveEventLoop,
-    window::WindowId,
+    application::ApplicationHandler, dpi::PhysicalSize, event::WindowEvent,
+    event_loop::ActiveEventLoop, window::WindowId,
 };
 
 use super::{ApplicationContext, DrawResult};
@@ -23,17 +23,27 @@ pub struct State<T> {
     pub context: T,
 }
 
-struct App<T> {
+struct App<T: ApplicationContext> {
     state: Option<State<T>>,
     visible: bool,
     close_promptly: bool,
+    /// Size the window opens with, chosen by the window system when not set.
+    size: Option<Extent2<u32>>,
+    options: T::Options,
+    /// Stopped the event loop, returned by [`State::run_loop`].
+    error: Option<anyhow::Error>,
 }
 
 impl<T: ApplicationContext + 'static> ApplicationHandler<()> for App<T> {
     // The resumed/suspended handlers are mostly for Android compatiblity since the context can get lost there at any point.
     // For convenience's sake, the resumed handler is also called on other platforms on program startup.
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        self.state = Some(State::new(event_loop, self.visible));
+        self.state = Some(State::new(
+            event_loop,
+            self.visible,
+            self.size,
+            self.options.clone(),
+        ));
         if !self.visible && self.close_promptly {
             event_loop.exit();
         }
@@ -67,6 +77,15 @@ impl<T: ApplicationContext + 'static> ApplicationHandler<()> for App<T> {
                     if result == DrawResult::TurnDisplayOff || result == DrawResult::TurnDisplayOn {
                         warn!("Turning display off/on is not supported on desktop platforms");
                     }
+                    if result == DrawResult::ContextLost {
+                        warn!("GL context lost, opening the window again");
+                        if let Err(err) = state.restore_context(event_loop, self.visible, self.size)
+                        {
+                            self.error = Some(err);
+                            event_loop.exit();
+                            return;
+                        }
+                    }
                     if self.close_promptly {
                         event_loop.exit();
                     }
@@ -100,10 +119,52 @@ impl<T: ApplicationContext + 'static> ApplicationHandler<()> for App<T> {
     }
 }
 impl<T: ApplicationContext + 'static> State<T> {
-    pub fn new(event_loop: &winit::event_loop::ActiveEventLoop, visible: bool) -> Self {
-        let window_attributes = winit::window::Window::default_attributes()
+    pub fn new(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        visible: bool,
+        size: Option<Extent2<u32>>,
+        options: T::Options,
+    ) -> Self {
+        let (window, gl, bg_gl) = Self::create_window(event_loop, visible, size);
+        Self::from_display_window(gl, window, bg_gl, options)
+    }
+
+    /// A window of its own is needed: a window cannot get a second surface while the lost
+    /// one is alive, which is until the application dropped its objects.
+    fn restore_context(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        visible: bool,
+        size: Option<Extent2<u32>>,
+    ) -> Result<()> {
+        let current = self.window.inner_size();
+        let size = size.or(Some(Extent2::new(current.width, current.height)));
+        let (window, gl, bg_gl) = Self::create_window(event_loop, visible, size);
+        let gl = gl.activate().context("Cannot make context current")?;
+        self.context
+            .context_restored(Rc::clone(&gl), bg_gl)
+            .context("Cannot restore application")?;
+        self.gl = gl;
+        self.window = window;
+        Ok(())
+    }
+
+    fn create_window(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        visible: bool,
+        size: Option<Extent2<u32>>,
+    ) -> (
+        winit::window::Window,
+        FutureGlThreadContext,
+        FutureGlThreadContext,
+    ) {
+        let mut window_attributes = winit::window::Window::default_attributes()
             .with_title(T::WINDOW_TITLE)
             .with_visible(visible);
+        if let Some(size) = size {
+            window_attributes =
+                window_attributes.with_inner_size(PhysicalSize::new(size.w, size.h));
+        }
         let config_template_builder = glutin::config::ConfigTemplateBuilder::new();
         let display_builder =
             glutin_winit::DisplayBuilder::new().with_window_attributes(Some(window_attributes));
@@ -123,16 +184,27 @@ impl<T: ApplicationContext + 'static> State<T> {
         let window_handle = window
             .window_handle()
             .expect("couldn't obtain window handle");
-        let context_attributes = context::ContextAttributesBuilder::new()
-            .with_context_api(context::ContextApi::Gles(Version::new(2, 0).into()))
-            .build(Some(window_handle.into()));
-
-        let not_current_gl_context = unsafe {
+        // Lost on GPU resets when the driver supports it, rather than only noticed when
+        // presenting fails
+        let create_context = |robustness| unsafe {
+            let context_attributes = context::ContextAttributesBuilder::new()
+                .with_context_api(context::ContextApi::Gles(Version::new(2, 0).into()))
+                .with_robustness(robustness)
+                .build(Some(window_handle.into()));
             gl_config
                 .display()
                 .create_context(&gl_config, &context_attributes)
-                .expect("failed to create context")
         };
+        let (not_current_gl_context, robustness) =
+            match create_context(Robustness::RobustLoseContextOnReset) {
+                Ok(context) => (context, Robustness::RobustLoseContextOnReset),
+                Err(err) => {
+                    info!("No robust GL context, GPU resets are noticed when presenting: {err}");
+                    let context =
+                        create_context(Robustness::NotRobust).expect("failed to create context");
+                    (context, Robustness::NotRobust)
+                }
+            };
 
         // Determine our framebuffer size based on the window size, or default to 800x600 if it's invisible
         let (width, height): (u32, u32) = if visible {
@@ -153,11 +225,16 @@ impl<T: ApplicationContext + 'static> State<T> {
                 .expect("Cannot create window surface")
         };
 
-        let gl =
+        let mut gl =
             FutureGlThreadContext::new(Some(surface), not_current_gl_context, gl_config.display());
+        if robustness == Robustness::RobustLoseContextOnReset {
+            gl = gl.with_reset_notification();
+        }
 
+        // Shared contexts must have the same reset strategy
         let bg_context_attributes = context::ContextAttributesBuilder::new()
             .with_context_api(context::ContextApi::Gles(Version::new(2, 0).into()))
+            .with_robustness(robustness)
             .with_sharing(gl.get_context())
             .with_priority(glutin::context::Priority::Low)
             .build(None);
@@ -171,16 +248,17 @@ impl<T: ApplicationContext + 'static> State<T> {
 
         let bg_gl = FutureGlThreadContext::new(None, bg_context, gl_config.display());
 
-        Self::from_display_window(gl, window, bg_gl)
+        (window, gl, bg_gl)
     }
 
     pub fn from_display_window(
         gl: FutureGlThreadContext,
         window: winit::window::Window,
         bg_gl: FutureGlThreadContext,
+        options: T::Options,
     ) -> Self {
         let gl = gl.activate().expect("Cannot make context current");
-        let context = T::new(Rc::clone(&gl), bg_gl).expect("Cannot create application");
+        let context = T::new(Rc::clone(&gl), bg_gl, options).expect("Cannot create application");
         Self {
             gl,
             window,
@@ -189,7 +267,7 @@ impl<T: ApplicationContext + 'static> State<T> {
     }
 
     /// Start the event_loop and keep rendering frames until the program is closed
-    pub fn run_loop() -> Result<()> {
+    pub fn run_loop(size: Option<Extent2<u32>>, options: T::Options) -> Result<()> {
         let event_loop = winit::event_loop::EventLoop::builder()
             .build()
             .context("event loop building")?;
@@ -197,7 +275,13 @@ impl<T: ApplicationContext + 'static> State<T> {
             state: None,
             visible: true,
             close_promptly: false,
+            size,
+            options,
+            error: None,
         };
-        event_loop.run_app(&mut app).context("Running application")
+        event_loop
+            .run_app(&mut app)
+            .context("Running application")?;
+        app.error.map_or(Ok(()), Err)
     }
 }
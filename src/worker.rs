@@ -1,78 +1,379 @@
 use std::{
+    cell::{Cell, RefCell},
+    ops::Range,
     rc::Rc,
-    sync::mpsc::{Receiver, SyncSender},
-    time::Duration,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, SendError, Sender, SyncSender, TryRecvError},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
 };
 
-use anyhow::{Context, Result};
-use backon::{BlockingRetryable, ExponentialBuilder};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
 use image::{imageops::FilterType, DynamicImage, GenericImageView};
-use log::error;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 use thread_priority::{set_current_thread_priority, ThreadPriority};
-use tokio::sync::watch;
+use tokio::sync::{mpsc::UnboundedSender, watch};
 use vek::Extent2;
 
 use crate::{
-    configuration::{ImageFilter, Settings, Source},
-    gallery::{build_sources, Gallery, ImageDetails},
+    application::pick_transition,
+    configuration::{
+        Background, ImageFilter, QualityLevel, ReverseGeocodingConfig, Settings, Source,
+        TextureFilter, TransitionKind,
+    },
+    gallery::{
+        build_enrichers, build_sources, is_immich_asset_id, Gallery, GalleryEmpty, ImageDetails,
+        ImageDetailsEnricher, ImageWithDetails, PreparationInfo, EMPTY_RETRY_INTERVAL,
+    },
     gl::{
-        texture::{DetachedTexture, Texture},
+        texture::{
+            texture_memory, DetachedTexture, Texture, TextureFiltering, TextureOptions,
+            TextureWrapMode,
+        },
         FutureGlThreadContext, GlContext,
     },
-    graphics::ImageBlurr,
+    graphics::{BlurOptions, ImageBlurr},
+    random,
 };
 
-type Message = PreloadedSlide;
+/// Time between two checks of the display size while it's still unknown.
+const IDEAL_SIZE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Wait after all sources failed, doubled on each new failure.
+const FAILURE_BACKOFF_MIN: Duration = Duration::from_secs(5);
+const FAILURE_BACKOFF_MAX: Duration = Duration::from_secs(5 * 60);
+/// Time between two checks of the photos alive while they're at the limit.
+const LIVE_SLIDES_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Lowest limit of the photos alive: the one shown and the next one, a lower limit would
+/// never let the next one be prepared.
+const MIN_LIVE_SLIDES: usize = 2;
+
+/// Photos alive, from their preparation until the display drops them, whatever the thread
+/// holding them.
+static LIVE_SLIDES: AtomicUsize = AtomicUsize::new(0);
+/// Largest side of the thumbnails of the closing mosaic, see [`Settings::display_off_recap`].
+const RECAP_THUMBNAIL_SIZE: u32 = 160;
+/// Longest wait for a photo of the leader frame, before checking the settings again.
+const SYNC_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Shortest time between the leader frame preparing a photo and all frames showing it,
+/// for the followers to fetch and prepare it too.
+const SYNC_LEAD: TimeDelta = TimeDelta::seconds(5);
+
+pub enum Message {
+    Slide(PreloadedSlide),
+    /// Sources work but none of them match any photo.
+    NoPhotos,
+    /// The worker stopped on this error, it starts again after a while.
+    Failed(String),
+}
 
 pub struct PreloadedSlide {
+    /// Counts the photo among the ones alive, until it's dropped along with its textures.
+    pub live: LiveSlide,
     pub details: ImageDetails,
     pub texture: DetachedTexture,
-    pub blurred_texture: DetachedTexture,
+    /// Not set for the thumbnail shown first, which is not blurred, nor over a black
+    /// background.
+    pub blurred_texture: Option<DetachedTexture>,
+    pub preparation: PreparationInfo,
+    /// Small copy of the photo for the mosaic shown when the display is turned off, see
+    /// [`Settings::display_off_recap`].
+    pub recap_thumbnail: Option<DynamicImage>,
+    /// Transition to the photo, picked from [`Settings::transitions`] when `None`.
+    pub transition: Option<TransitionKind>,
+    /// When the synchronized frames show the photo, as soon as possible when `None`.
+    pub show_at: Option<DateTime<Utc>>,
+}
+
+impl PreloadedSlide {
+    /// Deletes the textures of a photo that won't be shown, with `gl`, a context sharing the
+    /// one of the worker. The textures of a lost context are just dropped, gone with it.
+    pub fn delete(self, gl: &Rc<GlContext>) {
+        self.texture.delete(Rc::clone(gl));
+        if let Some(blurred_texture) = self.blurred_texture {
+            blurred_texture.delete(Rc::clone(gl));
+        }
+    }
+}
+
+impl Message {
+    /// Deletes the textures of the photo of a message that won't be shown, see
+    /// [`PreloadedSlide::delete`].
+    pub fn delete(self, gl: &Rc<GlContext>) {
+        if let Message::Slide(slide) = self {
+            slide.delete(gl);
+        }
+    }
+}
+
+/// Counts a photo among the ones alive while it's held, see [`Settings::max_live_slides`].
+pub struct LiveSlide(());
+
+impl LiveSlide {
+    pub fn counted() -> Self {
+        LIVE_SLIDES.fetch_add(1, Ordering::Relaxed);
+        Self(())
+    }
+}
+
+impl Drop for LiveSlide {
+    fn drop(&mut self) {
+        LIVE_SLIDES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Photo the synchronized frames show at the same time, published by the leader frame when
+/// it's prepared, see [`crate::configuration::SyncConfig`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedPhoto {
+    pub asset_id: String,
+    /// Picked by each frame when missing.
+    pub transition: Option<TransitionKind>,
+    pub show_at: DateTime<Utc>,
+}
+
+/// How a photo of the leader frame follows the one received before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncedOrder {
+    Next,
+    /// Received again, e.g. when reconnected.
+    Duplicate,
+    /// Shown no later than the previous one: the leader started a new schedule, after a
+    /// restart or [`Worker::restart_schedule`]. The photos of the previous one not shown yet
+    /// never are.
+    NewSchedule,
+}
+
+impl SyncedPhoto {
+    /// How the photo follows `previous`, the photo of the leader received before it.
+    pub fn order_after(&self, previous: Option<&SyncedPhoto>) -> SyncedOrder {
+        match previous {
+            Some(previous) if previous == self => SyncedOrder::Duplicate,
+            Some(previous) if self.show_at <= previous.show_at => SyncedOrder::NewSchedule,
+            _ => SyncedOrder::Next,
+        }
+    }
+}
+
+/// Photos published by the leader frame, shared by the worker scheduling them and the
+/// display moving the next one sooner, see [`Worker::restart_schedule`].
+#[derive(Clone)]
+struct LeaderSchedule {
+    published: UnboundedSender<SyncedPhoto>,
+    state: Arc<Mutex<ScheduleState>>,
+}
+
+#[derive(Default)]
+struct ScheduleState {
+    /// The next photo is shown after this one.
+    last: Option<SyncedPhoto>,
+    /// Photos prepared for an older generation are dropped by the display, never published.
+    generation: u64,
+}
+
+/// Part of the frame among the synchronized ones.
+pub enum SyncPart {
+    /// Picks the photos and when they're shown, published in order on the sender: photos
+    /// are prepared ahead, followers must get each of them.
+    Leader(UnboundedSender<SyncedPhoto>),
+    /// Only prepares the photos of the leader, see [`Worker::show_synced`].
+    Follower,
+}
+
+/// Source selected with [`Worker::set_active_source`], numbered so that the photos prepared
+/// before a selection can be told apart.
+#[derive(Debug, Clone, Default)]
+struct SourceSelection {
+    range: Option<Range<usize>>,
+    generation: u64,
 }
 
 pub struct Worker {
     ideal_max_size_sender: watch::Sender<Extent2<u32>>,
-    recv: Receiver<Message>,
+    active_source_sender: watch::Sender<SourceSelection>,
+    quality_sender: watch::Sender<QualityLevel>,
+    /// Messages with the generation of the selection they were prepared for.
+    recv: Receiver<(u64, Message)>,
+    /// Photos prepared for an older selection are dropped, see [`Worker::switch_source`].
+    discard_before: Cell<u64>,
+    upgrades: Receiver<PreloadedSlide>,
+    /// Cuts the wait after a failure of all sources short.
+    retry_now: Sender<()>,
+    /// Contexts replacing a lost one, see [`Worker::restore_context`].
+    contexts: Sender<(FutureGlThreadContext, Option<String>)>,
+    /// Photos of the leader frame, with the generation they are prepared for, see
+    /// [`Worker::show_synced`].
+    synced: Sender<(u64, SyncedPhoto)>,
+    /// Last photo of the leader frame received, when following it.
+    last_received: RefCell<Option<SyncedPhoto>>,
+    /// Set when leading the synchronized frames.
+    schedule: Option<LeaderSchedule>,
+}
+
+/// Waits between two failures of all sources, longer and longer while they last, so that
+/// a lost network doesn't keep the worker busy.
+struct FailureBackoff {
+    delay: Duration,
+    /// Ends the wait early, to try again right away.
+    wake: Receiver<()>,
 }
 
 struct WorkerImpl {
-    send: SyncSender<Message>,
+    send: SyncSender<(u64, Message)>,
+    /// Full images of photos first sent as thumbnails.
+    upgrades: Sender<PreloadedSlide>,
     ideal_max_size: watch::Receiver<Extent2<u32>>,
     config: Settings,
     config_watch: watch::Receiver<Settings>,
+    /// Lowers `config` when frames are slow to draw.
+    quality: watch::Receiver<QualityLevel>,
+    /// Source to fetch from, all in turn when `None`.
+    active_source: watch::Receiver<SourceSelection>,
+    /// Generation of the selection photos are prepared for.
+    generation: u64,
     sources: Vec<Source>,
+    randomize_start: bool,
+    empty_sources: watch::Sender<Vec<String>>,
+    /// Photo to prepare first, before the regular rotation.
+    pinned: Option<String>,
+    reverse_geocoding: Option<ReverseGeocodingConfig>,
+    /// Complete the details of photos, built with the sources.
+    enrichers: Vec<Box<dyn ImageDetailsEnricher>>,
+    backoff: FailureBackoff,
+    /// When sources are tried again after they all failed, `None` when they work.
+    retry_at: watch::Sender<Option<Instant>>,
+    /// Contexts replacing a lost one, with the photo to prepare first.
+    contexts: Receiver<(FutureGlThreadContext, Option<String>)>,
+    /// Only prepares the photos of the leader frame, see [`SyncPart::Follower`].
+    follower: bool,
+    /// Photos shown by the leader frame, when following it.
+    synced: Receiver<(u64, SyncedPhoto)>,
+    /// Set when leading the synchronized frames.
+    schedule: Option<LeaderSchedule>,
+}
+
+impl FailureBackoff {
+    fn new(wake: Receiver<()>) -> Self {
+        Self {
+            delay: FAILURE_BACKOFF_MIN,
+            wake,
+        }
+    }
+
+    /// Delay before the next attempt, the following one will be twice as long.
+    fn failed(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(FAILURE_BACKOFF_MAX);
+        delay
+    }
+
+    fn succeeded(&mut self) {
+        self.delay = FAILURE_BACKOFF_MIN;
+    }
+
+    /// Waits for `delay`, unless woken up meanwhile. Returns whether it was woken up, wake
+    /// ups from before the wait are ignored.
+    fn wait(&self, delay: Duration) -> bool {
+        while self.wake.try_recv().is_ok() {}
+        match self.wake.recv_timeout(delay) {
+            Ok(()) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                // Nobody can wake it up anymore
+                std::thread::sleep(delay);
+                false
+            }
+        }
+    }
 }
 
 impl Worker {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         mut config_watch: watch::Receiver<Settings>,
         ideal_max_size: Extent2<u32>,
         gl: FutureGlThreadContext,
         sources: Vec<Source>,
+        randomize_start: bool,
+        empty_sources: watch::Sender<Vec<String>>,
+        pinned: Option<String>,
+        reverse_geocoding: Option<ReverseGeocodingConfig>,
+        retry_at: watch::Sender<Option<Instant>>,
+        sync: Option<SyncPart>,
     ) -> Self {
         let (send, recv) = std::sync::mpsc::sync_channel(1);
+        let (upgrades_send, upgrades) = std::sync::mpsc::channel();
+        let (retry_now, wake) = std::sync::mpsc::channel();
+        let (contexts_send, contexts) = std::sync::mpsc::channel();
+        let (synced_send, synced) = std::sync::mpsc::channel();
         let config = config_watch.borrow_and_update().clone();
         let (ideal_max_size_sender, ideal_max_size_receiver) = watch::channel(ideal_max_size);
+        let (active_source_sender, active_source) = watch::channel(SourceSelection::default());
+        let (quality_sender, quality) = watch::channel(QualityLevel::Full);
+        let schedule = match &sync {
+            Some(SyncPart::Leader(published)) => Some(LeaderSchedule::new(published.clone())),
+            _ => None,
+        };
         let mut worker_impl = WorkerImpl {
             send,
+            upgrades: upgrades_send,
             ideal_max_size: ideal_max_size_receiver,
             config,
             config_watch,
+            quality,
+            active_source,
+            generation: 0,
             sources,
+            randomize_start,
+            empty_sources,
+            pinned,
+            reverse_geocoding,
+            enrichers: Vec::new(),
+            backoff: FailureBackoff::new(wake),
+            retry_at,
+            contexts,
+            follower: matches!(sync, Some(SyncPart::Follower)),
+            synced,
+            schedule: schedule.clone(),
         };
         std::thread::spawn(move || {
-            let gl = gl
-                .activate()
-                .expect("Cannot make worker thread context current");
-            let blurr =
-                crate::graphics::ImageBlurr::new(gl.clone()).expect("Cannot create ImageBlurr");
-            worker_impl
-                .work(&gl, &blurr)
-                .expect("Worker encountered an error, abort");
+            let (mut gl, mut blurr) = match activate(gl) {
+                Ok(activated) => activated,
+                Err(err) => {
+                    error!("Worker stopped: {:?}", err);
+                    let _ = worker_impl.send_message(Message::Failed(format!("{err:#}")));
+                    return;
+                }
+            };
+            // Started again until the display is gone, e.g. once the network is back
+            while let Err(err) = worker_impl.work(&mut gl, &mut blurr) {
+                let delay = worker_impl.backoff.failed();
+                error!("Worker stopped, restarting in {:?}: {:?}", delay, err);
+                // Shown on screen, fails when the display is the one gone
+                if worker_impl
+                    .send_message(Message::Failed(format!("{err:#}")))
+                    .is_err()
+                {
+                    return;
+                }
+                worker_impl.backoff.wait(delay);
+            }
         });
         Worker {
             ideal_max_size_sender,
+            active_source_sender,
+            quality_sender,
             recv,
+            discard_before: Cell::new(0),
+            upgrades,
+            retry_now,
+            contexts: contexts_send,
+            synced: synced_send,
+            last_received: RefCell::new(None),
+            schedule,
         }
     }
 
@@ -80,30 +381,352 @@ impl Worker {
         self.ideal_max_size_sender.send_replace(size);
     }
 
-    pub fn recv(&self) -> &Receiver<Message> {
-        &self.recv
+    /// Only prepares photos of the galleries in `range`, see [`Gallery::set_active_source`],
+    /// or of all of them in turn when `None`. The photo already prepared is still shown.
+    pub fn set_active_source(&self, range: Option<Range<usize>>) {
+        self.active_source_sender.send_modify(|selection| {
+            selection.range = range;
+            selection.generation += 1;
+        });
+        // The new source may work
+        self.retry_now();
+    }
+
+    /// Like [`Self::set_active_source`], but the photos prepared before are never shown.
+    pub fn switch_source(&self, range: Option<Range<usize>>) {
+        self.set_active_source(range);
+        self.discard_before
+            .set(self.active_source_sender.borrow().generation);
+    }
+
+    /// Drops the photos prepared so far, returns the generation of the ones prepared next.
+    fn drop_prepared(&self) -> u64 {
+        self.active_source_sender
+            .send_modify(|selection| selection.generation += 1);
+        let generation = self.active_source_sender.borrow().generation;
+        self.discard_before.set(generation);
+        generation
+    }
+
+    /// Quality of the blurred background of the photos prepared from now on.
+    pub fn set_quality(&self, level: QualityLevel) {
+        self.quality_sender.send_replace(level);
+    }
+
+    /// Next message of the worker, if any is ready. The photos prepared for a previous
+    /// source are deleted with `gl` instead.
+    pub fn try_recv(&self, gl: &Rc<GlContext>) -> Result<Message, TryRecvError> {
+        loop {
+            let (generation, message) = self.recv.try_recv()?;
+            if generation >= self.discard_before.get() {
+                return Ok(message);
+            }
+            debug!("Dropping a photo prepared for the previous source");
+            message.delete(gl);
+        }
+    }
+
+    /// Tries the sources again right away if they all failed, instead of waiting.
+    pub fn retry_now(&self) {
+        // Only fails when the worker is gone
+        let _ = self.retry_now.send(());
+    }
+
+    /// Full images replacing the thumbnail shown first, at startup or after a source change.
+    pub fn upgrades(&self) -> &Receiver<PreloadedSlide> {
+        &self.upgrades
+    }
+
+    /// Prepares photos with `gl` from now on, the lost context being gone with the photos
+    /// prepared with it. The photo `asset_id`, the one that was on screen, is prepared first.
+    pub fn restore_context(&self, gl: FutureGlThreadContext, asset_id: Option<String>) {
+        // Only fails when the worker is gone
+        let _ = self.contexts.send((gl, asset_id));
+        self.drop_prepared();
+        while self.upgrades.try_recv().is_ok() {}
+        self.retry_now();
+    }
+
+    /// Prepares the photo of the leader frame, to be shown at the same time. Only followers
+    /// prepare them. On a new schedule, the photos prepared before are dropped, the caller
+    /// drops the ones it holds.
+    pub fn show_synced(&self, photo: SyncedPhoto) -> SyncedOrder {
+        let order = photo.order_after(self.last_received.borrow().as_ref());
+        let generation = match order {
+            SyncedOrder::Duplicate => return order,
+            SyncedOrder::NewSchedule => {
+                info!("New schedule of the leader frame from {:?}", photo);
+                self.drop_prepared()
+            }
+            SyncedOrder::Next => self.active_source_sender.borrow().generation,
+        };
+        *self.last_received.borrow_mut() = Some(photo.clone());
+        // Only fails when the worker is gone
+        let _ = self.synced.send((generation, photo));
+        order
+    }
+
+    /// Brings `slide`, the next photo of the leader frame, forward to [`SYNC_LEAD`] from now
+    /// when it's due later, e.g. on Next: followers need that long to prepare it again. The
+    /// next photos are scheduled after it, the ones prepared meanwhile are dropped.
+    pub fn restart_schedule(&self, slide: &mut PreloadedSlide) {
+        let (Some(schedule), Some(asset_id), Some(show_at)) =
+            (&self.schedule, &slide.details.asset_id, slide.show_at)
+        else {
+            return;
+        };
+        let Some(show_at) = restarted_show_at(show_at, Utc::now()) else {
+            return;
+        };
+        let photo = SyncedPhoto {
+            asset_id: asset_id.clone(),
+            transition: slide.transition,
+            show_at,
+        };
+        info!("Schedule restarted from {:?}", photo);
+        schedule.restart(photo, self.drop_prepared());
+        slide.show_at = Some(show_at);
     }
 }
+
+impl LeaderSchedule {
+    fn new(published: UnboundedSender<SyncedPhoto>) -> Self {
+        Self {
+            published,
+            state: Arc::default(),
+        }
+    }
+
+    /// Publishes the photo `asset_id`, prepared for `generation`, to be shown after the last
+    /// one. `None` when the schedule restarted since: the display drops the photo.
+    fn lead(
+        &self,
+        asset_id: String,
+        transition: Option<TransitionKind>,
+        generation: u64,
+        display_duration: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<SyncedPhoto> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if generation < state.generation {
+            return None;
+        }
+        let photo = SyncedPhoto {
+            asset_id,
+            transition,
+            show_at: next_show_at(state.last.as_ref(), display_duration, now),
+        };
+        self.publish(&mut state, photo.clone());
+        Some(photo)
+    }
+
+    /// Publishes `photo` again at its new time, the photos prepared for an older generation
+    /// than `generation` are never published.
+    fn restart(&self, photo: SyncedPhoto, generation: u64) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.generation = generation;
+        self.publish(&mut state, photo);
+    }
+
+    fn publish(&self, state: &mut ScheduleState, photo: SyncedPhoto) {
+        debug!("Leading with {:?}", photo);
+        // Only fails without MQTT, when nobody follows
+        let _ = self.published.send(photo.clone());
+        state.last = Some(photo);
+    }
+}
+
+/// When the photo after `last` is shown, [`Settings::display_duration`] later, but never
+/// before the followers had [`SYNC_LEAD`] to prepare it.
+fn next_show_at(
+    last: Option<&SyncedPhoto>,
+    display_duration: Duration,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let earliest = now + SYNC_LEAD;
+    last.and_then(|last| Some(last.show_at + TimeDelta::from_std(display_duration).ok()?))
+        .map_or(earliest, |next| next.max(earliest))
+}
+
+/// New time of the next photo of the leader brought forward, `None` when it's due within
+/// [`SYNC_LEAD`] anyway.
+fn restarted_show_at(show_at: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let earliest = now + SYNC_LEAD;
+    (show_at > earliest).then_some(earliest)
+}
+
+/// Photos of the leader frame to prepare among the ones `received` in order, with the
+/// generation they're prepared for. The ones of a schedule replaced meanwhile would be
+/// dropped by the display, and the ones followed by a photo already due never shown.
+fn synced_to_prepare(
+    mut received: Vec<(u64, SyncedPhoto)>,
+    now: DateTime<Utc>,
+) -> Vec<(u64, SyncedPhoto)> {
+    let Some(&(generation, _)) = received.last() else {
+        return received;
+    };
+    received.retain(|(photo_generation, _)| *photo_generation == generation);
+    let due = received
+        .iter()
+        .rposition(|(_, photo)| photo.show_at <= now)
+        .unwrap_or(0);
+    received.split_off(due)
+}
+
+fn activate(gl: FutureGlThreadContext) -> Result<(Rc<GlContext>, ImageBlurr)> {
+    let gl = gl
+        .activate()
+        .context("Cannot make worker thread context current")?;
+    let blurr = ImageBlurr::new(gl.clone()).context("Cannot create ImageBlurr")?;
+    Ok((gl, blurr))
+}
+
 impl WorkerImpl {
-    fn work(&mut self, gl: &Rc<GlContext>, blurr: &ImageBlurr) -> Result<()> {
+    /// Prepares photos until it fails, from the sources built again on each call. A new
+    /// context replaces `gl` and `blurr` when the previous one is lost.
+    fn work(&mut self, gl: &mut Rc<GlContext>, blurr: &mut ImageBlurr) -> Result<()> {
         if let Err(err) = set_current_thread_priority(ThreadPriority::Min) {
             error!("Cannot change worker thread priority to minimal: {:?}", err);
         }
-        let mut source = build_sources(&self.sources).context("Cannot build source")?;
+        let mut source = build_sources(
+            &self.sources,
+            self.randomize_start,
+            self.ideal_max_size.clone(),
+        )
+        .context("Cannot build source")?;
+        source.set_group_by_day(self.config.group_by_day);
+        source.set_date_source(self.config.caption.date_source);
+        // Photos are still shown without their city
+        self.enrichers = build_enrichers(self.reverse_geocoding.as_ref()).unwrap_or_else(|err| {
+            error!("Cannot load photo details enrichers: {:?}", err);
+            Vec::new()
+        });
+        // Photos prepared before would be resized to nothing or a stale size
+        let ideal_size = wait_for_ideal_size(&self.ideal_max_size);
+        debug!("Preparing photos for a {:?} display", ideal_size);
+        if let Some(id) = self.pinned.take() {
+            match self.get_pinned(&mut *source, &id, gl, blurr) {
+                Ok(slide) => self
+                    .send_message(Message::Slide(slide))
+                    .context("While sending pinned image to display thread")?,
+                Err(err) => error!("Cannot load pinned photo {id}: {:?}", err),
+            }
+        } else if self.follower {
+            info!("Waiting for the photos of the leader frame");
+        } else if let Err(err) = self.send_first_thumbnail(&mut *source, gl, blurr) {
+            warn!("Cannot show a thumbnail of the first photo: {:?}", err);
+        }
         loop {
-            if let Ok(true) = self.config_watch.has_changed() {
-                self.config = self.config_watch.borrow_and_update().clone();
+            let config_changed = matches!(self.config_watch.has_changed(), Ok(true));
+            let quality_changed = matches!(self.quality.has_changed(), Ok(true));
+            if config_changed || quality_changed {
+                let mut config = self.config_watch.borrow_and_update().clone();
+                self.quality.borrow_and_update().apply(&mut config);
+                self.config = config;
+                source.set_group_by_day(self.config.group_by_day);
+                source.set_date_source(self.config.caption.date_source);
+            }
+            if let Ok(true) = self.active_source.has_changed() {
+                let SourceSelection { range, generation } =
+                    self.active_source.borrow_and_update().clone();
+                self.generation = generation;
+                match source.set_active_source(range.clone()) {
+                    Ok(()) => info!("Active sources: {:?}", range),
+                    Err(err) => error!("Cannot change active source: {:?}", err),
+                }
+                // The photos of the previous sources may be dropped, the new ones are awaited
+                if !self.follower {
+                    if let Err(err) = self.send_first_thumbnail(&mut *source, gl, blurr) {
+                        warn!("Cannot show a thumbnail of the first photo: {:?}", err);
+                    }
+                }
+            }
+            // After the selection, whose generation the display now expects
+            if let Ok((context, asset_id)) = self.contexts.try_recv() {
+                info!("Preparing photos with a new GL context");
+                gl.mark_lost();
+                (*gl, *blurr) = activate(context)?;
+                if let Some(id) = asset_id {
+                    match self.get_pinned(&mut *source, &id, gl, blurr) {
+                        Ok(slide) => self
+                            .send_message(Message::Slide(slide))
+                            .context("While sending restored image to display thread")?,
+                        Err(err) => error!("Cannot prepare photo {id} again: {:?}", err),
+                    }
+                }
+            }
+            if self.follower {
+                self.prepare_synced(&mut *source, gl, blurr)?;
+                continue;
+            }
+            self.throttle();
+            let result = self.get_next(&mut *source, gl, blurr);
+            self.empty_sources.send_if_modified(|empty_sources| {
+                let current = source.empty_sources();
+                let changed = *empty_sources != current;
+                *empty_sources = current;
+                changed
+            });
+            match result {
+                Ok(mut slide) => {
+                    self.backoff.succeeded();
+                    self.lead(&mut slide);
+                    self.send_message(Message::Slide(slide))
+                        .context("While sending next image to display thread")?;
+                }
+                Err(err) if err.is::<GalleryEmpty>() => {
+                    self.backoff.succeeded();
+                    info!("{}, trying again in {:?}", err, EMPTY_RETRY_INTERVAL);
+                    self.send_message(Message::NoPhotos)
+                        .context("While sending no photos notice to display thread")?;
+                    self.backoff.wait(EMPTY_RETRY_INTERVAL);
+                }
+                Err(err) => {
+                    let delay = self.backoff.failed();
+                    error!("Cannot get next image, retrying in {:?}: {:?}", delay, err);
+                    self.retry_at.send_replace(Some(Instant::now() + delay));
+                    if self.backoff.wait(delay) {
+                        info!("Retrying right away as requested");
+                    }
+                    self.retry_at.send_replace(None);
+                }
+            }
+        }
+    }
+
+    fn send_message(&self, message: Message) -> Result<(), SendError<(u64, Message)>> {
+        self.send_message_for(self.generation, message)
+    }
+
+    /// Sends a message prepared for `generation`, dropped by the display when older than
+    /// its current one.
+    fn send_message_for(
+        &self,
+        generation: u64,
+        message: Message,
+    ) -> Result<(), SendError<(u64, Message)>> {
+        self.send.send((generation, message))
+    }
+
+    /// Waits before preparing another photo while [`Settings::max_live_slides`] photos are
+    /// alive, until the display drops the one it's done with.
+    fn throttle(&self) {
+        let limit = self.config.max_live_slides as usize;
+        let mut throttled_since = None;
+        while must_throttle(LIVE_SLIDES.load(Ordering::Relaxed), limit) {
+            if throttled_since.is_none() {
+                info!(
+                    "Throttling photo preparation, {} photos alive out of {}, textures take {} MB",
+                    LIVE_SLIDES.load(Ordering::Relaxed),
+                    limit.max(MIN_LIVE_SLIDES),
+                    texture_memory() / (1024 * 1024)
+                );
+                throttled_since = Some(Instant::now());
             }
-            let msg = (|| self.get_next(&mut *source, gl, blurr))
-                .retry(
-                    ExponentialBuilder::default()
-                        .with_max_delay(Duration::from_secs(10))
-                        .with_max_times(10),
-                )
-                .call()?;
-            self.send
-                .send(msg)
-                .context("While sending next image to display thread")?;
+            std::thread::sleep(LIVE_SLIDES_POLL_INTERVAL);
+        }
+        if let Some(start) = throttled_since {
+            info!("Photo preparation resumed after {:?}", start.elapsed());
         }
     }
 
@@ -113,30 +736,292 @@ impl WorkerImpl {
         gl: &Rc<GlContext>,
         blurr: &ImageBlurr,
     ) -> Result<PreloadedSlide> {
-        let mut img_with_details = source.get_next_image()?;
+        let img_with_details = source.get_next_image(self.config.preparation_timeout)?;
+        self.prepare(img_with_details, gl, blurr)
+    }
+
+    /// Shows a thumbnail of the first photo without waiting for the whole preparation, then
+    /// sends its full image to replace it in place. Done when the worker starts or restarts
+    /// and when the active sources change, not when the display wakes up: the photo prepared
+    /// while it was off is already waiting in the channel.
+    fn send_first_thumbnail(
+        &self,
+        source: &mut dyn Gallery,
+        gl: &Rc<GlContext>,
+        blurr: &ImageBlurr,
+    ) -> Result<()> {
+        // After a source change, the display may still hold the photos of the previous ones
+        self.throttle();
+        let thumbnail = source.get_next_thumbnail(self.config.preparation_timeout)?;
+        if !thumbnail.low_res {
+            // The source has no thumbnails, this is already the full image
+            let slide = self.prepare(thumbnail, gl, blurr)?;
+            return self
+                .send_message(Message::Slide(slide))
+                .context("While sending first image to display thread");
+        }
+        let asset_id = thumbnail.details.asset_id.clone();
+        let slide = self.prepare_thumbnail(thumbnail, gl)?;
+        self.send_message(Message::Slide(slide))
+            .context("While sending thumbnail to display thread")?;
+
+        let Some(asset_id) = asset_id else {
+            return Ok(());
+        };
+        let image = source.get_image_by_id(&asset_id, self.config.preparation_timeout)?;
+        let slide = self.prepare(image, gl, blurr)?;
+        self.upgrades
+            .send(slide)
+            .context("While sending full image to display thread")
+    }
+
+    /// When leading the synchronized frames, picks the transition to `slide` and when all
+    /// frames show it, then publishes it for the followers to prepare it meanwhile. Photos
+    /// follow each other every [`Settings::display_duration`].
+    fn lead(&mut self, slide: &mut PreloadedSlide) {
+        let Some(schedule) = &self.schedule else {
+            return;
+        };
+        let Some(asset_id) = slide
+            .details
+            .asset_id
+            .clone()
+            .filter(|id| is_immich_asset_id(id))
+        else {
+            // Followers cannot fetch it, shown by the leader alone
+            return;
+        };
+        let transition = random::with_rng(|rng| pick_transition(&self.config.transitions, rng));
+        if let Some(photo) = schedule.lead(
+            asset_id,
+            Some(transition),
+            self.generation,
+            self.config.display_duration,
+            Utc::now(),
+        ) {
+            slide.transition = photo.transition;
+            slide.show_at = Some(photo.show_at);
+        }
+    }
+
+    /// Prepares the photos of the leader frame received, if any comes within
+    /// [`SYNC_POLL_INTERVAL`], in the order they are shown, see [`synced_to_prepare`].
+    fn prepare_synced(
+        &mut self,
+        source: &mut dyn Gallery,
+        gl: &Rc<GlContext>,
+        blurr: &ImageBlurr,
+    ) -> Result<()> {
+        let first = match self.synced.recv_timeout(SYNC_POLL_INTERVAL) {
+            Ok(photo) => photo,
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => bail!("Display thread gone"),
+        };
+        let received = std::iter::once(first)
+            .chain(self.synced.try_iter())
+            .collect();
+        for (generation, photo) in synced_to_prepare(received, Utc::now()) {
+            match self.get_pinned(source, &photo.asset_id, gl, blurr) {
+                Ok(mut slide) => {
+                    slide.transition = photo.transition;
+                    slide.show_at = Some(photo.show_at);
+                    self.send_message_for(generation, Message::Slide(slide))
+                        .context("While sending synchronized image to display thread")?;
+                }
+                Err(err) => error!(
+                    "Cannot prepare photo {} of the leader frame: {:?}",
+                    photo.asset_id, err
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn get_pinned(
+        &self,
+        source: &mut dyn Gallery,
+        id: &str,
+        gl: &Rc<GlContext>,
+        blurr: &ImageBlurr,
+    ) -> Result<PreloadedSlide> {
+        let img_with_details = source.get_image_by_id(id, self.config.preparation_timeout)?;
+        self.prepare(img_with_details, gl, blurr)
+    }
+
+    fn prepare(
+        &self,
+        mut img_with_details: ImageWithDetails,
+        gl: &Rc<GlContext>,
+        blurr: &ImageBlurr,
+    ) -> Result<PreloadedSlide> {
         img_with_details.image = self.resize_image_if_necessay(img_with_details.image);
-        let texture = Texture::new_from_image(gl.clone(), &img_with_details.image).unwrap();
-        let blurred_texture = blurr
-            .blur(self.config.blur_options.clone().into(), &texture)
-            .unwrap();
+        self.enrich(&mut img_with_details.details);
+        prepare_slide(img_with_details, gl, blurr, &self.config)
+    }
+
+    /// Uploads a thumbnail as is, skipping the blurred background and sharpening.
+    fn prepare_thumbnail(
+        &self,
+        mut img_with_details: ImageWithDetails,
+        gl: &Rc<GlContext>,
+    ) -> Result<PreloadedSlide> {
+        self.enrich(&mut img_with_details.details);
+        let live = LiveSlide::counted();
+        let mut texture = Texture::new_from_image(gl.clone(), &img_with_details.image)
+            .context("Cannot create thumbnail texture")?;
+        set_photo_options(&mut texture, &self.config);
         unsafe { gl.finish() };
-        let msg = PreloadedSlide {
+        Ok(PreloadedSlide {
+            live,
+            recap_thumbnail: recap_thumbnail(&img_with_details.image, &self.config),
             details: img_with_details.details,
             texture: texture.detach(),
-            blurred_texture: blurred_texture.detach(),
-        };
-        Ok(msg)
+            blurred_texture: None,
+            preparation: img_with_details.preparation,
+            transition: None,
+            show_at: None,
+        })
+    }
+
+    fn enrich(&self, details: &mut ImageDetails) {
+        for enricher in &self.enrichers {
+            enricher.enrich(details);
+        }
     }
 
     fn resize_image_if_necessay(&self, image: DynamicImage) -> DynamicImage {
-        let image_dims: Extent2<u32> = image.dimensions().into();
         let ideal_size = *self.ideal_max_size.borrow();
-        let should_resize = image_dims.cmpgt(&ideal_size).reduce_or();
-        if should_resize {
-            let filter = self.config.downscaled_image_filter;
-            image.resize(ideal_size.w, ideal_size.h, filter.into())
-        } else {
-            image
+        fit_image(image, ideal_size, self.config.downscaled_image_filter)
+    }
+}
+
+/// Whether preparing the next photo must wait for the display to drop one of the `live`
+/// ones. No limit when 0, never below [`MIN_LIVE_SLIDES`].
+fn must_throttle(live: usize, limit: usize) -> bool {
+    limit > 0 && live >= limit.max(MIN_LIVE_SLIDES)
+}
+
+/// Uploads a photo with its blurred background and sharpening, ready to be shown. Doesn't
+/// resize it nor complete its details.
+pub fn prepare_slide(
+    img_with_details: ImageWithDetails,
+    gl: &Rc<GlContext>,
+    blurr: &ImageBlurr,
+    config: &Settings,
+) -> Result<PreloadedSlide> {
+    let live = LiveSlide::counted();
+    let mut texture = Texture::new_from_image(gl.clone(), &img_with_details.image)
+        .context("Cannot create photo texture")?;
+    // Blurred and sharpened by sampling around each pixel, edges must not wrap around
+    texture.set_wrap(TextureWrapMode::ClampToEdge);
+    let blurred_texture = match config.background {
+        // Not shown, not worth preparing
+        Background::Black => None,
+        Background::Blur(_) => {
+            // A smaller version of the photo, when provided, is quicker to blur
+            let background = img_with_details
+                .background_image
+                .as_ref()
+                .map(|image| -> Result<Texture> {
+                    let mut background = Texture::new_from_image(gl.clone(), image)
+                        .context("Cannot create background texture")?;
+                    background.set_wrap(TextureWrapMode::ClampToEdge);
+                    Ok(background)
+                })
+                .transpose()?;
+            let blur_input = background.as_ref().unwrap_or(&texture);
+            let options = blur_options(config, texture.size(), blur_input.size());
+            let blurred_texture = blurr
+                .blur(options, blur_input)
+                .context("Cannot blur background")?;
+            let blurred_texture = blurr
+                .tone((&config.background).into(), blurred_texture)
+                .context("Cannot tone blurred background")?;
+            Some(blurred_texture.detach())
+        }
+    };
+    let mut texture = blurr
+        .sharpen(config.sharpen.amount, texture)
+        .context("Cannot sharpen image")?;
+    set_photo_options(&mut texture, config);
+    unsafe { gl.finish() };
+    Ok(PreloadedSlide {
+        live,
+        recap_thumbnail: recap_thumbnail(&img_with_details.image, config),
+        details: img_with_details.details,
+        texture: texture.detach(),
+        blurred_texture,
+        preparation: img_with_details.preparation,
+        transition: None,
+        show_at: None,
+    })
+}
+
+/// Blur of a background of `blur_input_size` looking like the configured blur of a photo of
+/// `photo_size`: the radius is in pixels of the blurred texture.
+fn blur_options(
+    config: &Settings,
+    photo_size: Extent2<u32>,
+    blur_input_size: Extent2<u32>,
+) -> BlurOptions {
+    let mut options = BlurOptions::from(config.blur_options.clone());
+    options.radius *= blur_input_size.w as f32 / photo_size.w.max(1) as f32;
+    options
+}
+
+/// Kept in memory for the whole day, only when the mosaic is enabled.
+fn recap_thumbnail(image: &DynamicImage, config: &Settings) -> Option<DynamicImage> {
+    config
+        .display_off_recap
+        .then(|| image.thumbnail(RECAP_THUMBNAIL_SIZE, RECAP_THUMBNAIL_SIZE))
+}
+
+fn set_photo_options(texture: &mut Texture, config: &Settings) {
+    let filtering = &config.texture_filtering;
+    texture.set_options(TextureOptions {
+        min: filtering.photo.into(),
+        mag: filtering.photo.into(),
+        anisotropy: filtering.photo_anisotropy,
+        wrap: TextureWrapMode::ClampToEdge,
+    });
+}
+
+/// Downscales `image` to fit in `ideal_size`, smaller images are left as is.
+fn fit_image(image: DynamicImage, ideal_size: Extent2<u32>, filter: ImageFilter) -> DynamicImage {
+    let image_dims: Extent2<u32> = image.dimensions().into();
+    let should_resize = image_dims.cmpgt(&ideal_size).reduce_or();
+    if should_resize {
+        image.resize(ideal_size.w, ideal_size.h, filter.into())
+    } else {
+        image
+    }
+}
+
+/// Blocks until the display reported a non-zero size, which may only be known once the
+/// first frame is drawn, and returns it.
+fn wait_for_ideal_size(ideal_max_size: &watch::Receiver<Extent2<u32>>) -> Extent2<u32> {
+    let start = Instant::now();
+    loop {
+        let size = *ideal_max_size.borrow();
+        if size.product() > 0 {
+            if !start.elapsed().is_zero() {
+                debug!("Waited {:?} for the display size", start.elapsed());
+            }
+            return size;
+        }
+        if ideal_max_size.has_changed().is_err() {
+            // The display is gone, the worker stops on its next send anyway
+            return size;
+        }
+        std::thread::sleep(IDEAL_SIZE_POLL_INTERVAL);
+    }
+}
+
+impl From<TextureFilter> for TextureFiltering {
+    fn from(f: TextureFilter) -> Self {
+        match f {
+            TextureFilter::Linear => TextureFiltering::Linear,
+            TextureFilter::Nearest => TextureFiltering::Nearest,
         }
     }
 }
@@ -152,3 +1037,223 @@ impl From<ImageFilter> for FilterType {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use chrono::{DateTime, TimeDelta, Utc};
+    use googletest::{expect_that, gtest, prelude::eq};
+    use image::{DynamicImage, GenericImageView};
+    use tokio::sync::{mpsc::unbounded_channel, watch};
+    use vek::Extent2;
+
+    use super::{
+        blur_options, fit_image, must_throttle, next_show_at, restarted_show_at, synced_to_prepare,
+        wait_for_ideal_size, FailureBackoff, LeaderSchedule, SyncedOrder, SyncedPhoto,
+        FAILURE_BACKOFF_MAX, FAILURE_BACKOFF_MIN, SYNC_LEAD,
+    };
+    use crate::configuration::{ImageFilter, Settings};
+
+    #[gtest]
+    fn test_backoff_doubles_until_success() {
+        let (_wake, receiver) = std::sync::mpsc::channel();
+        let mut backoff = FailureBackoff::new(receiver);
+        let delays: Vec<_> = (0..8).map(|_| backoff.failed().as_secs()).collect();
+        expect_that!(delays, eq(&vec![5, 10, 20, 40, 80, 160, 300, 300]));
+        expect_that!(backoff.failed(), eq(FAILURE_BACKOFF_MAX));
+
+        backoff.succeeded();
+        expect_that!(backoff.failed(), eq(FAILURE_BACKOFF_MIN));
+    }
+
+    #[gtest]
+    fn test_backoff_is_interrupted() {
+        let (wake, receiver) = std::sync::mpsc::channel();
+        let mut backoff = FailureBackoff::new(receiver);
+        let delay = backoff.failed();
+        let waker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            wake.send(()).unwrap();
+            wake
+        });
+
+        let start = Instant::now();
+        expect_that!(backoff.wait(delay), eq(true));
+        expect_that!(start.elapsed() < delay, eq(true));
+        let wake = waker.join().unwrap();
+
+        // Interrupting doesn't reset the backoff
+        expect_that!(backoff.failed(), eq(delay * 2));
+
+        // Wake ups sent while fetching don't skip the next wait
+        wake.send(()).unwrap();
+        let start = Instant::now();
+        expect_that!(backoff.wait(Duration::from_millis(30)), eq(false));
+        expect_that!(start.elapsed() >= Duration::from_millis(30), eq(true));
+    }
+
+    #[gtest]
+    fn test_first_photo_waits_for_display_size() {
+        let (sender, receiver) = watch::channel(Extent2::zero());
+        let setter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            sender.send_replace(Extent2::new(80, 60));
+            // Keep the sender alive until the size is observed
+            thread::sleep(Duration::from_millis(200));
+        });
+
+        let size = wait_for_ideal_size(&receiver);
+        expect_that!(size, eq(Extent2::new(80, 60)));
+        let image = fit_image(DynamicImage::new_rgb8(400, 300), size, ImageFilter::Nearest);
+        expect_that!(image.dimensions(), eq((80, 60)));
+        setter.join().unwrap();
+    }
+
+    #[gtest]
+    fn test_preparation_throttled_at_live_slides_limit() {
+        expect_that!(must_throttle(2, 3), eq(false));
+        expect_that!(must_throttle(3, 3), eq(true));
+        // The one shown and the next one are always allowed
+        expect_that!(must_throttle(1, 1), eq(false));
+        expect_that!(must_throttle(2, 1), eq(true));
+        // No limit
+        expect_that!(must_throttle(10, 0), eq(false));
+    }
+
+    #[gtest]
+    fn test_blur_radius_follows_background_size() {
+        let mut config = Settings::default();
+        config.blur_options.radius = 6.;
+        let photo = Extent2::new(1280, 960);
+
+        // Without a smaller version, the photo itself is blurred
+        let options = blur_options(&config, photo, photo);
+        expect_that!(options.radius, eq(6.));
+        expect_that!(options.passes, eq(config.blur_options.passes));
+
+        // A thumbnail of a quarter of the width gets a quarter of the radius
+        let options = blur_options(&config, photo, Extent2::new(320, 240));
+        expect_that!(options.radius, eq(1.5));
+    }
+
+    fn photo(asset_id: &str, show_at: DateTime<Utc>) -> SyncedPhoto {
+        SyncedPhoto {
+            asset_id: asset_id.into(),
+            transition: None,
+            show_at,
+        }
+    }
+
+    #[gtest]
+    fn test_photos_scheduled_one_after_the_other() {
+        let now = Utc::now();
+        let duration = Duration::from_secs(60);
+        expect_that!(next_show_at(None, duration, now), eq(now + SYNC_LEAD));
+        let last = photo("a", now + TimeDelta::seconds(30));
+        expect_that!(
+            next_show_at(Some(&last), duration, now),
+            eq(now + TimeDelta::seconds(90))
+        );
+        // Followers still get time to prepare it after a long wait
+        let last = photo("a", now - TimeDelta::seconds(600));
+        expect_that!(
+            next_show_at(Some(&last), duration, now),
+            eq(now + SYNC_LEAD)
+        );
+    }
+
+    #[gtest]
+    fn test_photos_led_back_to_back_both_published() {
+        let (published, mut followers) = unbounded_channel();
+        let schedule = LeaderSchedule::new(published);
+        let now = Utc::now();
+        let duration = Duration::from_secs(60);
+
+        let first = schedule.lead("a".into(), None, 0, duration, now).unwrap();
+        let second = schedule.lead("b".into(), None, 0, duration, now).unwrap();
+        expect_that!(second.show_at, eq(first.show_at + TimeDelta::seconds(60)));
+        expect_that!(followers.try_recv(), eq(Ok(first)));
+        expect_that!(followers.try_recv(), eq(Ok(second)));
+    }
+
+    #[gtest]
+    fn test_restarted_schedule_published_and_followed() {
+        let (published, mut followers) = unbounded_channel();
+        let schedule = LeaderSchedule::new(published);
+        let now = Utc::now();
+        let duration = Duration::from_secs(60);
+        let held = schedule.lead("a".into(), None, 0, duration, now).unwrap();
+        schedule.lead("b".into(), None, 0, duration, now).unwrap();
+        while followers.try_recv().is_ok() {}
+
+        // Not moved when due within the time the followers need
+        expect_that!(restarted_show_at(held.show_at, now), eq(None));
+        let show_at = restarted_show_at(now + TimeDelta::seconds(60), now).unwrap();
+        expect_that!(show_at, eq(now + SYNC_LEAD));
+
+        let restarted = photo("a", show_at);
+        schedule.restart(restarted.clone(), 1);
+        expect_that!(followers.try_recv(), eq(Ok(restarted)));
+        // Prepared before the restart, dropped by the display
+        expect_that!(schedule.lead("c".into(), None, 0, duration, now), eq(None));
+        let next = schedule.lead("c".into(), None, 1, duration, now).unwrap();
+        expect_that!(next.show_at, eq(show_at + TimeDelta::seconds(60)));
+        expect_that!(followers.try_recv(), eq(Ok(next)));
+    }
+
+    #[gtest]
+    fn test_synced_photo_order() {
+        let now = Utc::now();
+        let last = photo("a", now + TimeDelta::seconds(60));
+        expect_that!(last.order_after(None), eq(SyncedOrder::Next));
+        expect_that!(
+            photo("b", now + TimeDelta::seconds(120)).order_after(Some(&last)),
+            eq(SyncedOrder::Next)
+        );
+        // The retained photo received again when reconnected
+        expect_that!(last.order_after(Some(&last)), eq(SyncedOrder::Duplicate));
+        // Restarted leader, or Next on it
+        expect_that!(
+            photo("b", now + TimeDelta::seconds(5)).order_after(Some(&last)),
+            eq(SyncedOrder::NewSchedule)
+        );
+        expect_that!(
+            photo("a", now + TimeDelta::seconds(5)).order_after(Some(&last)),
+            eq(SyncedOrder::NewSchedule)
+        );
+    }
+
+    #[gtest]
+    fn test_synced_photos_to_prepare() {
+        let now = Utc::now();
+        let at = |seconds| now + TimeDelta::seconds(seconds);
+        expect_that!(synced_to_prepare(Vec::new(), now).is_empty(), eq(true));
+
+        // Only the last one already due is shown, right away
+        let received = vec![
+            (0, photo("a", at(-120))),
+            (0, photo("b", at(-60))),
+            (0, photo("c", at(0))),
+            (0, photo("d", at(60))),
+        ];
+        expect_that!(
+            synced_to_prepare(received, now),
+            eq(&vec![(0, photo("c", at(0))), (0, photo("d", at(60)))])
+        );
+
+        // Photos of the previous schedule are dropped, even when shown later
+        let received = vec![
+            (0, photo("a", at(60))),
+            (1, photo("b", at(5))),
+            (1, photo("c", at(65))),
+        ];
+        expect_that!(
+            synced_to_prepare(received, now),
+            eq(&vec![(1, photo("b", at(5))), (1, photo("c", at(65)))])
+        );
+    }
+}